@@ -0,0 +1,43 @@
+struct EnvironmentUniforms {
+    ray_transform: mat4x4<f32>,
+    exposure: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: EnvironmentUniforms;
+@group(0) @binding(1)
+var environment_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var environment_sampler: sampler;
+
+const PI: f32 = 3.14159265;
+
+// One triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let ndc = vec2(f32(index & 1u) * 4.0 - 1.0, f32(index >> 1u) * 4.0 - 1.0);
+    out.clip_position = vec4(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let far = uniforms.ray_transform * vec4(in.ndc, 1.0, 1.0);
+    let direction = normalize(far.xyz / far.w);
+    // Equirectangular, -Z in the middle and +Y at the top
+    let uv = vec2(atan2(direction.x, -direction.z) / (2.0 * PI) + 0.5, acos(clamp(direction.y, -1.0, 1.0)) / PI);
+    // The largest level only, mips would show the seam where u wraps
+    var color = textureSampleLevel(environment_texture, environment_sampler, uv, 0.0).rgb * uniforms.exposure.x;
+    if uniforms.exposure.y > 0.5 {
+        // Reinhard, so the bright parts of an HDR image roll off instead of clipping
+        color = color / (1.0 + color);
+    }
+    return vec4(color, 1.0);
+}
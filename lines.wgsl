@@ -0,0 +1,27 @@
+struct Uniforms {
+    mvp: mat4x4<f32>,
+    position_scale: vec4<f32>,
+    position_offset: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    let position = pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
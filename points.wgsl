@@ -0,0 +1,48 @@
+struct Uniforms {
+    mvp: mat4x4<f32>,
+    position_scale: vec4<f32>,
+    position_offset: vec4<f32>,
+    light_direction: vec4<f32>,
+    camera_position: vec4<f32>,
+    environment_rotation: mat3x3<f32>,
+    ambient: array<vec4<f32>, 9>,
+    clip_plane: vec4<f32>,
+    // x is the point size in pixels at w away from the camera, y and z the viewport size
+    display: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // -1 to 1 across the point's square
+    @location(1) corner: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// Each point is an instance drawn as a four-vertex strip, spread to its size on screen.
+// Points shrink with distance like the surface they sample would, but never below a
+// pixel, so far-off parts of a scan stay visible, and grow to at most four times the size.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, @location(0) pos: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2(f32(index & 1u), f32(index >> 1u)) * 2.0 - 1.0;
+    let position = pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz;
+    let center = uniforms.mvp * vec4(position, 1.0);
+    let size = clamp(uniforms.display.x * uniforms.display.w / center.w, 1.0, uniforms.display.x * 4.0);
+    let spread = corner * size / uniforms.display.yz * center.w;
+    out.clip_position = vec4(center.xy + spread, center.zw);
+    out.color = color;
+    out.corner = corner;
+    return out;
+}
+
+// Round points, so a dense cloud doesn't look like a pile of squares
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if dot(in.corner, in.corner) > 1.0 {
+        discard;
+    }
+    return in.color;
+}
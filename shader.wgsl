@@ -1,39 +1,374 @@
 struct Uniforms {
     mvp: mat4x4<f32>,
+    position_scale: vec4<f32>,
+    position_offset: vec4<f32>,
+    // w is 1 for flat shading
+    light_direction: vec4<f32>,
+    camera_position: vec4<f32>,
+    // Model space to the environment's, and its diffuse light as spherical harmonics
+    environment_rotation: mat3x3<f32>,
+    ambient: array<vec4<f32>, 9>,
+    // Model space plane with the normal in xyz: the clipped pipelines cut away what's
+    // in front of it, past w along the normal
+    clip_plane: vec4<f32>,
+    // x is the checker squares per UV unit, 0 to shade with the material; y is the debug
+    // view, 1 for normals and 2 for depth, with depth running from z to w away from the camera
+    display: vec4<f32>,
+}
+
+// Per-draw material: metallic, roughness, normal scale and occlusion strength, then the
+// emissive color. The scale and strength are zero when there's no texture to apply, and
+// the emissive color's w is 1 for unlit materials.
+// The offset moves the whole draw, for the exploded view.
+struct DrawMaterial {
+    @location(6) factors: vec4<f32>,
+    @location(7) emissive: vec4<f32>,
+    @location(8) offset: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) frag_pos: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(3) normal: vec3<f32>,
+    @location(4) @interpolate(flat) factors: vec4<f32>,
+    @location(5) @interpolate(flat) emissive: vec4<f32>,
+}
+
+struct TexturedVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) frag_pos: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) normal: vec3<f32>,
+    @location(4) @interpolate(flat) factors: vec4<f32>,
+    @location(5) @interpolate(flat) emissive: vec4<f32>,
 }
 
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+// The material's textures, white where it has none
+@group(1) @binding(0)
+var base_color_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var base_color_sampler: sampler;
+@group(1) @binding(2)
+var metallic_roughness_texture: texture_2d<f32>;
+@group(1) @binding(3)
+var metallic_roughness_sampler: sampler;
+@group(1) @binding(4)
+var normal_texture: texture_2d<f32>;
+@group(1) @binding(5)
+var normal_sampler: sampler;
+@group(1) @binding(6)
+var occlusion_texture: texture_2d<f32>;
+@group(1) @binding(7)
+var occlusion_sampler: sampler;
+@group(1) @binding(8)
+var emissive_texture: texture_2d<f32>;
+@group(1) @binding(9)
+var emissive_sampler: sampler;
+
+// Only bound for the skinned pipelines
+@group(2) @binding(0)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
+struct Skinned {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+}
+
+// Blends the vertex between its joints' poses. Zero normals stay zero, they still mean
+// "use the face normal".
+fn skin(position: vec3<f32>, normal: vec3<f32>, joints: vec4<u32>, weights: vec4<f32>) -> Skinned {
+    let matrix = joint_matrices[joints.x] * weights.x
+        + joint_matrices[joints.y] * weights.y
+        + joint_matrices[joints.z] * weights.z
+        + joint_matrices[joints.w] * weights.w;
+    var out: Skinned;
+    out.position = (matrix * vec4(position, 1.0)).xyz;
+    out.normal = (matrix * vec4(normal, 0.0)).xyz;
+    return out;
+}
+
 @vertex
-fn vs_main(@location(0) pos: vec3<f32>) -> VertexOutput {
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) color: vec4<f32>, @location(3) normal: vec3<f32>, draw: DrawMaterial) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = uniforms.mvp * vec4(pos, 1.0);
-    out.frag_pos = pos;
+    out.factors = draw.factors;
+    out.emissive = draw.emissive;
+    let position = pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz + draw.offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.frag_pos = position;
+    out.color = color;
+    out.normal = normal;
     return out;
 }
 
+@vertex
+fn vs_textured(@location(0) pos: vec3<f32>, @location(1) color: vec4<f32>, @location(2) uv: vec2<f32>, @location(3) normal: vec3<f32>, draw: DrawMaterial) -> TexturedVertexOutput {
+    var out: TexturedVertexOutput;
+    out.factors = draw.factors;
+    out.emissive = draw.emissive;
+    let position = pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz + draw.offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.frag_pos = position;
+    out.color = color;
+    out.uv = uv;
+    out.normal = normal;
+    return out;
+}
+
+@vertex
+fn vs_vertex_color(@location(0) pos: vec3<f32>, @location(2) vertex_color: vec4<f32>, @location(3) normal: vec3<f32>, draw: DrawMaterial) -> VertexOutput {
+    var out: VertexOutput;
+    out.factors = draw.factors;
+    out.emissive = draw.emissive;
+    let position = pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz + draw.offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.frag_pos = position;
+    out.color = vertex_color;
+    out.normal = normal;
+    return out;
+}
+
+@vertex
+fn vs_skinned(@location(0) pos: vec3<f32>, @location(1) color: vec4<f32>, @location(3) normal: vec3<f32>, @location(4) joints: vec4<u32>, @location(5) weights: vec4<f32>, draw: DrawMaterial) -> VertexOutput {
+    var out: VertexOutput;
+    out.factors = draw.factors;
+    out.emissive = draw.emissive;
+    let skinned = skin(pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz, normal, joints, weights);
+    let position = skinned.position + draw.offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.frag_pos = position;
+    out.color = color;
+    out.normal = skinned.normal;
+    return out;
+}
+
+@vertex
+fn vs_textured_skinned(@location(0) pos: vec3<f32>, @location(1) color: vec4<f32>, @location(2) uv: vec2<f32>, @location(3) normal: vec3<f32>, @location(4) joints: vec4<u32>, @location(5) weights: vec4<f32>, draw: DrawMaterial) -> TexturedVertexOutput {
+    var out: TexturedVertexOutput;
+    out.factors = draw.factors;
+    out.emissive = draw.emissive;
+    let skinned = skin(pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz, normal, joints, weights);
+    let position = skinned.position + draw.offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.frag_pos = position;
+    out.color = color;
+    out.uv = uv;
+    out.normal = skinned.normal;
+    return out;
+}
+
+@vertex
+fn vs_vertex_color_skinned(@location(0) pos: vec3<f32>, @location(2) vertex_color: vec4<f32>, @location(3) normal: vec3<f32>, @location(4) joints: vec4<u32>, @location(5) weights: vec4<f32>, draw: DrawMaterial) -> VertexOutput {
+    var out: VertexOutput;
+    out.factors = draw.factors;
+    out.emissive = draw.emissive;
+    let skinned = skin(pos * uniforms.position_scale.xyz + uniforms.position_offset.xyz, normal, joints, weights);
+    let position = skinned.position + draw.offset.xyz;
+    out.clip_position = uniforms.mvp * vec4(position, 1.0);
+    out.frag_pos = position;
+    out.color = vertex_color;
+    out.normal = skinned.normal;
+    return out;
+}
+
+// Light from every direction around the normal, flat without an environment
+fn ambient_light(model_normal: vec3<f32>) -> vec3<f32> {
+    let n = uniforms.environment_rotation * model_normal;
+    let c = uniforms.ambient;
+    let light = c[0].rgb + c[1].rgb * n.y + c[2].rgb * n.z + c[3].rgb * n.x
+        + c[4].rgb * (n.x * n.y) + c[5].rgb * (n.y * n.z) + c[6].rgb * (3.0 * n.z * n.z - 1.0)
+        + c[7].rgb * (n.x * n.z) + c[8].rgb * (n.x * n.x - n.y * n.y);
+    return max(light, vec3(0.0));
+}
+
+const PI: f32 = 3.14159265;
+// Radiance of the light, so a white dielectric facing it gets the 0.7 diffuse the
+// shading had before it was physically based
+const LIGHT_INTENSITY: f32 = 2.2;
+// Multiplies the inside of a clipped model, so it stands apart from the outside
+const INTERIOR_TINT: vec3<f32> = vec3(1.0, 0.6, 0.5);
+
+struct Surface {
+    base_color: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    // Of the ambient light only
+    occlusion: f32,
+    emissive: vec3<f32>,
+    // Shown in its base color as is
+    unlit: bool,
+}
+
+// The interpolated normal, or the face's when shading flat or where the vertex has none
+// (still streaming, or degenerate). Derivatives have to be taken in uniform control flow,
+// so they're passed in.
+fn surface_normal(vertex_normal: vec3<f32>, dp_dx: vec3<f32>, dp_dy: vec3<f32>) -> vec3<f32> {
+    // Framebuffer y points down, so this order faces the viewer on counter-clockwise triangles
+    let face_normal = normalize(cross(dp_dy, dp_dx));
+    let has_normal = dot(vertex_normal, vertex_normal) > 1e-8 && uniforms.light_direction.w < 0.5;
+    return select(face_normal, normalize(vertex_normal), has_normal);
+}
+
+// A normal texel applied in a tangent frame built from the position and UV derivatives,
+// as the glTF sample viewer does for meshes without tangents
+fn perturb_normal(normal: vec3<f32>, texel: vec3<f32>, scale: f32, dp_dx: vec3<f32>, dp_dy: vec3<f32>, uv_dx: vec2<f32>, uv_dy: vec2<f32>) -> vec3<f32> {
+    let determinant = uv_dx.x * uv_dy.y - uv_dy.x * uv_dx.y;
+    // UVs that don't change across the pixel have no frame to apply it in
+    if abs(determinant) < 1e-12 {
+        return normal;
+    }
+    let along_u = (uv_dy.y * dp_dx - uv_dx.y * dp_dy) / determinant;
+    let tangent_direction = along_u - normal * dot(normal, along_u);
+    if dot(tangent_direction, tangent_direction) < 1e-12 {
+        return normal;
+    }
+    let tangent = normalize(tangent_direction);
+    let bitangent = cross(normal, tangent);
+    let bent = texel * 2.0 - 1.0;
+    return normalize(mat3x3(tangent, bitangent, normal) * vec3(bent.xy * scale, bent.z));
+}
+
+// Karis' fit of the split-sum environment BRDF, so the ambient is reflected like the light
+fn environment_brdf(f0: vec3<f32>, roughness: f32, n_dot_v: f32) -> vec3<f32> {
+    let r = roughness * vec4(-1.0, -0.0275, -0.572, 0.022) + vec4(1.0, 0.0425, 1.04, -0.04);
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let ab = vec2(-1.04, 1.04) * a004 + r.zw;
+    return f0 * ab.x + ab.y;
+}
+
+// glTF metallic-roughness: GGX specular with height-correlated Smith visibility and
+// Schlick Fresnel over Lambert diffuse, lit by the light and the ambient, all in model space
+fn shade(frag_pos: vec3<f32>, normal: vec3<f32>, surface: Surface) -> vec4<f32> {
+    // Debug views take the place of the lighting. The target encodes to sRGB, so they're
+    // decoded first to come out as the values themselves, like a normal map does.
+    if uniforms.display.y > 1.5 {
+        let depth = (distance(uniforms.camera_position.xyz, frag_pos) - uniforms.display.z) / max(uniforms.display.w - uniforms.display.z, 1e-6);
+        return vec4(pow(vec3(1.0 - clamp(depth, 0.0, 1.0)), vec3(2.2)), 1.0);
+    }
+    if uniforms.display.y > 0.5 {
+        return vec4(pow(normalize(uniforms.environment_rotation * normal) * 0.5 + 0.5, vec3(2.2)), 1.0);
+    }
+
+    if surface.unlit {
+        return vec4(surface.base_color, 1.0);
+    }
+
+    let light_dir = uniforms.light_direction.xyz;
+    let view_dir = normalize(uniforms.camera_position.xyz - frag_pos);
+    let half_dir = normalize(light_dir + view_dir);
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    let n_dot_v = max(dot(normal, view_dir), 1e-4);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+    let v_dot_h = max(dot(view_dir, half_dir), 0.0);
+
+    let roughness = clamp(surface.roughness, 0.03, 1.0);
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+    let f0 = mix(vec3(0.04), surface.base_color, surface.metallic);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+    let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    let distribution = alpha2 / (PI * d * d);
+    let visibility = 0.5 / max(n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2)
+        + n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2), 1e-5);
+
+    let diffuse_color = surface.base_color * (1.0 - surface.metallic);
+    let direct = ((1.0 - fresnel) * diffuse_color / PI + fresnel * distribution * visibility) * LIGHT_INTENSITY * n_dot_l;
+    let reflected = reflect(-view_dir, normal);
+    let ambient = (diffuse_color * ambient_light(normal)
+        + environment_brdf(f0, roughness, n_dot_v) * ambient_light(reflected)) * surface.occlusion;
+
+    return vec4(direct + ambient + surface.emissive, 1.0);
+}
+
+// A surface and the normal to shade it with at one fragment
+struct Shading {
+    normal: vec3<f32>,
+    surface: Surface,
+}
+
+// Cuts away the fragments in front of the clipping plane and shades the inside that
+// opens up, which the clipped pipelines don't cull
+fn shade_clipped(frag_pos: vec3<f32>, shading: Shading, front_facing: bool) -> vec4<f32> {
+    if dot(uniforms.clip_plane.xyz, frag_pos) > uniforms.clip_plane.w {
+        discard;
+    }
+    if front_facing {
+        return shade(frag_pos, shading.normal, shading.surface);
+    }
+    // Face normals already face the viewer, vertex normals on the inside face away
+    let toward_viewer = dot(shading.normal, uniforms.camera_position.xyz - frag_pos) > 0.0;
+    let normal = select(-shading.normal, shading.normal, toward_viewer);
+    return vec4(shade(frag_pos, normal, shading.surface).rgb * INTERIOR_TINT, 1.0);
+}
+
+fn material_shading(in: VertexOutput) -> Shading {
+    let normal = surface_normal(in.normal, dpdx(in.frag_pos), dpdy(in.frag_pos));
+    return Shading(normal, Surface(in.color.rgb, in.factors.x, in.factors.y, 1.0, in.emissive.rgb, in.emissive.w > 0.5));
+}
+
+// Light and dark squares, tinted red along U and green along V so stretching, seams and
+// flipped or rotated islands show
+fn checker(uv: vec2<f32>, squares: f32) -> vec3<f32> {
+    let cell = floor(uv * squares);
+    let light = (i32(cell.x) + i32(cell.y)) % 2 == 0;
+    let tint = vec3(0.6 + 0.4 * fract(uv.x), 0.6 + 0.4 * fract(uv.y), 0.8);
+    return select(0.25, 0.9, light) * tint;
+}
+
+fn textured_shading(in: TexturedVertexOutput) -> Shading {
+    // Sampled up front, implicit derivatives need uniform control flow too
+    let base_color = textureSample(base_color_texture, base_color_sampler, in.uv);
+    let metallic_roughness = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in.uv);
+    let normal_texel = textureSample(normal_texture, normal_sampler, in.uv);
+    let occlusion = textureSample(occlusion_texture, occlusion_sampler, in.uv);
+    let emissive = textureSample(emissive_texture, emissive_sampler, in.uv);
+    let dp_dx = dpdx(in.frag_pos);
+    let dp_dy = dpdy(in.frag_pos);
+    let uv_dx = dpdx(in.uv);
+    let uv_dy = dpdy(in.uv);
+
+    var normal = surface_normal(in.normal, dp_dx, dp_dy);
+    if uniforms.display.x > 0.0 {
+        return Shading(normal, Surface(checker(in.uv, uniforms.display.x), 0.0, 0.6, 1.0, vec3(0.0), false));
+    }
+    if in.factors.z != 0.0 {
+        normal = perturb_normal(normal, normal_texel.xyz, in.factors.z, dp_dx, dp_dy, uv_dx, uv_dy);
+    }
+    let surface = Surface(
+        in.color.rgb * base_color.rgb,
+        in.factors.x * metallic_roughness.b,
+        in.factors.y * metallic_roughness.g,
+        // One without a texture, where the strength is zero
+        1.0 + in.factors.w * (occlusion.r - 1.0),
+        in.emissive.rgb * emissive.rgb,
+        in.emissive.w > 0.5,
+    );
+    return Shading(normal, surface);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let shading = material_shading(in);
+    return shade(in.frag_pos, shading.normal, shading.surface);
+}
 
-    let dx = dpdx(in.frag_pos);
-    let dy = dpdy(in.frag_pos);
-    let face_normal = normalize(cross(dx, dy));
-    
-    let light_dir = normalize(vec3<f32>(0.5, 1.0, 0.3));
-    let n_dot_l = max(dot(face_normal, light_dir), 0.0);
-    
-    let ambient = 0.3;
-    let diffuse = 0.7 * n_dot_l;
-    let brightness = ambient + diffuse;
-    
-    let base_color = vec3<f32>(0.8, 0.8, 0.8);
-    let final_color = base_color * brightness;
-    
-    return vec4(final_color, 1.0);
-}
\ No newline at end of file
+@fragment
+fn fs_textured(in: TexturedVertexOutput) -> @location(0) vec4<f32> {
+    let shading = textured_shading(in);
+    return shade(in.frag_pos, shading.normal, shading.surface);
+}
+
+// Separate entry points, so the discard never costs the unclipped pipelines early depth tests
+@fragment
+fn fs_main_clipped(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    return shade_clipped(in.frag_pos, material_shading(in), front_facing);
+}
+
+@fragment
+fn fs_textured_clipped(in: TexturedVertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    return shade_clipped(in.frag_pos, textured_shading(in), front_facing);
+}
@@ -0,0 +1,211 @@
+use cgmath::{InnerSpace, Matrix4, One, Quaternion, Vector3};
+
+// Animation //
+// The glTF node tree with each node's rest pose, the joints vertices are bound to (a
+// skin's joints, or the node of an animated mesh, which moves it rigidly), and the clips
+// that move the nodes. Posing a clip at some time overrides the animated
+// translations, rotations and scales, and each joint's matrix (the joint node's world
+// transform times its inverse bind matrix) goes to the GPU, where every vertex blends
+// up to four of them.
+
+#[derive(Clone, Copy)]
+pub enum LocalTransform {
+    // Nodes given as a matrix can't be animated
+    Matrix(Matrix4<f32>),
+    Trs { translation: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32> },
+}
+
+impl LocalTransform {
+    fn matrix(&self) -> Matrix4<f32> {
+        match *self {
+            LocalTransform::Matrix(matrix) => matrix,
+            LocalTransform::Trs { translation, rotation, scale } => {
+                Matrix4::from_translation(translation) * Matrix4::from(rotation) * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z)
+            }
+        }
+    }
+}
+
+pub struct RigNode {
+    pub children: Vec<usize>,
+    pub rest: LocalTransform,
+}
+
+// One entry of the joint palette the vertices' joint indices point into
+pub struct Joint {
+    // None for the identity entry that unskinned vertices use
+    pub node: Option<usize>,
+    pub inverse_bind: Matrix4<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    // Hermite splines through the keyframes, with in and out tangents around each value
+    CubicSpline,
+}
+
+// Keyframes for one property of one node; rotations are xyzw quaternions, the other
+// properties leave w unused. Cubic splines store in tangent, value and out tangent for
+// every keyframe.
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<[f32; 4]>,
+}
+
+pub struct Clip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+impl Clip {
+    // Seconds until the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.channels.iter().filter_map(|channel| channel.times.last()).fold(0.0, |a, &b| a.max(b))
+    }
+}
+
+pub struct Rig {
+    nodes: Vec<RigNode>,
+    // Parents before their children, so world transforms can be built in one pass
+    order: Vec<(usize, Option<usize>)>,
+    joints: Vec<Joint>,
+    pub clips: Vec<Clip>,
+    // Positions were multiplied by this on load, so translations have to be too
+    unit_scale: f32,
+}
+
+impl Rig {
+    pub fn new(nodes: Vec<RigNode>, roots: &[usize], joints: Vec<Joint>, clips: Vec<Clip>) -> Self {
+        // Each node is visited once, which also stops malformed files with cycles
+        let mut order = Vec::with_capacity(nodes.len());
+        let mut visited = vec![false; nodes.len()];
+        let mut stack: Vec<(usize, Option<usize>)> = roots.iter().rev().map(|&root| (root, None)).collect();
+        while let Some((node, parent)) = stack.pop() {
+            if node >= nodes.len() || std::mem::replace(&mut visited[node], true) {
+                continue;
+            }
+            order.push((node, parent));
+            stack.extend(nodes[node].children.iter().rev().map(|&child| (child, Some(node))));
+        }
+
+        Self { nodes, order, joints, clips, unit_scale: 1.0 }
+    }
+
+    pub fn set_unit_scale(&mut self, scale: f32) {
+        self.unit_scale = scale;
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    // The joint palette with `clip` posed at `time` seconds, or the rest pose without a clip
+    pub fn joint_matrices(&self, clip: Option<&Clip>, time: f32) -> Vec<Matrix4<f32>> {
+        let locals: Vec<Matrix4<f32>> = match clip {
+            Some(clip) => self.posed_locals(clip, time),
+            None => self.nodes.iter().map(|node| node.rest.matrix()).collect(),
+        };
+
+        let mut worlds = vec![Matrix4::one(); self.nodes.len()];
+        for &(node, parent) in &self.order {
+            worlds[node] = match parent {
+                Some(parent) => worlds[parent] * locals[node],
+                None => locals[node],
+            };
+        }
+
+        // Unit scaling is applied around the joint matrices, which work in file units
+        let scale = Matrix4::from_scale(self.unit_scale);
+        let unscale = Matrix4::from_scale(1.0 / self.unit_scale);
+        self.joints
+            .iter()
+            .map(|joint| match joint.node.and_then(|node| worlds.get(node)) {
+                Some(world) => scale * world * joint.inverse_bind * unscale,
+                None => Matrix4::one(),
+            })
+            .collect()
+    }
+
+    fn posed_locals(&self, clip: &Clip, time: f32) -> Vec<Matrix4<f32>> {
+        let mut poses: Vec<LocalTransform> = self.nodes.iter().map(|node| node.rest).collect();
+
+        for channel in &clip.channels {
+            let Some(LocalTransform::Trs { translation, rotation, scale }) = poses.get_mut(channel.node) else {
+                continue;
+            };
+            let Some(value) = channel.sample(time) else {
+                continue;
+            };
+            match channel.property {
+                Property::Translation => *translation = Vector3::new(value[0], value[1], value[2]),
+                Property::Rotation => *rotation = Quaternion::new(value[3], value[0], value[1], value[2]).normalize(),
+                Property::Scale => *scale = Vector3::new(value[0], value[1], value[2]),
+            }
+        }
+
+        poses.iter().map(LocalTransform::matrix).collect()
+    }
+}
+
+impl Channel {
+    // Holds the first and last keyframes outside their range
+    fn sample(&self, time: f32) -> Option<[f32; 4]> {
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.value(0);
+        }
+        if next >= self.times.len() || self.interpolation == Interpolation::Step {
+            return self.value(next - 1);
+        }
+
+        let (from, to) = (self.value(next - 1)?, self.value(next)?);
+        let span = self.times[next] - self.times[next - 1];
+        let amount = if span > 0.0 { (time - self.times[next - 1]) / span } else { 0.0 };
+        if self.interpolation == Interpolation::CubicSpline {
+            // Tangents are per second, so they're scaled by the keyframe spacing
+            let out_tangent = self.values.get((next - 1) * 3 + 2)?;
+            let in_tangent = self.values.get(next * 3)?;
+            let (t, t2, t3) = (amount, amount * amount, amount * amount * amount);
+            return Some(std::array::from_fn(|i| {
+                (2.0 * t3 - 3.0 * t2 + 1.0) * from[i]
+                    + (t3 - 2.0 * t2 + t) * span * out_tangent[i]
+                    + (-2.0 * t3 + 3.0 * t2) * to[i]
+                    + (t3 - t2) * span * in_tangent[i]
+            }));
+        }
+
+        Some(match self.property {
+            Property::Rotation => {
+                let from = Quaternion::new(from[3], from[0], from[1], from[2]);
+                let mut to = Quaternion::new(to[3], to[0], to[1], to[2]);
+                // The shorter way around
+                if from.dot(to) < 0.0 {
+                    to = -to;
+                }
+                let q = from.slerp(to, amount);
+                [q.v.x, q.v.y, q.v.z, q.s]
+            }
+            _ => std::array::from_fn(|i| from[i] + (to[i] - from[i]) * amount),
+        })
+    }
+
+    // The nth keyframe's value, skipping the tangents of cubic splines
+    fn value(&self, keyframe: usize) -> Option<[f32; 4]> {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values.get(keyframe * 3 + 1).copied(),
+            _ => self.values.get(keyframe).copied(),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use wgpu::util::StagingBelt;
+
+use crate::upload::DynamicBuffer;
+
+// Background //
+// What's behind the model: one color, or a vertical gradient drawn as a fullscreen quad
+// before the scene. Colors are sRGB like the hex on the command line and the panel's
+// color picker, and converted for the render target when it's an sRGB format.
+
+// The original blue, 0.1/0.2/0.3 on an sRGB target
+pub const DEFAULT_COLOR: [u8; 3] = [89, 124, 149];
+// How much darker the bottom of a new gradient starts out than its top
+const GRADIENT_DARKENING: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Background {
+    pub top: [u8; 3],
+    // A gradient from top to bottom when set, a single color otherwise
+    pub bottom: Option<[u8; 3]>,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self { top: DEFAULT_COLOR, bottom: None }
+    }
+}
+
+impl Background {
+    // `202030` for one color, `303050,101018` for a gradient from top to bottom
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.split_once(',') {
+            Some((top, bottom)) => Some(Self { top: parse_hex(top)?, bottom: Some(parse_hex(bottom)?) }),
+            None => Some(Self { top: parse_hex(value)?, bottom: None }),
+        }
+    }
+
+    // The same top color as a gradient into a darker shade of it, or back to one color
+    pub fn with_gradient(self, gradient: bool) -> Self {
+        let bottom = self.top.map(|c| (c as f32 * GRADIENT_DARKENING).round() as u8);
+        Self { bottom: gradient.then(|| self.bottom.unwrap_or(bottom)), ..self }
+    }
+
+    // Color the scene pass clears to; gradients are drawn over it
+    pub fn clear_color(&self, format: wgpu::TextureFormat) -> wgpu::Color {
+        let [r, g, b] = target_color(self.top, format);
+        wgpu::Color { r: r as f64, g: g as f64, b: b as f64, a: 1.0 }
+    }
+}
+
+fn parse_hex(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().trim_start_matches('#');
+    if value.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(value.get(i * 2..i * 2 + 2)?, 16).ok();
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+// sRGB bytes as the values to write to a target of `format`
+fn target_color(color: [u8; 3], format: wgpu::TextureFormat) -> [f32; 3] {
+    color.map(|c| {
+        let c = c as f32 / 255.0;
+        if !format.is_srgb() {
+            c
+        } else if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct BackgroundVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl BackgroundVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<BackgroundVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+pub struct BackgroundPass {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: DynamicBuffer,
+    format: wgpu::TextureFormat,
+    // What the vertex buffer holds, None until the first set
+    current: Option<Background>,
+}
+
+impl BackgroundPass {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, cache: Option<&wgpu::PipelineCache>) -> Self {
+        // Clip space positions with a color each, the same as the frame graph
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../overlay.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Background Pipeline"),
+            layout: Some(&layout),
+            cache,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[BackgroundVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Drawn first inside the scene pass, leaving the cleared depth for the model
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        let vertex_buffer = DynamicBuffer::new(
+            device,
+            "Background Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            (6 * std::mem::size_of::<BackgroundVertex>()) as wgpu::BufferAddress,
+        );
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            format: color_format,
+            current: None,
+        }
+    }
+
+    // Rewrites the quad when the background changed; call before the render pass that draws it
+    pub fn set(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, background: Background) {
+        if self.current == Some(background) {
+            return;
+        }
+        self.current = Some(background);
+        let Some(bottom) = background.bottom else {
+            return;
+        };
+
+        let top = target_color(background.top, self.format);
+        let bottom = target_color(bottom, self.format);
+        let vertex = |x: f32, y: f32, [r, g, b]: [f32; 3]| BackgroundVertex { position: [x, y], color: [r, g, b, 1.0] };
+        let vertices = [
+            vertex(-1.0, -1.0, bottom),
+            vertex(1.0, -1.0, bottom),
+            vertex(1.0, 1.0, top),
+            vertex(-1.0, -1.0, bottom),
+            vertex(1.0, 1.0, top),
+            vertex(-1.0, 1.0, top),
+        ];
+        self.vertex_buffer.write(device, encoder, belt, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    // Only gradients need drawing, a single color is the clear color
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.current.is_none_or(|background| background.bottom.is_none()) {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::{HashMap, HashSet};
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector3;
+use wgpu::util::{DrawIndexedIndirectArgs, StagingBelt};
+
+use crate::frustum::Frustum;
+use crate::palette::distinct_color;
+use crate::parse::{Material, Mesh, SubMesh};
+use crate::texture::{self, MaterialSlot, Sampling, TextureImage, MATERIAL_SLOTS};
+use crate::upload::DynamicBuffer;
+
+// Draw batching //
+// Every primitive of a file shares one vertex and index buffer and becomes one
+// indirect draw; its instance index picks the per-draw data. Draws are grouped by
+// their material's textures so each group goes out as a single
+// multi_draw_indexed_indirect where the GPU allows it. With --grid every draw has one
+// instance per copy of the model, each with its own per-draw data.
+
+pub const DEFAULT_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct DrawData {
+    color: [f32; 4],
+    // Metallic, roughness, normal scale and occlusion strength
+    factors: [f32; 4],
+    emissive: [f32; 4],
+    // Added to the positions, in model space
+    offset: [f32; 4],
+}
+
+impl DrawData {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![1 => Float32x4, 6 => Float32x4, 7 => Float32x4, 8 => Float32x4];
+
+    // The normal scale and occlusion strength are zeroed without their textures, which
+    // tells the shader to leave the normal and the ambient alone. The emissive color's
+    // fourth channel marks unlit materials.
+    fn new(color: [f32; 4], material: &Material) -> Self {
+        let [r, g, b] = material.emissive;
+        Self {
+            color,
+            factors: [
+                material.metallic,
+                material.roughness,
+                if material.normal_texture.is_some() { material.normal_scale } else { 0.0 },
+                if material.occlusion_texture.is_some() { material.occlusion_strength } else { 0.0 },
+            ],
+            emissive: [r, g, b, if material.unlit { 1.0 } else { 0.0 }],
+            offset: [0.0; 4],
+        }
+    }
+
+    fn offset_by(self, [x, y, z]: [f32; 3]) -> Self {
+        Self { offset: [x, y, z, 0.0], ..self }
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DrawData>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// Indices into Mesh::textures for each MaterialSlot
+type MaterialTextures = [Option<usize>; MATERIAL_SLOTS];
+
+fn material_textures(submesh: &SubMesh) -> MaterialTextures {
+    let material = &submesh.material;
+    [submesh.texture, material.metallic_roughness_texture, material.normal_texture, material.occlusion_texture, material.emissive_texture]
+}
+
+// Corners of each submesh's bounds, None for one without triangles
+pub type PartBounds = Option<([f32; 3], [f32; 3])>;
+
+pub fn part_bounds(mesh: &Mesh) -> Vec<PartBounds> {
+    mesh.submeshes
+        .iter()
+        .map(|submesh| {
+            let range = submesh.first_index as usize..(submesh.first_index + submesh.index_count) as usize;
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for vertex in mesh.indices.get(range)?.iter().filter_map(|&i| mesh.vertices.get(i as usize)) {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(vertex[axis]);
+                    max[axis] = max[axis].max(vertex[axis]);
+                }
+            }
+            (min[0] <= max[0]).then_some((min, max))
+        })
+        .collect()
+}
+
+fn middle((min, max): ([f32; 3], [f32; 3])) -> [f32; 3] {
+    [0, 1, 2].map(|axis| (min[axis] + max[axis]) * 0.5)
+}
+
+// Consecutive draws sharing the same textures
+struct DrawRun {
+    bind_group: wgpu::BindGroup,
+    first_draw: u32,
+    draw_count: u32,
+}
+
+pub struct DrawBatch {
+    instance_buffer: DynamicBuffer,
+    indirect_buffer: DynamicBuffer,
+    // Sorted by textures, with each draw's position in the mesh's submesh list
+    draws: Vec<(usize, SubMesh)>,
+    // Color draws by part instead of by material
+    by_part: bool,
+    // Per submesh in file order, and the middle of them all; parts explode away from it
+    part_bounds: Vec<PartBounds>,
+    parts_middle: [f32; 3],
+    explode: f32,
+    // Copies of the model along X and Z, and how far apart they are in model units
+    grid: (u32, u32),
+    grid_spacing: f32,
+    // The last grid too big for the instance buffer, so it's only reported once
+    rejected_grid: (u32, u32),
+    // Parts left out of the draws, by their position in the file
+    hidden: HashSet<usize>,
+    // Parts outside the view, left out the same way
+    culled: HashSet<usize>,
+    runs: Vec<DrawRun>,
+    texture_layout: wgpu::BindGroupLayout,
+    // Created as textures ask for them, shared between textures sampled the same way
+    samplers: HashMap<Sampling, wgpu::Sampler>,
+    // Stands in for every missing texture; bound in full for untextured draws so every
+    // pipeline sees the same bind groups
+    white_texture: wgpu::TextureView,
+    white_bind_group: wgpu::BindGroup,
+    // Indirect draws need a non-zero first_instance, which is its own feature
+    multi_draw: bool,
+}
+
+impl DrawBatch {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let instance_buffer = DynamicBuffer::new(
+            device,
+            "Draw Data Buffer",
+            wgpu::BufferUsages::VERTEX,
+            std::mem::size_of::<DrawData>() as wgpu::BufferAddress,
+        );
+        queue.write_buffer(&instance_buffer.buffer, 0, bytemuck::bytes_of(&DrawData::new(DEFAULT_COLOR, &Material::default())));
+
+        let indirect_buffer = DynamicBuffer::new(device, "Indirect Draw Buffer", wgpu::BufferUsages::INDIRECT, 0);
+
+        let texture_layout = texture::bind_group_layout(device);
+        let sampler = Sampling::default().create_sampler(device);
+        let white_texture = texture::white_texture(device, queue).create_view(&wgpu::TextureViewDescriptor::default());
+        let white_bind_group = texture::create_bind_group(device, &texture_layout, [(&white_texture, &sampler); MATERIAL_SLOTS]);
+
+        Self {
+            instance_buffer,
+            indirect_buffer,
+            draws: Vec::new(),
+            by_part: false,
+            part_bounds: Vec::new(),
+            parts_middle: [0.0; 3],
+            explode: 0.0,
+            grid: (1, 1),
+            grid_spacing: 0.0,
+            rejected_grid: (1, 1),
+            hidden: HashSet::new(),
+            culled: HashSet::new(),
+            runs: Vec::new(),
+            texture_layout,
+            samplers: HashMap::from([(Sampling::default(), sampler)]),
+            white_texture,
+            white_bind_group,
+            multi_draw: device.features().contains(Self::features()),
+        }
+    }
+
+    // Optional device features that let draw() use a single multi-draw call per material,
+    // and that let textures stay block-compressed on the GPU
+    pub fn features() -> wgpu::Features {
+        wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE | wgpu::Features::TEXTURE_COMPRESSION_BC
+    }
+
+    // Group 1 of every scene pipeline
+    pub fn texture_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.texture_layout
+    }
+
+    // Replaces the draw list; an empty list draws all indices with the default color
+    pub fn set(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut StagingBelt,
+        submeshes: &[SubMesh],
+        textures: &[TextureImage],
+    ) {
+        for image in textures {
+            self.samplers.entry(image.sampling).or_insert_with(|| image.sampling.create_sampler(device));
+        }
+        // By texture and whether it's read as a color, None when it couldn't be uploaded
+        let mut uploaded = HashMap::new();
+
+        self.draws = submeshes.iter().cloned().enumerate().collect();
+        self.draws.sort_by_key(|(_, draw)| material_textures(draw));
+
+        self.runs.clear();
+        let mut run_textures = None;
+        for (i, (_, draw)) in self.draws.iter().enumerate() {
+            let draw_textures = material_textures(draw);
+            match self.runs.last_mut() {
+                Some(run) if run_textures == Some(draw_textures) => run.draw_count += 1,
+                _ => {
+                    self.runs.push(DrawRun {
+                        bind_group: self.material_bind_group(device, queue, textures, &mut uploaded, draw_textures),
+                        first_draw: i as u32,
+                        draw_count: 1,
+                    });
+                    run_textures = Some(draw_textures);
+                }
+            }
+        }
+
+        self.write_draw_data(device, encoder, belt);
+        self.hidden.clear();
+        self.culled.clear();
+        self.write_indirect_args(device, encoder, belt);
+
+        if submeshes.len() > 1 {
+            println!("Draws: {} primitives in {}", submeshes.len(),
+                     if self.multi_draw { format!("{} indirect multi-draws", self.runs.len()) } else { "separate draw calls".to_string() });
+        }
+    }
+
+    fn material_bind_group(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[TextureImage],
+        uploaded: &mut HashMap<(usize, bool), Option<wgpu::TextureView>>,
+        textures: MaterialTextures,
+    ) -> wgpu::BindGroup {
+        let views: Vec<Option<(wgpu::TextureView, &wgpu::Sampler)>> = MaterialSlot::ALL
+            .iter()
+            .zip(textures)
+            .map(|(&slot, texture)| {
+                let image = images.get(texture?)?;
+                let view = uploaded
+                    .entry((texture?, slot.is_color()))
+                    .or_insert_with(|| {
+                        let texture = texture::upload_texture(device, queue, image, slot.is_color())?;
+                        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+                    })
+                    .clone()?;
+                Some((view, &self.samplers[&image.sampling]))
+            })
+            .collect();
+        let default_sampler = &self.samplers[&Sampling::default()];
+        let slots = std::array::from_fn(|slot| match &views[slot] {
+            Some((view, sampler)) => (view, *sampler),
+            None => (&self.white_texture, default_sampler),
+        });
+        texture::create_bind_group(device, &self.texture_layout, slots)
+    }
+
+    pub fn part_count(&self) -> usize {
+        self.draws.len()
+    }
+
+    // Part names with the colors they get when coloring by part, in file order
+    pub fn legend(&self) -> Vec<(String, [f32; 3])> {
+        let mut parts: Vec<&(usize, SubMesh)> = self.draws.iter().collect();
+        parts.sort_by_key(|(part, _)| *part);
+        parts
+            .into_iter()
+            .map(|(part, submesh)| {
+                let name = if submesh.name.is_empty() { format!("part {}", part + 1) } else { submesh.name.clone() };
+                (name, distinct_color(*part))
+            })
+            .collect()
+    }
+
+    // Switches between material colors and a distinct color per part
+    pub fn color_by_part(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, by_part: bool) {
+        if by_part != self.by_part {
+            self.by_part = by_part;
+            self.write_draw_data(device, encoder, belt);
+        }
+    }
+
+    // Per submesh, from part_bounds; taken up by the next set, without them the parts
+    // stay in place when exploded and can't be framed
+    pub fn set_part_bounds(&mut self, bounds: Vec<PartBounds>) {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for (part_min, part_max) in bounds.iter().flatten() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(part_min[axis]);
+                max[axis] = max[axis].max(part_max[axis]);
+            }
+        }
+        self.parts_middle = if min[0] <= max[0] { middle((min, max)) } else { [0.0; 3] };
+        self.part_bounds = bounds;
+    }
+
+    // How far the part is moved by the exploded view
+    fn explode_offset(&self, part: usize) -> [f32; 3] {
+        match self.part_bounds.get(part).copied().flatten() {
+            Some(bounds) => {
+                let center = middle(bounds);
+                [0, 1, 2].map(|axis| (center[axis] - self.parts_middle[axis]) * self.explode)
+            }
+            None => [0.0; 3],
+        }
+    }
+
+    // Where the part is drawn, exploded or not, and its largest dimension
+    pub fn part_frame(&self, part: usize) -> Option<([f32; 3], f32)> {
+        let (min, max) = self.part_bounds.get(part).copied().flatten()?;
+        let offset = self.explode_offset(part);
+        let center = middle((min, max));
+        let size = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+        Some(([0, 1, 2].map(|axis| center[axis] + offset[axis]), size))
+    }
+
+    // Moves each part away from the middle by `amount` times its distance from it
+    pub fn set_explode(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, amount: f32) {
+        if amount != self.explode {
+            self.explode = amount;
+            self.write_draw_data(device, encoder, belt);
+        }
+    }
+
+    // Leaves these parts, by their position in the file, out of the draws
+    pub fn set_hidden(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, hidden: &HashSet<usize>) {
+        if *hidden != self.hidden {
+            self.hidden = hidden.clone();
+            self.write_indirect_args(device, encoder, belt);
+        }
+    }
+
+    // Parts whose bounds, exploded or not, are entirely outside the frustum; parts
+    // without bounds are always drawn
+    pub fn parts_outside(&self, frustum: &Frustum) -> HashSet<usize> {
+        self.draws
+            .iter()
+            .map(|&(part, _)| part)
+            .filter(|&part| {
+                self.part_bounds.get(part).copied().flatten().is_some_and(|(min, max)| {
+                    let offset = self.explode_offset(part);
+                    let center = Vector3::from(middle((min, max))) + Vector3::from(offset);
+                    let radius = (0..3).map(|axis| (max[axis] - min[axis]).powi(2)).sum::<f32>().sqrt() * 0.5;
+                    !frustum.intersects_sphere(center, radius)
+                })
+            })
+            .collect()
+    }
+
+    // Leaves these parts out of the draws until the next call; culling is kept apart
+    // from hiding so neither undoes the other
+    pub fn set_culled(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, culled: &HashSet<usize>) {
+        if *culled != self.culled {
+            self.culled = culled.clone();
+            self.write_indirect_args(device, encoder, belt);
+        }
+    }
+
+    // Draws every part `columns` x `rows` times, `spacing` apart along X and Z
+    pub fn set_grid(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, grid: (u32, u32), spacing: f32) {
+        if grid != self.grid || spacing != self.grid_spacing {
+            // Every part needs an instance per copy, and instance indices are u32
+            let instances = (self.draws.len().max(1) as u64).saturating_mul(grid.0 as u64 * grid.1 as u64);
+            let size = instances.saturating_mul(std::mem::size_of::<DrawData>() as u64);
+            if instances > u32::MAX as u64 || size > device.limits().max_buffer_size {
+                if grid != self.rejected_grid {
+                    eprintln!("Grid: {}x{} copies of {} parts don't fit in one instance buffer, drawing a single copy",
+                              grid.0, grid.1, self.draws.len().max(1));
+                    self.rejected_grid = grid;
+                }
+                return;
+            }
+            self.grid = grid;
+            self.grid_spacing = spacing;
+            self.write_draw_data(device, encoder, belt);
+            self.write_indirect_args(device, encoder, belt);
+        }
+    }
+
+    fn instances(&self) -> u32 {
+        self.grid.0 * self.grid.1
+    }
+
+    // Where each copy of the model goes, the first one at the model's own place
+    fn grid_offsets(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        let (columns, rows) = self.grid;
+        (0..rows).flat_map(move |row| (0..columns).map(move |column| [column as f32 * self.grid_spacing, 0.0, row as f32 * self.grid_spacing]))
+    }
+
+    // Hidden and culled parts keep their slot with no instances, so the runs stay as they are
+    fn write_indirect_args(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt) {
+        if !self.multi_draw || self.draws.is_empty() {
+            return;
+        }
+
+        let instances = self.instances();
+        let mut args = Vec::with_capacity(self.draws.len() * std::mem::size_of::<DrawIndexedIndirectArgs>());
+        for (i, (part, submesh)) in self.draws.iter().enumerate() {
+            let drawn = !(self.hidden.contains(part) || self.culled.contains(part));
+            args.extend_from_slice(DrawIndexedIndirectArgs {
+                index_count: submesh.index_count,
+                instance_count: if drawn { instances } else { 0 },
+                first_index: submesh.first_index,
+                base_vertex: 0,
+                first_instance: i as u32 * instances,
+            }.as_bytes());
+        }
+        self.indirect_buffer.write(device, encoder, belt, 0, &args);
+    }
+
+    fn write_draw_data(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt) {
+        let draw_data: Vec<DrawData> = if self.draws.is_empty() {
+            let draw_data = DrawData::new(DEFAULT_COLOR, &Material::default());
+            self.grid_offsets().map(|offset| draw_data.offset_by(offset)).collect()
+        } else {
+            self.draws
+                .iter()
+                .flat_map(|(part, submesh)| {
+                    // Parts are shown in the default material, so only their colors tell them apart
+                    let draw_data = if self.by_part {
+                        let [r, g, b] = distinct_color(*part);
+                        DrawData::new([r, g, b, 1.0], &Material::default())
+                    } else {
+                        DrawData::new(submesh.color, &submesh.material)
+                    };
+                    let explode = self.explode_offset(*part);
+                    self.grid_offsets().map(move |offset| draw_data.offset_by([0, 1, 2].map(|axis| explode[axis] + offset[axis])))
+                })
+                .collect()
+        };
+        self.instance_buffer.write(device, encoder, belt, 0, bytemuck::cast_slice(&draw_data));
+    }
+
+    // Expects the pipeline and the vertex and index buffers to be bound already
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, num_indices: u32) {
+        render_pass.set_vertex_buffer(1, self.instance_buffer.buffer.slice(..));
+
+        if self.draws.is_empty() {
+            render_pass.set_bind_group(1, &self.white_bind_group, &[]);
+            render_pass.draw_indexed(0..num_indices, 0, 0..self.instances());
+            return;
+        }
+
+        for run in &self.runs {
+            render_pass.set_bind_group(1, &run.bind_group, &[]);
+
+            if self.multi_draw {
+                let offset = run.first_draw as wgpu::BufferAddress * std::mem::size_of::<DrawIndexedIndirectArgs>() as wgpu::BufferAddress;
+                render_pass.multi_draw_indexed_indirect(&self.indirect_buffer.buffer, offset, run.draw_count);
+            } else {
+                for i in run.first_draw..run.first_draw + run.draw_count {
+                    let (part, draw) = &self.draws[i as usize];
+                    if self.hidden.contains(part) || self.culled.contains(part) {
+                        continue;
+                    }
+                    let instances = self.instances();
+                    render_pass.draw_indexed(draw.first_index..draw.first_index + draw.index_count, 0, i * instances..(i + 1) * instances);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use serde::Deserialize;
+
+use crate::parse::Mesh;
+use crate::simplify;
+
+// Asset budgets //
+// Limits a team sets on what a model may cost to render. Models over budget are
+// flagged on load and can be brought under it: the mesh by clustering it down to the
+// triangle limit, textures by dropping mip levels until they fit.
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Budget {
+    pub max_triangles: Option<usize>,
+    // Largest allowed width or height of any texture, in pixels
+    pub max_texture_size: Option<u32>,
+}
+
+impl Budget {
+    // One line per exceeded limit, empty when the mesh fits
+    pub fn check(&self, mesh: &Mesh) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        let triangles = mesh.indices.len() / 3;
+        if let Some(max) = self.max_triangles && triangles > max {
+            violations.push(format!("{} triangles, budget is {}", triangles, max));
+        }
+
+        if let Some(max) = self.max_texture_size {
+            for (i, texture) in mesh.textures.iter().enumerate() {
+                if texture.width.max(texture.height) > max {
+                    violations.push(format!("texture {} is {}x{}, budget is {}", i, texture.width, texture.height, max));
+                }
+            }
+        }
+
+        violations
+    }
+
+    // Simplifies the mesh and shrinks its textures to fit; a decimated mesh loses its
+    // UVs and materials like the preview does. Textures without smaller mips stay as they are.
+    pub fn fit(&self, mesh: &mut Mesh) {
+        if let Some(max) = self.max_triangles && mesh.indices.len() / 3 > max {
+            *mesh = simplify::cluster_decimate(mesh, max);
+        }
+
+        if let Some(max) = self.max_texture_size {
+            for texture in &mut mesh.textures {
+                while texture.width.max(texture.height) > max && texture.levels.len() > 1 {
+                    texture.levels.remove(0);
+                    texture.width = (texture.width / 2).max(1);
+                    texture.height = (texture.height / 2).max(1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use cgmath::{InnerSpace, Vector3};
+
+// Bounding volume hierarchy //
+// Binary tree of axis-aligned boxes over the mesh triangles, used to answer ray
+// queries without testing every triangle. Built once per loaded mesh.
+
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+struct Node {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    // Leaves: first entry in `triangles`; inner nodes: index of the left child (right is +1)
+    first: u32,
+    // Number of triangles, zero for inner nodes
+    count: u32,
+}
+
+pub struct Hit {
+    pub triangle: u32,
+    pub t: f32,
+    // Weights of the triangle's second and third corner, the first gets 1 - u - v
+    pub barycentric: [f32; 2],
+    pub point: Vector3<f32>,
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // Triangle ids in leaf order
+    triangles: Vec<u32>,
+    vertices: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+impl Bvh {
+    pub fn build(vertices: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        // Drop triangles pointing outside the vertex list instead of panicking later
+        let kept: Vec<u32> = (0..indices.len() as u32 / 3)
+            .filter(|&t| (0..3).all(|c| (indices[t as usize * 3 + c] as usize) < vertices.len()))
+            .collect();
+
+        let centroids: Vec<Vector3<f32>> = kept
+            .iter()
+            .map(|&t| {
+                let [a, b, c] = Self::corners_of(&vertices, &indices, t);
+                (a + b + c) / 3.0
+            })
+            .collect();
+
+        // Sorted as positions into `kept`, turned back into triangle ids once built
+        let mut triangles: Vec<u32> = (0..kept.len() as u32).collect();
+
+        let mut nodes = Vec::with_capacity(triangles.len() / MAX_LEAF_TRIANGLES * 2 + 1);
+        nodes.push(Node {
+            min: Vector3::new(0.0, 0.0, 0.0),
+            max: Vector3::new(0.0, 0.0, 0.0),
+            first: 0,
+            count: triangles.len() as u32,
+        });
+
+        // Iterative so very large meshes can't overflow the stack
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let first = nodes[node_index].first as usize;
+            let count = nodes[node_index].count as usize;
+            let range = &mut triangles[first..first + count];
+
+            let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+            let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+            let mut centroid_min = min;
+            let mut centroid_max = max;
+            for &t in range.iter() {
+                for corner in Self::corners_of(&vertices, &indices, kept[t as usize]) {
+                    min = min_vec(min, corner);
+                    max = max_vec(max, corner);
+                }
+                centroid_min = min_vec(centroid_min, centroids[t as usize]);
+                centroid_max = max_vec(centroid_max, centroids[t as usize]);
+            }
+            nodes[node_index].min = min;
+            nodes[node_index].max = max;
+
+            if count <= MAX_LEAF_TRIANGLES {
+                continue;
+            }
+
+            // Median split along the axis where the centroids spread the most
+            let spread = centroid_max - centroid_min;
+            let axis = if spread.x >= spread.y && spread.x >= spread.z { 0 } else if spread.y >= spread.z { 1 } else { 2 };
+            let mid = count / 2;
+            range.select_nth_unstable_by(mid, |&a, &b| {
+                centroids[a as usize][axis].total_cmp(&centroids[b as usize][axis])
+            });
+
+            let left = nodes.len();
+            for (child_first, child_count) in [(first, mid), (first + mid, count - mid)] {
+                nodes.push(Node {
+                    min,
+                    max,
+                    first: child_first as u32,
+                    count: child_count as u32,
+                });
+            }
+
+            nodes[node_index].first = left as u32;
+            nodes[node_index].count = 0;
+            stack.push(left);
+            stack.push(left + 1);
+        }
+
+        Self {
+            nodes,
+            triangles: triangles.into_iter().map(|t| kept[t as usize]).collect(),
+            vertices,
+            indices,
+        }
+    }
+
+    fn corners_of(vertices: &[[f32; 3]], indices: &[u32], triangle: u32) -> [Vector3<f32>; 3] {
+        let base = triangle as usize * 3;
+        [0, 1, 2].map(|c| Vector3::from(vertices[indices[base + c] as usize]))
+    }
+
+    // Corner positions of a triangle, as returned in Hit::triangle
+    pub fn triangle(&self, triangle: u32) -> [Vector3<f32>; 3] {
+        Self::corners_of(&self.vertices, &self.indices, triangle)
+    }
+
+    pub fn vertex(&self, index: u32) -> [f32; 3] {
+        self.vertices[index as usize]
+    }
+
+    // Indices of a triangle's corners into the mesh's vertices
+    pub fn corner_indices(&self, triangle: u32) -> [u32; 3] {
+        let base = triangle as usize * 3;
+        [0, 1, 2].map(|c| self.indices[base + c])
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // Box around the whole mesh, None when it has no triangles
+    pub fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        (!self.triangles.is_empty()).then(|| (self.nodes[0].min, self.nodes[0].max))
+    }
+
+    // Triangles with corners on both sides of the plane dot(normal, p) = distance, plus
+    // any with a corner on it
+    pub fn triangles_crossing_plane(&self, normal: Vector3<f32>, distance: f32) -> Vec<u32> {
+        let mut crossing = Vec::new();
+        if self.triangles.is_empty() {
+            return crossing;
+        }
+
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            // The box straddles the plane when its extent along the normal does
+            let center = (node.min + node.max) * 0.5;
+            let half = (node.max - node.min) * 0.5;
+            let radius = half.x * normal.x.abs() + half.y * normal.y.abs() + half.z * normal.z.abs();
+            if (normal.dot(center) - distance).abs() > radius {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.first as usize);
+                stack.push(node.first as usize + 1);
+                continue;
+            }
+
+            for &triangle in &self.triangles[node.first as usize..(node.first + node.count) as usize] {
+                let sides = Self::corners_of(&self.vertices, &self.indices, triangle).map(|c| normal.dot(c) - distance);
+                if sides.iter().any(|&d| d <= 0.0) && sides.iter().any(|&d| d >= 0.0) {
+                    crossing.push(triangle);
+                }
+            }
+        }
+
+        crossing
+    }
+
+    // Closest hit along the ray, if any; `direction` does not need to be normalized
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<Hit> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+
+        let inv_direction = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut best: Option<Hit> = None;
+        let mut stack = vec![0usize];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let limit = best.as_ref().map_or(f32::MAX, |hit| hit.t);
+
+            match ray_box(origin, inv_direction, node.min, node.max) {
+                Some(t) if t <= limit => {}
+                _ => continue,
+            }
+
+            if node.count == 0 {
+                let left = node.first as usize;
+                // Visit the nearer child first so later boxes can be culled by the best hit
+                let near_left = ray_box(origin, inv_direction, self.nodes[left].min, self.nodes[left].max);
+                let near_right = ray_box(origin, inv_direction, self.nodes[left + 1].min, self.nodes[left + 1].max);
+                if near_left.unwrap_or(f32::MAX) <= near_right.unwrap_or(f32::MAX) {
+                    stack.push(left + 1);
+                    stack.push(left);
+                } else {
+                    stack.push(left);
+                    stack.push(left + 1);
+                }
+                continue;
+            }
+
+            for &triangle in &self.triangles[node.first as usize..(node.first + node.count) as usize] {
+                let corners = Self::corners_of(&self.vertices, &self.indices, triangle);
+                if let Some((t, u, v)) = ray_triangle(origin, direction, corners)
+                    && t < best.as_ref().map_or(f32::MAX, |hit| hit.t) {
+                    best = Some(Hit {
+                        triangle,
+                        t,
+                        barycentric: [u, v],
+                        point: origin + direction * t,
+                    });
+                }
+            }
+        }
+
+        best
+    }
+
+    // Nearest point on the surface to `point`, with the triangle it lies on
+    pub fn closest_point(&self, point: Vector3<f32>) -> Option<(u32, Vector3<f32>)> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+
+        let mut best: Option<(u32, Vector3<f32>)> = None;
+        let mut best_distance2 = f32::MAX;
+        let mut stack = vec![0usize];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if box_distance2(point, node.min, node.max) > best_distance2 {
+                continue;
+            }
+
+            if node.count == 0 {
+                let left = node.first as usize;
+                // Nearer child first, it's the likelier one to shrink the search radius
+                let left_distance2 = box_distance2(point, self.nodes[left].min, self.nodes[left].max);
+                let right_distance2 = box_distance2(point, self.nodes[left + 1].min, self.nodes[left + 1].max);
+                if left_distance2 <= right_distance2 {
+                    stack.push(left + 1);
+                    stack.push(left);
+                } else {
+                    stack.push(left);
+                    stack.push(left + 1);
+                }
+                continue;
+            }
+
+            for &triangle in &self.triangles[node.first as usize..(node.first + node.count) as usize] {
+                let corners = Self::corners_of(&self.vertices, &self.indices, triangle);
+                let closest = closest_point_on_triangle(point, corners);
+                let distance2 = (closest - point).magnitude2();
+                if distance2 < best_distance2 {
+                    best_distance2 = distance2;
+                    best = Some((triangle, closest));
+                }
+            }
+        }
+
+        best
+    }
+}
+
+fn min_vec(a: Vector3<f32>, b: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn max_vec(a: Vector3<f32>, b: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+// Slab test, returns the entry distance when the ray touches the box
+fn ray_box(origin: Vector3<f32>, inv_direction: Vector3<f32>, min: Vector3<f32>, max: Vector3<f32>) -> Option<f32> {
+    let mut t_min: f32 = 0.0;
+    let mut t_max = f32::MAX;
+
+    for axis in 0..3 {
+        let t1 = (min[axis] - origin[axis]) * inv_direction[axis];
+        let t2 = (max[axis] - origin[axis]) * inv_direction[axis];
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+    }
+
+    (t_min <= t_max).then_some(t_min)
+}
+
+// Möller–Trumbore, hits from either side count since picking shouldn't depend on winding
+fn ray_triangle(origin: Vector3<f32>, direction: Vector3<f32>, [a, b, c]: [Vector3<f32>; 3]) -> Option<(f32, f32, f32)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < f32::EPSILON * edge1.magnitude() * edge2.magnitude() {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inv_det;
+    (t > 0.0).then_some((t, u, v))
+}
+
+// Squared distance from a point to a box, zero inside it
+fn box_distance2(point: Vector3<f32>, min: Vector3<f32>, max: Vector3<f32>) -> f32 {
+    let outside = max_vec(max_vec(min - point, point - max), Vector3::new(0.0, 0.0, 0.0));
+    outside.magnitude2()
+}
+
+// Ericson, Real-Time Collision Detection 5.1.5: find the Voronoi region of the point
+fn closest_point_on_triangle(p: Vector3<f32>, [a, b, c]: [Vector3<f32>; 3]) -> Vector3<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // Inside the face; a degenerate triangle gets a zero denominator, fall back to a corner
+    let denominator = va + vb + vc;
+    if denominator == 0.0 {
+        return a;
+    }
+    let v = vb / denominator;
+    let w = vc / denominator;
+    a + ab * v + ac * w
+}
@@ -0,0 +1,48 @@
+use cgmath::{InnerSpace, Vector3};
+use serde::{Deserialize, Serialize};
+
+// Clipping plane //
+// A plane facing any way that cuts away the part of the model in front of it, so the
+// inside can be looked at. The fragment shader discards what's cut and shades the
+// inside surfaces it opens up; unlike the section plane it measures nothing, so it
+// doesn't wait for the BVH. Set from the panel as an angle around Y, a tilt up from the
+// XZ plane and an offset through the model's bounds.
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipPlane {
+    pub enabled: bool,
+    // Direction of the normal, which points at the part that's cut away: degrees around
+    // Y from +Z toward +X, then up toward +Y
+    pub angle: f32,
+    pub tilt: f32,
+    // Where the plane crosses the bounds along its normal, from -1 (all cut) to 1 (none)
+    pub offset: f32,
+}
+
+impl Default for ClipPlane {
+    fn default() -> Self {
+        Self { enabled: false, angle: 0.0, tilt: 0.0, offset: 0.0 }
+    }
+}
+
+impl ClipPlane {
+    pub fn normal(&self) -> Vector3<f32> {
+        let (angle, tilt) = (self.angle.to_radians(), self.tilt.to_radians());
+        Vector3::new(tilt.cos() * angle.sin(), tilt.sin(), tilt.cos() * angle.cos())
+    }
+
+    // Normal and distance along it in model space for a model with these bounds, all
+    // zero when disabled, which cuts nothing
+    pub fn equation(&self, min: Vector3<f32>, max: Vector3<f32>) -> [f32; 4] {
+        if !self.enabled {
+            return [0.0; 4];
+        }
+
+        let normal = self.normal().normalize();
+        // How far the box reaches along the normal either side of its middle
+        let middle = normal.dot((min + max) * 0.5);
+        let reach = normal.x.abs() * (max.x - min.x) * 0.5 + normal.y.abs() * (max.y - min.y) * 0.5 + normal.z.abs() * (max.z - min.z) * 0.5;
+        normal.extend(middle + self.offset * reach).into()
+    }
+}
@@ -0,0 +1,131 @@
+use crate::palette::distinct_color;
+use crate::parse::Mesh;
+use crate::repair;
+
+// Connected components //
+// Shells of triangles connected through shared positions, so UV and normal seams
+// don't split a part in two. Scans tend to come with a large main shell and a
+// scatter of small floating debris, which can be coloured, isolated or removed.
+
+// Shells under this fraction of the largest one count as debris
+pub const DEBRIS_FRACTION: f64 = 0.01;
+// Sizes listed in the summary before the rest is elided
+const SUMMARY_SHELLS: usize = 5;
+
+pub struct Components {
+    // Shell of every vertex, numbered from the largest shell down; u32::MAX for unused vertices
+    pub vertex_shell: Vec<u32>,
+    // Triangles in each shell, largest first
+    pub shell_triangles: Vec<usize>,
+}
+
+impl Components {
+    // Indices must be in range
+    pub fn compute(vertices: &[[f32; 3]], indices: &[u32]) -> Self {
+        let welded = repair::weld_positions(vertices);
+        let mut parent: Vec<u32> = (0..welded.iter().map(|&id| id + 1).max().unwrap_or(0)).collect();
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| welded[triangle[corner] as usize]);
+            union(&mut parent, a, b);
+            union(&mut parent, a, c);
+        }
+
+        let mut triangles_per_root = vec![0usize; parent.len()];
+        for triangle in indices.chunks_exact(3) {
+            triangles_per_root[find(&mut parent, welded[triangle[0] as usize]) as usize] += 1;
+        }
+
+        // Number shells by size, ties broken by root so the order is stable between runs
+        let mut roots: Vec<usize> = (0..parent.len()).filter(|&root| triangles_per_root[root] > 0).collect();
+        roots.sort_unstable_by_key(|&root| (std::cmp::Reverse(triangles_per_root[root]), root));
+        let mut rank = vec![u32::MAX; parent.len()];
+        for (shell, &root) in roots.iter().enumerate() {
+            rank[root] = shell as u32;
+        }
+
+        let vertex_shell = welded.iter().map(|&id| rank[find(&mut parent, id) as usize]).collect();
+        let shell_triangles = roots.iter().map(|&root| triangles_per_root[root]).collect();
+
+        Self {
+            vertex_shell,
+            shell_triangles,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.shell_triangles.len()
+    }
+
+    // Shells smaller than this are debris
+    pub fn debris_threshold(&self) -> usize {
+        let largest = self.shell_triangles.first().copied().unwrap_or(0);
+        ((largest as f64 * DEBRIS_FRACTION).ceil() as usize).max(1)
+    }
+
+    // Per-vertex RGBA8 colors, a different hue for every shell
+    pub fn colors(&self) -> Vec<[u8; 4]> {
+        self.vertex_shell
+            .iter()
+            .map(|&shell| {
+                if shell == u32::MAX {
+                    return [128, 128, 128, 255];
+                }
+                let [r, g, b] = distinct_color(shell as usize).map(|c| (c * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut sizes: Vec<String> = self.shell_triangles.iter().take(SUMMARY_SHELLS).map(usize::to_string).collect();
+        if self.count() > SUMMARY_SHELLS {
+            sizes.push("...".to_string());
+        }
+        let debris = self.shell_triangles.iter().filter(|&&t| t < self.debris_threshold()).count();
+        format!("{} shells ({} triangles), {} debris", self.count(), sizes.join(", "), debris)
+    }
+}
+
+// Drops every shell with fewer than `min_triangles` triangles, returns how many went
+pub fn remove_small_shells(mesh: &mut Mesh, min_triangles: usize) -> usize {
+    retain_shells(mesh, |components, shell| components.shell_triangles[shell] >= min_triangles)
+}
+
+// Keeps only the given shell, numbered from the largest down
+pub fn isolate_shell(mesh: &mut Mesh, shell: usize) -> usize {
+    retain_shells(mesh, |_, s| s == shell)
+}
+
+fn retain_shells(mesh: &mut Mesh, keep: impl Fn(&Components, usize) -> bool) -> usize {
+    if mesh.indices.iter().any(|&i| i as usize >= mesh.vertices.len()) {
+        return 0;
+    }
+
+    let components = Components::compute(&mesh.vertices, &mesh.indices);
+    let kept: Vec<bool> = (0..components.count()).map(|shell| keep(&components, shell)).collect();
+
+    let triangle_shells: Vec<u32> = mesh.indices
+        .chunks_exact(3)
+        .map(|triangle| components.vertex_shell[triangle[0] as usize])
+        .collect();
+    repair::retain_triangles(mesh, |t| kept[triangle_shells[t] as usize]);
+
+    kept.iter().filter(|&&k| !k).count()
+}
+
+fn find(parent: &mut [u32], mut x: u32) -> u32 {
+    while parent[x as usize] != x {
+        // Path halving keeps the trees shallow
+        parent[x as usize] = parent[parent[x as usize] as usize];
+        x = parent[x as usize];
+    }
+    x
+}
+
+fn union(parent: &mut [u32], a: u32, b: u32) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a.max(b) as usize] = a.min(b);
+    }
+}
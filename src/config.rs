@@ -0,0 +1,34 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::budget::Budget;
+
+// Configuration //
+// Optional settings in config.json under the rsview config directory. Everything has
+// a default, so a missing file or missing keys just mean the defaults apply.
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub budget: Budget,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let Some(path) = dirs::config_dir().map(|dir| dir.join("rsview").join("config.json")) else {
+            return Self::default();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match serde_json::from_str(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Ignoring {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::repair;
+
+// Curvature //
+// Mean curvature at every vertex, estimated from how far its neighbours drop below the
+// tangent plane: along an edge of length l whose far end sits h below the plane, the
+// surface bends by 2h / l². Averaged over the vertex's edges this is 1 / r on a sphere
+// of radius r, positive where the surface is convex and negative where it's concave.
+// Vertices split along UV or normal seams are welded first so seams don't read as
+// creases. Shown as a heatmap by the debug view.
+
+// Heatmap stops from the most concave, through flat, to the most convex
+const CONCAVE: [f32; 3] = [0.15, 0.35, 0.9];
+const FLAT: [f32; 3] = [0.85, 0.85, 0.85];
+const CONVEX: [f32; 3] = [0.9, 0.2, 0.15];
+// Share of the vertices whose curvature fits in the heatmap's range; the rest, often
+// sharp edges and noise, clamp to its ends
+const RANGE_PERCENTILE: f32 = 0.95;
+
+pub struct Curvature {
+    // One per vertex, in 1 / model units
+    pub values: Vec<f32>,
+    // Largest magnitude inside the heatmap's range
+    pub range: f32,
+}
+
+impl Curvature {
+    // Indices have to be in range
+    pub fn compute(vertices: &[[f32; 3]], indices: &[u32]) -> Self {
+        let welded = repair::weld_positions(vertices);
+        let count = welded.iter().map(|&id| id as usize + 1).max().unwrap_or(0);
+        let mut positions = vec![Vector3::new(0.0, 0.0, 0.0); count];
+        for (&id, &vertex) in welded.iter().zip(vertices) {
+            positions[id as usize] = Vector3::from(vertex);
+        }
+
+        // Area weighted, so slivers barely tilt the tangent plane
+        let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); count];
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| welded[triangle[corner] as usize] as usize);
+            let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+            for id in [a, b, c] {
+                normals[id] += normal;
+            }
+        }
+
+        // Each edge counts once per triangle it's in, which weighs both sides of a ridge alike
+        let mut sums = vec![(0.0, 0u32); count];
+        for triangle in indices.chunks_exact(3) {
+            for corner in 0..3 {
+                let a = welded[triangle[corner] as usize] as usize;
+                let b = welded[triangle[(corner + 1) % 3] as usize] as usize;
+                let edge = positions[b] - positions[a];
+                let length2 = edge.magnitude2();
+                if a == b || length2 == 0.0 {
+                    continue;
+                }
+                for (from, along) in [(a, edge), (b, -edge)] {
+                    if normals[from].magnitude2() > 0.0 {
+                        let drop = -normals[from].normalize().dot(along);
+                        sums[from].0 += 2.0 * drop / length2;
+                        sums[from].1 += 1;
+                    }
+                }
+            }
+        }
+        let welded_values: Vec<f32> = sums.iter().map(|&(sum, n)| if n > 0 { sum / n as f32 } else { 0.0 }).collect();
+        let values: Vec<f32> = welded.iter().map(|&id| welded_values[id as usize]).collect();
+
+        let mut magnitudes: Vec<f32> = welded_values.iter().map(|value| value.abs()).collect();
+        let range = if magnitudes.is_empty() {
+            0.0
+        } else {
+            let nth = ((magnitudes.len() - 1) as f32 * RANGE_PERCENTILE) as usize;
+            *magnitudes.select_nth_unstable_by(nth, f32::total_cmp).1
+        };
+
+        Self { values, range }
+    }
+
+    // Per-vertex RGBA8 colors, flat in the middle and the range's ends at the outer stops
+    pub fn colors(&self) -> Vec<[u8; 4]> {
+        let scale = if self.range > 0.0 { 1.0 / self.range } else { 0.0 };
+        self.values
+            .iter()
+            .map(|&value| {
+                let t = (value * scale).clamp(-1.0, 1.0);
+                let (end, f) = if t < 0.0 { (CONCAVE, -t) } else { (CONVEX, t) };
+                let [r, g, b] = [0, 1, 2].map(|c| ((FLAT[c] + (end[c] - FLAT[c]) * f) * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{:.0}% of vertices within ±{:.4} per unit", RANGE_PERCENTILE * 100.0, self.range)
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+// Debug views //
+// What the main model is shaded with in place of its lit materials, to look at the
+// data behind the shading: the normal in world space as a color, the distance from the
+// camera across the model's depth, or the mean curvature as a heatmap. Cycled with Y
+// and picked in the panel.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DebugView {
+    #[default]
+    Off,
+    Normals,
+    Depth,
+    Curvature,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 4] = [DebugView::Off, DebugView::Normals, DebugView::Depth, DebugView::Curvature];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Off => "Off",
+            DebugView::Normals => "Normals",
+            DebugView::Depth => "Depth",
+            DebugView::Curvature => "Curvature",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&view| view == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // How the fragment shader reads it; curvature comes in as vertex colors instead
+    pub fn shader_mode(self) -> f32 {
+        match self {
+            DebugView::Normals => 1.0,
+            DebugView::Depth => 2.0,
+            DebugView::Off | DebugView::Curvature => 0.0,
+        }
+    }
+}
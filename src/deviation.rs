@@ -0,0 +1,75 @@
+use cgmath::{InnerSpace, Vector3};
+use rayon::prelude::*;
+
+use crate::bvh::Bvh;
+use crate::units::Unit;
+
+// Mesh deviation //
+// Distance from every vertex of a mesh to the nearest point on a reference surface,
+// shown as a heatmap to check decimation, retopology or scan alignment against the
+// original. Distances are unsigned.
+
+// Heatmap stops from no deviation to the largest one
+const HEATMAP: [[f32; 3]; 4] = [
+    [0.1, 0.2, 0.9],
+    [0.1, 0.8, 0.3],
+    [0.95, 0.85, 0.1],
+    [0.9, 0.1, 0.1],
+];
+
+pub struct Deviation {
+    // One per vertex of the compared mesh
+    pub distances: Vec<f32>,
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+}
+
+impl Deviation {
+    pub fn compute(reference: &Bvh, vertices: &[[f32; 3]]) -> Self {
+        let distances: Vec<f32> = vertices
+            .par_iter()
+            .map(|&v| {
+                let point = Vector3::from(v);
+                reference.closest_point(point).map_or(f32::MAX, |(_, closest)| (closest - point).magnitude())
+            })
+            .collect();
+
+        let min = distances.iter().copied().fold(f32::MAX, f32::min);
+        let max = distances.iter().copied().fold(0.0, f32::max);
+        let sum_squares: f64 = distances.iter().map(|&d| d as f64 * d as f64).sum();
+        let rms = (sum_squares / distances.len().max(1) as f64).sqrt() as f32;
+
+        Self {
+            distances,
+            min: if min == f32::MAX { 0.0 } else { min },
+            max,
+            rms,
+        }
+    }
+
+    // Per-vertex RGBA8 colors, scaled so the largest deviation gets the last stop
+    pub fn colors(&self) -> Vec<[u8; 4]> {
+        let scale = if self.max > 0.0 { 1.0 / self.max } else { 0.0 };
+        let last = (HEATMAP.len() - 1) as f32;
+
+        self.distances
+            .iter()
+            .map(|&d| {
+                let x = (d * scale).clamp(0.0, 1.0) * last;
+                let stop = (x as usize).min(HEATMAP.len() - 2);
+                let f = x - stop as f32;
+                let [r, g, b] = [0, 1, 2].map(|c| {
+                    let value = HEATMAP[stop][c] + (HEATMAP[stop + 1][c] - HEATMAP[stop][c]) * f;
+                    (value * 255.0).round() as u8
+                });
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+
+    // More digits than other readouts, since deviations are usually tiny next to the model
+    pub fn summary(&self, unit: Unit) -> String {
+        format!("deviation min {:.6}{suffix}, max {:.6}{suffix}, RMS {:.6}{suffix}", self.min, self.max, self.rms, suffix = unit.suffix())
+    }
+}
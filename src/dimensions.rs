@@ -0,0 +1,154 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::units::Unit;
+
+// Bounding dimensions //
+// Axis-aligned bounds of the mesh, plus a box aligned to its principal axes (PCA of
+// the vertex positions), which is usually much tighter for parts that were modelled
+// or scanned at an angle.
+
+const JACOBI_SWEEPS: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub struct OrientedBox {
+    pub center: Vector3<f64>,
+    // Unit axes, longest extent first
+    pub axes: [Vector3<f64>; 3],
+    pub size: [f64; 3],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Dimensions {
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+    pub oriented: OrientedBox,
+}
+
+impl Dimensions {
+    pub fn compute(vertices: &[[f32; 3]]) -> Self {
+        let points = || vertices.iter().map(|v| Vector3::from(v.map(f64::from)));
+
+        let mut min = Vector3::new(f64::MAX, f64::MAX, f64::MAX);
+        let mut max = Vector3::new(f64::MIN, f64::MIN, f64::MIN);
+        let mut sum = Vector3::new(0.0, 0.0, 0.0);
+        for p in points() {
+            min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+            sum += p;
+        }
+        if vertices.is_empty() {
+            min = Vector3::new(0.0, 0.0, 0.0);
+            max = min;
+        }
+
+        let mean = sum / vertices.len().max(1) as f64;
+        let mut covariance = [[0.0; 3]; 3];
+        for p in points() {
+            let d = p - mean;
+            for row in 0..3 {
+                for column in 0..3 {
+                    covariance[row][column] += d[row] * d[column];
+                }
+            }
+        }
+
+        let axes = principal_axes(covariance);
+        let mut low = [f64::MAX; 3];
+        let mut high = [f64::MIN; 3];
+        for p in points() {
+            for (i, axis) in axes.iter().enumerate() {
+                let d = p.dot(*axis);
+                low[i] = low[i].min(d);
+                high[i] = high[i].max(d);
+            }
+        }
+        if vertices.is_empty() {
+            low = [0.0; 3];
+            high = [0.0; 3];
+        }
+
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| (high[b] - low[b]).total_cmp(&(high[a] - low[a])));
+        let center = axes.iter().enumerate().fold(Vector3::new(0.0, 0.0, 0.0), |center, (i, axis)| {
+            center + axis * ((low[i] + high[i]) * 0.5)
+        });
+
+        Self {
+            min,
+            max,
+            oriented: OrientedBox {
+                center,
+                axes: order.map(|i| axes[i]),
+                size: order.map(|i| high[i] - low[i]),
+            },
+        }
+    }
+
+    pub fn size(&self) -> Vector3<f64> {
+        self.max - self.min
+    }
+
+    pub fn summary(&self, oriented: bool, unit: Unit) -> String {
+        let mut text = format!("size {}", unit.size(self.size().into()));
+        if oriented {
+            text += &format!(", oriented {}", unit.size(self.oriented.size));
+        }
+        text
+    }
+
+    // Multi-line readout for the clipboard
+    pub fn report(&self, unit: Unit) -> String {
+        let vector = |v: Vector3<f64>| format!("({:.4}, {:.4}, {:.4})", v.x, v.y, v.z);
+        let point = |v: Vector3<f64>| format!("{}{}", vector(v), unit.suffix());
+
+        format!(
+            "Size: {}\nMin: {}\nMax: {}\nOriented size: {}\nOriented center: {}\nOriented axes: {} {} {}\n",
+            unit.size(self.size().into()),
+            point(self.min), point(self.max),
+            unit.size(self.oriented.size),
+            point(self.oriented.center),
+            vector(self.oriented.axes[0]), vector(self.oriented.axes[1]), vector(self.oriented.axes[2]),
+        )
+    }
+}
+
+// Eigenvectors of a symmetric 3x3 matrix by cyclic Jacobi rotations
+fn principal_axes(mut m: [[f64; 3]; 3]) -> [Vector3<f64>; 3] {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..JACOBI_SWEEPS {
+        let off_diagonal = m[0][1].abs() + m[0][2].abs() + m[1][2].abs();
+        if off_diagonal <= f64::EPSILON * (m[0][0].abs() + m[1][1].abs() + m[2][2].abs()) {
+            break;
+        }
+
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if m[p][q] == 0.0 {
+                continue;
+            }
+
+            // Rotation angle that zeroes m[p][q]
+            let theta = (m[q][q] - m[p][p]) / (2.0 * m[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            for row in &mut m {
+                let (mp, mq) = (row[p], row[q]);
+                row[p] = c * mp - s * mq;
+                row[q] = s * mp + c * mq;
+            }
+            let (row_p, row_q) = (m[p], m[q]);
+            m[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            m[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in &mut v {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+
+    // Eigenvectors are the columns
+    [0, 1, 2].map(|column| Vector3::new(v[0][column], v[1][column], v[2][column]).normalize())
+}
@@ -0,0 +1,461 @@
+use std::fs;
+use std::path::Path;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::Matrix4;
+use wgpu::util::{DeviceExt, StagingBelt};
+
+use crate::texture;
+use crate::upload::DynamicBuffer;
+
+// Environment //
+// An equirectangular image around the model, a Radiance .hdr or a plain PNG or JPEG,
+// drawn behind it by a pass that looks up each pixel's view direction. Its light is also
+// reduced to nine spherical harmonic coefficients that replace the model shading's flat
+// ambient term, scaled so the model is as bright on average as without an environment.
+
+// Ambient of the model shading, averaged over every direction when it comes from an environment
+pub const AMBIENT: f32 = 0.3;
+// Rec. 709 weights of linear RGB
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+// Decoded image in linear RGB, the top row looking straight up
+pub struct EnvironmentImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+    // Shown scaled to an average luminance of one and tone mapped, instead of as it is
+    pub high_dynamic_range: bool,
+}
+
+// Images wider than `max_width` are halved until they fit, as a texture must
+pub fn load_environment(path: &Path, max_width: u32) -> Result<EnvironmentImage, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read environment: {}", e))?;
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let mut image = match extension.as_str() {
+        "hdr" => parse_hdr(&data, max_width)?,
+        "png" | "jpg" | "jpeg" => {
+            let decoded = texture::decode_texture(&data, &extension)?;
+            let pixels = decoded.levels[0].chunks_exact(4).map(|p| [p[0], p[1], p[2]].map(srgb_to_linear)).collect();
+            EnvironmentImage { width: decoded.width, height: decoded.height, pixels, high_dynamic_range: false }
+        }
+        _ => return Err("Unsupported environment format, only .hdr, PNG and JPEG images are supported".to_string()),
+    };
+
+    while image.width > max_width {
+        image = image.halved();
+    }
+    Ok(image)
+}
+
+impl EnvironmentImage {
+    // Diffuse light as coefficients of ambient(n) = c0 + c1 y + c2 z + c3 x + c4 xy + c5 yz
+    // + c6 (3z² - 1) + c7 xz + c8 (x² - y²), with the spherical harmonic constants and the
+    // cosine lobe folded in (Ramamoorthi and Hanrahan)
+    pub fn ambient_coefficients(&self) -> [[f32; 4]; 9] {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut projected = [[0.0f32; 3]; 9];
+        for y in 0..height {
+            let theta = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+            let solid_angle = (std::f32::consts::TAU / width as f32) * (std::f32::consts::PI / height as f32) * theta.sin();
+            for x in 0..width {
+                let phi = ((x as f32 + 0.5) / width as f32 - 0.5) * std::f32::consts::TAU;
+                let direction = [theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos()];
+                let basis = basis(direction);
+                let pixel = self.pixels[x + y * width];
+                for (coefficient, weight) in projected.iter_mut().zip(basis) {
+                    for (sum, value) in coefficient.iter_mut().zip(pixel) {
+                        *sum += value * weight * solid_angle;
+                    }
+                }
+            }
+        }
+
+        // The first coefficient is the average light, whose luminance becomes AMBIENT
+        let average = luminance(projected[0]) * BASIS_SCALE[0] * BAND_SCALE[0];
+        if average <= 0.0 || !average.is_finite() {
+            return flat_ambient();
+        }
+        let mut coefficients = [[0.0; 4]; 9];
+        for (i, coefficient) in coefficients.iter_mut().enumerate() {
+            let scale = BASIS_SCALE[i] * BAND_SCALE[BAND[i]] * AMBIENT / average;
+            *coefficient = [projected[i][0] * scale, projected[i][1] * scale, projected[i][2] * scale, 0.0];
+        }
+        coefficients
+    }
+
+    // What the image is multiplied by before it's shown
+    fn exposure(&self) -> f32 {
+        if !self.high_dynamic_range {
+            return 1.0;
+        }
+        let total: f32 = self.pixels.iter().map(|&pixel| luminance(pixel)).sum();
+        let average = total / self.pixels.len().max(1) as f32;
+        if average > 0.0 && average.is_finite() { 1.0 / average } else { 1.0 }
+    }
+
+    // Averages each 2x2 block
+    fn halved(&self) -> Self {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut pixels = Vec::with_capacity(half_width * half_height);
+        for y in 0..half_height {
+            for x in 0..half_width {
+                let texels = [(x * 2, y * 2), (x * 2 + 1, y * 2), (x * 2, y * 2 + 1), (x * 2 + 1, y * 2 + 1)]
+                    .map(|(tx, ty)| self.pixels[tx.min(width - 1) + ty.min(height - 1) * width]);
+                pixels.push([0, 1, 2].map(|c| texels.iter().map(|texel| texel[c]).sum::<f32>() / 4.0));
+            }
+        }
+        Self { width: half_width as u32, height: half_height as u32, pixels, high_dynamic_range: self.high_dynamic_range }
+    }
+}
+
+// Ambient without an environment: the same from every direction
+pub fn flat_ambient() -> [[f32; 4]; 9] {
+    let mut coefficients = [[0.0; 4]; 9];
+    coefficients[0] = [AMBIENT, AMBIENT, AMBIENT, 0.0];
+    coefficients
+}
+
+// Constants of the real spherical harmonics, in the order of the polynomials above
+const BASIS_SCALE: [f32; 9] = [0.282095, 0.488603, 0.488603, 0.488603, 1.092548, 1.092548, 0.315392, 1.092548, 0.546274];
+const BAND: [usize; 9] = [0, 1, 1, 1, 2, 2, 2, 2, 2];
+// Convolution with the cosine lobe per band, divided by pi to go from irradiance to reflected light
+const BAND_SCALE: [f32; 3] = [1.0, 2.0 / 3.0, 0.25];
+
+fn basis([x, y, z]: [f32; 3]) -> [f32; 9] {
+    let polynomials = [1.0, y, z, x, x * y, y * z, 3.0 * z * z - 1.0, x * z, x * x - y * y];
+    std::array::from_fn(|i| polynomials[i] * BASIS_SCALE[i])
+}
+
+fn luminance(color: [f32; 3]) -> f32 {
+    color[0] * LUMINANCE[0] + color[1] * LUMINANCE[1] + color[2] * LUMINANCE[2]
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// Radiance HDR //
+
+// How many times an HDR image may be halved to fit the texture limit; anything wider
+// is rejected before its pixels are allocated
+const MAX_HDR_HALVINGS: u32 = 4;
+
+fn parse_hdr(data: &[u8], max_width: u32) -> Result<EnvironmentImage, String> {
+    let mut position = 0;
+    let magic = read_line(data, &mut position).ok_or("Invalid HDR file: no header")?;
+    if !magic.starts_with("#?") {
+        return Err("Invalid HDR file: missing the #? signature".to_string());
+    }
+    // Variables until a blank line, only the pixel format matters
+    loop {
+        let line = read_line(data, &mut position).ok_or("Invalid HDR file: the header doesn't end")?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+            && format != "32-bit_rle_rgbe"
+        {
+            return Err(format!("Unsupported HDR pixel format {}", format));
+        }
+    }
+
+    let resolution = read_line(data, &mut position).ok_or("Invalid HDR file: no resolution")?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["-Y", height, "+X", width] => (height.parse::<usize>().ok(), width.parse::<usize>().ok()),
+        _ => return Err(format!("Unsupported HDR orientation {}, only -Y +X is supported", resolution)),
+    };
+    let (Some(height), Some(width)) = (height, width) else {
+        return Err(format!("Invalid HDR resolution {}", resolution));
+    };
+    if width == 0 || height == 0 {
+        return Err("HDR image is empty".to_string());
+    }
+    let pixel_count = width.checked_mul(height).filter(|_| width <= (max_width as usize) << MAX_HDR_HALVINGS);
+    let Some(pixel_count) = pixel_count else {
+        return Err(format!("HDR image is {}x{}, too large for an environment", width, height));
+    };
+    // The header's size can't be trusted: every scanline takes at least a run-length
+    // header and two bytes per 127-pixel run of each channel, or 4 bytes per pixel flat
+    let scanline_bytes = if (8..0x8000).contains(&width) { 4 + width.div_ceil(127) * 8 } else { width * 4 };
+    if height.checked_mul(scanline_bytes).is_none_or(|bytes| bytes > data.len() - position) {
+        return Err("Invalid HDR file: the pixel data is truncated".to_string());
+    }
+
+    let mut pixels = Vec::with_capacity(pixel_count);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(data, &mut position, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_float(rgbe)));
+    }
+
+    Ok(EnvironmentImage { width: width as u32, height: height as u32, pixels, high_dynamic_range: true })
+}
+
+fn read_line<'a>(data: &'a [u8], position: &mut usize) -> Option<&'a str> {
+    let rest = data.get(*position..)?;
+    let end = rest.iter().position(|&b| b == b'\n')?;
+    *position += end + 1;
+    std::str::from_utf8(&rest[..end]).ok().map(|line| line.trim_end_matches('\r'))
+}
+
+// One row of RGBE pixels, run-length encoded per channel or stored flat
+fn read_scanline(data: &[u8], position: &mut usize, scanline: &mut [[u8; 4]]) -> Result<(), String> {
+    const TRUNCATED: &str = "Invalid HDR file: the pixel data is truncated";
+    let width = scanline.len();
+    let start = data.get(*position..*position + 4).ok_or(TRUNCATED)?;
+
+    let run_length_encoded = (8..0x8000).contains(&width) && start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0;
+    if !run_length_encoded {
+        if start[..3] == [1, 1, 1] {
+            return Err("Old run-length encoded HDR files aren't supported".to_string());
+        }
+        let bytes = data.get(*position..*position + width * 4).ok_or(TRUNCATED)?;
+        for (pixel, bytes) in scanline.iter_mut().zip(bytes.chunks_exact(4)) {
+            pixel.copy_from_slice(bytes);
+        }
+        *position += width * 4;
+        return Ok(());
+    }
+
+    if ((start[2] as usize) << 8 | start[3] as usize) != width {
+        return Err("Invalid HDR file: a scanline has the wrong width".to_string());
+    }
+    *position += 4;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(*position).ok_or(TRUNCATED)? as usize;
+            *position += 1;
+            if count > 128 {
+                // A run of one value
+                let count = count - 128;
+                let value = *data.get(*position).ok_or(TRUNCATED)?;
+                *position += 1;
+                let pixels = scanline.get_mut(x..x + count).ok_or("Invalid HDR file: a run overflows its scanline")?;
+                pixels.iter_mut().for_each(|pixel| pixel[channel] = value);
+                x += count;
+            } else {
+                if count == 0 {
+                    return Err("Invalid HDR file: empty run".to_string());
+                }
+                let values = data.get(*position..*position + count).ok_or(TRUNCATED)?;
+                *position += count;
+                let pixels = scanline.get_mut(x..x + count).ok_or("Invalid HDR file: a run overflows its scanline")?;
+                pixels.iter_mut().zip(values).for_each(|(pixel, &value)| pixel[channel] = value);
+                x += count;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Three mantissas sharing the fourth byte as exponent
+fn rgbe_to_float([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2.0f32.powi(e as i32 - (128 + 8));
+    [r, g, b].map(|c| (c as f32 + 0.5) * scale)
+}
+
+// Shared exponent floats, the HDR format every device can filter, four bytes a texel
+fn pack_rgb9e5(color: [f32; 3]) -> u32 {
+    const MANTISSA_BITS: i32 = 9;
+    const BIAS: i32 = 15;
+    const MAX: f32 = 65408.0;
+
+    let [r, g, b] = color.map(|c| c.clamp(0.0, MAX));
+    let max = r.max(g).max(b);
+    let mut exponent = (max.log2().floor() as i32).max(-BIAS - 1) + 1 + BIAS;
+    if (max / 2.0f32.powi(exponent - BIAS - MANTISSA_BITS) + 0.5).floor() as i32 == 1 << MANTISSA_BITS {
+        exponent += 1;
+    }
+    let scale = 2.0f32.powi(exponent - BIAS - MANTISSA_BITS);
+    let [r, g, b] = [r, g, b].map(|c| ((c / scale + 0.5).floor() as u32).min(511));
+    r | g << 9 | b << 18 | (exponent as u32) << 27
+}
+
+// Sky pass //
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct EnvironmentUniforms {
+    // Clip space to view directions in the world
+    ray_transform: [[f32; 4]; 4],
+    // x multiplies the image, y is 1 to tone map it
+    exposure: [f32; 4],
+}
+
+pub struct EnvironmentPass {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: DynamicBuffer,
+    bind_group: wgpu::BindGroup,
+    exposure: [f32; 4],
+    ambient: [[f32; 4]; 9],
+}
+
+impl EnvironmentPass {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, image: &EnvironmentImage, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let texels: Vec<u32> = image.pixels.iter().map(|&pixel| pack_rgb9e5(pixel)).collect();
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Environment Texture"),
+                size: wgpu::Extent3d {
+                    width: image.width,
+                    height: image.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgb9e5Ufloat,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&texels),
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Around wraps, the poles don't
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_size = std::mem::size_of::<EnvironmentUniforms>() as wgpu::BufferAddress;
+        let uniform_buffer = DynamicBuffer::new(device, "Environment Uniforms", wgpu::BufferUsages::UNIFORM, uniform_size);
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Environment Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(uniform_size),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../environment.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Environment Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Environment Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Drawn first inside the scene pass like the background gradient
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            exposure: [image.exposure(), if image.high_dynamic_range { 1.0 } else { 0.0 }, 0.0, 0.0],
+            ambient: image.ambient_coefficients(),
+        }
+    }
+
+    // Spherical harmonic coefficients of the model's ambient light, see ambient_coefficients
+    pub fn ambient(&self) -> [[f32; 4]; 9] {
+        self.ambient
+    }
+
+    // `ray_transform` undoes the projection and the camera rotation, without its translation
+    pub fn set(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, ray_transform: Matrix4<f32>) {
+        let uniforms = EnvironmentUniforms {
+            ray_transform: ray_transform.into(),
+            exposure: self.exposure,
+        };
+        self.uniform_buffer.write(device, encoder, belt, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+use std::io;
+
+// Errors //
+// Why loading or rendering a model failed, by cause, so callers can tell a missing
+// file from a malformed or unsupported one. Problems that only skip part of a model
+// (a texture, a material library) are logged instead and never get here.
+
+#[derive(Debug)]
+pub enum Error {
+    // `context` says what was being read, e.g. "Failed to open file"
+    Io { context: String, source: io::Error },
+    // `line` counts from 1
+    ObjParse { line: usize, reason: String },
+    GltfParse(String),
+    PlyParse(String),
+    LasParse(String),
+    FbxParse(String),
+    UsdParse(String),
+    // The named file's extension isn't one of the supported model formats
+    UnsupportedFormat(String),
+    // The named output file's extension isn't one `rsview convert` can write
+    UnsupportedExport(String),
+    Gpu(String),
+}
+
+impl Error {
+    // For map_err on io::Result
+    pub(crate) fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Error {
+        let context = context.into();
+        move |source| Error::Io { context, source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+            Error::ObjParse { line, reason } => write!(f, "OBJ line {}: {}", line, reason),
+            Error::GltfParse(reason) | Error::PlyParse(reason) | Error::LasParse(reason) | Error::FbxParse(reason) | Error::UsdParse(reason) | Error::Gpu(reason) => write!(f, "{}", reason),
+            Error::UnsupportedFormat(name) => {
+                write!(f, "Unsupported file format for {}, only .obj, .gltf, .glb, .fbx, .usda, .usdz, .ply and .las files are supported", name)
+            }
+            Error::UnsupportedExport(name) => write!(f, "Can't write {}, only .obj and .ply files can be written", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
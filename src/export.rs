@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::Error;
+use crate::parse::Mesh;
+
+// Model export //
+// Writes a loaded mesh back out for `rsview convert`: OBJ with an object per part, or
+// binary PLY, which keeps vertex colors too. Both keep positions, normals and UVs with
+// the shared vertex list as is. Materials, textures and rigs aren't written, skinned
+// meshes come out in their bind pose. A mesh without faces is written as its vertices
+// alone, which reads back as a point cloud.
+
+pub fn save_model(path: &str, mesh: &Mesh) -> Result<(), Error> {
+    let lower = path.to_lowercase();
+    let write: fn(&mut BufWriter<File>, &Mesh) -> std::io::Result<()> = if lower.ends_with(".obj") {
+        write_obj
+    } else if lower.ends_with(".ply") {
+        write_ply
+    } else {
+        let name = Path::new(path).file_name().map_or_else(|| path.to_string(), |name| name.to_string_lossy().into_owned());
+        return Err(Error::UnsupportedExport(name));
+    };
+
+    let file = File::create(path).map_err(Error::io("Failed to create file"))?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer, mesh)
+        .and_then(|()| writer.flush())
+        .map_err(Error::io("Failed to write file"))
+}
+
+fn write_obj(out: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    writeln!(out, "# Written by rsview")?;
+    for [x, y, z] in &mesh.vertices {
+        writeln!(out, "v {} {} {}", x, y, z)?;
+    }
+    for [u, v] in mesh.uvs.iter().flatten() {
+        // OBJ's V points up the image, glTF's down; the OBJ parser flips it back
+        writeln!(out, "vt {} {}", u, 1.0 - v)?;
+    }
+    for [x, y, z] in mesh.normals.iter().flatten() {
+        writeln!(out, "vn {} {} {}", x, y, z)?;
+    }
+
+    // OBJ indices count from 1, and every attribute is indexed like the positions
+    let corner = |index: u32| {
+        let index = index + 1;
+        match (mesh.uvs.is_some(), mesh.normals.is_some()) {
+            (true, true) => format!("{0}/{0}/{0}", index),
+            (true, false) => format!("{0}/{0}", index),
+            (false, true) => format!("{0}//{0}", index),
+            (false, false) => index.to_string(),
+        }
+    };
+    let write_faces = |out: &mut dyn Write, indices: &[u32]| -> std::io::Result<()> {
+        for triangle in indices.chunks_exact(3) {
+            writeln!(out, "f {} {} {}", corner(triangle[0]), corner(triangle[1]), corner(triangle[2]))?;
+        }
+        Ok(())
+    };
+
+    if mesh.submeshes.is_empty() {
+        return write_faces(out, &mesh.indices);
+    }
+    for (part, submesh) in mesh.submeshes.iter().enumerate() {
+        let name = submesh.name.split_whitespace().collect::<Vec<_>>().join("_");
+        if name.is_empty() {
+            writeln!(out, "o part_{}", part + 1)?;
+        } else {
+            writeln!(out, "o {}", name)?;
+        }
+        let range = submesh.first_index as usize..(submesh.first_index + submesh.index_count) as usize;
+        write_faces(out, mesh.indices.get(range).unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+fn write_ply(out: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "comment Written by rsview")?;
+    writeln!(out, "element vertex {}", mesh.vertices.len())?;
+    writeln!(out, "property float x\nproperty float y\nproperty float z")?;
+    if mesh.normals.is_some() {
+        writeln!(out, "property float nx\nproperty float ny\nproperty float nz")?;
+    }
+    if mesh.uvs.is_some() {
+        writeln!(out, "property float s\nproperty float t")?;
+    }
+    if mesh.colors.is_some() {
+        writeln!(out, "property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha")?;
+    }
+    writeln!(out, "element face {}", mesh.indices.len() / 3)?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    let floats = |out: &mut dyn Write, values: &[f32]| values.iter().try_for_each(|value| out.write_all(&value.to_le_bytes()));
+    for (i, position) in mesh.vertices.iter().enumerate() {
+        floats(out, position)?;
+        if let Some(normals) = &mesh.normals {
+            floats(out, &normals[i])?;
+        }
+        if let Some(uvs) = &mesh.uvs {
+            floats(out, &uvs[i])?;
+        }
+        if let Some(colors) = &mesh.colors {
+            out.write_all(&colors[i])?;
+        }
+    }
+    for triangle in mesh.indices.chunks_exact(3) {
+        out.write_all(&[3])?;
+        for index in triangle {
+            out.write_all(&index.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,502 @@
+use cgmath::{Deg, Matrix4, One, SquareMatrix, Vector3};
+use flate2::read::ZlibDecoder;
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::batch::DEFAULT_COLOR;
+use crate::error::Error;
+use crate::parse::{map_file, transform_vertices, Material, Mesh, SubMesh};
+
+// FBX parser //
+// Binary FBX 7.x, the format most downloadable models ship in. The file is a tree of
+// nodes with typed properties; meshes are Geometry nodes under Objects, placed by the
+// Model nodes they're connected to in Connections. Each model using a geometry becomes
+// a part with the model's transform baked in. Positions, normals and UVs are read;
+// materials, skins and animation aren't.
+
+const MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+// Magic, two unknown bytes and the version
+const HEADER_SIZE: usize = 27;
+// Files from 7.5 on have 64-bit node offsets
+const WIDE_VERSION: u32 = 7500;
+// Nodes nest a handful of levels in real files; deeper ones would overflow the stack
+const MAX_NODE_DEPTH: usize = 256;
+
+enum Property {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Integers(Vec<i64>),
+    Floats(Vec<f64>),
+    // Raw bytes and boolean arrays, which nothing here reads
+    Other,
+}
+
+struct Node {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn integer(&self, index: usize) -> Option<i64> {
+        match self.properties.get(index)? {
+            Property::Integer(value) => Some(*value),
+            Property::Float(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    fn float(&self, index: usize) -> Option<f64> {
+        match self.properties.get(index)? {
+            Property::Float(value) => Some(*value),
+            Property::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    fn string(&self, index: usize) -> Option<&str> {
+        match self.properties.get(index)? {
+            Property::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // The first array property of a child, like the Vertices of a Geometry
+    fn floats(&self, child: &str) -> Option<&[f64]> {
+        self.child(child)?.properties.iter().find_map(|property| match property {
+            Property::Floats(values) => Some(values.as_slice()),
+            _ => None,
+        })
+    }
+
+    fn integers(&self, child: &str) -> Option<&[i64]> {
+        self.child(child)?.properties.iter().find_map(|property| match property {
+            Property::Integers(values) => Some(values.as_slice()),
+            _ => None,
+        })
+    }
+}
+
+struct FbxReader<'a> {
+    data: &'a [u8],
+    at: usize,
+    wide: bool,
+}
+
+impl FbxReader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self.data.get(self.at..self.at.checked_add(count).ok_or("Unexpected end of FBX data")?).ok_or("Unexpected end of FBX data")?;
+        self.at += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn offset(&mut self) -> Result<usize, String> {
+        if self.wide {
+            Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()) as usize)
+        } else {
+            Ok(self.u32()? as usize)
+        }
+    }
+
+    // None for the empty record that ends a list of nodes. `depth` counts the nodes above.
+    fn node(&mut self, depth: usize) -> Result<Option<Node>, String> {
+        if depth >= MAX_NODE_DEPTH {
+            return Err(format!("FBX nodes nest more than {} levels deep", MAX_NODE_DEPTH));
+        }
+        let start = self.at;
+        let end = self.offset()?;
+        let property_count = self.offset()?;
+        let _property_bytes = self.offset()?;
+        let name_length = self.u8()? as usize;
+        if end == 0 {
+            return Ok(None);
+        }
+        // Every node has to end past its own header, or a child could send the reader
+        // back to where it started
+        if end < start || end <= self.at {
+            return Err(format!("FBX node at byte {} ends at {}, before its own header does", start, end));
+        }
+        if end > self.data.len() {
+            return Err(format!("FBX node at byte {} runs past the end of the file", start));
+        }
+        let name = String::from_utf8_lossy(self.bytes(name_length)?).into_owned();
+
+        let properties = (0..property_count).map(|_| self.property()).collect::<Result<Vec<_>, _>>()?;
+        if self.at > end {
+            return Err(format!("FBX node {} has properties past its end", name));
+        }
+        let mut children = Vec::new();
+        while self.at < end {
+            match self.node(depth + 1)? {
+                Some(child) => children.push(child),
+                None => break,
+            }
+        }
+        if self.at > end {
+            return Err(format!("FBX node {} has children past its end", name));
+        }
+        self.at = end;
+        Ok(Some(Node { name, properties, children }))
+    }
+
+    fn property(&mut self) -> Result<Property, String> {
+        let code = self.u8()?;
+        Ok(match code {
+            b'C' => Property::Integer(self.u8()? as i64),
+            b'Y' => Property::Integer(i16::from_le_bytes(self.bytes(2)?.try_into().unwrap()) as i64),
+            b'I' => Property::Integer(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as i64),
+            b'L' => Property::Integer(i64::from_le_bytes(self.bytes(8)?.try_into().unwrap())),
+            b'F' => Property::Float(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as f64),
+            b'D' => Property::Float(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap())),
+            b'S' => {
+                let length = self.u32()? as usize;
+                Property::String(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+            }
+            b'R' => {
+                let length = self.u32()? as usize;
+                self.bytes(length)?;
+                Property::Other
+            }
+            b'i' => Property::Integers(self.array(4)?.chunks_exact(4).map(|b| i32::from_le_bytes(b.try_into().unwrap()) as i64).collect()),
+            b'l' => Property::Integers(self.array(8)?.chunks_exact(8).map(|b| i64::from_le_bytes(b.try_into().unwrap())).collect()),
+            b'f' => Property::Floats(self.array(4)?.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64).collect()),
+            b'd' => Property::Floats(self.array(8)?.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect()),
+            b'b' => {
+                self.array(1)?;
+                Property::Other
+            }
+            _ => return Err(format!("Unknown FBX property type {:?}", code as char)),
+        })
+    }
+
+    // Array contents, inflated when the exporter compressed them
+    fn array(&mut self, element_size: usize) -> Result<Vec<u8>, String> {
+        let length = self.u32()? as usize;
+        let encoding = self.u32()?;
+        let stored = self.u32()? as usize;
+        let bytes = self.bytes(stored)?;
+        let expected = length * element_size;
+        let data = match encoding {
+            0 => bytes.to_vec(),
+            1 => {
+                // The length comes from the file, so let the buffer grow with what inflates
+                let mut data = Vec::new();
+                ZlibDecoder::new(bytes)
+                    .take(expected as u64)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format!("Invalid compressed FBX array: {}", e))?;
+                data
+            }
+            _ => return Err(format!("Unknown FBX array encoding {}", encoding)),
+        };
+        if data.len() < expected {
+            return Err("FBX array is shorter than its length".into());
+        }
+        Ok(data)
+    }
+}
+
+pub fn parse_fbx(file_path: &str) -> Result<Mesh, Error> {
+    let mmap = map_file(file_path)?;
+    parse_fbx_data(mmap.as_deref().unwrap_or(&[])).map_err(Error::FbxParse)
+}
+
+fn parse_fbx_data(data: &[u8]) -> Result<Mesh, String> {
+    if !data.starts_with(MAGIC) {
+        if data.starts_with(b"; FBX") {
+            return Err("ASCII FBX files aren't supported, only binary FBX 7.x".into());
+        }
+        return Err("Not a binary FBX file".into());
+    }
+    let version = u32::from_le_bytes(data.get(23..HEADER_SIZE).ok_or("FBX file has no header")?.try_into().unwrap());
+    if version < 7000 {
+        return Err(format!("FBX version {} is too old, only 7.x is supported", version));
+    }
+
+    let mut reader = FbxReader { data, at: HEADER_SIZE, wide: version >= WIDE_VERSION };
+    let mut nodes = Vec::new();
+    while let Some(node) = reader.node(0)? {
+        nodes.push(node);
+    }
+    let objects = nodes.iter().find(|node| node.name == "Objects").ok_or("FBX file has no Objects")?;
+
+    let geometries: HashMap<i64, &Node> = objects
+        .children_named("Geometry")
+        .filter(|geometry| geometry.string(2) == Some("Mesh"))
+        .filter_map(|geometry| Some((geometry.integer(0)?, geometry)))
+        .collect();
+    let models: HashMap<i64, &Node> = objects.children_named("Model").filter_map(|model| Some((model.integer(0)?, model))).collect();
+
+    // Object to object connections, child first; 0 is the scene root
+    let mut parents = HashMap::new();
+    let mut instances: HashMap<i64, Vec<i64>> = HashMap::new();
+    for connection in nodes.iter().filter(|node| node.name == "Connections").flat_map(|node| node.children_named("C")) {
+        let (Some("OO"), Some(child), Some(parent)) = (connection.string(0), connection.integer(1), connection.integer(2)) else {
+            continue;
+        };
+        if geometries.contains_key(&child) && models.contains_key(&parent) {
+            instances.entry(child).or_default().push(parent);
+        } else if models.contains_key(&child) && models.contains_key(&parent) {
+            parents.insert(child, parent);
+        }
+    }
+
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        normals: Some(Vec::new()),
+        uvs: Some(Vec::new()),
+        colors: None,
+        joints: None,
+        weights: None,
+        rig: None,
+        submeshes: Vec::new(),
+        textures: Vec::new(),
+        sources: Vec::new(),
+    };
+    let mut has_normals = false;
+    let mut has_uvs = false;
+
+    // In file order, so parts come out the way the exporter listed them
+    for geometry in objects.children_named("Geometry") {
+        let Some(id) = geometry.integer(0).filter(|id| geometries.contains_key(id)) else {
+            continue;
+        };
+        let part = read_geometry(geometry)?;
+        has_normals |= part.normals.is_some();
+        has_uvs |= part.uvs.is_some();
+
+        // A geometry no model uses is still shown, where it was modeled
+        let placements: Vec<(Matrix4<f32>, String)> = match instances.get(&id) {
+            Some(users) => users
+                .iter()
+                .map(|&model| (world_transform(model, &models, &parents) * geometric_transform(models[&model]), object_name(models[&model])))
+                .collect(),
+            None => vec![(Matrix4::one(), object_name(geometry))],
+        };
+        for (transform, name) in placements {
+            append_part(&mut mesh, &part, transform, name);
+        }
+    }
+
+    if !has_normals {
+        mesh.normals = None;
+    }
+    if !has_uvs {
+        mesh.uvs = None;
+    }
+    if mesh.submeshes.len() == 1 {
+        mesh.submeshes.clear();
+    }
+
+    eprintln!("FBX Parser: Loaded {} vertices, {} indices ({} triangles) from {} geometries (FBX {}.{})",
+             mesh.vertices.len(), mesh.indices.len(), mesh.indices.len() / 3, geometries.len(), version / 1000, version % 1000 / 100);
+    Ok(mesh)
+}
+
+// Names are stored as "name\0\x01Class"
+fn object_name(node: &Node) -> String {
+    node.string(1).map_or_else(String::new, |name| name.split('\0').next().unwrap_or_default().to_string())
+}
+
+// A Properties70 vector, like "Lcl Translation", or `default` when the model doesn't set it
+fn vector_property(model: &Node, name: &str, default: f32) -> Vector3<f32> {
+    let property = model.child("Properties70").and_then(|properties| properties.children_named("P").find(|p| p.string(0) == Some(name)));
+    let component = |index: usize| property.and_then(|p| p.float(index)).map_or(default, |value| value as f32);
+    Vector3::new(component(4), component(5), component(6))
+}
+
+// Rotations are in degrees and applied X first
+fn euler(degrees: Vector3<f32>) -> Matrix4<f32> {
+    Matrix4::from_angle_z(Deg(degrees.z)) * Matrix4::from_angle_y(Deg(degrees.y)) * Matrix4::from_angle_x(Deg(degrees.x))
+}
+
+fn scaling(scale: Vector3<f32>) -> Matrix4<f32> {
+    Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z)
+}
+
+// Translation, pre-rotation, rotation and scale; pivots and offsets are ignored
+fn local_transform(model: &Node) -> Matrix4<f32> {
+    Matrix4::from_translation(vector_property(model, "Lcl Translation", 0.0))
+        * euler(vector_property(model, "PreRotation", 0.0))
+        * euler(vector_property(model, "Lcl Rotation", 0.0))
+        * scaling(vector_property(model, "Lcl Scaling", 1.0))
+}
+
+// Only applies to the model's own geometry, not to its children
+fn geometric_transform(model: &Node) -> Matrix4<f32> {
+    Matrix4::from_translation(vector_property(model, "GeometricTranslation", 0.0))
+        * euler(vector_property(model, "GeometricRotation", 0.0))
+        * scaling(vector_property(model, "GeometricScaling", 1.0))
+}
+
+fn world_transform(model: i64, models: &HashMap<i64, &Node>, parents: &HashMap<i64, i64>) -> Matrix4<f32> {
+    let mut transform = Matrix4::one();
+    let mut current = Some(model);
+    // Bounded, in case a broken file connects models in a loop
+    for _ in 0..models.len() {
+        let Some(id) = current else {
+            break;
+        };
+        transform = local_transform(models[&id]) * transform;
+        current = parents.get(&id).copied();
+    }
+    transform
+}
+
+// A geometry with one vertex per distinct combination of position, normal and UV
+struct Part {
+    vertices: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    uvs: Option<Vec<[f32; 2]>>,
+    indices: Vec<u32>,
+}
+
+// How a layer element (normals, UVs) maps onto the mesh, and the values it holds,
+// `width` to each
+struct Layer<'a> {
+    values: &'a [f64],
+    width: usize,
+    indices: Option<&'a [i64]>,
+    mapping: &'a str,
+}
+
+impl<'a> Layer<'a> {
+    fn read(geometry: &'a Node, element: &str, values: &str, indices: &str, width: usize) -> Option<Self> {
+        let layer = geometry.child(element)?;
+        let direct = layer.child("ReferenceInformationType").and_then(|node| node.string(0)) == Some("Direct");
+        Some(Layer {
+            values: layer.floats(values)?,
+            width,
+            indices: if direct { None } else { layer.integers(indices) },
+            mapping: layer.child("MappingInformationType").and_then(|node| node.string(0)).unwrap_or("ByPolygonVertex"),
+        })
+    }
+
+    // Index of the value for one polygon corner, by its position among all corners, its
+    // control point and its polygon
+    fn index(&self, corner: usize, vertex: usize, polygon: usize) -> Option<usize> {
+        let direct = match self.mapping {
+            "ByPolygonVertex" => corner,
+            "ByVertice" | "ByVertex" | "ByControlPoint" => vertex,
+            "ByPolygon" => polygon,
+            "AllSame" => 0,
+            _ => return None,
+        };
+        let index = match self.indices {
+            Some(indices) => usize::try_from(*indices.get(direct)?).ok()?,
+            None => direct,
+        };
+        (index * self.width + self.width <= self.values.len()).then_some(index)
+    }
+
+    fn value<const N: usize>(&self, index: usize) -> [f32; N] {
+        std::array::from_fn(|i| self.values[index * self.width + i] as f32)
+    }
+}
+
+fn read_geometry(geometry: &Node) -> Result<Part, String> {
+    let positions = geometry.floats("Vertices").ok_or("FBX geometry has no Vertices")?;
+    let polygon_vertices = geometry.integers("PolygonVertexIndex").ok_or("FBX geometry has no PolygonVertexIndex")?;
+    let normal_layer = Layer::read(geometry, "LayerElementNormal", "Normals", "NormalsIndex", 3);
+    let uv_layer = Layer::read(geometry, "LayerElementUV", "UV", "UVIndex", 2);
+
+    let mut part = Part {
+        vertices: Vec::new(),
+        normals: normal_layer.as_ref().map(|_| Vec::new()),
+        uvs: uv_layer.as_ref().map(|_| Vec::new()),
+        indices: Vec::new(),
+    };
+    let mut corners_seen: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut polygon = Vec::new();
+    let mut polygon_index = 0;
+    let vertex_count = positions.len() / 3;
+
+    for (corner, &value) in polygon_vertices.iter().enumerate() {
+        // The last corner of each polygon is stored as its bitwise complement
+        let last = value < 0;
+        let vertex = if last { !value } else { value } as usize;
+        if vertex >= vertex_count {
+            return Err(format!("FBX polygon vertex {} is out of range", vertex));
+        }
+        let normal = normal_layer.as_ref().and_then(|layer| layer.index(corner, vertex, polygon_index));
+        let uv = uv_layer.as_ref().and_then(|layer| layer.index(corner, vertex, polygon_index));
+
+        let index = *corners_seen.entry((vertex, normal, uv)).or_insert_with(|| {
+            part.vertices.push([0, 1, 2].map(|axis| positions[vertex * 3 + axis] as f32));
+            if let (Some(normals), Some(layer)) = (&mut part.normals, &normal_layer) {
+                normals.push(normal.map_or([0.0; 3], |i| layer.value(i)));
+            }
+            if let (Some(uvs), Some(layer)) = (&mut part.uvs, &uv_layer) {
+                // V runs up in FBX and down in textures
+                uvs.push(uv.map_or([0.0; 2], |i| {
+                    let [u, v] = layer.value(i);
+                    [u, 1.0 - v]
+                }));
+            }
+            part.vertices.len() as u32 - 1
+        });
+        polygon.push(index);
+
+        if last {
+            for i in 1..polygon.len().saturating_sub(1) {
+                part.indices.extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+            }
+            polygon.clear();
+            polygon_index += 1;
+        }
+    }
+    Ok(part)
+}
+
+// Adds a placed copy of a geometry to the mesh as its own part
+fn append_part(mesh: &mut Mesh, part: &Part, transform: Matrix4<f32>, name: String) {
+    let base = mesh.vertices.len() as u32;
+    let first_index = mesh.indices.len() as u32;
+
+    let mut vertices = part.vertices.clone();
+    let mut normals = part.normals.clone().unwrap_or_default();
+    transform_vertices(&transform, &mut vertices, &mut normals);
+    // Mirroring transforms turn the faces inside out
+    let mirrored = transform.determinant() < 0.0;
+
+    mesh.vertices.extend(vertices);
+    if let Some(all_normals) = &mut mesh.normals {
+        normals.resize(part.vertices.len(), [0.0; 3]);
+        all_normals.extend(normals);
+    }
+    if let Some(uvs) = &mut mesh.uvs {
+        uvs.extend(part.uvs.clone().unwrap_or_else(|| vec![[0.0; 2]; part.vertices.len()]));
+    }
+    for triangle in part.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| base + index);
+        let triangle = if mirrored { [a, c, b] } else { [a, b, c] };
+        mesh.indices.extend_from_slice(&triangle);
+    }
+
+    mesh.submeshes.push(SubMesh {
+        first_index,
+        index_count: mesh.indices.len() as u32 - first_index,
+        color: DEFAULT_COLOR,
+        texture: None,
+        material: Material::default(),
+        name,
+    });
+}
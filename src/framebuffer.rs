@@ -0,0 +1,93 @@
+// Size-dependent render targets //
+// Depth, multisampled color and any post-process targets all follow the window size.
+// They're created on first use after a size change rather than on every resize event,
+// and dropped entirely while the window is minimized.
+
+#[derive(Clone)]
+pub struct FramebufferTargets {
+    pub depth: wgpu::TextureView,
+    // Rendered into and resolved to the swapchain when multisampling, None otherwise
+    pub msaa_color: Option<wgpu::TextureView>,
+}
+
+pub struct Framebuffer {
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    width: u32,
+    height: u32,
+    targets: Option<FramebufferTargets>,
+}
+
+impl Framebuffer {
+    pub fn new(color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, width: u32, height: u32) -> Self {
+        Self {
+            color_format,
+            depth_format,
+            sample_count,
+            width,
+            height,
+            targets: None,
+        }
+    }
+
+    // Targets are only dropped when the size really differs; a zero size (minimized) frees them
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.targets = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // Creates the targets for the current size if they don't exist yet
+    pub fn targets(&mut self, device: &wgpu::Device) -> Option<&FramebufferTargets> {
+        if self.is_empty() {
+            return None;
+        }
+
+        if self.targets.is_none() {
+            // A multisampled depth buffer can't be sampled like a plain one, and the GL backend
+            // leaves the framebuffer incomplete when asked to make it bindable
+            let depth_usage = if self.sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            };
+            let depth = self.create_target(device, "Depth Texture", self.depth_format, depth_usage);
+            let msaa_color = (self.sample_count > 1).then(|| {
+                self.create_target(device, "MSAA Color Texture", self.color_format, wgpu::TextureUsages::RENDER_ATTACHMENT)
+            });
+
+            self.targets = Some(FramebufferTargets { depth, msaa_color });
+        }
+
+        self.targets.as_ref()
+    }
+
+    fn create_target(&self, device: &wgpu::Device, label: &str, format: wgpu::TextureFormat, usage: wgpu::TextureUsages) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: self.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        // The view keeps the texture alive
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
@@ -0,0 +1,27 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+// Frustum culling //
+// The six planes of what the camera sees, taken from a model-view-projection matrix so
+// they're in model space (Gribb and Hartmann's extraction). Parts whose bounding sphere
+// is fully behind any plane are left out of the draws.
+
+pub struct Frustum {
+    // Normal in xyz pointing inwards, normalized so w is a distance
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    pub fn new(model_view_projection: Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| model_view_projection.row(i));
+        // Near is taken as -w <= z, which holds for either depth range
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+            let length = plane.truncate().magnitude();
+            if length > 0.0 { plane / length } else { plane }
+        });
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
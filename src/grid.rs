@@ -0,0 +1,80 @@
+use cgmath::Vector3;
+
+use crate::lines::LineVertex;
+
+// Ground grid //
+// A reference grid on the model's XZ plane at the bottom of its bounds, plus red, green
+// and blue lines along X, Y and Z from its origin, to judge scale and which way is up.
+// Cells are a power of ten in file units, at most a fifth of the model's size, with every
+// tenth line brighter. The grid reaches far past the model and fades out toward its
+// edge, so it reads as an endless floor.
+
+// How far the grid reaches from the model's middle, in model sizes
+const REACH: f32 = 4.0;
+// Each line is split so its fade follows the distance from the middle
+const SEGMENTS: usize = 16;
+const MINOR_COLOR: [f32; 4] = [0.55, 0.55, 0.55, 0.3];
+const MAJOR_COLOR: [f32; 4] = [0.75, 0.75, 0.75, 0.6];
+const AXIS_COLORS: [[f32; 4]; 3] = [[0.9, 0.2, 0.2, 1.0], [0.2, 0.8, 0.2, 1.0], [0.25, 0.4, 1.0, 1.0]];
+
+// Side of a grid cell for a model whose largest dimension is `size`
+pub fn cell_size(size: f32) -> f32 {
+    10f32.powf((size / 5.0).max(f32::MIN_POSITIVE).log10().floor())
+}
+
+// Grid segments for a model with these bounds, in model space
+pub fn grid_vertices(min: Vector3<f32>, max: Vector3<f32>) -> Vec<LineVertex> {
+    let size = (max - min).x.max((max - min).y).max((max - min).z);
+    if !size.is_finite() || size <= 0.0 {
+        return Vec::new();
+    }
+
+    let cell = cell_size(size);
+    let reach = size * REACH;
+    let (center_x, center_z, y) = ((min.x + max.x) * 0.5, (min.z + max.z) * 0.5, min.y);
+    // Lines sit on multiples of the cell size, so the ones through the origin are major
+    let first = |center: f32| ((center - reach) / cell).ceil() as i64;
+    let last = |center: f32| ((center + reach) / cell).floor() as i64;
+
+    let vertex = |x: f32, z: f32, color: [f32; 4]| {
+        let distance = ((x - center_x).powi(2) + (z - center_z).powi(2)).sqrt() / reach;
+        let fade = (1.0 - distance).clamp(0.0, 1.0).powi(2);
+        LineVertex { position: [x, y, z], color: [color[0], color[1], color[2], color[3] * fade] }
+    };
+    let mut vertices = Vec::new();
+    // One line at a fixed `across` coordinate, running along the other axis
+    let mut line = |across: f32, index: i64, along_x: bool| {
+        let color = if index % 10 == 0 { MAJOR_COLOR } else { MINOR_COLOR };
+        let (start, end) = if along_x { (center_x - reach, center_x + reach) } else { (center_z - reach, center_z + reach) };
+        for i in 0..SEGMENTS {
+            for t in [i, i + 1] {
+                let along = start + (end - start) * t as f32 / SEGMENTS as f32;
+                vertices.push(if along_x { vertex(along, across, color) } else { vertex(across, along, color) });
+            }
+        }
+    };
+    for index in first(center_z)..=last(center_z) {
+        line(index as f32 * cell, index, true);
+    }
+    for index in first(center_x)..=last(center_x) {
+        line(index as f32 * cell, index, false);
+    }
+    vertices
+}
+
+// Axis lines from the origin, each as long as ten grid cells
+pub fn axis_vertices(min: Vector3<f32>, max: Vector3<f32>) -> Vec<LineVertex> {
+    let size = (max - min).x.max((max - min).y).max((max - min).z);
+    if !size.is_finite() || size <= 0.0 {
+        return Vec::new();
+    }
+
+    let length = cell_size(size) * 10.0;
+    (0..3)
+        .flat_map(|axis| {
+            let mut end = [0.0; 3];
+            end[axis] = length;
+            [LineVertex { position: [0.0; 3], color: AXIS_COLORS[axis] }, LineVertex { position: end, color: AXIS_COLORS[axis] }]
+        })
+        .collect()
+}
@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+use crate::parse::Mesh;
+use crate::stats::GeometryStats;
+
+// Model info //
+// What a file contains and how big it is, emitted as JSON by `rsview info` for scripts
+// that sort or catalog models without opening them.
+
+#[derive(Debug, Serialize)]
+pub struct PartInfo {
+    // Empty when the file doesn't name it
+    pub name: String,
+    pub triangles: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelInfo {
+    pub file: String,
+    pub vertices: usize,
+    pub triangles: usize,
+    // Vertices drawn as a point cloud, for files without faces
+    pub points: usize,
+    // Empty when the whole model is a single part
+    pub parts: Vec<PartInfo>,
+    pub normals: bool,
+    pub uvs: bool,
+    pub vertex_colors: bool,
+    pub textures: usize,
+    // Palette entries besides the identity, and clips, for rigged glTF files
+    pub joints: usize,
+    pub animations: usize,
+    // In model units, the axis-aligned bounds and their extent
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    pub size: [f64; 3],
+    pub surface_area: f64,
+    // Only meaningful when watertight
+    pub volume: f64,
+    pub watertight: bool,
+}
+
+impl ModelInfo {
+    pub fn new(file: &str, mesh: &Mesh) -> Self {
+        let stats = GeometryStats::compute(&mesh.vertices, &mesh.indices);
+        let dimensions = &stats.dimensions;
+        Self {
+            file: file.to_string(),
+            vertices: mesh.vertices.len(),
+            triangles: mesh.indices.len() / 3,
+            points: if mesh.indices.is_empty() { mesh.vertices.len() } else { 0 },
+            parts: mesh.submeshes
+                .iter()
+                .map(|submesh| PartInfo { name: submesh.name.clone(), triangles: submesh.index_count as usize / 3 })
+                .collect(),
+            normals: mesh.normals.is_some(),
+            uvs: mesh.uvs.is_some(),
+            vertex_colors: mesh.colors.is_some(),
+            textures: mesh.textures.len(),
+            joints: mesh.rig.as_ref().map_or(0, |rig| rig.joint_count().saturating_sub(1)),
+            animations: mesh.rig.as_ref().map_or(0, |rig| rig.clips.len()),
+            min: dimensions.min.into(),
+            max: dimensions.max.into(),
+            size: dimensions.size().into(),
+            surface_area: stats.surface_area,
+            volume: stats.volume,
+            watertight: !mesh.indices.is_empty() && stats.is_watertight(),
+        }
+    }
+}
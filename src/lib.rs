@@ -0,0 +1,65 @@
+// rsview //
+// Model loading, analysis and the wgpu viewer behind the rsview binary, for tools that
+// want to open or render models the same way. Parsers and the analysis passes work
+// without a GPU; Viewer needs a device and, unless it only renders thumbnails, a window.
+
+pub mod parse;
+pub mod fbx;
+pub mod usd;
+pub mod loader;
+pub mod export;
+pub mod mesh;
+pub mod animation;
+pub mod optimize;
+pub mod simplify;
+pub mod repair;
+pub mod bvh;
+pub mod scene;
+pub mod components;
+pub mod manifold;
+pub mod validate;
+pub mod info;
+pub mod topology;
+pub mod stats;
+pub mod dimensions;
+pub mod deviation;
+pub mod measure;
+pub mod section;
+pub mod clip;
+pub mod normals;
+pub mod curvature;
+pub mod debug_view;
+pub mod uv_layout;
+pub mod texture;
+pub mod budget;
+pub mod config;
+pub mod units;
+pub mod session;
+pub mod views;
+pub mod turntable;
+pub mod background;
+pub mod environment;
+pub mod viewer;
+pub mod error;
+mod palette;
+mod upload;
+mod profiling;
+mod overlay;
+mod screenshot;
+mod recording;
+mod batch;
+mod uniforms;
+mod pipeline_cache;
+mod framebuffer;
+mod frustum;
+mod lines;
+mod points;
+mod grid;
+mod ui;
+mod watch;
+mod recent;
+mod skinning;
+
+pub use error::Error;
+pub use parse::Mesh;
+pub use viewer::{Viewer, ViewerOptions};
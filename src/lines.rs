@@ -0,0 +1,156 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::StagingBelt;
+
+use crate::upload::DynamicBuffer;
+
+// Line overlays //
+// Colored line segments in model space, drawn on top of the scene or, for things that
+// sit in the scene like the ground grid, hidden behind the model. They take the same
+// object uniforms as the scene pipelines, from their own slot.
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl LineVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// The twelve edges of an axis-aligned box
+pub fn box_vertices(min: [f32; 3], max: [f32; 3], color: [f32; 4]) -> Vec<LineVertex> {
+    let corner = |i: usize| LineVertex {
+        position: [0, 1, 2].map(|axis| if i >> axis & 1 == 0 { min[axis] } else { max[axis] }),
+        color,
+    };
+    // Each corner to its neighbor one step up along each axis
+    (0..8)
+        .flat_map(|i| (0..3).filter(move |axis| i >> axis & 1 == 0).map(move |axis| (i, i | 1 << axis)))
+        .flat_map(|(a, b)| [corner(a), corner(b)])
+        .collect()
+}
+
+pub struct Lines {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: DynamicBuffer,
+    vertex_count: u32,
+}
+
+impl Lines {
+    pub fn new(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        // Lines on the surface would z-fight with it, so overlays are never depth tested
+        Self::with_depth_compare(device, uniform_layout, color_format, depth_format, sample_count, cache, wgpu::CompareFunction::Always)
+    }
+
+    // Lines the model hides, for ones that aren't on its surface
+    pub fn depth_tested(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        Self::with_depth_compare(device, uniform_layout, color_format, depth_format, sample_count, cache, wgpu::CompareFunction::LessEqual)
+    }
+
+    fn with_depth_compare(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+        depth_compare: wgpu::CompareFunction,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../lines.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Line Pipeline Layout"),
+            bind_group_layouts: &[uniform_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(&layout),
+            cache,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[LineVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer: DynamicBuffer::new(device, "Line Vertex Buffer", wgpu::BufferUsages::VERTEX, 0),
+            vertex_count: 0,
+        }
+    }
+
+    // Replaces the segments, two vertices each
+    pub fn set(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, vertices: &[LineVertex]) {
+        if !vertices.is_empty() {
+            self.vertex_buffer.write(device, encoder, belt, 0, bytemuck::cast_slice(vertices));
+        }
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    // Expects the uniforms for the lines to be bound at group 0
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
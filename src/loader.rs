@@ -0,0 +1,347 @@
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crate::budget::Budget;
+use crate::bvh::Bvh;
+use crate::components::{self, Components};
+use crate::curvature::Curvature;
+use crate::deviation::Deviation;
+use crate::error::Error;
+use crate::fbx::parse_fbx;
+use crate::manifold::ManifoldReport;
+use crate::mesh;
+use crate::normals::NormalSegments;
+use crate::optimize;
+use crate::parse::{parse_gltf, parse_las, parse_obj_streaming, parse_ply, Mesh, MeshChunk};
+use crate::repair;
+use crate::scene::Scene;
+use crate::simplify;
+use crate::stats::GeometryStats;
+use crate::topology::TopologyReport;
+use crate::units::Unit;
+use crate::usd::parse_usd;
+use crate::uv_layout::UvLayout;
+use crate::validate::ValidationReport;
+
+// Background loading //
+
+// Called after each event is sent so a sleeping event loop notices it
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
+
+pub enum LoadEvent {
+    // Partial geometry, only sent by formats that can be displayed while parsing
+    Chunk(MeshChunk),
+    // The file failed to load; the events after this are for the default model
+    Fallback(Error),
+    // Decimated stand-in for a mesh over the preview budget, followed by Loaded
+    Preview(Mesh),
+    Loaded(Mesh),
+    // Coarser versions of the loaded mesh, finest first, for meshes over the LOD threshold
+    Lods(Vec<Mesh>),
+    // Problems with the file's data, sent after Loaded if there are any
+    Validation(ValidationReport),
+    // Edge count and connectivity problems of the file's mesh, sent after Loaded when its indices are in range
+    Topology(TopologyReport),
+    // What the loaded mesh exceeds of the configured budget, sent after Loaded if anything
+    OverBudget(Vec<String>),
+    // Area, volume and watertightness of the loaded mesh, sent after Loaded
+    Stats(GeometryStats),
+    // Holes and non-manifold edges of the loaded mesh, for meshes whose indices are all in range
+    Manifold(ManifoldReport),
+    // Connected shells of the loaded mesh, for meshes whose indices are all in range
+    Components(Components),
+    // Mean curvature at each vertex of the loaded mesh, for meshes whose indices are all in range
+    Curvature(Curvature),
+    // Vertex and face normal directions of the loaded mesh, for meshes whose indices are all in range
+    Normals(NormalSegments),
+    // Texture coordinate layout with flipped and overlapping triangles, for meshes with UVs
+    UvLayout(UvLayout),
+    // Per-vertex distance of the loaded mesh to the reference it's compared against
+    Deviation(Deviation),
+    // Ray queries against the loaded mesh, built after Loaded so the BVH never delays display
+    Scene(Scene),
+    // Neither the file nor the default model could be loaded; the default model's error
+    Failed(Error),
+}
+
+#[derive(Clone)]
+pub struct LoadOptions {
+    pub optimize: bool,
+    // Remove degenerate and duplicate triangles and unused vertices
+    pub clean: bool,
+    // Make triangle winding consistent and outward facing before anything else sees the mesh
+    pub fix_winding: bool,
+    // Multiplies positions on load, converting the file's units to `unit`
+    pub unit_scale: f32,
+    // What one model unit is after scaling, for the printed readouts
+    pub unit: Unit,
+    // Drop shells with fewer triangles than this, 0 keeps them all
+    pub min_shell_triangles: usize,
+    // Keep only this shell, numbered from the largest down
+    pub isolate_shell: Option<usize>,
+    pub budget: Budget,
+    // Bring the mesh and its textures within the budget instead of just reporting it
+    pub fit_budget: bool,
+    // Reference model to measure the loaded mesh's deviation from
+    pub compare: Option<String>,
+    // Meshes with more triangles than this are shown as a decimated preview first
+    pub preview_triangles: usize,
+    // Meshes with more triangles than this get levels of detail, 0 for none
+    pub lod_triangles: usize,
+}
+
+pub fn load_model(path: &str) -> Result<Mesh, Error> {
+    load_model_streaming(path, &mut |_| {})
+}
+
+pub fn load_model_streaming(path: &str, on_chunk: &mut dyn FnMut(MeshChunk)) -> Result<Mesh, Error> {
+    let path_lower = path.to_lowercase();
+    if path_lower.ends_with(".obj") {
+        parse_obj_streaming(path, on_chunk)
+    } else if path_lower.ends_with(".gltf") || path_lower.ends_with(".glb") {
+        parse_gltf(path)
+    } else if path_lower.ends_with(".fbx") {
+        parse_fbx(path)
+    } else if path_lower.ends_with(".usda") || path_lower.ends_with(".usdz") || path_lower.ends_with(".usd") {
+        parse_usd(path)
+    } else if path_lower.ends_with(".ply") {
+        parse_ply(path)
+    } else if path_lower.ends_with(".las") || path_lower.ends_with(".laz") {
+        parse_las(path)
+    } else {
+        let name = Path::new(path).file_name().map_or_else(|| path.to_string(), |name| name.to_string_lossy().into_owned());
+        Err(Error::UnsupportedFormat(name))
+    }
+}
+
+// Parses the model on its own thread so the window stays responsive. The returned
+// channel yields the mesh, streamed in chunks where the format allows, then the
+// analyses of it as they finish; LoadEvent says when each one is sent.
+pub fn spawn_loader(path: String, options: LoadOptions, waker: Waker) -> Receiver<LoadEvent> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        // Stop streaming once the partial mesh would be too heavy to draw interactively
+        let mut streamed_triangles = 0;
+        let mut on_chunk = |chunk: MeshChunk| {
+            // A mesh about to be fitted to the budget would stream in at full size
+            if !options.fit_budget && streamed_triangles <= options.preview_triangles {
+                streamed_triangles += chunk.indices.len() / 3;
+                let _ = sender.send(LoadEvent::Chunk(chunk));
+                waker();
+            }
+        };
+
+        let mut loaded_path = path.clone();
+        let result = load_model_streaming(&path, &mut on_chunk).or_else(|e| {
+            eprintln!("Failed to load {}: {}", path, e);
+            eprintln!("Loading default model...");
+            let _ = sender.send(LoadEvent::Fallback(e));
+            waker();
+            // Try to load the default model as fallback
+            loaded_path = "test_files/cows.obj".to_string();
+            load_model(&loaded_path)
+        });
+
+        match result {
+            Ok(mut mesh) => {
+                // Checked before anything below gets a chance to repair or hide the problems
+                let validation = ValidationReport::check(&loaded_path, &mesh);
+                if !validation.is_clean() {
+                    eprintln!("Validation: {}", validation.summary());
+                }
+                let topology = TopologyReport::analyze(&mesh.vertices, &mesh.indices);
+                if let Some(topology) = &topology {
+                    println!("Topology: {} vertices, {} edges, {} faces, {}", topology.vertices, topology.edges, topology.faces, topology.summary());
+                }
+
+                scale_positions(&mut mesh.vertices, options.unit_scale);
+                if let Some(rig) = &mut mesh.rig {
+                    rig.set_unit_scale(options.unit_scale);
+                }
+
+                if options.clean {
+                    let cleanup = repair::clean(&mut mesh);
+                    println!("Cleanup: Removed {} degenerate and {} duplicate triangles, {} unreferenced vertices",
+                             cleanup.degenerate_triangles, cleanup.duplicate_triangles, cleanup.unreferenced_vertices);
+                }
+
+                if options.fix_winding {
+                    let repair = repair::orient_faces(&mut mesh);
+                    println!("Winding: Flipped {} triangles ({} inconsistent edges, {} of {} components inside out)",
+                             repair.flipped, repair.inconsistent, repair.inverted_components, repair.components);
+                }
+
+                if options.min_shell_triangles > 1 {
+                    let removed = components::remove_small_shells(&mut mesh, options.min_shell_triangles);
+                    println!("Shells: Removed {} with fewer than {} triangles", removed, options.min_shell_triangles);
+                }
+
+                if let Some(shell) = options.isolate_shell {
+                    components::isolate_shell(&mut mesh, shell);
+                    println!("Shells: Showing shell {} only", shell + 1);
+                }
+
+                if options.fit_budget {
+                    let triangles = mesh.indices.len() / 3;
+                    options.budget.fit(&mut mesh);
+                    if mesh.indices.len() / 3 != triangles {
+                        println!("Budget: Simplified {} to {} triangles", triangles, mesh.indices.len() / 3);
+                    }
+                }
+                let violations = options.budget.check(&mesh);
+                for violation in &violations {
+                    eprintln!("OVER BUDGET: {}", violation);
+                }
+
+                mesh::generate_normals(&mut mesh);
+
+                let triangles = mesh.indices.len() / 3;
+                if triangles > options.preview_triangles {
+                    let mut preview = simplify::cluster_decimate(&mesh, options.preview_triangles);
+                    mesh::generate_normals(&mut preview);
+                    println!("Preview: Showing {} of {} triangles until the full mesh is ready",
+                             preview.indices.len() / 3, triangles);
+                    let _ = sender.send(LoadEvent::Preview(preview));
+                    waker();
+                }
+
+                if options.optimize {
+                    optimize::optimize_mesh(&mut mesh);
+                }
+
+                let vertices = mesh.vertices.clone();
+                let indices = mesh.indices.clone();
+                let submeshes = mesh.submeshes.clone();
+                let normals = mesh.normals.clone();
+                let uvs = mesh.uvs.clone().filter(|uvs| uvs.len() == vertices.len());
+                let _ = sender.send(LoadEvent::Loaded(mesh));
+                waker();
+
+                if options.lod_triangles > 0 && triangles > options.lod_triangles {
+                    let start = Instant::now();
+                    let lods = simplify::build_lods(&vertices, &indices, &submeshes);
+                    let counts: Vec<String> = lods.iter().map(|lod| (lod.indices.len() / 3).to_string()).collect();
+                    println!("LOD: {} levels of {} triangles in {:.1} ms", lods.len(), counts.join(", "), start.elapsed().as_secs_f64() * 1000.0);
+                    if !lods.is_empty() {
+                        let _ = sender.send(LoadEvent::Lods(lods));
+                        waker();
+                    }
+                }
+
+                if !validation.is_clean() {
+                    let _ = sender.send(LoadEvent::Validation(validation));
+                    waker();
+                }
+
+                if let Some(topology) = topology {
+                    let _ = sender.send(LoadEvent::Topology(topology));
+                    waker();
+                }
+
+                if !violations.is_empty() {
+                    let _ = sender.send(LoadEvent::OverBudget(violations));
+                    waker();
+                }
+
+                let start = Instant::now();
+                let stats = GeometryStats::compute(&vertices, &indices);
+                println!("Geometry: {} in {:.1} ms", stats.summary(options.unit), start.elapsed().as_secs_f64() * 1000.0);
+                println!("Dimensions: {}", stats.dimensions.summary(true, options.unit));
+                if stats.inconsistent_edges > 0 && !options.fix_winding {
+                    println!("Geometry: Winding is inconsistent, --fix-winding reorients the faces");
+                }
+                let _ = sender.send(LoadEvent::Stats(stats));
+                waker();
+
+                if indices.iter().all(|&i| (i as usize) < vertices.len()) {
+                    let manifold = ManifoldReport::analyze(&vertices, &indices);
+                    println!("Manifold: {}", manifold.summary(options.unit));
+                    let _ = sender.send(LoadEvent::Manifold(manifold));
+                    waker();
+
+                    let components = Components::compute(&vertices, &indices);
+                    println!("Shells: {}", components.summary());
+                    let _ = sender.send(LoadEvent::Components(components));
+                    waker();
+
+                    let _ = sender.send(LoadEvent::Curvature(Curvature::compute(&vertices, &indices)));
+                    waker();
+
+                    let _ = sender.send(LoadEvent::Normals(NormalSegments::new(&vertices, normals.as_deref(), &indices)));
+                    waker();
+                }
+
+                if let Some(layout) = uvs.and_then(|uvs| UvLayout::analyze(&uvs, &indices)) {
+                    println!("UV layout: {}", layout.summary());
+                    let _ = sender.send(LoadEvent::UvLayout(layout));
+                    waker();
+                }
+
+                if let Some(reference_path) = &options.compare {
+                    let start = Instant::now();
+                    match load_model(reference_path) {
+                        Ok(mut reference) => {
+                            // The reference is assumed to be in the same units as the model
+                            scale_positions(&mut reference.vertices, options.unit_scale);
+                            let reference = Bvh::build(reference.vertices, reference.indices);
+                            let deviation = Deviation::compute(&reference, &vertices);
+                            println!("Compare: {} over {} vertices against {} in {:.1} ms",
+                                     deviation.summary(options.unit), vertices.len(), reference_path, start.elapsed().as_secs_f64() * 1000.0);
+                            let _ = sender.send(LoadEvent::Deviation(deviation));
+                            waker();
+                        }
+                        Err(e) => eprintln!("Failed to load reference {}: {}", reference_path, e),
+                    }
+                }
+
+                let start = Instant::now();
+                let bvh = Bvh::build(vertices, indices);
+                println!("BVH: {} nodes over {} triangles in {:.1} ms",
+                         bvh.node_count(), bvh.triangle_count(), start.elapsed().as_secs_f64() * 1000.0);
+                let _ = sender.send(LoadEvent::Scene(Scene::new(bvh, &submeshes)));
+                waker();
+            }
+            Err(e) => {
+                let _ = sender.send(LoadEvent::Failed(e));
+                waker();
+            }
+        }
+    });
+
+    receiver
+}
+
+// Loads the models shown beside the main one, in order, on a thread of their own. They
+// are only drawn, so they skip the processing and analysis; failures are logged and skipped
+pub fn spawn_side_loader(paths: Vec<String>, waker: Waker) -> Receiver<(usize, Mesh)> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for (index, path) in paths.iter().enumerate() {
+            match load_model(path) {
+                Ok(mut mesh) => {
+                    mesh::generate_normals(&mut mesh);
+                    if sender.send((index, mesh)).is_err() {
+                        return;
+                    }
+                    waker();
+                }
+                Err(e) => eprintln!("Failed to load {}: {}", path, e),
+            }
+        }
+    });
+
+    receiver
+}
+
+fn scale_positions(vertices: &mut [[f32; 3]], scale: f32) {
+    if scale != 1.0 {
+        for v in vertices {
+            *v = v.map(|c| c * scale);
+        }
+    }
+}
@@ -1,443 +1,752 @@
 use winit:: {
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, Event, MouseButton, WindowEvent},
+    event_loop::{ControlFlow, EventLoopBuilder},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::{Window, WindowBuilder},
 };
-use wgpu::util::DeviceExt;
-use bytemuck::*;
 use std::sync::Arc;
-use cgmath::*;
-use std::env;
-
-mod parse;
-use parse::{parse_obj, parse_gltf, Mesh};
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Pod, Zeroable)]
-struct Uniforms {
-    mvp: [[f32; 4]; 4],
-}
-
-struct State<'a> {
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
-    render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    rotation: f32,
-    depth_texture: wgpu::Texture,
-    depth_view: wgpu::TextureView,
-    model_scale: f32,
-    model_center: Vector3<f32>,
-    camera_distance: f32,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-}
-
-impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 1] =
-        wgpu::vertex_attr_array![0 => Float32x3];
-
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
+use std::sync::mpsc;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use rsview::background::Background;
+use rsview::config::Config;
+use rsview::export;
+use rsview::info::ModelInfo;
+use rsview::loader::{self, Waker};
+use rsview::repair;
+use rsview::session::Session;
+use rsview::units::Unit;
+use rsview::validate::ValidationReport;
+use rsview::topology::TopologyReport;
+use rsview::turntable::TurntableWriter;
+use rsview::views::ViewPreset;
+use rsview::{Viewer, ViewerOptions};
+
+// How often the stats in the window title are refreshed
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+// What the open dialog offers
+const MODEL_EXTENSIONS: [&str; 10] = ["obj", "gltf", "glb", "fbx", "usda", "usdz", "usd", "ply", "las", "laz"];
+// Four seconds per turn at the recording frame rate
+const DEFAULT_TURNTABLE_FRAMES: u32 = 120;
+const DEFAULT_RENDER_SIZE: (u32, u32) = (512, 512);
+
+// Command line //
+// `rsview <files>` opens the viewer, the same as `rsview view <files>`. The other
+// subcommands work without a window and exit with 0 on success and 2 when the model
+// can't be loaded; check and --analyze exit with 1 when they find problems.
+
+#[derive(Parser)]
+#[command(name = "rsview", version, about = "Fast 3D model viewer for OBJ, glTF, FBX, USD, PLY and LAS files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    view: ViewArgs,
+    /// Worker threads for parsing and analysis [default: one per core]
+    #[arg(long, global = true, value_name = "COUNT", value_parser = parse_count::<usize>)]
+    threads: Option<usize>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open models in the viewer window; the same as leaving the subcommand out
+    View(ViewArgs),
+    /// Render a model to a PNG, or a full turn of it to a video, without opening a window
+    Render(RenderArgs),
+    /// Convert a model to OBJ or PLY
+    Convert(ConvertArgs),
+    /// Print what a model contains and how big it is as JSON
+    Info {
+        file: String,
+    },
+    /// Print a validation report as JSON
+    Check {
+        file: String,
+    },
+}
+
+// How a model is loaded and shown, shared by the viewer and rendering
+#[derive(Args)]
+struct ModelArgs {
+    /// Model or session file; further models are shown side by side
+    files: Vec<String>,
+    /// Deviation heatmap against REFERENCE, or an A/B split screen of two models
+    #[arg(long, num_args = 1..=2, value_names = ["REFERENCE", "B"])]
+    compare: Vec<String>,
+    /// Multiplies positions on load
+    #[arg(long, value_name = "FACTOR", value_parser = parse_positive)]
+    unit_scale: Option<f32>,
+    /// What one model unit is, for the measurements: mm, cm, m or in
+    #[arg(long, value_parser = parse_unit)]
+    unit: Option<Unit>,
+    /// Triangles shown while a bigger model finishes loading [default: 20000000]
+    #[arg(long, value_name = "TRIANGLES")]
+    preview_budget: Option<usize>,
+    /// Meshes with more triangles get levels of detail, 0 for none [default: 1000000]
+    #[arg(long, value_name = "TRIANGLES")]
+    lod: Option<usize>,
+    /// Drops shells with fewer triangles on load
+    #[arg(long, value_name = "TRIANGLES")]
+    remove_debris: Option<usize>,
+    /// Reorders vertices and indices for the GPU caches on load
+    #[arg(long)]
+    optimize: bool,
+    /// Removes degenerate and duplicate triangles and unused vertices on load
+    #[arg(long)]
+    clean: bool,
+    /// Turns faces so neighbours agree on their winding
+    #[arg(long)]
+    fix_winding: bool,
+    /// Quantizes vertex positions to save GPU memory
+    #[arg(long)]
+    compact_vertices: bool,
+    /// Background color as hex, or two for a gradient like 303050,101018
+    #[arg(long, value_name = "COLOR", value_parser = parse_background)]
+    bg: Option<Background>,
+    /// Equirectangular .hdr, PNG or JPEG image lighting the model and drawn around it
+    #[arg(long, value_name = "IMAGE")]
+    env: Option<String>,
+    /// Multisampling: 1, 2, 4 or 8 [default: 4]
+    #[arg(long, value_name = "SAMPLES", value_parser = parse_msaa)]
+    msaa: Option<u32>,
+    /// Draws COUNT or WIDTHxHEIGHT copies of the model
+    #[arg(long, value_name = "COUNT", value_parser = parse_grid)]
+    grid: Option<(u32, u32)>,
+    /// Camera view to start in, without turning: front, back, left, right, top, bottom or iso
+    #[arg(long, value_parser = parse_view)]
+    view: Option<ViewPreset>,
+}
+
+#[derive(Args)]
+struct ViewArgs {
+    #[command(flatten)]
+    model: ModelArgs,
+    /// Window size in logical pixels
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    window_size: Option<(u32, u32)>,
+    /// fifo, mailbox or immediate [default: fifo]
+    #[arg(long, value_name = "MODE", value_parser = parse_present_mode)]
+    present_mode: Option<wgpu::PresentMode>,
+    /// Draws at most this many frames per second, at least 1
+    #[arg(long, value_name = "FPS", value_parser = parse_fps)]
+    fps_cap: Option<f32>,
+    /// Only draws when something changes
+    #[arg(long)]
+    power_save: bool,
+    /// Writes a row of frame timings per frame to a CSV file
+    #[arg(long, value_name = "CSV")]
+    profile: Option<String>,
+    /// Prints the topology report as JSON instead of opening a window
+    #[arg(long)]
+    analyze: bool,
+    /// Renders a PNG instead of opening a window, like `rsview render`
+    #[arg(long, value_name = "PNG")]
+    render_to: Option<String>,
+    /// Renders a full turn to an .mp4, .gif or animated .png instead of opening a window
+    #[arg(long, value_name = "VIDEO")]
+    turntable: Option<String>,
+    /// Frames in a turntable [default: 120]
+    #[arg(long, value_parser = parse_count::<u32>)]
+    frames: Option<u32>,
+    /// Size of a rendered image or turntable [default: 512x512]
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    size: Option<(u32, u32)>,
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    #[command(flatten)]
+    model: ModelArgs,
+    /// PNG to write, or with --turntable an .mp4, .gif or animated .png
+    #[arg(short, long)]
+    output: String,
+    /// Renders the model turning once around instead of a single image
+    #[arg(long)]
+    turntable: bool,
+    /// Frames in a turntable [default: 120]
+    #[arg(long, value_parser = parse_count::<u32>)]
+    frames: Option<u32>,
+    /// Image size [default: 512x512]
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    size: Option<(u32, u32)>,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    input: String,
+    /// .obj or .ply file to write
+    output: String,
+    /// Removes degenerate and duplicate triangles and unused vertices first
+    #[arg(long)]
+    clean: bool,
+    /// Turns faces so neighbours agree on their winding first
+    #[arg(long)]
+    fix_winding: bool,
+}
+
+struct Options {
+    viewer: ViewerOptions,
+    // `--analyze` prints the topology report as JSON instead of opening a window
+    analyze: bool,
+    power_save: bool,
+    // Frames are drawn at most this often, None to draw as fast as the present mode allows
+    frame_interval: Option<Duration>,
+    // In logical pixels, the platform's default when None
+    window_size: Option<(u32, u32)>,
+    // Renders one frame to this PNG without opening a window, then exits
+    render_to: Option<String>,
+    // Renders a full turn of the model to this video without opening a window, then exits
+    turntable: Option<String>,
+    turntable_frames: u32,
+    render_size: (u32, u32),
+}
+
+impl Options {
+    fn view(args: ViewArgs) -> Self {
+        let mut viewer = viewer_options(args.model);
+        viewer.profile = args.profile;
+        if let Some(mode) = args.present_mode {
+            viewer.present_mode = mode;
+        }
+        Options {
+            viewer,
+            analyze: args.analyze,
+            power_save: args.power_save,
+            frame_interval: args.fps_cap.map(|fps| Duration::from_secs_f32(1.0 / fps)),
+            window_size: args.window_size,
+            render_to: args.render_to,
+            turntable: args.turntable,
+            turntable_frames: args.frames.unwrap_or(DEFAULT_TURNTABLE_FRAMES),
+            render_size: args.size.unwrap_or(DEFAULT_RENDER_SIZE),
         }
-    }    
-}
-
-impl<'a> State<'a> {
-    fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (wgpu::Texture, wgpu::TextureView) {
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width: config.width,
-                height: config.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
-
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        (depth_texture, depth_view)
     }
 
-    fn calculate_model_bounds(vertices: &[[f32; 3]]) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>, f32) {
-        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
-        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
-
-        for vertex in vertices {
-            min.x = min.x.min(vertex[0]);
-            min.y = min.y.min(vertex[1]);
-            min.z = min.z.min(vertex[2]);
-            
-            max.x = max.x.max(vertex[0]);
-            max.y = max.y.max(vertex[1]);
-            max.z = max.z.max(vertex[2]);
+    fn render(args: RenderArgs) -> Self {
+        let (render_to, turntable) = if args.turntable { (None, Some(args.output)) } else { (Some(args.output), None) };
+        Options {
+            viewer: viewer_options(args.model),
+            analyze: false,
+            power_save: false,
+            frame_interval: None,
+            window_size: None,
+            render_to,
+            turntable,
+            turntable_frames: args.frames.unwrap_or(DEFAULT_TURNTABLE_FRAMES),
+            render_size: args.size.unwrap_or(DEFAULT_RENDER_SIZE),
         }
+    }
+}
+
+fn viewer_options(args: ModelArgs) -> ViewerOptions {
+    let defaults = ViewerOptions::default();
+    let mut files = args.files.into_iter();
+    let mut compare = args.compare.into_iter();
+    // `--compare a.obj b.obj` is an A/B split screen, a on the left
+    let (file, compare, split) = match (compare.next(), compare.next()) {
+        (Some(first), Some(second)) => (Some(first), Some(second), true),
+        (reference, _) => (files.next(), reference, false),
+    };
+    ViewerOptions {
+        file,
+        side_by_side: files.collect(),
+        optimize: args.optimize,
+        clean: args.clean,
+        fix_winding: args.fix_winding,
+        compare,
+        split,
+        unit_scale: args.unit_scale.unwrap_or(defaults.unit_scale),
+        unit: args.unit.unwrap_or(defaults.unit),
+        min_shell_triangles: args.remove_debris.unwrap_or(defaults.min_shell_triangles),
+        preview_triangles: args.preview_budget.unwrap_or(defaults.preview_triangles),
+        lod_triangles: args.lod.unwrap_or(defaults.lod_triangles),
+        compact_vertices: args.compact_vertices,
+        msaa_samples: args.msaa.unwrap_or(defaults.msaa_samples),
+        background: args.bg.unwrap_or(defaults.background),
+        environment: args.env,
+        grid: args.grid.unwrap_or(defaults.grid),
+        view: args.view,
+        ..defaults
+    }
+}
+
+// Value parsers for clap; the messages follow "invalid value 'x' for '--flag': "
 
-        let center = (min + max) / 2.0;
-        let size = max - min;
-        let max_dimension = size.x.max(size.y).max(size.z);
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let size = value.split_once(['x', 'X']).and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+    size.filter(|&(width, height)| width > 0 && height > 0).ok_or_else(|| "expected WIDTHxHEIGHT, e.g. 512x512".to_string())
+}
+
+// Copies --grid may draw, 256x256; every part keeps a 64-byte instance per copy, so this
+// leaves room for a few hundred parts within the default 256 MiB buffer limit
+const MAX_GRID_COPIES: u32 = 65_536;
 
-        (min, max, center, max_dimension)
+// A single count is a square grid
+fn parse_grid(value: &str) -> Result<(u32, u32), String> {
+    let (columns, rows) = match value.parse() {
+        Ok(count) if count > 0 => (count, count),
+        _ => parse_size(value).map_err(|_| "expected a copy count like 10 or 10x10".to_string())?,
+    };
+    match columns.checked_mul(rows) {
+        Some(copies) if copies <= MAX_GRID_COPIES => Ok((columns, rows)),
+        _ => Err(format!("at most {} copies, e.g. 256x256", MAX_GRID_COPIES)),
     }
+}
 
-    async fn new(window: &'a winit::window::Window, initial_file: Option<String>) -> Self {
-        let size = window.inner_size();
-
-        let instance = wgpu::Instance::default();
-        let surface = unsafe { instance.create_surface(window)}.unwrap();
-
-        let file_to_load = initial_file.unwrap_or_else(|| "test_files/cows".to_string());
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("Device"),
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: Default::default(),
-                    trace: wgpu::Trace::default(),
-            },
-        )
-        .await
-        .expect("Failed to create device");
-
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps.formats[0];
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            view_formats: vec![surface_format],
-            desired_maximum_frame_latency: 2,
-        };
+fn parse_count<T: std::str::FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    value.parse().ok().filter(|count| *count > T::default()).ok_or_else(|| "expected a positive count".to_string())
+}
 
-        let mesh = load_model(&file_to_load)
-            .unwrap_or_else(|e| {
-        eprintln!("Failed to load {}: {}", file_to_load, e);
-        eprintln!("Loading default model...");
-        // Try to load the default model as fallback
-        load_model("test_files/cows.obj")
-            .expect("Failed to load default model")
-        });
-
-        // Calculate model bounds for auto-scaling
-        let (_, _, center, max_dimension) = Self::calculate_model_bounds(&mesh.vertices);
-        let model_scale = 2.0 / max_dimension; // Scale to fit in a 2-unit cube
-        let camera_distance = 3.0; // Adjust this to zoom in/out
-
-        let vertices: Vec<Vertex> = mesh.vertices.into_iter().map(|p| Vertex { position: p }).collect();
-        let indices: Vec<u32> = mesh.indices.into_iter().map(|i| i as u32).collect();
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        let num_indices = indices.len() as u32;
-
-        // Create uniform buffer
-        let uniforms = Uniforms {
-            mvp: Matrix4::identity().into(),
-        };
+// Below one frame a second the frame interval stops being a useful limit, and tiny values
+// don't fit a Duration
+fn parse_fps(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().ok().filter(|fps| *fps >= 1.0 && fps.is_finite()).ok_or_else(|| "expected frames per second of at least 1".to_string())
+}
 
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Uniform Buffer"),
-            contents: bytemuck::cast_slice(&[uniforms]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        // Create bind group layout
-        let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            ],
-            label: Some("uniform_bind_group_layout"),
-        });
-
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                }
-            ],
-            label: Some("uniform_bind_group"),
-        });
-
-        surface.configure(&device, &config);
-
-        // Create depth texture
-        let (depth_texture, depth_view) = Self::create_depth_texture(&device, &config);
-
-        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader.wgsl"));
-
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&uniform_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            cache: None,
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-        });
-
-        Self {
-            surface,
-            device,
-            queue,
-            config,
-            size,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
-            uniform_buffer,
-            uniform_bind_group,
-            rotation: 0.0,
-            depth_texture,
-            depth_view,
-            model_scale,
-            model_center: center,
-            camera_distance,
-        }
+fn parse_positive(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().ok().filter(|value| *value > 0.0 && value.is_finite()).ok_or_else(|| "expected a positive number".to_string())
+}
+
+fn parse_msaa(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(samples @ (1 | 2 | 4 | 8)) => Ok(samples),
+        _ => Err("expected 1, 2, 4 or 8".to_string()),
+    }
+}
+
+fn parse_unit(value: &str) -> Result<Unit, String> {
+    Unit::parse(value).ok_or_else(|| "expected one of mm, cm, m, in".to_string())
+}
+
+fn parse_background(value: &str) -> Result<Background, String> {
+    Background::parse(value).ok_or_else(|| "expected a hex color like 202030, or two for a gradient like 303050,101018".to_string())
+}
+
+fn parse_view(value: &str) -> Result<ViewPreset, String> {
+    ViewPreset::parse(value).ok_or_else(|| "expected front, back, left, right, top, bottom or iso".to_string())
+}
+
+fn parse_present_mode(value: &str) -> Result<wgpu::PresentMode, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "fifo" | "vsync" => Ok(wgpu::PresentMode::Fifo),
+        "mailbox" => Ok(wgpu::PresentMode::Mailbox),
+        "immediate" => Ok(wgpu::PresentMode::Immediate),
+        _ => Err("expected fifo, mailbox or immediate".to_string()),
+    }
+}
+
+// Viewer flags before a subcommand would be parsed into the top-level ViewArgs and then
+// dropped, so they're rejected instead. clap's args_conflicts_with_subcommands can't do
+// this, as the top-level files would take the subcommand's name as a file.
+fn parse_cli() -> Cli {
+    let mut command = Cli::command();
+    let matches = command.get_matches_mut();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut command).exit());
+    if let Some((subcommand, _)) = matches.subcommand()
+        && let Some(id) = matches.ids().find(|id| id.as_str() != "threads" && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+    {
+        let name = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .map_or_else(|| id.to_string(), |arg| arg.get_long().map_or_else(|| arg.get_id().to_string(), |long| format!("--{}", long)));
+        command
+            .error(clap::error::ErrorKind::ArgumentConflict, format!("{} can't be used with the {} subcommand; options go after it", name, subcommand))
+            .exit();
+    }
+    cli
+}
+
+fn main() {
+    let cli = parse_cli();
+    if let Some(threads) = cli.threads
+        && let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
+    {
+        eprintln!("--threads: {}", e);
     }
 
-    fn update(&mut self) {
-        self.rotation += 0.01;
-
-        let aspect_ratio = self.size.width as f32 / self.size.height as f32;
-        
-        let model = Matrix4::from_angle_y(Rad(self.rotation)) * 
-                    Matrix4::from_scale(self.model_scale) * 
-                    Matrix4::from_translation(-self.model_center);
-        
-        let camera_pos = Point3::new(
-            self.camera_distance,
-            self.camera_distance * 0.5,
-            self.camera_distance
-        );
-        
-        let view = Matrix4::look_at_rh(
-            camera_pos,
-            Point3::new(0.0, 0.0, 0.0),  // Look at origin
-            Vector3::unit_y(),           // Up vector
-        );
-        
-        let proj = perspective(Rad(std::f32::consts::FRAC_PI_4), aspect_ratio, 0.1, 100.0);
-        
-        let mvp = proj * view * model;
-
-        let uniforms = Uniforms {
-            mvp: mvp.into(),
+    let mut options = match cli.command.unwrap_or(Command::View(cli.view)) {
+        Command::Check { file } => std::process::exit(check(&file)),
+        Command::Info { file } => std::process::exit(info(&file)),
+        Command::Convert(args) => std::process::exit(convert(&args)),
+        Command::Render(args) => Options::render(args),
+        Command::View(args) => Options::view(args),
+    };
+    if options.analyze {
+        let Some(file) = options.viewer.file.as_deref() else {
+            eprintln!("Usage: rsview --analyze <file>");
+            std::process::exit(2);
         };
-        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        std::process::exit(analyze(file));
+    }
+
+    let session = match options.viewer.file.as_deref().filter(|file| Session::is_session_file(file)) {
+        Some(file) => match Session::load(Path::new(file)) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    if options.turntable.is_some() {
+        std::process::exit(pollster::block_on(turntable(options, session)));
     }
+    if options.render_to.is_some() {
+        std::process::exit(pollster::block_on(render_to(options, session)));
+    }
+
+    // Launched without a file, e.g. from a desktop shortcut; cancelling shows the default model
+    if options.viewer.file.is_none() {
+        options.viewer.file = pick_model(None);
+    }
+
+    pollster::block_on(run(options, session));
+}
+
+// A windowless viewer at --size with the model fully loaded, or None when there's
+// nothing to draw
+async fn headless_viewer(options: &Options, session: Option<Session>) -> Option<Viewer<'static>> {
+    // The loader wakes this thread up instead of an event loop
+    let (wake, woken) = mpsc::channel();
+    let waker: Waker = Arc::new(move || {
+        let _ = wake.send(());
+    });
+
+    let (width, height) = options.render_size;
+    let config = Config::load();
+    let mut viewer = Viewer::new(None, PhysicalSize::new(width, height), &options.viewer, &config, session, waker).await;
+    // Always the same front view, however long loading takes
+    viewer.set_auto_rotate(false);
+    viewer.wait_for_model(&woken);
+
+    if !viewer.has_geometry() {
+        eprintln!("Nothing to render from {}", viewer.model_path());
+        return None;
+    }
+    Some(viewer)
+}
 
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            
-            let (depth_texture, depth_view) = Self::create_depth_texture(&self.device, &self.config);
-            self.depth_texture = depth_texture;
-            self.depth_view = depth_view;
+// Loads the model, draws it once at --size over a transparent background and writes
+// the PNG. Exit status 0 on success and 2 when the model or the image fails.
+async fn render_to(options: Options, session: Option<Session>) -> i32 {
+    let Some(path) = options.render_to.clone() else {
+        return 2;
+    };
+    let Some(mut viewer) = headless_viewer(&options, session).await else {
+        return 2;
+    };
+
+    match viewer.save_png(Path::new(&path)) {
+        Ok(()) => {
+            println!("Rendered {} to {}", viewer.model_path(), path);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
         }
     }
+}
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.update();
-
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder")
-        });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+// Renders --frames views of the model turning once around and encodes them at --size.
+// Exit status 0 on success and 2 when the model or the video fails.
+async fn turntable(options: Options, session: Option<Session>) -> i32 {
+    let Some(path) = options.turntable.clone() else {
+        return 2;
+    };
+    let Some(mut viewer) = headless_viewer(&options, session).await else {
+        return 2;
+    };
+
+    let frames = options.turntable_frames;
+    let (width, height) = options.render_size;
+    let path = Path::new(&path);
+    // Only an animated PNG can keep the background transparent
+    let transparent = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png") || extension.eq_ignore_ascii_case("apng"));
+    let result = TurntableWriter::start(path, width, height, frames).and_then(|mut writer| {
+        for frame in 0..frames {
+            let angle = std::f32::consts::TAU * frame as f32 / frames as f32;
+            writer.push(&viewer.turntable_frame(angle, transparent)?)?;
+        }
+        writer.finish()
+    });
+
+    match result {
+        Ok(()) => {
+            println!("Rendered a {} frame turntable of {} to {}", frames, viewer.model_path(), path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
         }
+    }
+}
+
+// Exit status 0 when the file is clean, 1 when it has issues and 2 when it can't be loaded
+fn check(file: &str) -> i32 {
+    print_report(file, |file, mesh| {
+        let report = ValidationReport::check(file, mesh);
+        let clean = report.is_clean();
+        Some((report, clean))
+    })
+}
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+// The same exit statuses as check, for the topology problems
+fn analyze(file: &str) -> i32 {
+    print_report(file, |_, mesh| {
+        let Some(report) = TopologyReport::analyze(&mesh.vertices, &mesh.indices) else {
+            eprintln!("Indices past the end of the vertex list, rsview check lists them");
+            return None;
+        };
+        let clean = report.is_clean();
+        Some((report, clean))
+    })
+}
 
-        Ok(())
+// Exit status 0 when the model loads and 2 when it can't be
+fn info(file: &str) -> i32 {
+    print_report(file, |file, mesh| Some((ModelInfo::new(file, mesh), true)))
+}
+
+// Exit status 0 when the converted model was written and 2 when it couldn't be read or written
+fn convert(args: &ConvertArgs) -> i32 {
+    let mut mesh = match loader::load_model(&args.input) {
+        Ok(mesh) => mesh,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", args.input, e);
+            return 2;
+        }
+    };
+    if args.clean {
+        let cleanup = repair::clean(&mut mesh);
+        println!("Cleanup: Removed {} degenerate and {} duplicate triangles, {} unreferenced vertices",
+                 cleanup.degenerate_triangles, cleanup.duplicate_triangles, cleanup.unreferenced_vertices);
+    }
+    if args.fix_winding {
+        let repair = repair::orient_faces(&mut mesh);
+        println!("Winding: Flipped {} triangles ({} inconsistent edges, {} of {} components inside out)",
+                 repair.flipped, repair.inconsistent, repair.inverted_components, repair.components);
+    }
+
+    match export::save_model(&args.output, &mesh) {
+        Ok(()) => {
+            println!("Converted {} to {}", args.input, args.output);
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", args.output, e);
+            2
+        }
+    }
+}
+
+// Loads `file` and prints the report `build` makes of it as JSON, returning the exit status
+fn print_report<R: serde::Serialize>(file: &str, build: impl FnOnce(&str, &rsview::Mesh) -> Option<(R, bool)>) -> i32 {
+    match loader::load_model(file) {
+        Ok(mesh) => {
+            let Some((report, clean)) = build(file, &mesh) else {
+                return 2;
+            };
+            match serde_json::to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to write the report: {}", e),
+            }
+            if clean { 0 } else { 1 }
+        }
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", file, e);
+            2
+        }
     }
 }
 
-fn load_model(path: &str) -> Result<Mesh, String> {
-    let path_lower = path.to_lowercase();
-    if path_lower.ends_with(".obj") {
-        parse_obj(path)
-    } else if path_lower.ends_with(".gltf") {
-        parse_gltf(path)
-    } else {
-        Err("Unsupported file format, only .obj and .gltf (NOT GLB) files are supported.".to_string())
+// Native open dialog for the supported model formats; None when cancelled
+fn pick_model(parent: Option<&Window>) -> Option<String> {
+    // The desktop portal is reached over D-Bus, which runs on tokio
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| eprintln!("Open dialog: {}", e)).ok()?;
+    let _context = runtime.enter();
+
+    let mut dialog = rfd::FileDialog::new()
+        .set_title("Open model")
+        .add_filter("3D models", &MODEL_EXTENSIONS);
+    if let Some(parent) = parent {
+        dialog = dialog.set_parent(parent);
     }
+    dialog.pick_file().map(|path| path.to_string_lossy().into_owned())
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let initial_file = if args.len() > 1 {
-        Some(args[1].clone())
-    } else {
-        None
-    };
-    
-    pollster::block_on(run(initial_file));
+// Sent to the event loop from other threads to ask for a frame
+#[derive(Debug)]
+enum UserEvent {
+    Redraw,
 }
 
-async fn run(initial_file: Option<String>) {
-    let event_loop = EventLoop::new().unwrap();
+async fn run(options: Options, session: Option<Session>) {
+    let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build().unwrap();
     let window = Arc::new(
-        WindowBuilder::new()
+        options.window_size
+            .map_or_else(WindowBuilder::new, |(width, height)| WindowBuilder::new().with_inner_size(LogicalSize::new(width, height)))
             .with_title("rsview - Model Viewer")
             .build(&event_loop)
             .unwrap()
     );
 
-    let mut state = State::new(&window, initial_file).await;
+    let proxy = event_loop.create_proxy();
+    let waker: Waker = Arc::new(move || {
+        let _ = proxy.send_event(UserEvent::Redraw);
+    });
+
+    let config = Config::load();
+    let mut state = Viewer::new(Some(&window), window.inner_size(), &options.viewer, &config, session, waker).await;
+    // An idle auto-rotation would redraw every frame; Space still turns it on
+    if options.power_save {
+        state.set_auto_rotate(false);
+    }
     let window_clone = window.clone();
 
-    event_loop.run(move |event, event_loop_window_target| {
-        event_loop_window_target.set_control_flow(ControlFlow::Poll);
+    // In power-save mode the loop sleeps until input, loader progress or animation needs a frame
+    let control_flow = if options.power_save { ControlFlow::Wait } else { ControlFlow::Poll };
+    let mut occluded = false;
+    let mut last_stats_update = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut modifiers = ModifiersState::empty();
+
+    let _ = event_loop.run(move |event, event_loop_window_target| {
+        event_loop_window_target.set_control_flow(control_flow);
+
+        let handling_start = Instant::now();
+        let is_redraw = matches!(event, Event::WindowEvent { event: WindowEvent::RedrawRequested, .. });
     
         match event {
-            Event::WindowEvent { event, window_id } if window_id == window_clone.id() => match event {
-                WindowEvent::CloseRequested => {
-                    let _ = event_loop_window_target.exit();
+            Event::WindowEvent { event, window_id } if window_id == window_clone.id() => {
+                // Clicks and scrolling over the panel are the panel's
+                let overlay_consumed = state.overlay_event(&event);
+                match event {
+                    WindowEvent::CloseRequested => {
+                        state.stop_recording();
+                        state.stop_profiling();
+                        event_loop_window_target.exit();
+                    }
+                    WindowEvent::RedrawRequested => {
+                        if last_stats_update.elapsed() >= STATS_INTERVAL {
+                            window_clone.set_title(&format!("rsview - Model Viewer | {}", state.stats_text()));
+                            last_stats_update = Instant::now();
+                        }
+
+                        last_frame = Instant::now();
+                        match state.render() {
+                            Ok(()) => {}
+                            // The surface no longer matches the window, usually mid-resize
+                            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                                state.recover_surface();
+                                window_clone.request_redraw();
+                            }
+                            Err(wgpu::SurfaceError::OutOfMemory) => {
+                                eprintln!("Out of GPU memory, exiting");
+                                event_loop_window_target.exit();
+                            }
+                            Err(e) => eprintln!("Skipped frame: {}", e),
+                        }
+                    }
+                    WindowEvent::Resized(physical_size) => {
+                        state.resize(physical_size);
+                        window_clone.request_redraw();
+                    }
+                    WindowEvent::ModifiersChanged(new_modifiers) => {
+                        modifiers = new_modifiers.state();
+                    }
+                    WindowEvent::KeyboardInput { event, .. } => {
+                        if event.state == ElementState::Pressed
+                            && let PhysicalKey::Code(code) = event.physical_key {
+                            if code == KeyCode::KeyO && modifiers.control_key() {
+                                // Blocks the loop until the dialog closes, like any modal dialog
+                                if !event.repeat && let Some(path) = pick_model(Some(&window_clone)) {
+                                    state.open(path);
+                                }
+                            } else if let Some(preset) = ViewPreset::for_key(code, modifiers.control_key()) {
+                                if !event.repeat {
+                                    state.snap_to_view(preset);
+                                }
+                            } else {
+                                state.key_pressed(code, event.repeat);
+                            }
+                        }
+                        window_clone.request_redraw();
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        state.cursor_moved(position);
+                        // The panel reacts to hovering
+                        if options.power_save && (state.overlay_visible() || state.is_dragging_splitter()) {
+                            window_clone.request_redraw();
+                        }
+                    }
+                    WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Middle, .. } if !overlay_consumed => {
+                        state.focus_at_cursor();
+                        window_clone.request_redraw();
+                    }
+                    WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } if !overlay_consumed => {
+                        state.grab_splitter();
+                        if !state.is_dragging_splitter() {
+                            // Shift-click adds a measurement pick, a plain click picks a triangle
+                            if modifiers.shift_key() {
+                                state.measure_at_cursor();
+                            } else {
+                                state.select_at_cursor();
+                            }
+                            window_clone.request_redraw();
+                        }
+                    }
+                    WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+                        state.release_splitter();
+                    }
+                    WindowEvent::Occluded(is_occluded) => {
+                        occluded = is_occluded;
+                        window_clone.request_redraw();
+                    }
+                    _ => {
+                        if options.power_save {
+                            window_clone.request_redraw();
+                        }
+                    }
                 }
-                WindowEvent::RedrawRequested => {
-                    state.render().unwrap();
-                }
-                WindowEvent::Resized(physical_size) => {
-                    state.resize(physical_size);
-                }
-                _ => {}
-            },
-            Event::AboutToWait => {
+            }
+            Event::UserEvent(UserEvent::Redraw) => {
                 window_clone.request_redraw();
             }
+            Event::AboutToWait => {
+                if state.is_minimized() && state.resize_deadline().is_none() {
+                    // Nothing to draw into until the window is restored
+                    event_loop_window_target.set_control_flow(ControlFlow::Wait);
+                } else if !options.power_save || (state.is_animating() && !occluded) {
+                    // With --fps-cap the loop sleeps out the rest of the frame instead
+                    match options.frame_interval.map(|interval| last_frame + interval) {
+                        Some(next_frame) if Instant::now() < next_frame => {
+                            event_loop_window_target.set_control_flow(ControlFlow::WaitUntil(next_frame));
+                        }
+                        _ => window_clone.request_redraw(),
+                    }
+                } else if let Some(deadline) = state.resize_deadline() {
+                    // Wake up once resizing has settled so the surface catches up
+                    if Instant::now() >= deadline {
+                        window_clone.request_redraw();
+                    } else {
+                        event_loop_window_target.set_control_flow(ControlFlow::WaitUntil(deadline));
+                    }
+                }
+            }
             _ => {}
         }
+
+        // Frames time themselves, everything else counts as event handling
+        if !is_redraw {
+            state.record_event_time(handling_start.elapsed());
+        }
     });    
-}
\ No newline at end of file
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+use serde::Serialize;
+
+use crate::lines::LineVertex;
+use crate::repair;
+use crate::units::Unit;
+
+// Manifold and hole analysis //
+// Where a surface fails to be a closed 2-manifold: boundary edges chained into the
+// loops that outline each hole, and edges shared by more than two triangles. Both are
+// kept as segments for the edge overlay, and summarised for printability reports.
+
+const BOUNDARY_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const NON_MANIFOLD_COLOR: [f32; 4] = [1.0, 0.2, 1.0, 1.0];
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Hole {
+    pub edges: usize,
+    pub perimeter: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifoldReport {
+    pub boundary_edges: usize,
+    pub non_manifold_edges: usize,
+    // Closed boundary loops, largest perimeter first
+    pub holes: Vec<Hole>,
+    // Boundary edges that don't chain into a loop, usually from inconsistent winding
+    pub open_boundary_edges: usize,
+    #[serde(skip)]
+    pub boundary_segments: Vec<[[f32; 3]; 2]>,
+    #[serde(skip)]
+    pub non_manifold_segments: Vec<[[f32; 3]; 2]>,
+}
+
+impl ManifoldReport {
+    // Indices must be in range
+    pub fn analyze(vertices: &[[f32; 3]], indices: &[u32]) -> Self {
+        let welded = repair::weld_positions(vertices);
+        let mut positions = vec![[0.0; 3]; welded.iter().map(|&id| id as usize + 1).max().unwrap_or(0)];
+        for (v, &id) in welded.iter().enumerate() {
+            positions[id as usize] = vertices[v];
+        }
+
+        // Boundary edges in the direction their triangle walks them
+        let mut boundary: Vec<(u32, u32)> = Vec::new();
+        let mut non_manifold_segments = Vec::new();
+        for group in repair::sorted_edges(&welded, indices).chunk_by(|x, y| x.0 == y.0) {
+            let (key, _, forward) = group[0];
+            let (low, high) = ((key >> 32) as u32, key as u32);
+            match group.len() {
+                1 if forward => boundary.push((low, high)),
+                1 => boundary.push((high, low)),
+                2 => {}
+                _ => non_manifold_segments.push([positions[low as usize], positions[high as usize]]),
+            }
+        }
+
+        let boundary_segments = boundary.iter().map(|&(a, b)| [positions[a as usize], positions[b as usize]]).collect();
+        let (holes, open_boundary_edges) = chain_loops(&boundary, &positions);
+
+        Self {
+            boundary_edges: boundary.len(),
+            non_manifold_edges: non_manifold_segments.len(),
+            holes,
+            open_boundary_edges,
+            boundary_segments,
+            non_manifold_segments,
+        }
+    }
+
+    pub fn is_manifold(&self) -> bool {
+        self.boundary_edges == 0 && self.non_manifold_edges == 0
+    }
+
+    pub fn summary(&self, unit: Unit) -> String {
+        if self.is_manifold() {
+            return "closed manifold".to_string();
+        }
+
+        let mut text = format!("{} holes", self.holes.len());
+        if let Some(largest) = self.holes.first() {
+            text += &format!(" (largest {} edges, perimeter {})", largest.edges, unit.length(largest.perimeter));
+        }
+        if self.open_boundary_edges > 0 {
+            text += &format!(", {} open boundary edges", self.open_boundary_edges);
+        }
+        text + &format!(", {} non-manifold edges", self.non_manifold_edges)
+    }
+
+    pub fn line_vertices(&self) -> Vec<LineVertex> {
+        let boundary = self.boundary_segments.iter().map(|s| (s, BOUNDARY_COLOR));
+        let non_manifold = self.non_manifold_segments.iter().map(|s| (s, NON_MANIFOLD_COLOR));
+        boundary
+            .chain(non_manifold)
+            .flat_map(|(&[a, b], color)| [LineVertex { position: a, color }, LineVertex { position: b, color }])
+            .collect()
+    }
+}
+
+// Follows boundary edges end to start into loops; where several edges leave the same
+// point any unused one is taken, which splits figure-eight holes but keeps every edge
+// in exactly one loop. Returns the loops and the number of edges left over.
+fn chain_loops(edges: &[(u32, u32)], positions: &[[f32; 3]]) -> (Vec<Hole>, usize) {
+    let mut outgoing: HashMap<u32, Vec<usize>> = HashMap::with_capacity(edges.len());
+    for (e, &(a, _)) in edges.iter().enumerate() {
+        outgoing.entry(a).or_default().push(e);
+    }
+
+    let length = |e: usize| {
+        let (a, b) = edges[e];
+        (Vector3::from(positions[b as usize].map(f64::from)) - Vector3::from(positions[a as usize].map(f64::from))).magnitude()
+    };
+
+    let mut used = vec![false; edges.len()];
+    let mut holes = Vec::new();
+    let mut open = 0;
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+
+        let start = edges[first].0;
+        let mut path = vec![first];
+        used[first] = true;
+        let mut at = edges[first].1;
+        while at != start {
+            let next = outgoing.get(&at).and_then(|out| out.iter().copied().find(|&e| !used[e]));
+            let Some(next) = next else {
+                break;
+            };
+            used[next] = true;
+            path.push(next);
+            at = edges[next].1;
+        }
+
+        if at == start {
+            holes.push(Hole {
+                edges: path.len(),
+                perimeter: path.iter().map(|&e| length(e)).sum(),
+            });
+        } else {
+            open += path.len();
+        }
+    }
+
+    holes.sort_by(|a, b| b.perimeter.total_cmp(&a.perimeter));
+    (holes, open)
+}
@@ -0,0 +1,115 @@
+use std::fmt::Write;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::lines::LineVertex;
+use crate::scene::{Hit, Scene};
+use crate::units::Unit;
+
+// Measurements //
+// Points picked on the surface, with the edge of the picked triangle closest to each
+// point, and a plain-text report of the distances and face angles between them that
+// can be copied out for recording dimensions. In the view each pick gets a cross and
+// consecutive picks a line between them.
+
+const LINE_COLOR: [f32; 4] = [1.0, 0.55, 0.1, 1.0];
+const MARKER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+pub struct Pick {
+    pub point: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    // Length of the picked triangle's edge nearest to the point
+    pub edge_length: f32,
+}
+
+impl Pick {
+    pub fn from_hit(scene: &Scene, hit: &Hit) -> Self {
+        let [a, b, c] = scene.bvh().triangle(hit.triangle);
+        let [u, v] = hit.barycentric;
+
+        // The corner with the smallest weight lies opposite the nearest edge
+        let weights = [1.0 - u - v, u, v];
+        let nearest_edge = if weights[0] <= weights[1] && weights[0] <= weights[2] {
+            c - b
+        } else if weights[1] <= weights[2] {
+            a - c
+        } else {
+            b - a
+        };
+
+        let normal = (b - a).cross(c - a);
+        Self {
+            point: hit.point,
+            normal: if normal.magnitude2() > 0.0 { normal.normalize() } else { normal },
+            edge_length: nearest_edge.magnitude(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Measurements {
+    picks: Vec<Pick>,
+}
+
+impl Measurements {
+    pub fn add(&mut self, pick: Pick) {
+        self.picks.push(pick);
+    }
+
+    pub fn clear(&mut self) {
+        self.picks.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.picks.is_empty()
+    }
+
+    pub fn picks(&self) -> &[Pick] {
+        &self.picks
+    }
+
+    // Between the last two picks
+    pub fn last_distance(&self) -> Option<f32> {
+        match self.picks.as_slice() {
+            [.., a, b] => Some((b.point - a.point).magnitude()),
+            _ => None,
+        }
+    }
+
+    // Crosses `marker` long either way on the picks, and lines joining them in order
+    pub fn line_vertices(&self, marker: f32) -> Vec<LineVertex> {
+        let mut vertices = Vec::new();
+        for pick in &self.picks {
+            for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+                for p in [pick.point - axis * marker, pick.point + axis * marker] {
+                    vertices.push(LineVertex { position: p.into(), color: MARKER_COLOR });
+                }
+            }
+        }
+        for pair in self.picks.windows(2) {
+            for pick in pair {
+                vertices.push(LineVertex { position: pick.point.into(), color: LINE_COLOR });
+            }
+        }
+        vertices
+    }
+
+    // One line per pick, then the distance and face angle to the previous pick
+    pub fn report(&self, unit: Unit) -> String {
+        let mut report = String::new();
+        for (i, pick) in self.picks.iter().enumerate() {
+            let _ = writeln!(report, "P{}: ({:.4}, {:.4}, {:.4}){}  edge {}",
+                             i + 1, pick.point.x, pick.point.y, pick.point.z, unit.suffix(), unit.length(pick.edge_length as f64));
+
+            if i > 0 {
+                let previous = &self.picks[i - 1];
+                let distance = (pick.point - previous.point).magnitude();
+                // Between the winding normals, so parallel faces read 0 and opposite ones 180
+                let angle = previous.normal.dot(pick.normal).clamp(-1.0, 1.0).acos().to_degrees();
+                let _ = writeln!(report, "P{}-P{}: distance {}, face angle {:.2} deg", i, i + 1, unit.length(distance as f64), angle);
+            }
+        }
+
+        report
+    }
+}
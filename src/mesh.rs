@@ -0,0 +1,39 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::parse::Mesh;
+
+// Mesh processing //
+// Steps that fill in what a file left out, run on the loader thread before the mesh
+// is handed to the renderer.
+
+// Smooth normals for OBJ files without vn lines, position-only glTFs and anything else
+// without one normal per vertex, averaged from the faces around each vertex and weighted
+// by their area, so slivers barely count
+pub fn generate_normals(mesh: &mut Mesh) {
+    if mesh.normals.as_ref().is_some_and(|normals| normals.len() == mesh.vertices.len()) {
+        return;
+    }
+    mesh.normals = Some(vertex_normals(&mesh.vertices, &mesh.indices));
+}
+
+// Unused and fully degenerate vertices get a zero normal, which the shader treats as
+// "use the face normal"
+fn vertex_normals(vertices: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut sums = vec![Vector3::new(0.0f32, 0.0, 0.0); vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        if triangle.iter().any(|&i| i as usize >= vertices.len()) {
+            continue;
+        }
+        let [a, b, c] = [0, 1, 2].map(|corner| Vector3::from(vertices[triangle[corner] as usize]));
+        // Twice the area, pointing along the winding normal
+        let normal = (b - a).cross(c - a);
+        for &i in triangle {
+            sums[i as usize] += normal;
+        }
+    }
+
+    sums.into_iter()
+        .map(|sum| if sum.magnitude2() > 0.0 { sum.normalize().into() } else { [0.0; 3] })
+        .collect()
+}
@@ -0,0 +1,95 @@
+use cgmath::{InnerSpace, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::lines::LineVertex;
+
+// Normal display //
+// Short segments along each vertex normal, and optionally along each face normal from
+// the middle of its triangle, for tracking down shading artifacts: normals that point
+// inward, were smoothed across hard edges, or don't match the winding. The directions
+// are worked out once on the loader thread; the segments are rebuilt at the length set
+// in the panel, a fraction of the model's size.
+
+const VERTEX_COLOR: [f32; 4] = [0.3, 0.55, 1.0, 1.0];
+const FACE_COLOR: [f32; 4] = [1.0, 0.75, 0.2, 1.0];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalDisplay {
+    pub vertex: bool,
+    pub face: bool,
+    // Segment length as a fraction of the model's largest dimension
+    pub length: f32,
+}
+
+impl Default for NormalDisplay {
+    fn default() -> Self {
+        Self { vertex: false, face: false, length: 0.02 }
+    }
+}
+
+impl NormalDisplay {
+    pub fn is_shown(&self) -> bool {
+        self.vertex || self.face
+    }
+
+    // N goes from none to vertex normals, to both, to none
+    pub fn cycled(self) -> Self {
+        let (vertex, face) = match (self.vertex, self.face) {
+            (false, false) => (true, false),
+            (true, false) => (true, true),
+            _ => (false, false),
+        };
+        Self { vertex, face, ..self }
+    }
+}
+
+pub struct NormalSegments {
+    // Where each segment starts and its unit direction; vertices without a normal and
+    // triangles without area have none
+    vertex: Vec<([f32; 3], [f32; 3])>,
+    face: Vec<([f32; 3], [f32; 3])>,
+}
+
+impl NormalSegments {
+    // Indices have to be in range
+    pub fn new(vertices: &[[f32; 3]], normals: Option<&[[f32; 3]]>, indices: &[u32]) -> Self {
+        let vertex = normals
+            .filter(|normals| normals.len() == vertices.len())
+            .map(|normals| {
+                vertices
+                    .iter()
+                    .zip(normals)
+                    .filter_map(|(&position, &normal)| {
+                        let normal = Vector3::from(normal);
+                        (normal.magnitude2() > 0.0).then(|| (position, normal.normalize().into()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let face = indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| Vector3::from(vertices[i as usize]));
+                let normal = (b - a).cross(c - a);
+                (normal.magnitude2() > 0.0).then(|| (((a + b + c) / 3.0).into(), normal.normalize().into()))
+            })
+            .collect();
+
+        Self { vertex, face }
+    }
+
+    // Segments `length` long in model units for the normals `display` shows
+    pub fn line_vertices(&self, display: &NormalDisplay, length: f32) -> Vec<LineVertex> {
+        let vertex = self.vertex.iter().filter(|_| display.vertex).map(|segment| (segment, VERTEX_COLOR));
+        let face = self.face.iter().filter(|_| display.face).map(|segment| (segment, FACE_COLOR));
+        vertex
+            .chain(face)
+            .flat_map(|(&(start, direction), color)| {
+                let end = [0, 1, 2].map(|axis| start[axis] + direction[axis] * length);
+                [LineVertex { position: start, color }, LineVertex { position: end, color }]
+            })
+            .collect()
+    }
+}
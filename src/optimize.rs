@@ -0,0 +1,334 @@
+use crate::parse::Mesh;
+
+// Mesh optimizer //
+// Reorders triangles and vertices so the GPU does less redundant work, following
+// the same three passes meshoptimizer uses: vertex cache, overdraw, vertex fetch.
+
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRI_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+pub fn optimize_mesh(mesh: &mut Mesh) {
+    let vertex_count = mesh.vertices.len();
+    if mesh.indices.is_empty() || mesh.indices.iter().any(|&i| i as usize >= vertex_count) {
+        println!("Optimizer: Skipped, mesh has no valid index buffer");
+        return;
+    }
+
+    let acmr_before = cache_miss_ratio(&mesh.indices, vertex_count);
+
+    // Triangles may only move within their own part, or the draw ranges would break
+    let mut ranges: Vec<(usize, usize)> = mesh.submeshes
+        .iter()
+        .map(|submesh| (submesh.first_index as usize, (submesh.first_index + submesh.index_count) as usize))
+        .collect();
+    if ranges.is_empty() {
+        ranges.push((0, mesh.indices.len()));
+    }
+
+    for (start, end) in ranges {
+        // Only whole triangles are reordered; a stray index or two at the end stays put
+        let end = end.min(mesh.indices.len());
+        if start >= end {
+            continue;
+        }
+        let end = start + (end - start) / 3 * 3;
+
+        // Per-vertex tables only need to reach the highest vertex this part uses
+        let used_vertices = mesh.indices[start..end].iter().max().map_or(0, |&i| i as usize + 1);
+        optimize_vertex_cache(&mut mesh.indices[start..end], used_vertices);
+        optimize_overdraw(&mut mesh.indices[start..end], &mesh.vertices, used_vertices);
+    }
+    optimize_vertex_fetch(mesh);
+
+    println!("Optimizer: ACMR {:.3} -> {:.3}, {} vertices after fetch remap",
+             acmr_before, cache_miss_ratio(&mesh.indices, mesh.vertices.len()), mesh.vertices.len());
+}
+
+// Average cache misses per triangle for a simulated FIFO post-transform cache
+fn cache_miss_ratio(indices: &[u32], vertex_count: usize) -> f32 {
+    let mut timestamps = vec![0usize; vertex_count];
+    let mut time = CACHE_SIZE + 1;
+    let mut misses = 0;
+
+    for &index in indices {
+        let v = index as usize;
+        if time - timestamps[v] > CACHE_SIZE {
+            timestamps[v] = time;
+            time += 1;
+            misses += 1;
+        }
+    }
+
+    misses as f32 / (indices.len() / 3).max(1) as f32
+}
+
+fn vertex_score(cache_pos: Option<usize>, live_triangles: u32) -> f32 {
+    if live_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_pos {
+        None => 0.0,
+        // The triangle that was just emitted; fixed score so it isn't reused immediately
+        Some(pos) if pos < 3 => LAST_TRI_SCORE,
+        Some(pos) => (1.0 - (pos - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER),
+    };
+
+    cache_score + VALENCE_BOOST_SCALE * (live_triangles as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+// Tom Forsyth's linear-speed vertex cache optimization
+fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+
+    // Per-vertex triangle adjacency, stored as one flat array with offsets
+    let mut live = vec![0u32; vertex_count];
+    for &index in indices.iter() {
+        live[index as usize] += 1;
+    }
+
+    let mut offsets = vec![0usize; vertex_count];
+    let mut total = 0;
+    for v in 0..vertex_count {
+        offsets[v] = total;
+        total += live[v] as usize;
+    }
+
+    let mut adjacency = vec![0u32; total];
+    let mut fill = offsets.clone();
+    for t in 0..triangle_count {
+        for &index in &indices[t * 3..t * 3 + 3] {
+            adjacency[fill[index as usize]] = t as u32;
+            fill[index as usize] += 1;
+        }
+    }
+
+    let mut vertex_scores: Vec<f32> = live.iter().map(|&n| vertex_score(None, n)).collect();
+    let triangle_scores: Vec<f32> = (0..triangle_count)
+        .map(|t| indices[t * 3..t * 3 + 3].iter().map(|&v| vertex_scores[v as usize]).sum())
+        .collect();
+
+    let mut emitted = vec![false; triangle_count];
+    let mut output = Vec::with_capacity(indices.len());
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut scan = 0;
+
+    let mut best = (0..triangle_count)
+        .max_by(|&a, &b| triangle_scores[a].total_cmp(&triangle_scores[b]));
+
+    while output.len() < triangle_count * 3 {
+        let triangle = match best {
+            Some(t) => t,
+            None => {
+                // Nothing useful left in the cache, continue with the next unemitted triangle
+                while emitted[scan] {
+                    scan += 1;
+                }
+                scan
+            }
+        };
+
+        emitted[triangle] = true;
+        let corners = [indices[triangle * 3], indices[triangle * 3 + 1], indices[triangle * 3 + 2]];
+        output.extend_from_slice(&corners);
+
+        for &v in &corners {
+            let v = v as usize;
+            let list = &mut adjacency[offsets[v]..offsets[v] + live[v] as usize];
+            if let Some(pos) = list.iter().position(|&t| t as usize == triangle) {
+                let last = list.len() - 1;
+                list.swap(pos, last);
+                live[v] -= 1;
+            }
+        }
+
+        // Move the new corners to the front of the LRU cache
+        let mut new_cache: Vec<u32> = corners.to_vec();
+        new_cache.extend(cache.iter().copied().filter(|v| !corners.contains(v)));
+
+        for (i, &v) in new_cache.iter().enumerate() {
+            let pos = if i < CACHE_SIZE { Some(i) } else { None };
+            vertex_scores[v as usize] = vertex_score(pos, live[v as usize]);
+        }
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for &v in &new_cache {
+            let v = v as usize;
+            for &t in &adjacency[offsets[v]..offsets[v] + live[v] as usize] {
+                let t = t as usize;
+                let score = indices[t * 3..t * 3 + 3].iter().map(|&c| vertex_scores[c as usize]).sum();
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+    }
+
+    indices.copy_from_slice(&output);
+}
+
+// Splits the cache-optimized triangle order into clusters at cache restarts, then
+// sorts the clusters so outward-facing ones on the hull draw first
+fn optimize_overdraw(indices: &mut [u32], vertices: &[[f32; 3]], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+
+    let mut clusters = vec![0];
+    let mut timestamps = vec![0usize; vertex_count];
+    let mut time = CACHE_SIZE + 1;
+
+    for t in 0..triangle_count {
+        let mut misses = 0;
+        for &index in &indices[t * 3..t * 3 + 3] {
+            let v = index as usize;
+            if time - timestamps[v] > CACHE_SIZE {
+                timestamps[v] = time;
+                time += 1;
+                misses += 1;
+            }
+        }
+
+        if misses == 3 && t > 0 {
+            clusters.push(t);
+        }
+    }
+    clusters.push(triangle_count);
+
+    let mut mesh_centroid = [0.0f32; 3];
+    for vertex in vertices {
+        for axis in 0..3 {
+            mesh_centroid[axis] += vertex[axis] / vertices.len() as f32;
+        }
+    }
+
+    let mut sort_keys: Vec<(f32, usize)> = Vec::with_capacity(clusters.len() - 1);
+    for (cluster, range) in clusters.windows(2).enumerate() {
+        let mut centroid = [0.0f32; 3];
+        let mut normal = [0.0f32; 3];
+        let mut area_sum = 0.0;
+
+        for t in range[0]..range[1] {
+            let a = vertices[indices[t * 3] as usize];
+            let b = vertices[indices[t * 3 + 1] as usize];
+            let c = vertices[indices[t * 3 + 2] as usize];
+
+            let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [
+                ab[1] * ac[2] - ab[2] * ac[1],
+                ab[2] * ac[0] - ab[0] * ac[2],
+                ab[0] * ac[1] - ab[1] * ac[0],
+            ];
+            let area = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+
+            for axis in 0..3 {
+                centroid[axis] += (a[axis] + b[axis] + c[axis]) / 3.0 * area;
+                normal[axis] += n[axis];
+            }
+            area_sum += area;
+        }
+
+        let key = if area_sum > 0.0 {
+            (0..3)
+                .map(|axis| (centroid[axis] / area_sum - mesh_centroid[axis]) * normal[axis])
+                .sum::<f32>()
+                / (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt().max(f32::EPSILON)
+        } else {
+            f32::MIN
+        };
+        sort_keys.push((key, cluster));
+    }
+
+    sort_keys.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut output = Vec::with_capacity(indices.len());
+    for &(_, cluster) in &sort_keys {
+        output.extend_from_slice(&indices[clusters[cluster] * 3..clusters[cluster + 1] * 3]);
+    }
+
+    indices.copy_from_slice(&output);
+}
+
+// Renumbers vertices in first-use order so vertex fetches walk memory linearly; vertices
+// no triangle uses are dropped
+pub fn optimize_vertex_fetch(mesh: &mut Mesh) {
+    let mut remap = vec![u32::MAX; mesh.vertices.len()];
+    let mut next = 0;
+
+    for index in mesh.indices.iter_mut() {
+        let slot = &mut remap[*index as usize];
+        if *slot == u32::MAX {
+            *slot = next;
+            next += 1;
+        }
+        *index = *slot;
+    }
+
+    let mut vertices = vec![[0.0; 3]; next as usize];
+    for (old, &new) in remap.iter().enumerate() {
+        if new != u32::MAX {
+            vertices[new as usize] = mesh.vertices[old];
+        }
+    }
+
+    // Normals only line up with positions when the file provided one per vertex
+    if let Some(normals) = mesh.normals.as_mut()
+        && normals.len() == mesh.vertices.len() {
+        let mut remapped = vec![[0.0; 3]; next as usize];
+        for (old, &new) in remap.iter().enumerate() {
+            if new != u32::MAX {
+                remapped[new as usize] = normals[old];
+            }
+        }
+        *normals = remapped;
+    }
+
+    if let Some(uvs) = mesh.uvs.as_mut() {
+        let mut remapped = vec![[0.0; 2]; next as usize];
+        for (old, &new) in remap.iter().enumerate() {
+            if new != u32::MAX {
+                remapped[new as usize] = uvs[old];
+            }
+        }
+        *uvs = remapped;
+    }
+
+    if let Some(colors) = mesh.colors.as_mut() {
+        let mut remapped = vec![[0; 4]; next as usize];
+        for (old, &new) in remap.iter().enumerate() {
+            if new != u32::MAX {
+                remapped[new as usize] = colors[old];
+            }
+        }
+        *colors = remapped;
+    }
+
+    if let Some(joints) = mesh.joints.as_mut() {
+        let mut remapped = vec![[0; 4]; next as usize];
+        for (old, &new) in remap.iter().enumerate() {
+            if new != u32::MAX {
+                remapped[new as usize] = joints[old];
+            }
+        }
+        *joints = remapped;
+    }
+
+    if let Some(weights) = mesh.weights.as_mut() {
+        let mut remapped = vec![[0.0; 4]; next as usize];
+        for (old, &new) in remap.iter().enumerate() {
+            if new != u32::MAX {
+                remapped[new as usize] = weights[old];
+            }
+        }
+        *weights = remapped;
+    }
+
+    mesh.vertices = vertices;
+}
@@ -0,0 +1,169 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::StagingBelt;
+
+use crate::profiling::{FrameHistory, FRAME_HISTORY};
+use crate::upload::DynamicBuffer;
+
+// Frame time graph //
+// Stacked bars of recent CPU frame timings in the bottom-left corner, one bar per
+// frame. Coordinates are in clip space so the graph ignores the camera entirely.
+
+const GRAPH_LEFT: f32 = -0.95;
+const GRAPH_BOTTOM: f32 = -0.95;
+const GRAPH_WIDTH: f32 = 0.8;
+const GRAPH_HEIGHT: f32 = 0.5;
+// Frame time that fills the full graph height
+const GRAPH_MAX_MS: f32 = 33.3;
+
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const TARGET_LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+// events, update, encode, present
+const SEGMENT_COLORS: [[f32; 4]; 4] = [
+    [0.3, 0.5, 1.0, 0.9],
+    [0.3, 0.9, 0.4, 0.9],
+    [1.0, 0.6, 0.2, 0.9],
+    [0.6, 0.6, 0.6, 0.9],
+];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct OverlayVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl OverlayVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+pub struct FrameGraph {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: DynamicBuffer,
+    vertex_count: u32,
+}
+
+impl FrameGraph {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32, cache: Option<&wgpu::PipelineCache>) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../overlay.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&layout),
+            cache,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[OverlayVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Drawn inside the scene pass, so it has to match its depth attachment
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        let vertex_buffer = DynamicBuffer::new(
+            device,
+            "Overlay Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            ((FRAME_HISTORY * 4 + 2) * 6 * std::mem::size_of::<OverlayVertex>()) as wgpu::BufferAddress,
+        );
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    // Rebuilds the bars from the history; call before the render pass that draws them
+    pub fn prepare(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, history: &FrameHistory) {
+        let mut vertices = Vec::with_capacity((FRAME_HISTORY * 4 + 2) * 6);
+
+        push_quad(&mut vertices, GRAPH_LEFT, GRAPH_BOTTOM, GRAPH_WIDTH, GRAPH_HEIGHT, BACKGROUND_COLOR);
+
+        let bar_width = GRAPH_WIDTH / FRAME_HISTORY as f32;
+        for (i, frame) in history.iter().enumerate() {
+            let x = GRAPH_LEFT + i as f32 * bar_width;
+            let mut y = GRAPH_BOTTOM;
+
+            for (ms, color) in [frame.events, frame.update, frame.encode, frame.present].into_iter().zip(SEGMENT_COLORS) {
+                let top = GRAPH_BOTTOM + GRAPH_HEIGHT;
+                let height = (ms / GRAPH_MAX_MS * GRAPH_HEIGHT).min(top - y);
+                if height > 0.0 {
+                    push_quad(&mut vertices, x, y, bar_width, height, color);
+                    y += height;
+                }
+            }
+        }
+
+        // Marks the 60 fps budget
+        let target_y = GRAPH_BOTTOM + 16.7 / GRAPH_MAX_MS * GRAPH_HEIGHT;
+        push_quad(&mut vertices, GRAPH_LEFT, target_y, GRAPH_WIDTH, 0.004, TARGET_LINE_COLOR);
+
+        self.vertex_buffer.write(device, encoder, belt, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+fn push_quad(vertices: &mut Vec<OverlayVertex>, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+    let corners = [
+        [x, y],
+        [x + width, y],
+        [x + width, y + height],
+        [x, y],
+        [x + width, y + height],
+        [x, y + height],
+    ];
+
+    vertices.extend(corners.into_iter().map(|position| OverlayVertex { position, color }));
+}
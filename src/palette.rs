@@ -0,0 +1,19 @@
+// Distinct colors //
+// Colors for telling numbered things apart (shells, parts), stable for a given number.
+
+// Golden ratio steps keep neighbouring numbers far apart in hue
+pub fn distinct_color(index: usize) -> [f32; 3] {
+    let hue = (index as f32 * 0.618_034).fract();
+    let h = hue * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let [r, g, b] = match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    };
+    // Softened a little so shading stays visible
+    [r, g, b].map(|c| 0.25 + c * 0.7)
+}
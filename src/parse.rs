@@ -1,7 +1,15 @@
-use std::io::{BufReader, BufRead};
-use std::fs::File;
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, One, Quaternion, SquareMatrix, Vector3, Vector4};
+use memmap2::Mmap;
+use rayon::prelude::*;
 use serde::Deserialize;
-use std::{fs, path::Path};
+use std::fs::File;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::animation::{Channel, Clip, Interpolation, Joint, LocalTransform, Property, Rig, RigNode};
+use crate::batch::DEFAULT_COLOR;
+use crate::error::Error;
+use crate::texture::{decode_texture, load_texture, Sampling, TextureImage};
 
 // Obj Parser //
 
@@ -10,98 +18,568 @@ pub struct Mesh {
     pub vertices: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
     pub normals: Option<Vec<[f32; 3]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    // RGBA8 per vertex, from PLY files
+    pub colors: Option<Vec<[u8; 4]>>,
+    // Up to four joints per vertex, as indices into the rig's joint palette, and how much
+    // each one moves the vertex; glTF skins only
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
+    pub rig: Option<Rig>,
+    // Separately drawn parts, empty when the whole mesh is a single part
+    pub submeshes: Vec<SubMesh>,
+    pub textures: Vec<TextureImage>,
+    // Other files the model refers to (glTF buffers and images, material libraries and
+    // their textures), whether or not they could be read
+    pub sources: Vec<PathBuf>,
+}
+
+// A range of the shared index buffer drawn with its own per-draw data
+#[derive(Clone, Debug)]
+pub struct SubMesh {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub color: [f32; 4],
+    // Index into Mesh::textures
+    pub texture: Option<usize>,
+    pub material: Material,
+    // OBJ object or group name, glTF mesh name; empty when the file has none
+    pub name: String,
+}
+
+// The rest of a glTF metallic-roughness material besides the base color. Other formats,
+// and glTF primitives without a material, get the default: a fairly rough dielectric.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    // How much the normal texture bends the normals, and how much the occlusion texture darkens the ambient
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    // Indices into Mesh::textures; metallic is read from blue and roughness from green
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+    pub emissive_texture: Option<usize>,
+    // Shown in its base color without lighting (KHR_materials_unlit)
+    pub unlit: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: [0.0; 3],
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            unlit: false,
+        }
+    }
+}
+
+// Maps a model file instead of reading it, so large files are parsed straight from the
+// page cache without a copy; None for an empty file
+pub(crate) fn map_file(file_path: &str) -> Result<Option<Mmap>, Error> {
+    let file = File::open(file_path).map_err(Error::io("Failed to open file"))?;
+    let file_len = file.metadata().map_err(Error::io("Failed to open file"))?.len();
+
+    // Mapping an empty file fails on some platforms, and there is nothing to parse anyway
+    if file_len == 0 {
+        return Ok(None);
+    }
+    // Safety: the mapping is only read while parsing; a file truncated by another
+    // process during that window is outside of what this viewer protects against
+    Ok(Some(unsafe { Mmap::map(&file) }.map_err(Error::io("Failed to map file"))?))
 }
 
-pub fn parse_obj(file_path: &str) -> Result<Mesh, String> {
+// Files are split into roughly this many bytes per parallel work item
+const OBJ_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+// Geometry from one piece of an OBJ file, handed out while the rest is still parsing
+pub struct MeshChunk {
+    pub vertex_base: usize,
+    pub total_vertices: usize,
+    pub vertices: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+struct ObjChunk {
+    vertices: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    // Texture coordinate and normal of each index, NO_INDEX for corners without one
+    uv_indices: Vec<u32>,
+    normal_indices: Vec<u32>,
+    // Objects and groups starting in this chunk, by their first index within it
+    groups: Vec<(usize, String)>,
+    // `usemtl` switches in this chunk, by their first index within it
+    materials: Vec<(usize, String)>,
+    // Files named by `mtllib` lines
+    libraries: Vec<String>,
+}
+
+// Vertices, texture coordinates, normals and lines before a chunk
+struct ChunkBase {
+    vertex: usize,
+    uv: usize,
+    normal: usize,
+    line: usize,
+}
+
+// Marks a face corner without a `vt` or `vn` index
+const NO_INDEX: u32 = u32::MAX;
+
+// A material from an .mtl file; only the diffuse color, opacity and texture are used
+struct ObjMaterial {
+    color: [f32; 4],
+    texture: Option<String>,
+}
+
+pub fn parse_obj_streaming(file_path: &str, on_chunk: &mut dyn FnMut(MeshChunk)) -> Result<Mesh, Error> {
     // function for parsing obj files at the simplest level, mesh data only
-    let file = File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
-    let reader = BufReader::new(file);
+    let mmap = map_file(file_path)?;
+    let data: &[u8] = mmap.as_deref().unwrap_or(&[]);
+
+    let chunks = split_lines(data, OBJ_CHUNK_SIZE);
+
+    // Relative (negative) face indices need to know how many vertices, texture
+    // coordinates and normals came before each chunk, so count those up front and turn
+    // them into base offsets. Lines are counted too so errors can say where they are.
+    // Only lines with enough values count, the same ones parse_obj_chunk keeps.
+    let counts: Vec<(usize, usize, usize, usize)> = chunks
+        .par_iter()
+        .map(|chunk| {
+            chunk.split(|&b| b == b'\n').fold((0, 0, 0, 0), |(v, vt, vn, lines), line| {
+                let mut tokens = tokens(line);
+                match tokens.next() {
+                    Some(b"v") if tokens.nth(2).is_some() => (v + 1, vt, vn, lines + 1),
+                    Some(b"vt") if tokens.next().is_some() => (v, vt + 1, vn, lines + 1),
+                    Some(b"vn") if tokens.nth(2).is_some() => (v, vt, vn + 1, lines + 1),
+                    _ => (v, vt, vn, lines + 1),
+                }
+            })
+        })
+        .collect();
+
+    let mut vertex_bases = Vec::with_capacity(chunks.len());
+    let mut total = 0;
+    let mut total_uvs = 0;
+    let mut total_normals = 0;
+    let mut total_lines = 0;
+    for &(count, uv_count, normal_count, lines) in &counts {
+        vertex_bases.push(ChunkBase { vertex: total, uv: total_uvs, normal: total_normals, line: total_lines });
+        total += count;
+        total_uvs += uv_count;
+        total_normals += normal_count;
+        // Every chunk but the last ends on a line break, which split() counts as an extra empty line
+        total_lines += lines - 1;
+    }
+
+    // Parse one chunk per thread at a time so finished geometry can be passed on early
+    let batch_size = rayon::current_num_threads().max(1);
+    let mut parsed = Vec::with_capacity(chunks.len());
+
+    for (batch, bases) in chunks.chunks(batch_size).zip(vertex_bases.chunks(batch_size)) {
+        let results: Vec<ObjChunk> = batch
+            .par_iter()
+            .zip(bases.par_iter())
+            .map(|(chunk, base)| parse_obj_chunk(chunk, base))
+            .collect::<Result<_, _>>()?;
+
+        for (chunk, base) in results.iter().zip(bases) {
+            on_chunk(MeshChunk {
+                vertex_base: base.vertex,
+                total_vertices: total,
+                vertices: chunk.vertices.clone(),
+                indices: chunk.indices.clone(),
+            });
+        }
+
+        parsed.extend(results);
+    }
+
+    let mut vertices = Vec::with_capacity(total);
+    let mut indices = Vec::with_capacity(parsed.iter().map(|c| c.indices.len()).sum());
+    let mut normals = Vec::with_capacity(total_normals);
+    let mut uvs = Vec::with_capacity(total_uvs);
+    let mut uv_indices = Vec::with_capacity(indices.capacity());
+    let mut normal_indices = Vec::with_capacity(indices.capacity());
+    let mut groups = Vec::new();
+    let mut material_uses = Vec::new();
+    let mut libraries = Vec::new();
+
+    for chunk in parsed {
+        groups.extend(chunk.groups.into_iter().map(|(first, name)| (indices.len() + first, name)));
+        material_uses.extend(chunk.materials.into_iter().map(|(first, name)| (indices.len() + first, name)));
+        libraries.extend(chunk.libraries);
+        vertices.extend(chunk.vertices);
+        indices.extend(chunk.indices);
+        normals.extend(chunk.normals);
+        uvs.extend(chunk.uvs);
+        uv_indices.extend(chunk.uv_indices);
+        normal_indices.extend(chunk.normal_indices);
+    }
+
+    // Positions, texture coordinates and normals are indexed separately in OBJ, so each
+    // distinct combination becomes its own vertex once faces reference either of the others.
+    // Files whose faces name no normals get them by position, as exporters that write
+    // one `vn` per `v` expect.
+    let has_uvs = uv_indices.iter().any(|&uv| (uv as usize) < uvs.len());
+    let has_normal_indices = normal_indices.iter().any(|&normal| (normal as usize) < normals.len());
+    let (vertices, normals, uvs) = if has_uvs || has_normal_indices {
+        let mut corners = std::collections::HashMap::new();
+        let mut welded_vertices = Vec::new();
+        let mut welded_normals = Vec::new();
+        let mut welded_uvs = Vec::new();
+        for ((index, &uv), &normal) in indices.iter_mut().zip(&uv_indices).zip(&normal_indices) {
+            let position = *index as usize;
+            // Left out of range so validation still reports it
+            if position >= vertices.len() {
+                *index = u32::MAX;
+                continue;
+            }
+            let normal = if has_normal_indices { normal } else { *index };
+            *index = *corners.entry((*index, uv, normal)).or_insert_with(|| {
+                welded_vertices.push(vertices[position]);
+                // Zero normals fall back to the face normal when shading
+                welded_normals.push(normals.get(normal as usize).copied().unwrap_or([0.0; 3]));
+                welded_uvs.push(uvs.get(uv as usize).copied().unwrap_or([0.0; 2]));
+                welded_vertices.len() as u32 - 1
+            });
+        }
+        let welded_normals = if normals.is_empty() { Vec::new() } else { welded_normals };
+        (welded_vertices, welded_normals, has_uvs.then_some(welded_uvs))
+    } else {
+        (vertices, normals, None)
+    };
+
+    eprintln!("OBJ Parser: Loaded {} vertices, {} indices ({} triangles)", 
+             vertices.len(), indices.len(), indices.len() / 3);
+
+    let base_dir = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let mut sources: Vec<PathBuf> = libraries.iter().map(|library| base_dir.join(library)).collect();
+    let mut materials = std::collections::HashMap::new();
+    for library in &libraries {
+        match parse_mtl(&base_dir.join(library)) {
+            Ok(parsed) => materials.extend(parsed),
+            Err(e) => eprintln!("OBJ Parser: Skipping material library {}: {}", library, e),
+        }
+    }
+
+    let mut submeshes = obj_groups(groups, material_uses, indices.len());
+    let mut textures = Vec::new();
+    // Texture file -> slot in `textures`, None when it couldn't be loaded
+    let mut texture_slots: std::collections::HashMap<String, Option<usize>> = std::collections::HashMap::new();
+    for (submesh, material) in &mut submeshes {
+        let Some(material) = material.as_ref().and_then(|name| materials.get(name)) else {
+            continue;
+        };
+        submesh.color = material.color;
+        submesh.texture = material.texture.as_ref().and_then(|file| {
+            *texture_slots.entry(file.clone()).or_insert_with(|| {
+                sources.push(base_dir.join(file));
+                let image = load_texture(&base_dir.join(file))
+                    .map_err(|e| eprintln!("OBJ Parser: Skipping texture {}: {}", file, e))
+                    .ok()?;
+                textures.push(image);
+                Some(textures.len() - 1)
+            })
+        });
+    }
+
+    if !materials.is_empty() {
+        eprintln!("OBJ Parser: Loaded {} materials, {} textures", materials.len(), textures.len());
+    }
+
+    Ok(Mesh {
+        vertices,
+        indices,
+        normals: if normals.is_empty() { None } else { Some(normals) },
+        uvs,
+        colors: None,
+        joints: None,
+        weights: None,
+        rig: None,
+        submeshes: submeshes.into_iter().map(|(submesh, _)| submesh).collect(),
+        textures,
+        sources,
+    })
+}
+
+// One submesh per object or group and material that has faces, each with the name of
+// the material it uses; faces before the first `o`, `g` or `usemtl` line form an
+// unnamed part. Files without groups or materials stay a single part.
+fn obj_groups(groups: Vec<(usize, String)>, materials: Vec<(usize, String)>, index_count: usize) -> Vec<(SubMesh, Option<String>)> {
+    if groups.is_empty() && materials.is_empty() {
+        return Vec::new();
+    }
+
+    // Both lists are in file order; a new part starts wherever either one changes
+    let mut starts: Vec<usize> = groups.iter().chain(&materials).map(|&(first, _)| first).chain([0]).collect();
+    starts.sort_unstable();
+    starts.dedup();
+
+    let mut parts = Vec::new();
+    let (mut group, mut material) = (0, 0);
+    for (i, &first) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(index_count);
+        while group < groups.len() && groups[group].0 <= first {
+            group += 1;
+        }
+        while material < materials.len() && materials[material].0 <= first {
+            material += 1;
+        }
+        if end <= first {
+            continue;
+        }
+
+        let group_name = group.checked_sub(1).map(|g| groups[g].1.clone());
+        let material_name = material.checked_sub(1).map(|m| materials[m].1.clone());
+        parts.push((
+            SubMesh {
+                first_index: first as u32,
+                index_count: (end - first) as u32,
+                color: DEFAULT_COLOR,
+                texture: None,
+                material: Material::default(),
+                // Parts without a group are named after their material
+                name: group_name.filter(|name| !name.is_empty()).or(material_name.clone()).unwrap_or_default(),
+            },
+            material_name,
+        ));
+    }
+    parts
+}
+
+// Reads every `newmtl` block's Kd, d/Tr and map_Kd
+fn parse_mtl(path: &Path) -> Result<std::collections::HashMap<String, ObjMaterial>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read material library: {}", e))?;
+
+    let mut materials = std::collections::HashMap::new();
+    let mut current: Option<(String, ObjMaterial)> = None;
+    for line in data.split(|&b| b == b'\n') {
+        let mut tokens = tokens(line);
+        let keyword = tokens.next();
+        if keyword == Some(b"newmtl") {
+            let name: Vec<&[u8]> = tokens.collect();
+            let material = ObjMaterial { color: DEFAULT_COLOR, texture: None };
+            if let Some((name, material)) = current.replace((String::from_utf8_lossy(&name.join(&b' ')).into_owned(), material)) {
+                materials.insert(name, material);
+            }
+            continue;
+        }
+        let Some((_, material)) = current.as_mut() else {
+            continue;
+        };
+
+        match keyword {
+            Some(b"Kd") => {
+                if let Some([r, g, b]) = parse_vec3(tokens, "diffuse color")? {
+                    material.color = [r, g, b, material.color[3]];
+                }
+            }
+            Some(keyword @ (b"d" | b"Tr")) => {
+                if let Some(value) = tokens.next().and_then(|t| fast_float2::parse::<f32, _>(t).ok()) {
+                    material.color[3] = if keyword == b"d" { value } else { 1.0 - value };
+                }
+            }
+            // Options such as -s or -bm come before the file name, which is last
+            Some(b"map_Kd") => {
+                if let Some(file) = tokens.last() {
+                    material.texture = Some(String::from_utf8_lossy(file).replace('\\', "/"));
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    Ok(materials)
+}
+
+// Cuts data into pieces of about chunk_size bytes, always ending on a line break
+fn split_lines(data: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
 
+    while rest.len() > chunk_size {
+        let split = match rest[chunk_size..].iter().position(|&b| b == b'\n') {
+            Some(pos) => chunk_size + pos + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(split);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+
+    chunks
+}
+
+fn tokens(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    line.split(|b| b.is_ascii_whitespace()).filter(|token| !token.is_empty())
+}
+
+// Reads the three floats after a v/vn keyword, None when the line is too short
+fn parse_vec3<'a>(mut tokens: impl Iterator<Item = &'a [u8]>, what: &str) -> Result<Option<[f32; 3]>, String> {
+    let (Some(x), Some(y), Some(z)) = (tokens.next(), tokens.next(), tokens.next()) else {
+        return Ok(None);
+    };
+
+    let x = fast_float2::parse(x).map_err(|_| format!("Invalid {} x", what))?;
+    let y = fast_float2::parse(y).map_err(|_| format!("Invalid {} y", what))?;
+    let z = fast_float2::parse(z).map_err(|_| format!("Invalid {} z", what))?;
+    Ok(Some([x, y, z]))
+}
+
+fn parse_index(token: &[u8]) -> Option<i64> {
+    let (negative, digits) = match token.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        Some((b'+', rest)) => (false, rest),
+        _ => (false, token),
+    };
+
+    if digits.is_empty() || digits.len() > 18 {
+        return None;
+    }
+
+    let mut value: i64 = 0;
+    for &b in digits {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + (b - b'0') as i64;
+    }
+
+    Some(if negative { -value } else { value })
+}
+
+fn parse_obj_chunk(data: &[u8], base: &ChunkBase) -> Result<ObjChunk, Error> {
     let mut vertices = Vec::new();
     let mut indices = Vec::new();
     let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut uv_indices = Vec::new();
+    let mut normal_indices = Vec::new();
+    let mut face_indices: Vec<u32> = Vec::new();
+    let mut face_uvs: Vec<u32> = Vec::new();
+    let mut face_normals: Vec<u32> = Vec::new();
+    let mut groups = Vec::new();
+    let mut materials = Vec::new();
+    let mut libraries = Vec::new();
 
-    for line in reader.lines() {
-        
-        let line = line.map_err(|e| format!("Failed to read line: {}", e))?;
-        let tokens: Vec<&str> = line.split_whitespace().collect();
+    for (line_number, line) in data.split(|&b| b == b'\n').enumerate() {
+        let mut tokens = tokens(line);
+        let error = |reason: String| Error::ObjParse { line: base.line + line_number + 1, reason };
 
-        match tokens.get(0) {
-             Some(&"v") => {
-                if tokens.len() < 4 {
-                    continue;
+        match tokens.next() {
+             Some(b"v") => {
+                if let Some(position) = parse_vec3(tokens, "vertex").map_err(error)? {
+                    vertices.push(position);
                 }
-                
-                let x = tokens[1].parse().map_err(|_| "Invalid vertex x")?;
-                let y = tokens[2].parse().map_err(|_| "Invalid vertex y")?;
-                let z = tokens[3].parse().map_err(|_| "Invalid vertex z")?;
-                vertices.push([x, y, z]);
              }
-             Some(&"vn") => {
-                if tokens.len() < 4 {
-                    continue;
+             Some(b"vn") => {
+                if let Some(normal) = parse_vec3(tokens, "normal").map_err(error)? {
+                    normals.push(normal);
                 }
-
-                let x = tokens[1].parse().map_err(|_| "Invalid normal x")?;
-                let y = tokens[2].parse().map_err(|_| "Invalid normal y")?;
-                let z = tokens[3].parse().map_err(|_| "Invalid normal z")?;
-                normals.push([x, y, z]);
              }
-             Some(&"f") => {
-                // Parse all face indices first
-                let face_indices: Vec<u32> = (1..tokens.len())
-                    .filter_map(|i| {
-                        let index_str = tokens[i];
-                        index_str
-                            .split('/')
-                            .next()
-                            .and_then(|s| s.parse::<u32>().ok())
-                            .map(|idx| idx - 1)
-                    })
-                    .collect();
-
-                if face_indices.len() >= 3 {
-
-                    if face_indices.len() == 3 {
-                        indices.extend_from_slice(&face_indices);
-                    }
-
-                    else if face_indices.len() == 4 {
-
-                        indices.push(face_indices[0]);
-                        indices.push(face_indices[1]);
-                        indices.push(face_indices[2]);
-                        
-                        indices.push(face_indices[0]);
-                        indices.push(face_indices[2]);
-                        indices.push(face_indices[3]);
+             Some(b"vt") => {
+                // OBJ's V axis points up, the textures' rows go down
+                let (Some(u), v) = (tokens.next(), tokens.next()) else {
+                    continue;
+                };
+                let u = fast_float2::parse(u).map_err(|_| error("Invalid texture coordinate u".to_string()))?;
+                let v: f32 = v.map(fast_float2::parse).transpose().map_err(|_| error("Invalid texture coordinate v".to_string()))?.unwrap_or(0.0);
+                uvs.push([u, 1.0 - v]);
+             }
+             Some(b"o" | b"g") => {
+                let name: Vec<&[u8]> = tokens.collect();
+                groups.push((indices.len(), String::from_utf8_lossy(&name.join(&b' ')).into_owned()));
+             }
+             Some(b"usemtl") => {
+                let name: Vec<&[u8]> = tokens.collect();
+                materials.push((indices.len(), String::from_utf8_lossy(&name.join(&b' ')).into_owned()));
+             }
+             Some(b"mtllib") => {
+                let name: Vec<&[u8]> = tokens.collect();
+                libraries.push(String::from_utf8_lossy(&name.join(&b' ')).into_owned());
+             }
+             Some(b"f") => {
+                // Parse all face indices first, resolving them against the whole file
+                let seen = (base.vertex + vertices.len()) as i64;
+                let seen_uvs = (base.uv + uvs.len()) as i64;
+                let seen_normals = (base.normal + normals.len()) as i64;
+                let resolve = |index: Option<&[u8]>, seen: i64| {
+                    index
+                        .and_then(parse_index)
+                        .and_then(|idx| match idx {
+                            0 => None,
+                            idx if idx > 0 => Some(idx - 1),
+                            idx => Some(seen + idx),
+                        })
+                        .and_then(|idx| u32::try_from(idx).ok())
+                };
+                face_indices.clear();
+                face_uvs.clear();
+                face_normals.clear();
+                // v, v/vt, v//vn or v/vt/vn
+                for index_str in tokens {
+                    let mut parts = index_str.split(|&b| b == b'/');
+                    if let Some(position) = resolve(parts.next(), seen) {
+                        face_indices.push(position);
+                        face_uvs.push(resolve(parts.next(), seen_uvs).unwrap_or(NO_INDEX));
+                        face_normals.push(resolve(parts.next(), seen_normals).unwrap_or(NO_INDEX));
                     }
+                }
 
-                    else {
-                        for i in 1..(face_indices.len() - 1) {
-                            indices.push(face_indices[0]);
-                            indices.push(face_indices[i]);
-                            indices.push(face_indices[i + 1]);
-                        }
-                    }
+                // Faces with more than three corners become fans
+                for i in 1..face_indices.len().saturating_sub(1) {
+                    indices.extend([face_indices[0], face_indices[i], face_indices[i + 1]]);
+                    uv_indices.extend([face_uvs[0], face_uvs[i], face_uvs[i + 1]]);
+                    normal_indices.extend([face_normals[0], face_normals[i], face_normals[i + 1]]);
                 }
              }
              _ => {}
         }
     }
 
-    println!("OBJ Parser: Loaded {} vertices, {} indices ({} triangles)", 
-             vertices.len(), indices.len(), indices.len() / 3);
-
-    Ok(Mesh {
+    Ok(ObjChunk {
         vertices,
         indices,
-        normals: if normals.is_empty() { None } else { Some(normals) },
+        normals,
+        uvs,
+        uv_indices,
+        normal_indices,
+        groups,
+        materials,
+        libraries,
     })
 }
 
 // GLTF parser //
 
+// GLB header magic and chunk types, little-endian "glTF", "JSON" and "BIN\0"
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+// Primitive modes with faces
+const MODE_TRIANGLES: u32 = 4;
+const MODE_TRIANGLE_STRIP: u32 = 5;
+const MODE_TRIANGLE_FAN: u32 = 6;
+
 #[derive(Debug, Deserialize)]
 pub struct GltfFile {
     buffers: Vec<Buffer>,
@@ -109,15 +587,41 @@ pub struct GltfFile {
     buffer_views: Vec<BufferView>,
     accessors: Vec<Accessor>,
     meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    materials: Vec<GltfMaterial>,
+    #[serde(default)]
+    textures: Vec<GltfTexture>,
+    #[serde(default)]
+    images: Vec<Image>,
+    #[serde(default)]
+    samplers: Vec<GltfSampler>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    scenes: Vec<GltfScene>,
+    scene: Option<usize>,
+    #[serde(default)]
+    skins: Vec<GltfSkin>,
+    #[serde(default)]
+    animations: Vec<GltfAnimation>,
 }
 
+impl GltfFile {
+    fn accessor(&self, index: usize) -> Result<&Accessor, Error> {
+        self.accessors.get(index).ok_or_else(|| Error::GltfParse(format!("Accessor {} out of range", index)))
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct Buffer {
-    uri: String,
+    // Missing for the buffer a GLB file embeds
+    uri: Option<String>,
     #[serde(rename = "byteLength")]
     byte_length: usize,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct BufferView {
     buffer: usize,
@@ -125,12 +629,17 @@ struct BufferView {
     byte_offset: Option<usize>,
     #[serde(rename = "byteLength")]
     byte_length: usize,
+    // Bytes from one element to the next when attributes are interleaved, tightly packed when missing
+    #[serde(rename = "byteStride")]
+    byte_stride: Option<usize>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct Accessor {
+    // Missing for sparse accessors that only store their changes from all zeros
     #[serde(rename = "bufferView")]
-    buffer_view: usize, 
+    buffer_view: Option<usize>,
     #[serde(rename = "byteOffset")]
     byte_offset: Option<usize>,
     #[serde(rename = "componentType")]
@@ -138,92 +647,1392 @@ struct Accessor {
     count: usize,
     #[serde(rename = "type")]
     accessor_type: String,
+    sparse: Option<Sparse>,
+}
+
+// `count` elements of the accessor replaced: `indices` says which, `values` with what
+#[derive(Debug, Deserialize)]
+struct Sparse {
+    count: usize,
+    indices: SparseIndices,
+    values: SparseValues,
+}
+
+#[derive(Debug, Deserialize)]
+struct SparseIndices {
+    #[serde(rename = "bufferView")]
+    buffer_view: usize,
+    #[serde(rename = "byteOffset")]
+    byte_offset: Option<usize>,
+    #[serde(rename = "componentType")]
+    component_type: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SparseValues {
+    #[serde(rename = "bufferView")]
+    buffer_view: usize,
+    #[serde(rename = "byteOffset")]
+    byte_offset: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
 struct GltfMesh {
+    name: Option<String>,
     primitives: Vec<Primitive>,
 }
 
+// Either `matrix` or translation, rotation (quaternion, xyzw) and scale
+#[derive(Debug, Deserialize)]
+struct Node {
+    name: Option<String>,
+    mesh: Option<usize>,
+    skin: Option<usize>,
+    #[serde(default)]
+    children: Vec<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+impl Node {
+    fn local_transform(&self) -> Matrix4<f32> {
+        if let Some(m) = self.matrix {
+            // Column-major, like cgmath
+            return matrix_from_columns(&m);
+        }
+
+        let [tx, ty, tz] = self.translation.unwrap_or([0.0; 3]);
+        let [x, y, z, w] = self.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        let [sx, sy, sz] = self.scale.unwrap_or([1.0; 3]);
+        Matrix4::from_translation(Vector3::new(tx, ty, tz))
+            * Matrix4::from(Quaternion::new(w, x, y, z).normalize())
+            * Matrix4::from_nonuniform_scale(sx, sy, sz)
+    }
+
+    // The same transform, kept as translation, rotation and scale when it was given that
+    // way so animations can replace them one at a time
+    fn rest_transform(&self) -> LocalTransform {
+        match self.matrix {
+            Some(m) => LocalTransform::Matrix(matrix_from_columns(&m)),
+            None => {
+                let [x, y, z, w] = self.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+                LocalTransform::Trs {
+                    translation: self.translation.unwrap_or([0.0; 3]).into(),
+                    rotation: Quaternion::new(w, x, y, z).normalize(),
+                    scale: self.scale.unwrap_or([1.0; 3]).into(),
+                }
+            }
+        }
+    }
+}
+
+fn matrix_from_columns(m: &[f32; 16]) -> Matrix4<f32> {
+    Matrix4::from(std::array::from_fn::<[f32; 4], 4, _>(|column| std::array::from_fn(|row| m[column * 4 + row])))
+}
+
+// Joints are nodes; the inverse bind matrices (identity when missing) take the mesh
+// into each joint's space at rest
+#[derive(Debug, Deserialize)]
+struct GltfSkin {
+    #[serde(rename = "inverseBindMatrices")]
+    inverse_bind_matrices: Option<usize>,
+    joints: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfAnimation {
+    name: Option<String>,
+    channels: Vec<AnimationChannel>,
+    samplers: Vec<AnimationSampler>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnimationChannel {
+    sampler: usize,
+    target: ChannelTarget,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelTarget {
+    node: Option<usize>,
+    // translation, rotation, scale or weights (morph targets, not supported)
+    path: String,
+}
+
+// Keyframe times from `input`, values from `output`
+#[derive(Debug, Deserialize)]
+struct AnimationSampler {
+    input: usize,
+    output: usize,
+    interpolation: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfScene {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
 #[derive(Debug, Deserialize)]
 struct Primitive {
     attributes: std::collections::HashMap<String, usize>,
     indices: Option<usize>,
+    material: Option<usize>,
+    // Topology, triangles when missing
+    mode: Option<u32>,
+    #[serde(default)]
+    extensions: PrimitiveExtensions,
+}
+
+// Draco compressed primitives keep their data in the extension's buffer view. Exporters
+// may also write uncompressed accessors as a fallback, which are what gets read.
+#[derive(Debug, Default, Deserialize)]
+struct PrimitiveExtensions {
+    #[serde(rename = "KHR_draco_mesh_compression")]
+    draco: Option<DracoCompression>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DracoCompression {
+    #[serde(rename = "bufferView")]
+    buffer_view: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfMaterial {
+    #[serde(rename = "pbrMetallicRoughness")]
+    pbr_metallic_roughness: Option<PbrMetallicRoughness>,
+    #[serde(rename = "normalTexture")]
+    normal_texture: Option<TextureInfo>,
+    #[serde(rename = "occlusionTexture")]
+    occlusion_texture: Option<TextureInfo>,
+    #[serde(rename = "emissiveTexture")]
+    emissive_texture: Option<TextureInfo>,
+    #[serde(rename = "emissiveFactor")]
+    emissive_factor: Option<[f32; 3]>,
+    #[serde(default)]
+    extensions: MaterialExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MaterialExtensions {
+    // An empty object, its presence is what counts
+    #[serde(rename = "KHR_materials_unlit")]
+    unlit: Option<serde_json::Value>,
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    emissive_strength: Option<EmissiveStrength>,
+}
+
+// Multiplies the emissive factor, which glTF otherwise caps at 1
+#[derive(Debug, Deserialize)]
+struct EmissiveStrength {
+    #[serde(rename = "emissiveStrength")]
+    emissive_strength: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PbrMetallicRoughness {
+    #[serde(rename = "baseColorFactor")]
+    base_color_factor: Option<[f32; 4]>,
+    #[serde(rename = "baseColorTexture")]
+    base_color_texture: Option<TextureInfo>,
+    #[serde(rename = "metallicFactor")]
+    metallic_factor: Option<f32>,
+    #[serde(rename = "roughnessFactor")]
+    roughness_factor: Option<f32>,
+    #[serde(rename = "metallicRoughnessTexture")]
+    metallic_roughness_texture: Option<TextureInfo>,
+}
+
+// `scale` is only read for normal textures and `strength` for occlusion textures
+#[derive(Debug, Deserialize)]
+struct TextureInfo {
+    index: usize,
+    scale: Option<f32>,
+    strength: Option<f32>,
+    #[serde(default)]
+    extensions: TextureInfoExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TextureInfoExtensions {
+    #[serde(rename = "KHR_texture_transform")]
+    transform: Option<TextureTransform>,
+}
+
+// Moves the UVs a texture is sampled at: scaled, then rotated counter-clockwise, then offset
+#[derive(Debug, Deserialize)]
+struct TextureTransform {
+    #[serde(default)]
+    offset: [f32; 2],
+    #[serde(default)]
+    rotation: f32,
+    scale: Option<[f32; 2]>,
+}
+
+impl TextureTransform {
+    fn apply(&self, [u, v]: [f32; 2]) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let [scale_u, scale_v] = self.scale.unwrap_or([1.0, 1.0]);
+        let [u, v] = [u * scale_u, v * scale_v];
+        [cos * u + sin * v + self.offset[0], cos * v - sin * u + self.offset[1]]
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfTexture {
+    source: Option<usize>,
+    sampler: Option<usize>,
+    #[serde(default)]
+    extensions: TextureExtensions,
+}
+
+// Compressed images are referenced through extensions, with `source` as the fallback
+#[derive(Debug, Default, Deserialize)]
+struct TextureExtensions {
+    #[serde(rename = "KHR_texture_basisu")]
+    ktx2: Option<ImageSource>,
+    #[serde(rename = "MSFT_texture_dds")]
+    dds: Option<ImageSource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageSource {
+    source: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Image {
+    uri: Option<String>,
+    #[serde(rename = "bufferView")]
+    buffer_view: Option<usize>,
+    #[serde(rename = "mimeType")]
+    mime_type: Option<String>,
+}
+
+// Filters and wrap modes are OpenGL enum values
+#[derive(Debug, Deserialize)]
+struct GltfSampler {
+    #[serde(rename = "magFilter")]
+    mag_filter: Option<u32>,
+    #[serde(rename = "minFilter")]
+    min_filter: Option<u32>,
+    #[serde(rename = "wrapS")]
+    wrap_s: Option<u32>,
+    #[serde(rename = "wrapT")]
+    wrap_t: Option<u32>,
+}
+
+impl GltfSampler {
+    fn sampling(&self) -> Sampling {
+        use wgpu::{AddressMode, FilterMode};
+
+        let address_mode = |wrap| match wrap {
+            Some(33071) => AddressMode::ClampToEdge,
+            Some(33648) => AddressMode::MirrorRepeat,
+            _ => AddressMode::Repeat,
+        };
+        let filter = |nearest| if nearest { FilterMode::Nearest } else { FilterMode::Linear };
+        let defaults = Sampling::default();
+        Sampling {
+            address_mode_u: address_mode(self.wrap_s),
+            address_mode_v: address_mode(self.wrap_t),
+            mag_filter: self.mag_filter.map_or(defaults.mag_filter, |f| filter(f == 9728)),
+            // NEAREST, LINEAR, then {NEAREST, LINEAR}_MIPMAP_{NEAREST, LINEAR}
+            min_filter: self.min_filter.map_or(defaults.min_filter, |f| filter(matches!(f, 9728 | 9984 | 9986))),
+            mipmap_filter: self.min_filter.map_or(defaults.mipmap_filter, |f| filter(matches!(f, 9984 | 9985))),
+            mipmaps: !matches!(self.min_filter, Some(9728 | 9729)),
+        }
+    }
 }
 
-pub fn parse_gltf(file_path: &str) -> Result<Mesh, String> {
+// Parts of a glTF file that nothing else in it points to, by index
+pub struct Unreferenced {
+    pub accessors: Vec<usize>,
+    pub buffer_views: Vec<usize>,
+    pub buffers: Vec<usize>,
+}
+
+pub fn gltf_unreferenced(file_path: &str) -> Result<Unreferenced, Error> {
+    let (gltf, _) = read_gltf(file_path)?;
+
+    let mut accessors = vec![false; gltf.accessors.len()];
+    let primitive_accessors = gltf.meshes.iter()
+        .flat_map(|mesh| &mesh.primitives)
+        .flat_map(|prim| prim.attributes.values().chain(&prim.indices).copied());
+    let skin_accessors = gltf.skins.iter().filter_map(|skin| skin.inverse_bind_matrices);
+    let animation_accessors = gltf.animations.iter()
+        .flat_map(|animation| &animation.samplers)
+        .flat_map(|sampler| [sampler.input, sampler.output]);
+    for accessor in primitive_accessors.chain(skin_accessors).chain(animation_accessors) {
+        if let Some(used) = accessors.get_mut(accessor) {
+            *used = true;
+        }
+    }
+
+    let mut buffer_views = vec![false; gltf.buffer_views.len()];
+    let sparse_views = gltf.accessors.iter()
+        .filter_map(|accessor| accessor.sparse.as_ref())
+        .flat_map(|sparse| [sparse.indices.buffer_view, sparse.values.buffer_view]);
+    let draco_views = gltf.meshes.iter()
+        .flat_map(|mesh| &mesh.primitives)
+        .filter_map(|prim| prim.extensions.draco.as_ref())
+        .map(|draco| draco.buffer_view);
+    let views = gltf.accessors.iter().filter_map(|accessor| accessor.buffer_view)
+        .chain(sparse_views)
+        .chain(draco_views)
+        .chain(gltf.images.iter().filter_map(|image| image.buffer_view));
+    for view in views {
+        if let Some(used) = buffer_views.get_mut(view) {
+            *used = true;
+        }
+    }
+
+    let mut buffers = vec![false; gltf.buffers.len()];
+    for view in &gltf.buffer_views {
+        if let Some(used) = buffers.get_mut(view.buffer) {
+            *used = true;
+        }
+    }
+
+    let unused = |used: Vec<bool>| used.iter().enumerate().filter(|(_, used)| !**used).map(|(i, _)| i).collect();
+    Ok(Unreferenced {
+        accessors: unused(accessors),
+        buffer_views: unused(buffer_views),
+        buffers: unused(buffers),
+    })
+}
+
+// The JSON part of a .gltf or .glb file, plus the BIN chunk a .glb carries
+fn read_gltf(file_path: &str) -> Result<(GltfFile, Option<Vec<u8>>), Error> {
+    if !file_path.to_lowercase().ends_with(".glb") {
+        let json_text = fs::read_to_string(file_path)
+            .map_err(Error::io("Failed to read gLTF file"))?;
+        let gltf = serde_json::from_str(&json_text)
+            .map_err(|e| Error::GltfParse(format!("Failed to parse JSON: {}", e)))?;
+        return Ok((gltf, None));
+    }
+
+    let mmap = map_file(file_path)?;
+    let (json, bin) = split_glb(mmap.as_deref().unwrap_or(&[])).map_err(Error::GltfParse)?;
+    let gltf = serde_json::from_slice(json)
+        .map_err(|e| Error::GltfParse(format!("Failed to parse JSON: {}", e)))?;
+    Ok((gltf, bin.map(<[u8]>::to_vec)))
+}
+
+// A 12-byte header (magic, version, total length), then chunks of length, type and
+// data; the JSON chunk comes first, an optional BIN chunk second
+fn split_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let word = |at: usize| data.get(at..at + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+
+    if word(0) != Some(GLB_MAGIC) {
+        return Err("Not a GLB file".into());
+    }
+    if word(4) != Some(2) {
+        return Err(format!("Unsupported GLB version {}", word(4).unwrap_or(0)));
+    }
+    let length = (word(8).ok_or("Truncated GLB header")? as usize).min(data.len());
+
+    let mut json = None;
+    let mut bin = None;
+    let mut at = 12;
+    while at + 8 <= length {
+        let chunk_length = word(at).unwrap() as usize;
+        let chunk_type = word(at + 4).unwrap();
+        let chunk = data.get(at + 8..at + 8 + chunk_length).ok_or("Truncated GLB chunk")?;
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            // Unknown chunks are allowed and skipped
+            _ => {}
+        }
+        at += 8 + chunk_length;
+    }
+
+    Ok((json.ok_or("GLB file has no JSON chunk")?, bin))
+}
+
+pub fn parse_gltf(file_path: &str) -> Result<Mesh, Error> {
     let path = Path::new(file_path);
 
-    let json_text = fs::read_to_string(file_path)
-                                .map_err(|e| format!("Failed to read gLTF file: {}", e))?;
-    let gltf: GltfFile = serde_json::from_str(&json_text)
-                            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    let (gltf, embedded) = read_gltf(file_path)?;
 
     let base_dir = path.parent()
-                            .ok_or("Failed to get base directory")?;
+                            .ok_or_else(|| Error::GltfParse("Failed to get base directory".into()))?;
 
-    let buffer_uri = &gltf.buffers[0].uri;
-    let buffer_path = base_dir.join(buffer_uri);
-    let buffer_data = fs::read(&buffer_path)
-        .map_err(|e| format!("Failed to read buffer: {}", e))?;
+    let buffer_data = match gltf.buffers.first().and_then(|buffer| buffer.uri.as_ref()) {
+        Some(buffer_uri) => match data_uri(buffer_uri) {
+            Some(decoded) => decoded.map(|(_, data)| data).map_err(Error::GltfParse)?,
+            None => fs::read(base_dir.join(buffer_uri)).map_err(Error::io("Failed to read buffer"))?,
+        },
+        None => embedded.ok_or_else(|| Error::GltfParse("Buffer has no uri and there is no GLB BIN chunk".into()))?,
+    };
+    let sources = gltf.buffers.iter().filter_map(|buffer| buffer.uri.as_ref())
+        .chain(gltf.images.iter().filter_map(|image| image.uri.as_ref()))
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| base_dir.join(uri))
+        .collect();
 
     let mut vertices = Vec::new();
     let mut indices = Vec::new();
+    let mut uvs = Vec::new();
+    let mut has_uvs = false;
+    let mut normals = Vec::new();
+    let mut has_normals = false;
+    let mut submeshes = Vec::new();
+    let mut textures = Vec::new();
+    // glTF texture index -> slot in `textures`, None when the image couldn't be loaded
+    let mut texture_slots: std::collections::HashMap<usize, Option<usize>> = std::collections::HashMap::new();
 
-    if let Some(mesh) = gltf.meshes.first() {
-        if let Some(prim) = mesh.primitives.first() {
-            if let Some(&pos_index) = prim.attributes.get("POSITION") {
-                let pos_accessor = &gltf.accessors[pos_index];
-                let view = &gltf.buffer_views[pos_accessor.buffer_view];
-                let offset = view.byte_offset.unwrap_or(0) + pos_accessor.byte_offset.unwrap_or(0);
-
-                for i in 0..pos_accessor.count {
-                    let start = offset + i * 12;
-                    let x = f32::from_le_bytes(buffer_data[start..start + 4]
-                        .try_into()
-                        .unwrap());
-                    let y = f32::from_le_bytes(buffer_data[start + 4..start + 8]
-                        .try_into()
-                        .unwrap());
-                    let z = f32::from_le_bytes(buffer_data[start + 8..start + 12]
-                        .try_into()
-                        .unwrap());
-                    vertices.push([x, y, z]);
-                }
-            }
-
-            if let Some(idx_index) = prim.indices {
-                let idx_accessor = &gltf.accessors[idx_index];
-                let view = &gltf.buffer_views[idx_accessor.buffer_view];
-                let offset = view.byte_offset.unwrap_or(0) + idx_accessor.byte_offset.unwrap_or(0);
-
-                let component_size = match idx_accessor.component_type {
-                    5121 => 1, // UNSIGNED_BYTE
-                    5123 => 2, // UNSIGNED_SHORT
-                    5125 => 4, // UNSIGNED_INT
-                    _ => return Err("Unsupported index component type".into()),
-                };
+    // Every skin's joints share one palette, after an identity entry for the vertices
+    // nothing moves. Meshes under animated nodes get a joint of their own too, so node
+    // animations pose them the same way skins do.
+    let mut joints = Vec::new();
+    let mut weights = Vec::new();
+    let mut has_joints = false;
+    let mut palette = vec![Joint { node: None, inverse_bind: Matrix4::one() }];
+    let mut skin_bases = Vec::with_capacity(gltf.skins.len());
+    for skin in &gltf.skins {
+        skin_bases.push(palette.len());
+        let inverse_binds = match skin.inverse_bind_matrices {
+            Some(index) => read_floats::<16>(&gltf, gltf.accessor(index)?, &buffer_data)?,
+            None => Vec::new(),
+        };
+        palette.extend(skin.joints.iter().enumerate().map(|(i, &node)| Joint {
+            node: Some(node),
+            inverse_bind: inverse_binds.get(i).map_or(Matrix4::one(), matrix_from_columns),
+        }));
+    }
+
+    let clips = gltf.animations.iter().enumerate().map(|(index, animation)| read_clip(&gltf, index, animation, &buffer_data)).collect::<Result<Vec<_>, _>>()?;
+    let mut animated = vec![false; gltf.nodes.len()];
+    for channel in clips.iter().flat_map(|clip| &clip.channels) {
+        if let Some(animated) = animated.get_mut(channel.node) {
+            *animated = true;
+        }
+    }
+    // Palette entry of each animated mesh node, added the first time one of its primitives is
+    let mut node_joints: std::collections::HashMap<usize, u16> = std::collections::HashMap::new();
+
+    // All primitives go into the same buffers, each one keeping its own index range; a
+    // mesh used by several nodes is added once per node
+    let instances = mesh_instances(&gltf, &animated);
+    let primitives = instances.iter().flat_map(|instance| {
+        gltf.meshes[instance.mesh].primitives.iter().map(move |prim| (instance, prim))
+    });
+    for (instance, prim) in primitives {
+        let Some(&pos_index) = prim.attributes.get("POSITION") else {
+            continue;
+        };
+        let mode = prim.mode.unwrap_or(MODE_TRIANGLES);
+        if !matches!(mode, MODE_TRIANGLES | MODE_TRIANGLE_STRIP | MODE_TRIANGLE_FAN) {
+            eprintln!("GLTF Parser: Skipping a point or line primitive (mode {})", mode);
+            continue;
+        }
+        // Without a fallback the accessors only describe the decoded data and would read as zeros
+        if prim.extensions.draco.is_some() && gltf.accessor(pos_index)?.buffer_view.is_none() {
+            return Err(Error::GltfParse(
+                "Draco compressed meshes (KHR_draco_mesh_compression) aren't supported; re-export without Draco compression".into(),
+            ));
+        }
 
-                for i in 0..idx_accessor.count {
-                    let start = offset + i * component_size;
-                    let index = match component_size {
-                        1 => buffer_data[start] as u32,
-                        2 => u16::from_le_bytes(buffer_data[start..start + 2].try_into().unwrap()) as u32,
-                        4 => u32::from_le_bytes(buffer_data[start..start + 4].try_into().unwrap()),
-                        _ => return Err("Unexpected index size".into()),
-                    };
-                    indices.push(index);
+        let vertex_base = vertices.len() as u32;
+        let first_index = indices.len() as u32;
+
+        let accessor = |index: usize| gltf.accessors.get(index).ok_or_else(|| Error::GltfParse(format!("Accessor {} out of range", index)));
+
+        vertices.extend(read_floats::<3>(&gltf, accessor(pos_index)?, &buffer_data)?);
+
+        match prim.attributes.get("TEXCOORD_0").map(|&i| accessor(i)).transpose()? {
+            // Only float UVs are read; normalized integer UVs are treated as missing
+            Some(uv_accessor) if uv_accessor.component_type == 5126 => {
+                uvs.extend(read_floats::<2>(&gltf, uv_accessor, &buffer_data)?);
+                has_uvs = true;
+            }
+            _ => {}
+        }
+        // Keep UVs lined up with positions even for primitives that have none
+        uvs.resize(vertices.len(), [0.0, 0.0]);
+
+        if let Some(normal_accessor) = prim.attributes.get("NORMAL").map(|&i| accessor(i)).transpose()?
+            && normal_accessor.component_type == 5126 {
+            normals.extend(read_floats::<3>(&gltf, normal_accessor, &buffer_data)?);
+            has_normals = true;
+        }
+        // Zero normals fall back to the face normal when shading
+        normals.resize(vertices.len(), [0.0; 3]);
+
+        // Skinned primitives stay in bind pose, their joints place them in the scene
+        let skin = instance.skin.zip(prim.attributes.get("JOINTS_0")).zip(prim.attributes.get("WEIGHTS_0"));
+        if let Some(((skin, &joint_index), &weight_index)) = skin {
+            let base = skin_bases[skin] as u16;
+            let count = gltf.skins[skin].joints.len() as u32;
+            let joint_accessor = gltf.accessor(joint_index)?;
+            let size = index_size(joint_accessor.component_type).ok().filter(|&size| size < 4)
+                .ok_or_else(|| Error::GltfParse("Unsupported joint component type".into()))?;
+            // Joints past the end of the skin are left unmoved
+            joints.extend(read_accessor(&gltf, joint_accessor, &buffer_data, size * 4, |bytes| {
+                std::array::from_fn(|i| match read_index(&bytes[i * size..(i + 1) * size]) {
+                    joint if joint < count => base + joint as u16,
+                    _ => 0,
+                })
+            })?);
+            weights.extend(read_weights(&gltf, gltf.accessor(weight_index)?, &buffer_data)?);
+            has_joints = true;
+        } else if let Some(node) = instance.node.filter(|_| instance.animated)
+            && let Some(inverse_bind) = instance.transform.invert() {
+            // Placed in the scene below like any other mesh, then moved rigidly from there
+            let joint = *node_joints.entry(node).or_insert_with(|| {
+                palette.push(Joint { node: Some(node), inverse_bind });
+                (palette.len() - 1) as u16
+            });
+            joints.resize(vertices.len(), [joint, 0, 0, 0]);
+            has_joints = true;
+        }
+        joints.resize(vertices.len(), [0; 4]);
+        weights.resize(vertices.len(), [1.0, 0.0, 0.0, 0.0]);
+
+        let mut primitive_indices = Vec::new();
+        if let Some(idx_index) = prim.indices {
+            let idx_accessor = accessor(idx_index)?;
+            let size = index_size(idx_accessor.component_type)?;
+            let read_indices = read_accessor(&gltf, idx_accessor, &buffer_data, size, read_index)?;
+            // Indices come straight from the file, so the offset can overflow before validation sees them
+            for index in read_indices {
+                let index = vertex_base
+                    .checked_add(index)
+                    .ok_or_else(|| Error::GltfParse(format!("Vertex index {} out of range", index)))?;
+                primitive_indices.push(index);
+            }
+        } else {
+            // Non-indexed primitives list their vertices in order
+            primitive_indices.extend(vertex_base..vertices.len() as u32);
+        }
+        match mode {
+            MODE_TRIANGLE_STRIP => indices.extend(strip_triangles(&primitive_indices)),
+            MODE_TRIANGLE_FAN => indices.extend(fan_triangles(&primitive_indices)),
+            _ => indices.extend(primitive_indices),
+        }
+
+        if skin.is_none() && instance.transform != Matrix4::one() {
+            let base = vertex_base as usize;
+            transform_vertices(&instance.transform, &mut vertices[base..], &mut normals[base..]);
+            // Mirroring transforms turn the faces inside out
+            if instance.transform.determinant() < 0.0 {
+                for triangle in indices[first_index as usize..].chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
                 }
             }
         }
+
+        let gltf_material = prim.material.and_then(|material| gltf.materials.get(material));
+        let pbr = gltf_material.and_then(|material| material.pbr_metallic_roughness.as_ref());
+        let color = pbr.and_then(|pbr| pbr.base_color_factor).unwrap_or(DEFAULT_COLOR);
+
+        let mut texture_slot = |info: Option<&TextureInfo>| {
+            let info = info?;
+            *texture_slots.entry(info.index).or_insert_with(|| {
+                let image = load_gltf_texture(&gltf, info.index, base_dir, &buffer_data)
+                    .map_err(|e| eprintln!("GLTF Parser: Skipping texture {}: {}", info.index, e))
+                    .ok()?;
+                textures.push(image);
+                Some(textures.len() - 1)
+            })
+        };
+        let texture = texture_slot(pbr.and_then(|pbr| pbr.base_color_texture.as_ref()));
+        // A material's missing factors are glTF's defaults, a missing material is ours
+        let material = match gltf_material {
+            Some(gltf_material) => Material {
+                metallic: pbr.and_then(|pbr| pbr.metallic_factor).unwrap_or(1.0),
+                roughness: pbr.and_then(|pbr| pbr.roughness_factor).unwrap_or(1.0),
+                emissive: {
+                    let strength = gltf_material.extensions.emissive_strength.as_ref().and_then(|strength| strength.emissive_strength).unwrap_or(1.0);
+                    gltf_material.emissive_factor.unwrap_or([0.0; 3]).map(|channel| channel * strength)
+                },
+                normal_scale: gltf_material.normal_texture.as_ref().and_then(|info| info.scale).unwrap_or(1.0),
+                occlusion_strength: gltf_material.occlusion_texture.as_ref().and_then(|info| info.strength).unwrap_or(1.0),
+                metallic_roughness_texture: texture_slot(pbr.and_then(|pbr| pbr.metallic_roughness_texture.as_ref())),
+                normal_texture: texture_slot(gltf_material.normal_texture.as_ref()),
+                occlusion_texture: texture_slot(gltf_material.occlusion_texture.as_ref()),
+                emissive_texture: texture_slot(gltf_material.emissive_texture.as_ref()),
+                unlit: gltf_material.extensions.unlit.is_some(),
+            },
+            None => Material::default(),
+        };
+
+        // Every texture of a draw is sampled at the same UVs, so one transform is baked into
+        // the primitive's own: the base color texture's, or the first other one that has one
+        if let Some(gltf_material) = gltf_material {
+            let texture_infos = [
+                pbr.and_then(|pbr| pbr.base_color_texture.as_ref()),
+                pbr.and_then(|pbr| pbr.metallic_roughness_texture.as_ref()),
+                gltf_material.normal_texture.as_ref(),
+                gltf_material.occlusion_texture.as_ref(),
+                gltf_material.emissive_texture.as_ref(),
+            ];
+            let transform = texture_infos.into_iter().flatten().find_map(|info| info.extensions.transform.as_ref());
+            if let Some(transform) = transform {
+                for uv in &mut uvs[vertex_base as usize..] {
+                    *uv = transform.apply(*uv);
+                }
+            }
+        }
+
+        submeshes.push(SubMesh {
+            first_index,
+            index_count: indices.len() as u32 - first_index,
+            color,
+            texture,
+            material,
+            name: instance.name.clone(),
+        });
     }
 
-    println!("GLTF Parser: Loaded {} vertices, {} indices ({} triangles)", 
+    eprintln!("GLTF Parser: Loaded {} vertices, {} indices ({} triangles)", 
              vertices.len(), indices.len(), indices.len() / 3);
 
+    if !textures.is_empty() {
+        eprintln!("GLTF Parser: Loaded {} textures", textures.len());
+    }
+
+    if palette.len() > u16::MAX as usize {
+        return Err(Error::GltfParse(format!("{} joints, at most {} are supported", palette.len(), u16::MAX)));
+    }
+
+    let rig = if has_joints {
+        let nodes = gltf.nodes.iter().map(|node| RigNode { children: node.children.clone(), rest: node.rest_transform() }).collect();
+        eprintln!("GLTF Parser: Loaded {} joints and {} animations", palette.len() - 1, clips.len());
+        Some(Rig::new(nodes, &scene_roots(&gltf), palette, clips))
+    } else {
+        None
+    };
+
+    Ok(Mesh {
+        vertices,
+        indices,
+        normals: if has_normals { Some(normals) } else { None },
+        uvs: if has_uvs { Some(uvs) } else { None },
+        colors: None,
+        joints: has_joints.then_some(joints),
+        weights: has_joints.then_some(weights),
+        rig,
+        submeshes,
+        textures,
+        sources,
+    })
+}
+
+// Bytes of a buffer view from `offset` on. Only the file's first buffer is loaded.
+fn view_data<'a>(gltf: &GltfFile, buffer_data: &'a [u8], view: usize, offset: usize) -> Result<&'a [u8], Error> {
+    let view = gltf.buffer_views.get(view).ok_or_else(|| Error::GltfParse(format!("Buffer view {} out of range", view)))?;
+    let start = view.byte_offset.unwrap_or(0);
+    buffer_data.get(start..start + view.byte_length)
+        .and_then(|data| data.get(offset..))
+        .ok_or_else(|| Error::GltfParse("Buffer view is past the end of the buffer".into()))
+}
+
+// `count` elements of `size` bytes each, starting `stride` bytes apart
+fn read_elements<T>(data: &[u8], count: usize, size: usize, stride: usize, read: impl Fn(&[u8]) -> T) -> Result<Vec<T>, Error> {
+    if count > 0 && (count - 1).saturating_mul(stride).saturating_add(size) > data.len() {
+        return Err(Error::GltfParse("Accessor is past the end of its buffer view".into()));
+    }
+    Ok((0..count).map(|i| read(&data[i * stride..i * stride + size])).collect())
+}
+
+// Every element of an accessor with its sparse substitutions applied. Accessors without
+// a buffer view start out as all zeros.
+fn read_accessor<T: Clone>(gltf: &GltfFile, accessor: &Accessor, buffer_data: &[u8], size: usize, read: impl Fn(&[u8]) -> T) -> Result<Vec<T>, Error> {
+    let mut elements = match accessor.buffer_view {
+        Some(view) => {
+            let data = view_data(gltf, buffer_data, view, accessor.byte_offset.unwrap_or(0))?;
+            let stride = gltf.buffer_views[view].byte_stride.unwrap_or(size);
+            read_elements(data, accessor.count, size, stride, &read)?
+        }
+        None => vec![read(&vec![0; size]); accessor.count],
+    };
+
+    if let Some(sparse) = &accessor.sparse {
+        // Sparse indices and values are always tightly packed
+        let indices = &sparse.indices;
+        let index_size = index_size(indices.component_type)?;
+        let targets = read_elements(
+            view_data(gltf, buffer_data, indices.buffer_view, indices.byte_offset.unwrap_or(0))?,
+            sparse.count,
+            index_size,
+            index_size,
+            read_index,
+        )?;
+        let values = &sparse.values;
+        let values = read_elements(view_data(gltf, buffer_data, values.buffer_view, values.byte_offset.unwrap_or(0))?, sparse.count, size, size, &read)?;
+        for (target, value) in targets.into_iter().zip(values) {
+            *elements.get_mut(target as usize).ok_or_else(|| Error::GltfParse("Sparse index past the end of the accessor".into()))? = value;
+        }
+    }
+
+    Ok(elements)
+}
+
+fn read_floats<const N: usize>(gltf: &GltfFile, accessor: &Accessor, buffer_data: &[u8]) -> Result<Vec<[f32; N]>, Error> {
+    read_accessor(gltf, accessor, buffer_data, N * 4, |bytes| {
+        std::array::from_fn(|i| f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()))
+    })
+}
+
+// Float or normalized unsigned byte/short weights, scaled to add up to one
+fn read_weights(gltf: &GltfFile, accessor: &Accessor, buffer_data: &[u8]) -> Result<Vec<[f32; 4]>, Error> {
+    let weights = match accessor.component_type {
+        5126 => read_floats::<4>(gltf, accessor, buffer_data)?,
+        5121 => read_accessor(gltf, accessor, buffer_data, 4, |bytes| std::array::from_fn(|i| bytes[i] as f32 / 255.0))?,
+        5123 => read_accessor(gltf, accessor, buffer_data, 8, |bytes| {
+            std::array::from_fn(|i| u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]) as f32 / 65535.0)
+        })?,
+        _ => return Err(Error::GltfParse("Unsupported weight component type".into())),
+    };
+    Ok(weights
+        .into_iter()
+        .map(|w| {
+            let sum: f32 = w.iter().sum();
+            if sum > 0.0 { w.map(|w| w / sum) } else { [1.0, 0.0, 0.0, 0.0] }
+        })
+        .collect())
+}
+
+// Translation, rotation and scale channels; morph target weights are skipped
+fn read_clip(gltf: &GltfFile, index: usize, animation: &GltfAnimation, buffer_data: &[u8]) -> Result<Clip, Error> {
+    let mut channels = Vec::new();
+    for channel in &animation.channels {
+        let Some(node) = channel.target.node else {
+            continue;
+        };
+        let property = match channel.target.path.as_str() {
+            "translation" => Property::Translation,
+            "rotation" => Property::Rotation,
+            "scale" => Property::Scale,
+            _ => continue,
+        };
+        let sampler = animation.samplers.get(channel.sampler)
+            .ok_or_else(|| Error::GltfParse(format!("Animation sampler {} out of range", channel.sampler)))?;
+        let interpolation = match sampler.interpolation.as_deref() {
+            None | Some("LINEAR") => Interpolation::Linear,
+            Some("STEP") => Interpolation::Step,
+            Some("CUBICSPLINE") => Interpolation::CubicSpline,
+            Some(other) => {
+                eprintln!("GLTF Parser: Skipping an animation channel with {} interpolation", other);
+                continue;
+            }
+        };
+
+        let output = gltf.accessor(sampler.output)?;
+        if output.component_type != 5126 {
+            eprintln!("GLTF Parser: Skipping an animation channel with integer values");
+            continue;
+        }
+        let times = read_floats::<1>(gltf, gltf.accessor(sampler.input)?, buffer_data)?.into_iter().map(|[t]| t).collect();
+        let values = match property {
+            Property::Rotation => read_floats::<4>(gltf, output, buffer_data)?,
+            _ => read_floats::<3>(gltf, output, buffer_data)?.into_iter().map(|[x, y, z]| [x, y, z, 0.0]).collect(),
+        };
+        channels.push(Channel { node, property, interpolation, times, values });
+    }
+
+    Ok(Clip {
+        name: animation.name.clone().unwrap_or_else(|| format!("Animation {}", index + 1)),
+        channels,
+    })
+}
+
+fn index_size(component_type: u32) -> Result<usize, Error> {
+    match component_type {
+        5121 => Ok(1), // UNSIGNED_BYTE
+        5123 => Ok(2), // UNSIGNED_SHORT
+        5125 => Ok(4), // UNSIGNED_INT
+        _ => Err(Error::GltfParse("Unsupported index component type".into())),
+    }
+}
+
+// An index of the size index_size gave
+fn read_index(bytes: &[u8]) -> u32 {
+    match *bytes {
+        [byte] => byte as u32,
+        [a, b] => u16::from_le_bytes([a, b]) as u32,
+        _ => u32::from_le_bytes(bytes.try_into().unwrap()),
+    }
+}
+
+struct MeshInstance {
+    mesh: usize,
+    // None for files without nodes
+    node: Option<usize>,
+    // The node or one of its ancestors is animated
+    animated: bool,
+    // Skinned meshes ignore `transform`, their joints place them
+    skin: Option<usize>,
+    // Node to world
+    transform: Matrix4<f32>,
+    // The node's name, or the mesh's when the node has none
+    name: String,
+}
+
+// Walks the default scene's node tree (or every root node when there are no scenes)
+// and returns each mesh with its world transform. Files without nodes get every mesh
+// once, untransformed.
+fn mesh_instances(gltf: &GltfFile, animated: &[bool]) -> Vec<MeshInstance> {
+    let mesh_name = |mesh: usize| gltf.meshes[mesh].name.clone().unwrap_or_default();
+    if gltf.nodes.is_empty() {
+        return (0..gltf.meshes.len())
+            .map(|mesh| MeshInstance { mesh, node: None, animated: false, skin: None, transform: Matrix4::one(), name: mesh_name(mesh) })
+            .collect();
+    }
+
+    let roots = scene_roots(gltf);
+    let mut instances = Vec::new();
+    // Each node is visited once, which also stops malformed files with cycles
+    let mut visited = vec![false; gltf.nodes.len()];
+    let mut stack: Vec<(usize, Matrix4<f32>, bool)> = roots.into_iter().rev().map(|root| (root, Matrix4::one(), false)).collect();
+    while let Some((index, parent, parent_animated)) = stack.pop() {
+        let Some(node) = gltf.nodes.get(index) else {
+            continue;
+        };
+        if std::mem::replace(&mut visited[index], true) {
+            continue;
+        }
+
+        let transform = parent * node.local_transform();
+        let node_animated = parent_animated || animated.get(index).copied().unwrap_or(false);
+        if let Some(mesh) = node.mesh.filter(|&mesh| mesh < gltf.meshes.len()) {
+            let name = node.name.clone().unwrap_or_else(|| mesh_name(mesh));
+            let skin = node.skin.filter(|&skin| skin < gltf.skins.len());
+            instances.push(MeshInstance { mesh, node: Some(index), animated: node_animated, skin, transform, name });
+        }
+        stack.extend(node.children.iter().rev().map(|&child| (child, transform, node_animated)));
+    }
+    instances
+}
+
+// The default scene's root nodes, or every node that isn't a child when there are no scenes
+fn scene_roots(gltf: &GltfFile) -> Vec<usize> {
+    match gltf.scene.or(if gltf.scenes.is_empty() { None } else { Some(0) }) {
+        Some(scene) => gltf.scenes.get(scene).map_or_else(Vec::new, |scene| scene.nodes.clone()),
+        None => {
+            let mut is_child = vec![false; gltf.nodes.len()];
+            for &child in gltf.nodes.iter().flat_map(|node| &node.children) {
+                if let Some(is_child) = is_child.get_mut(child) {
+                    *is_child = true;
+                }
+            }
+            (0..gltf.nodes.len()).filter(|&node| !is_child[node]).collect()
+        }
+    }
+}
+
+// Normals go through the inverse transpose so non-uniform scales keep them perpendicular
+pub(crate) fn transform_vertices(transform: &Matrix4<f32>, vertices: &mut [[f32; 3]], normals: &mut [[f32; 3]]) {
+    for v in vertices {
+        *v = (transform * Vector4::new(v[0], v[1], v[2], 1.0)).truncate().into();
+    }
+
+    let linear = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate());
+    let Some(normal_matrix) = linear.invert().map(|inverse| inverse.transpose()) else {
+        return;
+    };
+    for n in normals {
+        let normal = normal_matrix * Vector3::from(*n);
+        // Zero normals stay zero, they mean "not provided"
+        if normal.magnitude2() > 0.0 {
+            *n = normal.normalize().into();
+        }
+    }
+}
+
+// Every other strip triangle has its first two corners swapped to keep the winding
+fn strip_triangles(strip: &[u32]) -> impl Iterator<Item = u32> + '_ {
+    strip.windows(3).enumerate().flat_map(|(i, w)| if i % 2 == 0 { [w[0], w[1], w[2]] } else { [w[1], w[0], w[2]] })
+}
+
+fn fan_triangles(fan: &[u32]) -> impl Iterator<Item = u32> + '_ {
+    fan.windows(2).skip(1).flat_map(|w| [fan[0], w[0], w[1]])
+}
+
+fn load_gltf_texture(gltf: &GltfFile, texture: usize, base_dir: &Path, buffer_data: &[u8]) -> Result<TextureImage, String> {
+    let texture = gltf.textures.get(texture).ok_or("Texture index out of range")?;
+    // Compressed images first; one that can't be decoded, like a Basis Universal KTX2,
+    // falls back to the next, so files that also carry a PNG or JPEG still get textured
+    let mut sources = [texture.extensions.ktx2.as_ref(), texture.extensions.dds.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|ext| ext.source)
+        .chain(texture.source)
+        .collect::<Vec<_>>();
+    sources.dedup();
+
+    let mut error = "Texture has no image".to_string();
+    for source in sources {
+        match load_gltf_image(gltf, source, base_dir, buffer_data) {
+            Ok(mut texture_image) => {
+                if let Some(sampler) = texture.sampler.and_then(|sampler| gltf.samplers.get(sampler)) {
+                    texture_image.sampling = sampler.sampling();
+                }
+                return Ok(texture_image);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+fn load_gltf_image(gltf: &GltfFile, source: usize, base_dir: &Path, buffer_data: &[u8]) -> Result<TextureImage, String> {
+    let image = gltf.images.get(source).ok_or("Image index out of range")?;
+
+    let texture_image = match (&image.uri, image.buffer_view) {
+        (Some(uri), _) => match data_uri(uri) {
+            Some(decoded) => {
+                let (mime_type, data) = decoded?;
+                decode_texture(&data, image_kind(Some(mime_type))?)?
+            }
+            None => load_texture(&base_dir.join(uri))?,
+        },
+        // Images embedded in the buffer, as GLB exporters write them
+        (None, Some(view)) => {
+            let view = gltf.buffer_views.get(view).ok_or("Image buffer view out of range")?;
+            let start = view.byte_offset.unwrap_or(0);
+            let data = buffer_data.get(start..start + view.byte_length).ok_or("Image buffer view is past the end of the buffer")?;
+            decode_texture(data, image_kind(image.mime_type.as_deref())?)?
+        }
+        (None, None) => return Err("Image has neither a uri nor a buffer view".to_string()),
+    };
+    Ok(texture_image)
+}
+
+// File extension decode_texture expects for an image's MIME type
+fn image_kind(mime_type: Option<&str>) -> Result<&'static str, String> {
+    match mime_type {
+        Some("image/png") => Ok("png"),
+        Some("image/jpeg") => Ok("jpg"),
+        Some("image/ktx2") => Ok("ktx2"),
+        Some("image/vnd-ms.dds") => Ok("dds"),
+        other => Err(format!("Unsupported image type {:?}", other)),
+    }
+}
+
+// Buffers and images embedded as `data:<mime type>;base64,<data>`, as .gltf exporters
+// write them when asked for a single file. None for any other uri, which is a path
+// relative to the .gltf file.
+fn data_uri(uri: &str) -> Option<Result<(&str, Vec<u8>), String>> {
+    let rest = uri.strip_prefix("data:")?;
+    let Some((header, data)) = rest.split_once(',') else {
+        return Some(Err("Data URI has no data".to_string()));
+    };
+    let Some(mime_type) = header.strip_suffix(";base64") else {
+        return Some(Err("Only base64 data URIs are supported".to_string()));
+    };
+    Some(decode_base64(data).map(|data| (mime_type, data)))
+}
+
+// Standard and URL-safe alphabets, with or without padding
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    // Bits not yet written out, the newest in the lowest positions
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(format!("Invalid base64 character {:?}", c as char)),
+        };
+        bits = (bits << 6 | value as u32) & 0xFFFF;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+// PLY parser //
+
+#[derive(Clone, Copy, Debug)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(PlyType::I8),
+            "uchar" | "uint8" => Ok(PlyType::U8),
+            "short" | "int16" => Ok(PlyType::I16),
+            "ushort" | "uint16" => Ok(PlyType::U16),
+            "int" | "int32" => Ok(PlyType::I32),
+            "uint" | "uint32" => Ok(PlyType::U32),
+            "float" | "float32" => Ok(PlyType::F32),
+            "double" | "float64" => Ok(PlyType::F64),
+            _ => Err(format!("Unknown PLY property type {}", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    value_type: PlyType,
+    // Type of the leading item count for list properties
+    count_type: Option<PlyType>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+// Values come out as f64 either way; ASCII bodies are read token by token, since
+// element boundaries don't depend on line breaks
+struct PlyReader<'a> {
+    data: &'a [u8],
+    at: usize,
+    ascii: bool,
+}
+
+impl PlyReader<'_> {
+    fn read(&mut self, value_type: PlyType) -> Result<f64, String> {
+        if self.ascii {
+            let rest = &self.data[self.at..];
+            let start = rest.iter().position(|b| !b.is_ascii_whitespace()).ok_or("Unexpected end of PLY data")?;
+            let length = rest[start..].iter().position(|b| b.is_ascii_whitespace()).unwrap_or(rest.len() - start);
+            self.at += start + length;
+            return fast_float2::parse(&rest[start..start + length]).map_err(|_| "Invalid PLY value".to_string());
+        }
+
+        let bytes = self.data.get(self.at..self.at + value_type.size()).ok_or("Unexpected end of PLY data")?;
+        self.at += bytes.len();
+        Ok(match value_type {
+            PlyType::I8 => bytes[0] as i8 as f64,
+            PlyType::U8 => bytes[0] as f64,
+            PlyType::I16 => i16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            PlyType::U16 => u16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            PlyType::I32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            PlyType::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            PlyType::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            PlyType::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+
+    // Upper bound on the rows left in the data, from the smallest a row can be: an ASCII
+    // value takes at least a byte, a binary list at least its item count
+    fn rows_left(&self, properties: &[PlyProperty]) -> usize {
+        let row_size: usize = properties
+            .iter()
+            .map(|property| match (self.ascii, property.count_type) {
+                (true, _) => 1,
+                (false, Some(count_type)) => count_type.size(),
+                (false, None) => property.value_type.size(),
+            })
+            .sum();
+        (self.data.len() - self.at) / row_size.max(1)
+    }
+
+    // One row of an element: scalars as single values, lists as their items
+    fn read_row(&mut self, properties: &[PlyProperty], row: &mut Vec<Vec<f64>>) -> Result<(), String> {
+        row.resize(properties.len(), Vec::new());
+        for (property, values) in properties.iter().zip(row.iter_mut()) {
+            values.clear();
+            let count = match property.count_type {
+                Some(count_type) => self.read(count_type)? as usize,
+                None => 1,
+            };
+            for _ in 0..count {
+                values.push(self.read(property.value_type)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+// ASCII and binary little-endian PLY with positions, optional normals and optional
+// red/green/blue(/alpha) vertex colors, or scanner intensities shown as grey when there
+// are no colors; faces with more than three corners become fans
+pub fn parse_ply(file_path: &str) -> Result<Mesh, Error> {
+    let mmap = map_file(file_path)?;
+    parse_ply_data(mmap.as_deref().unwrap_or(&[])).map_err(Error::PlyParse)
+}
+
+fn parse_ply_data(data: &[u8]) -> Result<Mesh, String> {
+    let header_end = data
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or("PLY file has no end_header")?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |newline| header_end + newline + 1);
+    let header = String::from_utf8_lossy(&data[..header_end]);
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("Not a PLY file".into());
+    }
+
+    let mut ascii = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => ascii = Some(true),
+            ["format", "binary_little_endian", _] => ascii = Some(false),
+            ["format", format, _] => return Err(format!("Unsupported PLY format {}", format)),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("Invalid PLY element count {}", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, value_type, name] => {
+                let element = elements.last_mut().ok_or("PLY property before any element")?;
+                element.properties.push(PlyProperty {
+                    name: name.to_string(),
+                    value_type: PlyType::parse(value_type)?,
+                    count_type: Some(PlyType::parse(count_type)?),
+                });
+            }
+            ["property", value_type, name] => {
+                let element = elements.last_mut().ok_or("PLY property before any element")?;
+                element.properties.push(PlyProperty {
+                    name: name.to_string(),
+                    value_type: PlyType::parse(value_type)?,
+                    count_type: None,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let mut reader = PlyReader {
+        data,
+        at: body_start,
+        ascii: ascii.ok_or("PLY file has no format line")?,
+    };
+
+    let mut vertices = Vec::new();
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    let mut row = Vec::new();
+
+    for element in &elements {
+        let find = |name: &str| element.properties.iter().position(|property| property.name == name);
+
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [find("x"), find("y"), find("z")];
+                let normal = [find("nx"), find("ny"), find("nz")];
+                let color = [find("red"), find("green"), find("blue")];
+                let alpha = find("alpha");
+                let intensity = ["intensity", "scalar_intensity", "scalar_Intensity"].into_iter().find_map(find);
+                let [Some(x), Some(y), Some(z)] = position else {
+                    return Err("PLY vertices have no x, y, z".into());
+                };
+                let has_normals = normal.iter().all(Option::is_some);
+                let has_colors = color.iter().all(Option::is_some);
+                let mut intensities = Vec::new();
+
+                // The count comes from the header, so don't reserve more than the data can hold
+                vertices.reserve(element.count.min(reader.rows_left(&element.properties)));
+                for _ in 0..element.count {
+                    reader.read_row(&element.properties, &mut row)?;
+                    let value = |column: usize| row[column].first().copied().unwrap_or(0.0);
+
+                    vertices.push([x, y, z].map(|column| value(column) as f32));
+                    if has_normals {
+                        normals.push(normal.map(|column| value(column.unwrap()) as f32));
+                    }
+                    if has_colors {
+                        // Integer channels are 0-255, float ones 0-1
+                        let channel = |column: usize| match element.properties[column].value_type {
+                            PlyType::F32 | PlyType::F64 => (value(column) * 255.0).round().clamp(0.0, 255.0) as u8,
+                            _ => value(column).clamp(0.0, 255.0) as u8,
+                        };
+                        let [r, g, b] = color.map(|column| channel(column.unwrap()));
+                        colors.push([r, g, b, alpha.map_or(255, channel)]);
+                    } else if let Some(column) = intensity {
+                        intensities.push(value(column));
+                    }
+                }
+                colors.extend(intensity_colors(&intensities));
+            }
+            "face" => {
+                let Some(corners) = find("vertex_indices").or_else(|| find("vertex_index")) else {
+                    return Err("PLY faces have no vertex_indices".into());
+                };
+
+                for _ in 0..element.count {
+                    reader.read_row(&element.properties, &mut row)?;
+                    let face = &row[corners];
+                    for i in 1..face.len().saturating_sub(1) {
+                        indices.extend_from_slice(&[face[0] as u32, face[i] as u32, face[i + 1] as u32]);
+                    }
+                }
+            }
+            // Anything else (edges, materials) is read past
+            _ => {
+                for _ in 0..element.count {
+                    reader.read_row(&element.properties, &mut row)?;
+                }
+            }
+        }
+    }
+
+    eprintln!("PLY Parser: Loaded {} vertices, {} indices ({} triangles){}",
+             vertices.len(), indices.len(), indices.len() / 3,
+             if colors.is_empty() { "" } else { " with vertex colors" });
+
     Ok(Mesh {
         vertices,
         indices,
+        normals: if normals.is_empty() { None } else { Some(normals) },
+        uvs: None,
+        colors: if colors.is_empty() { None } else { Some(colors) },
+        joints: None,
+        weights: None,
+        rig: None,
+        submeshes: Vec::new(),
+        textures: Vec::new(),
+        sources: Vec::new(),
+    })
+}
+
+// Scanner intensities as grey, from black at zero to white at the brightest point,
+// since files store them as 8 bit, 16 bit or 0-1 values
+fn intensity_colors(intensities: &[f64]) -> Vec<[u8; 4]> {
+    let brightest = intensities.iter().copied().fold(0.0, f64::max);
+    intensities
+        .iter()
+        .map(|&intensity| {
+            let grey = if brightest > 0.0 { (intensity / brightest * 255.0).round() as u8 } else { 0 };
+            [grey, grey, grey, 255]
+        })
+        .collect()
+}
+
+// LAS parser //
+
+// Header sizes of LAS 1.0-1.2, and of LAS 1.4 with its 64-bit point count
+const LAS_HEADER_SIZE: usize = 227;
+const LAS_14_HEADER_SIZE: usize = 375;
+
+// LAS 1.0-1.4 point clouds, point formats 0-10, with RGB colors for the formats that have
+// them and intensities as grey otherwise. Records are decoded in parallel straight from the
+// mapped file. LAZ files are compressed with LASzip, which isn't supported.
+pub fn parse_las(file_path: &str) -> Result<Mesh, Error> {
+    if file_path.to_lowercase().ends_with(".laz") {
+        return Err(Error::LasParse("LAZ files are compressed with LASzip; decompress them to .las first, e.g. with laszip".into()));
+    }
+    let mmap = map_file(file_path)?;
+    parse_las_data(mmap.as_deref().unwrap_or(&[])).map_err(Error::LasParse)
+}
+
+fn parse_las_data(data: &[u8]) -> Result<Mesh, String> {
+    if data.len() < LAS_HEADER_SIZE || &data[..4] != b"LASF" {
+        return Err("Not a LAS file".into());
+    }
+    let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let f64_at = |at: usize| f64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+    let (major, minor) = (data[24], data[25]);
+    let point_offset = u32_at(96) as usize;
+    // The top two bits flag LASzip compression in files that kept the .las extension
+    let format = data[104] & 0x3F;
+    if data[104] & 0xC0 != 0 {
+        return Err("LAS file is LASzip compressed".into());
+    }
+    let record_length = u16_at(105) as usize;
+    let point_count = match u32_at(107) {
+        // LAS 1.4 files with more than 4 billion points, or newer formats, keep the count at 247
+        0 if (major, minor) >= (1, 4) && data.len() >= LAS_14_HEADER_SIZE => u64::from_le_bytes(data[247..255].try_into().unwrap()) as usize,
+        count => count as usize,
+    };
+    let scale = [131, 139, 147].map(f64_at);
+    let offset = [155, 163, 171].map(f64_at);
+    // Coordinates are often georeferenced, far too large for f32, so points are placed
+    // relative to the middle of the header's bounds
+    let max = [179, 195, 211].map(f64_at);
+    let min = [187, 203, 219].map(f64_at);
+    let middle = [0, 1, 2].map(|axis| (min[axis] + max[axis]) * 0.5);
+
+    // Where each format keeps its RGB, if it has one
+    let color_at = match format {
+        2 => Some(20),
+        3 | 5 => Some(28),
+        7 | 8 | 10 => Some(30),
+        0 | 1 | 4 | 6 | 9 => None,
+        _ => return Err(format!("Unsupported LAS point format {}", format)),
+    };
+    let minimum_length = color_at.map_or(14, |at| at + 6);
+    if record_length < minimum_length {
+        return Err(format!("LAS point records of {} bytes are too short for format {}", record_length, format));
+    }
+    let records = data
+        .get(point_offset..)
+        .and_then(|points| points.get(..point_count.checked_mul(record_length)?))
+        .ok_or("LAS file is shorter than its point count")?;
+
+    let (vertices, samples): (Vec<[f32; 3]>, Vec<[u16; 4]>) = records
+        .par_chunks_exact(record_length)
+        .map(|record| {
+            let coordinate = |axis: usize| i32::from_le_bytes(record[axis * 4..axis * 4 + 4].try_into().unwrap()) as f64;
+            let position = [0, 1, 2].map(|axis| (coordinate(axis) * scale[axis] + offset[axis] - middle[axis]) as f32);
+            let intensity = u16::from_le_bytes([record[12], record[13]]);
+            let color = color_at.map_or([0; 3], |at| [0, 1, 2].map(|channel| u16::from_le_bytes([record[at + channel * 2], record[at + channel * 2 + 1]])));
+            (position, [color[0], color[1], color[2], intensity])
+        })
+        .unzip();
+
+    let colors = match color_at {
+        Some(_) => {
+            // The spec asks for 16-bit channels, but plenty of writers store 8-bit values
+            let brightest = samples.par_iter().map(|sample| sample[0].max(sample[1]).max(sample[2])).max().unwrap_or(0);
+            let shift = if brightest > 255 { 8 } else { 0 };
+            samples.par_iter().map(|&[r, g, b, _]| [(r >> shift) as u8, (g >> shift) as u8, (b >> shift) as u8, 255]).collect()
+        }
+        None => intensity_colors(&samples.iter().map(|sample| sample[3] as f64).collect::<Vec<_>>()),
+    };
+
+    eprintln!("LAS Parser: Loaded {} points (LAS {}.{}, point format {}) with {}, relative to ({:.3}, {:.3}, {:.3})",
+             vertices.len(), major, minor, format, if color_at.is_some() { "colors" } else { "intensities" },
+             middle[0], middle[1], middle[2]);
+
+    Ok(Mesh {
+        vertices,
+        indices: Vec::new(),
         normals: None,
+        uvs: None,
+        colors: Some(colors),
+        joints: None,
+        weights: None,
+        rig: None,
+        submeshes: Vec::new(),
+        textures: Vec::new(),
+        sources: Vec::new(),
     })
-}
\ No newline at end of file
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::PathBuf;
+
+// Pipeline cache //
+// Compiled pipelines are kept in the config directory between runs so the driver can
+// skip shader compilation at startup. Only backends with PIPELINE_CACHE support
+// (currently Vulkan) get one; everywhere else pipelines are compiled as before.
+
+pub struct DiskPipelineCache {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+}
+
+impl DiskPipelineCache {
+    pub fn load(device: &wgpu::Device, adapter_info: &wgpu::AdapterInfo) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return None;
+        }
+
+        // The key changes with the driver, so stale caches from an old driver are never read
+        let key = wgpu::util::pipeline_cache_key(adapter_info)?;
+        let path = dirs::config_dir()?.join("rsview").join(key);
+        let data = fs::read(&path).ok();
+
+        // Safety: the data was written by get_data() for this adapter and driver, which
+        // is what the cache key encodes; fallback discards it if the driver rejects it
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline Cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+
+        Some(Self { cache, path })
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+
+    // Call after creating pipelines so their compiled form is there next launch
+    pub fn save(&self) {
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+
+        // Write then rename so a crash mid-write can't leave a truncated cache behind
+        let temp_path = self.path.with_extension("tmp");
+        let result = self.path.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temp_path, &data))
+            .and_then(|_| fs::rename(&temp_path, &self.path));
+
+        if let Err(e) = result {
+            eprintln!("Failed to save pipeline cache: {}", e);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::StagingBelt;
+
+use crate::upload::DynamicBuffer;
+
+// Point clouds //
+// Files with vertices but no faces, like scanner exports, drawn as round points that
+// shrink with distance. Each point is an instance of a four-vertex strip; the object
+// uniforms carry the point size, the viewport size and the distance the size is for in
+// their display field. Clouds of tens of millions of points are split into buffers of
+// a fixed size, uploaded one per frame so the first ones show while the rest follow.
+
+// 32 MB per buffer and per frame, well under any GPU's buffer size limit
+const POINTS_PER_CHUNK: usize = 1 << 21;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct PointVertex {
+    pub position: [f32; 3],
+    pub color: [u8; 4],
+}
+
+impl PointVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Unorm8x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PointVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+pub struct Points {
+    pipeline: wgpu::RenderPipeline,
+    // Uploaded buffers and the points in each, all full but the last
+    chunks: Vec<(DynamicBuffer, u32)>,
+    // Every point, kept until the last chunk is uploaded
+    pending: Vec<PointVertex>,
+    point_count: usize,
+}
+
+impl Points {
+    pub fn new(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../points.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Point Pipeline Layout"),
+            bind_group_layouts: &[uniform_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Point Pipeline"),
+            layout: Some(&layout),
+            cache,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[PointVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Points hide each other like the faces of a mesh would
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            chunks: Vec::new(),
+            pending: Vec::new(),
+            point_count: 0,
+        }
+    }
+
+    // Replaces the points; they're uploaded by step_upload
+    pub fn set(&mut self, points: Vec<PointVertex>) {
+        self.chunks.clear();
+        self.point_count = points.len();
+        self.pending = points;
+    }
+
+    // Uploads the next chunk of points, if any are left
+    pub fn step_upload(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt) {
+        if !self.is_uploading() {
+            return;
+        }
+
+        let start = self.chunks.len() * POINTS_PER_CHUNK;
+        let points = &self.pending[start..(start + POINTS_PER_CHUNK).min(self.pending.len())];
+        let mut buffer = DynamicBuffer::new(device, "Point Vertex Buffer", wgpu::BufferUsages::VERTEX, 0);
+        buffer.write(device, encoder, belt, 0, bytemuck::cast_slice(points));
+        self.chunks.push((buffer, points.len() as u32));
+        if !self.is_uploading() {
+            self.pending = Vec::new();
+        }
+    }
+
+    pub fn is_uploading(&self) -> bool {
+        self.chunks.len() * POINTS_PER_CHUNK < self.pending.len()
+    }
+
+    // Including the ones still to be uploaded
+    pub fn point_count(&self) -> usize {
+        self.point_count
+    }
+
+    // Expects the uniforms for the points to be bound at group 0
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.chunks.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        for (buffer, count) in &self.chunks {
+            render_pass.set_vertex_buffer(0, buffer.buffer.slice(..));
+            render_pass.draw(0..4, 0..*count);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::error::Error;
+
+// GPU timing //
+// Brackets each render pass with timestamp queries and reads the results back
+// asynchronously, so measuring never stalls the frame it measures.
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    map_state: Arc<AtomicU8>,
+    // The readback buffer is mapped (or being mapped) and can't receive a new copy
+    readback_pending: bool,
+    copy_recorded: bool,
+    pass_names: Vec<&'static str>,
+    period_ns: f32,
+    // Smoothed duration of each pass in milliseconds
+    timings: Vec<f32>,
+    // The last readback's durations, unsmoothed, for the profile log
+    latest: Vec<f32>,
+}
+
+impl GpuTimer {
+    // Returns None when the device was created without timestamp query support
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, pass_names: &[&'static str]) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let count = pass_names.len() as u32 * 2;
+        let size = count as wgpu::BufferAddress * 8;
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            map_state: Arc::new(AtomicU8::new(MAP_PENDING)),
+            readback_pending: false,
+            copy_recorded: false,
+            pass_names: pass_names.to_vec(),
+            period_ns: queue.get_timestamp_period(),
+            timings: vec![0.0; pass_names.len()],
+            latest: vec![0.0; pass_names.len()],
+        })
+    }
+
+    pub fn timestamp_writes(&self, pass: usize) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(pass as u32 * 2),
+            end_of_pass_write_index: Some(pass as u32 * 2 + 1),
+        }
+    }
+
+    // Records the query resolve; call after the last timed pass, before submitting
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let count = self.pass_names.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+
+        if !self.readback_pending {
+            encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, self.resolve_buffer.size());
+            self.copy_recorded = true;
+        }
+    }
+
+    pub fn after_submit(&mut self) {
+        if !self.copy_recorded {
+            return;
+        }
+
+        let map_state = self.map_state.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            map_state.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+        });
+
+        self.copy_recorded = false;
+        self.readback_pending = true;
+    }
+
+    // Picks up finished readbacks without blocking
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        if !self.readback_pending {
+            return;
+        }
+
+        let _ = device.poll(wgpu::PollType::Poll);
+        match self.map_state.swap(MAP_PENDING, Ordering::Acquire) {
+            MAP_DONE => {}
+            MAP_FAILED => {
+                self.readback_pending = false;
+                return;
+            }
+            _ => return,
+        }
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+
+            for (pass, (timing, latest)) in self.timings.iter_mut().zip(&mut self.latest).enumerate() {
+                let ticks = timestamps[pass * 2 + 1].saturating_sub(timestamps[pass * 2]);
+                let ms = ticks as f32 * self.period_ns / 1_000_000.0;
+                *timing = if *timing == 0.0 { ms } else { *timing * 0.9 + ms * 0.1 };
+                *latest = ms;
+            }
+        }
+
+        self.readback_buffer.unmap();
+        self.readback_pending = false;
+    }
+
+    pub fn timings(&self) -> impl Iterator<Item = (&'static str, f32)> + '_ {
+        self.pass_names.iter().copied().zip(self.timings.iter().copied())
+    }
+
+    pub fn pass_names(&self) -> &[&'static str] {
+        &self.pass_names
+    }
+
+    pub fn latest(&self) -> &[f32] {
+        &self.latest
+    }
+}
+
+// CPU timing //
+
+// Number of frames kept for the frame time graph
+pub const FRAME_HISTORY: usize = 240;
+
+// Milliseconds spent in each part of one frame on the CPU
+#[derive(Clone, Copy, Default)]
+pub struct FrameTiming {
+    pub events: f32,
+    pub update: f32,
+    pub encode: f32,
+    // Waiting for a swapchain image plus presenting it, mostly vsync
+    pub present: f32,
+}
+
+impl FrameTiming {
+    pub fn total(&self) -> f32 {
+        self.events + self.update + self.encode + self.present
+    }
+}
+
+// Ring buffer of the most recent frame timings, oldest overwritten first
+pub struct FrameHistory {
+    frames: Vec<FrameTiming>,
+    next: usize,
+    // Frames pushed so far, up to FRAME_HISTORY
+    recorded: usize,
+}
+
+impl Default for FrameHistory {
+    fn default() -> Self {
+        Self {
+            frames: vec![FrameTiming::default(); FRAME_HISTORY],
+            next: 0,
+            recorded: 0,
+        }
+    }
+}
+
+impl FrameHistory {
+    pub fn push(&mut self, timing: FrameTiming) {
+        self.frames[self.next] = timing;
+        self.next = (self.next + 1) % FRAME_HISTORY;
+        self.recorded = (self.recorded + 1).min(FRAME_HISTORY);
+    }
+
+    // Oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &FrameTiming> {
+        self.frames[self.next..].iter().chain(self.frames[..self.next].iter())
+    }
+
+    pub fn latest(&self) -> FrameTiming {
+        self.frames[(self.next + FRAME_HISTORY - 1) % FRAME_HISTORY]
+    }
+
+    pub fn average_total(&self) -> f32 {
+        // Slots not written yet hold zeros, which would drag the average down
+        self.frames.iter().map(FrameTiming::total).sum::<f32>() / self.recorded.max(1) as f32
+    }
+}
+
+// Profile log //
+// --profile writes a CSV row per frame: the CPU timings, then each GPU pass. GPU
+// timings are read back a few frames late, so a row has the latest ones that arrived.
+
+pub struct ProfileLog {
+    writer: BufWriter<File>,
+    frame: u64,
+    start: Instant,
+}
+
+impl ProfileLog {
+    pub fn create(path: &Path, gpu_passes: &[&str]) -> Result<Self, Error> {
+        let file = File::create(path).map_err(Error::io(format!("Failed to create {}", path.display())))?;
+        let mut writer = BufWriter::new(file);
+        let mut header = "frame,time_s,events_ms,update_ms,encode_ms,present_ms,total_ms".to_string();
+        for pass in gpu_passes {
+            header += &format!(",gpu_{}_ms", pass.to_lowercase());
+        }
+        writeln!(writer, "{}", header).map_err(Error::io(format!("Failed to write {}", path.display())))?;
+
+        Ok(Self {
+            writer,
+            frame: 0,
+            start: Instant::now(),
+        })
+    }
+
+    // Stops logging at the first write error rather than failing every frame after it
+    pub fn write(&mut self, timing: &FrameTiming, gpu: &[f32]) -> std::io::Result<()> {
+        write!(
+            self.writer,
+            "{},{:.4},{:.3},{:.3},{:.3},{:.3},{:.3}",
+            self.frame, self.start.elapsed().as_secs_f32(), timing.events, timing.update, timing.encode, timing.present, timing.total(),
+        )?;
+        for ms in gpu {
+            write!(self.writer, ",{:.3}", ms)?;
+        }
+        writeln!(self.writer)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Recent files //
+// The last models opened in a window, newest first, kept in recent.json next to
+// config.json so they survive restarts. Number keys 1-9 open them again.
+
+const MAX_RECENT: usize = 9;
+
+#[derive(Default)]
+pub struct RecentFiles {
+    // Absolute paths, so the list works from any working directory
+    paths: Vec<String>,
+}
+
+impl RecentFiles {
+    pub fn load() -> Self {
+        let Some(path) = Self::file() else {
+            return Self::default();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match serde_json::from_str(&text) {
+            Ok(paths) => Self { paths },
+            Err(e) => {
+                eprintln!("Ignoring {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    // Moves the model to the front of the list and saves it
+    pub fn add(&mut self, model: &str) {
+        let Ok(model) = std::path::absolute(model) else {
+            return;
+        };
+        let model = model.to_string_lossy().into_owned();
+        self.paths.retain(|path| *path != model);
+        self.paths.insert(0, model);
+        self.paths.truncate(MAX_RECENT);
+        self.save();
+    }
+
+    // The nth most recent model, counting from 0
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.paths.get(index).map(String::as_str)
+    }
+
+    // File names for the panel, newest first
+    pub fn names(&self) -> Vec<String> {
+        self.paths
+            .iter()
+            .map(|path| Path::new(path).file_name().map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::file() else {
+            return;
+        };
+        let result = path.parent().map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| serde_json::to_string_pretty(&self.paths).map_err(std::io::Error::other))
+            .and_then(|json| fs::write(&path, json));
+
+        if let Err(e) = result {
+            eprintln!("Failed to save recent files: {}", e);
+        }
+    }
+
+    fn file() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rsview").join("recent.json"))
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use crate::screenshot::save_png;
+
+// Session recording //
+// Rendered frames are handed to an encoder thread: piped into ffmpeg as raw RGBA for
+// an MP4 when ffmpeg is on the PATH, otherwise written out as a numbered PNG sequence.
+// Frames are dropped rather than queued without bound if encoding falls behind.
+
+pub const FRAMES_PER_SECOND: u32 = 30;
+// Frames waiting for the encoder before new ones are dropped
+const QUEUE_FRAMES: usize = 8;
+
+enum Sink {
+    Ffmpeg(Child),
+    Png(PathBuf),
+}
+
+pub struct Recorder {
+    sender: SyncSender<Vec<u8>>,
+    encoder: JoinHandle<()>,
+    width: u32,
+    height: u32,
+    frames: usize,
+    dropped: usize,
+}
+
+impl Recorder {
+    // `name` is used for the MP4 file or the PNG directory
+    pub fn start(name: &str, width: u32, height: u32) -> Result<Self, String> {
+        let output = format!("{}.mp4", name);
+        let ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height), "-framerate", &FRAMES_PER_SECOND.to_string(), "-i", "-"])
+            // yuv420p needs even dimensions
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-c:v", "libx264", "-pix_fmt", "yuv420p", &output])
+            .stdin(Stdio::piped())
+            .spawn();
+
+        let (sink, destination) = match ffmpeg {
+            Ok(child) => (Sink::Ffmpeg(child), output),
+            Err(_) => {
+                fs::create_dir_all(name).map_err(|e| format!("Failed to create {}: {}", name, e))?;
+                (Sink::Png(PathBuf::from(name)), format!("{}/", name))
+            }
+        };
+
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(QUEUE_FRAMES);
+        let encoder = thread::spawn(move || {
+            let result = match sink {
+                Sink::Ffmpeg(mut child) => {
+                    let mut result = Ok(());
+                    if let Some(mut stdin) = child.stdin.take() {
+                        for frame in receiver {
+                            if let Err(e) = stdin.write_all(&frame) {
+                                result = Err(format!("ffmpeg stopped taking frames: {}", e));
+                                break;
+                            }
+                        }
+                    }
+                    // Closing stdin lets ffmpeg finish the file
+                    match child.wait() {
+                        Ok(status) if !status.success() => Err(format!("ffmpeg exited with {}", status)),
+                        Err(e) => Err(e.to_string()),
+                        _ => result,
+                    }
+                }
+                Sink::Png(dir) => receiver.into_iter().enumerate().try_for_each(|(i, frame)| {
+                    save_png(&dir.join(format!("frame-{:05}.png", i)), width, height, &frame).map_err(|e| e.to_string())
+                }),
+            };
+
+            match result {
+                Ok(()) => println!("Recording: Saved {}", destination),
+                Err(e) => eprintln!("Recording: {}", e),
+            }
+        });
+
+        Ok(Self {
+            sender,
+            encoder,
+            width,
+            height,
+            frames: 0,
+            dropped: 0,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // RGBA rows at the size the recording started with
+    pub fn push(&mut self, frame: Vec<u8>) {
+        match self.sender.try_send(frame) {
+            Ok(()) => self.frames += 1,
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            // The encoder gave up and already said why
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    // Waits for the encoder to write out everything queued
+    pub fn finish(self) {
+        println!("Recording: Stopped after {} frames ({} dropped while encoding)", self.frames, self.dropped);
+        drop(self.sender);
+        let _ = self.encoder.join();
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use cgmath::{InnerSpace, Vector3};
+use rayon::prelude::*;
+
+use crate::optimize;
+use crate::parse::Mesh;
+
+// Mesh repair //
+// Fixes for messy exports that would otherwise render wrong with backface culling
+// or skew the geometry statistics.
+
+// Triangles whose height is below this fraction of their longest edge count as zero-area
+const DEGENERATE_HEIGHT_RATIO: f64 = 1e-6;
+
+// Gives vertices at the same position the same id, so topology can be followed across
+// the seams where formats like glTF split vertices for UVs or normals
+pub fn weld_positions(vertices: &[[f32; 3]]) -> Vec<u32> {
+    let mut ids = HashMap::with_capacity(vertices.len());
+    vertices
+        .iter()
+        .map(|v| {
+            let next = ids.len() as u32;
+            *ids.entry(v.map(f32::to_bits)).or_insert(next)
+        })
+        .collect()
+}
+
+// Every triangle edge keyed by its welded ends (lower id in the high bits), with the
+// triangle it belongs to and whether it was walked from the lower id; sorted by key
+pub fn sorted_edges(welded: &[u32], indices: &[u32]) -> Vec<(u64, u32, bool)> {
+    let mut edges = Vec::with_capacity(indices.len());
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        for corner in 0..3 {
+            let a = welded[triangle[corner] as usize];
+            let b = welded[triangle[(corner + 1) % 3] as usize];
+            if a != b {
+                let (low, high) = (a.min(b), a.max(b));
+                edges.push(((low as u64) << 32 | high as u64, t as u32, a < b));
+            }
+        }
+    }
+    edges.par_sort_unstable_by_key(|&(key, _, _)| key);
+    edges
+}
+
+// Whether a triangle is too thin to have a direction: its height is a negligible
+// fraction of its longest edge
+pub fn is_degenerate(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> bool {
+    let [a, b, c] = [a, b, c].map(|p| Vector3::from(p.map(f64::from)));
+    // Twice the area is height times longest edge; compared squared to avoid roots
+    let longest = (b - a).magnitude2().max((c - b).magnitude2()).max((a - c).magnitude2());
+    (b - a).cross(c - a).magnitude2() <= longest * longest * DEGENERATE_HEIGHT_RATIO.powi(2)
+}
+
+pub struct WindingRepair {
+    pub components: usize,
+    // Shared edges whose two triangles disagreed on winding
+    pub inconsistent: usize,
+    // Components where most triangles had to be flipped, i.e. that were mostly inside out
+    pub inverted_components: usize,
+    pub flipped: usize,
+}
+
+// Makes neighbouring triangles agree on winding within each connected component, then
+// turns closed components outward (positive volume). Components are grown across edges
+// shared by exactly two triangles, so non-manifold edges never force a choice.
+pub fn orient_faces(mesh: &mut Mesh) -> WindingRepair {
+    let triangle_count = mesh.indices.len() / 3;
+    let mut repair = WindingRepair {
+        components: 0,
+        inconsistent: 0,
+        inverted_components: 0,
+        flipped: 0,
+    };
+    if mesh.indices.iter().any(|&i| i as usize >= mesh.vertices.len()) {
+        return repair;
+    }
+
+    let welded = weld_positions(&mesh.vertices);
+    let edges = sorted_edges(&welded, &mesh.indices[..triangle_count * 3]);
+
+    // Triangle adjacency as one flat array with offsets; the flag is set when both
+    // triangles walk the shared edge the same way, i.e. their windings disagree
+    let mut open = vec![false; triangle_count];
+    let mut neighbour_counts = vec![0u32; triangle_count];
+    let mut pairs = Vec::with_capacity(edges.len() / 2);
+    for group in edges.chunk_by(|x, y| x.0 == y.0) {
+        if let [(_, t0, f0), (_, t1, f1)] = *group {
+            pairs.push((t0, t1, f0 == f1));
+            neighbour_counts[t0 as usize] += 1;
+            neighbour_counts[t1 as usize] += 1;
+        } else {
+            for &(_, t, _) in group {
+                open[t as usize] = true;
+            }
+        }
+    }
+
+    let mut offsets = vec![0usize; triangle_count + 1];
+    for t in 0..triangle_count {
+        offsets[t + 1] = offsets[t] + neighbour_counts[t] as usize;
+    }
+    let mut cursor = offsets.clone();
+    let mut neighbours = vec![(0u32, false); offsets[triangle_count]];
+    for &(t0, t1, same) in &pairs {
+        neighbours[cursor[t0 as usize]] = (t1, same);
+        cursor[t0 as usize] += 1;
+        neighbours[cursor[t1 as usize]] = (t0, same);
+        cursor[t1 as usize] += 1;
+    }
+    repair.inconsistent = pairs.iter().filter(|&&(_, _, same)| same).count();
+
+    let mut flip = vec![false; triangle_count];
+    let mut visited = vec![false; triangle_count];
+    let mut queue = VecDeque::new();
+    let mut component = Vec::new();
+    for seed in 0..triangle_count {
+        if visited[seed] {
+            continue;
+        }
+
+        repair.components += 1;
+        visited[seed] = true;
+        queue.push_back(seed);
+        component.clear();
+        let mut closed = true;
+
+        while let Some(t) = queue.pop_front() {
+            component.push(t);
+            closed &= !open[t];
+            for &(n, same) in &neighbours[offsets[t]..offsets[t + 1]] {
+                let n = n as usize;
+                if !visited[n] {
+                    visited[n] = true;
+                    flip[n] = flip[t] ^ same;
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        // Signed tetrahedra against the origin sum to the enclosed volume when the surface is closed
+        if closed {
+            let volume: f64 = component
+                .iter()
+                .map(|&t| {
+                    let [a, b, c] = [0, 1, 2].map(|corner| {
+                        Vector3::from(mesh.vertices[mesh.indices[t * 3 + corner] as usize].map(f64::from))
+                    });
+                    let volume = a.dot(b.cross(c));
+                    if flip[t] { -volume } else { volume }
+                })
+                .sum();
+
+            if volume < 0.0 {
+                for &t in &component {
+                    flip[t] = !flip[t];
+                }
+            }
+        }
+
+        if component.iter().filter(|&&t| flip[t]).count() * 2 > component.len() {
+            repair.inverted_components += 1;
+        }
+    }
+
+    for (t, _) in flip.iter().enumerate().filter(|&(_, &flip)| flip) {
+        mesh.indices.swap(t * 3 + 1, t * 3 + 2);
+        repair.flipped += 1;
+    }
+
+    repair
+}
+
+pub struct Cleanup {
+    pub degenerate_triangles: usize,
+    pub duplicate_triangles: usize,
+    pub unreferenced_vertices: usize,
+}
+
+// Drops zero-area triangles, repeated triangles (the same three positions in any order)
+// and vertices no triangle uses. Duplicates are only looked for within a submesh, since
+// the same face in two parts is drawn with two different materials.
+pub fn clean(mesh: &mut Mesh) -> Cleanup {
+    let mut cleanup = Cleanup {
+        degenerate_triangles: 0,
+        duplicate_triangles: 0,
+        unreferenced_vertices: 0,
+    };
+    if mesh.indices.iter().any(|&i| i as usize >= mesh.vertices.len()) {
+        return cleanup;
+    }
+
+    let welded = weld_positions(&mesh.vertices);
+    let mut ranges: Vec<(usize, usize)> = mesh.submeshes
+        .iter()
+        .map(|submesh| (submesh.first_index as usize, (submesh.first_index + submesh.index_count) as usize))
+        .collect();
+    if ranges.is_empty() {
+        ranges.push((0, mesh.indices.len() / 3 * 3));
+    }
+
+    // Surviving triangles are compacted towards the front, ranges shrink with them
+    let mut kept = Vec::with_capacity(mesh.indices.len());
+    let mut seen = HashSet::new();
+    for (range, (start, end)) in ranges.into_iter().enumerate() {
+        let first_index = kept.len();
+        seen.clear();
+
+        for triangle in mesh.indices[start..end].chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize]);
+            if is_degenerate(a, b, c) {
+                cleanup.degenerate_triangles += 1;
+                continue;
+            }
+
+            let mut key = [0, 1, 2].map(|corner| welded[triangle[corner] as usize]);
+            key.sort_unstable();
+            if !seen.insert(key) {
+                cleanup.duplicate_triangles += 1;
+                continue;
+            }
+
+            kept.extend_from_slice(triangle);
+        }
+
+        if let Some(submesh) = mesh.submeshes.get_mut(range) {
+            submesh.first_index = first_index as u32;
+            submesh.index_count = (kept.len() - first_index) as u32;
+        }
+    }
+    mesh.indices = kept;
+
+    let vertex_count = mesh.vertices.len();
+    optimize::optimize_vertex_fetch(mesh);
+    cleanup.unreferenced_vertices = vertex_count - mesh.vertices.len();
+
+    cleanup
+}
+
+// Keeps the triangles `keep` accepts (by triangle number), shrinking submesh ranges to
+// match, then drops the vertices nothing uses anymore
+pub fn retain_triangles(mesh: &mut Mesh, mut keep: impl FnMut(usize) -> bool) {
+    let mut ranges: Vec<(usize, usize)> = mesh.submeshes
+        .iter()
+        .map(|submesh| (submesh.first_index as usize, (submesh.first_index + submesh.index_count) as usize))
+        .collect();
+    if ranges.is_empty() {
+        ranges.push((0, mesh.indices.len() / 3 * 3));
+    }
+
+    let mut kept = Vec::with_capacity(mesh.indices.len());
+    for (range, (start, end)) in ranges.into_iter().enumerate() {
+        let first_index = kept.len();
+        for (t, triangle) in mesh.indices[start..end].chunks_exact(3).enumerate() {
+            if keep(start / 3 + t) {
+                kept.extend_from_slice(triangle);
+            }
+        }
+
+        if let Some(submesh) = mesh.submeshes.get_mut(range) {
+            submesh.first_index = first_index as u32;
+            submesh.index_count = (kept.len() - first_index) as u32;
+        }
+    }
+    mesh.indices = kept;
+
+    optimize::optimize_vertex_fetch(mesh);
+}
@@ -0,0 +1,71 @@
+use std::ops::Range;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::bvh::Bvh;
+use crate::parse::SubMesh;
+
+// Scene queries //
+// The loaded model as something to ask questions of, rather than draw: ray casts
+// against the full-resolution mesh that also say which node (glTF primitive or OBJ
+// material group) was hit. The viewer's own picking goes through here, and so should
+// any tool built on top of it.
+
+pub struct Hit {
+    // Index of the submesh the triangle belongs to
+    pub node: usize,
+    pub triangle: u32,
+    // Distance along the ray in multiples of its direction
+    pub t: f32,
+    // Weights of the triangle's second and third corner, the first gets 1 - u - v
+    pub barycentric: [f32; 2],
+    pub point: Vector3<f32>,
+}
+
+pub struct Scene {
+    bvh: Bvh,
+    // Triangle range of each node, in submesh order
+    nodes: Vec<Range<u32>>,
+}
+
+impl Scene {
+    // A mesh without submeshes is a single node
+    pub fn new(bvh: Bvh, submeshes: &[SubMesh]) -> Self {
+        let nodes = if submeshes.is_empty() {
+            std::iter::once(0..bvh.triangle_count() as u32).collect()
+        } else {
+            submeshes
+                .iter()
+                .map(|submesh| submesh.first_index / 3..(submesh.first_index + submesh.index_count) / 3)
+                .collect()
+        };
+
+        Self { bvh, nodes }
+    }
+
+    // Closest hit along the ray, if any; `direction` does not need to be normalized
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<Hit> {
+        let hit = self.bvh.raycast(origin, direction)?;
+        let node = self.nodes.iter().position(|range| range.contains(&hit.triangle)).unwrap_or(0);
+
+        Some(Hit {
+            node,
+            triangle: hit.triangle,
+            t: hit.t,
+            barycentric: hit.barycentric,
+            point: hit.point,
+        })
+    }
+
+    // Index of the hit triangle's corner closest to the hit point
+    pub fn nearest_vertex(&self, hit: &Hit) -> u32 {
+        let corners = self.bvh.corner_indices(hit.triangle);
+        let positions = self.bvh.triangle(hit.triangle);
+        let nearest = (0..3).min_by(|&a, &b| (positions[a] - hit.point).magnitude2().total_cmp(&(positions[b] - hit.point).magnitude2())).unwrap_or(0);
+        corners[nearest]
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+}
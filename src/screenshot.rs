@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::error::Error;
+
+// Screenshots //
+// The current view rendered again into an offscreen target and read back as RGBA,
+// for PNG screenshots (cleared to transparent so product shots composite cleanly) and
+// video frames. The target uses the surface format so the scene pipelines can draw
+// into it unchanged; BGRA surfaces are swizzled on the way out.
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+pub struct Capture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+    // Rows in the readback buffer are padded to COPY_BYTES_PER_ROW_ALIGNMENT
+    padded_row_bytes: u32,
+    width: u32,
+    height: u32,
+    bgra: bool,
+}
+
+impl Capture {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> Result<Self, String> {
+        let bgra = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            other => return Err(format!("Screenshots of a {:?} surface aren't supported", other)),
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Screenshot Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let padded_row_bytes = (width * BYTES_PER_PIXEL).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Readback Buffer"),
+            size: padded_row_bytes as wgpu::BufferAddress * height as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            buffer,
+            padded_row_bytes,
+            width,
+            height,
+            bgra,
+        })
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    // Records the copy into the readback buffer, after the capture has been rendered
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row_bytes),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // Tightly packed RGBA rows; waits for the GPU, so only call this once the copy has been submitted
+    pub fn read(self, device: &wgpu::Device) -> Result<Vec<u8>, String> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::Wait).map_err(|e| format!("Failed to wait for the GPU: {}", e))?;
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to read the screenshot back: {}", e))?;
+
+        let row_bytes = (self.width * BYTES_PER_PIXEL) as usize;
+        let mut pixels = Vec::with_capacity(row_bytes * self.height as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks_exact(self.padded_row_bytes as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.buffer.unmap();
+
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        Ok(pixels)
+    }
+}
+
+pub fn save_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), Error> {
+    let file = File::create(path).map_err(Error::io(format!("Failed to create {}", path.display())))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(io::Error::other)
+        .map_err(Error::io(format!("Failed to write {}", path.display())))
+}
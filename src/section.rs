@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::bvh::Bvh;
+use crate::units::Unit;
+
+// Cross sections //
+// Cuts the mesh with an axis-aligned plane and measures the contour. Each crossing
+// triangle contributes one segment, oriented so the cut surface is on its left when
+// looking against the plane normal; that way the contour's signed area counts holes
+// negatively without ever chaining segments into loops.
+
+const AXIS_NAMES: [&str; 3] = ["X", "Y", "Z"];
+
+pub struct Section {
+    pub axis: usize,
+    pub position: f32,
+    pub segments: Vec<[Vector3<f32>; 2]>,
+    pub perimeter: f32,
+    // Only meaningful for a closed contour, None otherwise
+    pub area: Option<f32>,
+}
+
+impl Section {
+    pub fn cut(bvh: &Bvh, axis: usize, position: f32) -> Self {
+        let mut normal = Vector3::new(0.0, 0.0, 0.0);
+        normal[axis] = 1.0;
+
+        let mut segments = Vec::new();
+        for triangle in bvh.triangles_crossing_plane(normal, position) {
+            let corners = bvh.triangle(triangle);
+            // Corners on the plane count as above it, so each crossing yields exactly one segment
+            let above = corners.map(|c| c[axis] >= position);
+            let Some(lone) = (0..3).find(|&i| above[i] != above[(i + 1) % 3] && above[i] != above[(i + 2) % 3]) else {
+                continue;
+            };
+
+            let p = edge_crossing(corners[lone], corners[(lone + 1) % 3], axis, position);
+            let q = edge_crossing(corners[lone], corners[(lone + 2) % 3], axis, position);
+            if p == q {
+                continue;
+            }
+
+            let triangle_normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+            if (q - p).dot(normal.cross(triangle_normal)) >= 0.0 {
+                segments.push([p, q]);
+            } else {
+                segments.push([q, p]);
+            }
+        }
+
+        // Closed when every point starts exactly one segment and ends another
+        let mut balance: HashMap<[u32; 3], i32> = HashMap::with_capacity(segments.len());
+        for [p, q] in &segments {
+            *balance.entry(point_key(*p)).or_default() += 1;
+            *balance.entry(point_key(*q)).or_default() -= 1;
+        }
+        let closed = !segments.is_empty() && balance.values().all(|&b| b == 0);
+
+        let perimeter = segments.iter().fold(0.0, |sum, [p, q]| sum + (q - p).magnitude());
+        let signed_area: f64 = segments
+            .iter()
+            .map(|[p, q]| p.cross(*q)[axis] as f64 * 0.5)
+            .sum();
+
+        Self {
+            axis,
+            position,
+            segments,
+            perimeter,
+            area: closed.then_some(signed_area.abs() as f32),
+        }
+    }
+
+    pub fn summary(&self, unit: Unit) -> String {
+        let area = match self.area {
+            Some(area) => format!("area {}", unit.area(area as f64)),
+            None if self.segments.is_empty() => "no contour".to_string(),
+            None => "open contour".to_string(),
+        };
+        format!("section {} = {}: perimeter {}, {}",
+                AXIS_NAMES[self.axis], unit.length(self.position as f64), unit.length(self.perimeter as f64), area)
+    }
+}
+
+// Where an edge meets the plane; endpoints are put in a fixed order first so both
+// triangles sharing the edge compute bit-identical points
+fn edge_crossing(a: Vector3<f32>, b: Vector3<f32>, axis: usize, position: f32) -> Vector3<f32> {
+    let (a, b) = if point_key(a) <= point_key(b) { (a, b) } else { (b, a) };
+    let t = (position - a[axis]) / (b[axis] - a[axis]);
+    let mut point = a + (b - a) * t;
+    // Land exactly on the plane so the area sum stays planar
+    point[axis] = position;
+    point
+}
+
+fn point_key(p: Vector3<f32>) -> [u32; 3] {
+    [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+}
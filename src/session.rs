@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::background::Background;
+use crate::clip::ClipPlane;
+use crate::debug_view::DebugView;
+use crate::normals::NormalDisplay;
+use crate::units::Unit;
+
+// Sessions //
+// A `.rsview` file holds what it takes to get back to a review setup: the model and
+// how it was loaded, its orientation and orbit center, the camera, display toggles and
+// the picked points. Model paths are stored relative to the session file when they sit
+// beside or below it, so a folder with both can be handed to someone else.
+
+pub const EXTENSION: &str = "rsview";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    // The first model is the one the viewer analyzes, any others are shown beside it
+    pub models: Vec<SessionModel>,
+    pub camera: Camera,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionModel {
+    pub path: String,
+    pub optimize: bool,
+    pub clean: bool,
+    pub fix_winding: bool,
+    pub unit_scale: f32,
+    pub min_shell_triangles: usize,
+    pub isolate_shell: Option<usize>,
+    pub compare: Option<String>,
+    // Turn around the vertical axis and tilt in radians, and the orbit center in model space
+    pub rotation: f32,
+    #[serde(default)]
+    pub pitch: f32,
+    pub center: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Camera {
+    pub distance: f32,
+    pub auto_rotate: bool,
+    // Yaw and elevation the camera orbits at, the default view when missing
+    #[serde(default)]
+    pub view: Option<(f32, f32)>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub unit: Unit,
+    pub show_oriented_box: bool,
+    pub show_deviation: bool,
+    pub show_components: bool,
+    pub show_manifold_edges: bool,
+    pub show_parts: bool,
+    pub show_grid: bool,
+    pub show_bounds: bool,
+    pub flat_shading: bool,
+    pub uv_checker: bool,
+    pub debug_view: DebugView,
+    pub clip_plane: ClipPlane,
+    pub normal_display: NormalDisplay,
+    pub explode: f32,
+    pub show_uv_layout: bool,
+    // Axis and position of the section plane
+    pub section_plane: Option<(usize, f32)>,
+    // Splitter of an A/B comparison as a fraction of the width, with the second model right of it
+    pub split: Option<f32>,
+    // None in sessions saved before the background could be changed
+    pub background: Option<Background>,
+}
+
+// A measurement pick
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Annotation {
+    pub point: [f32; 3],
+    pub normal: [f32; 3],
+    pub edge_length: f32,
+}
+
+impl Session {
+    pub fn is_session_file(path: &str) -> bool {
+        Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case(EXTENSION))
+    }
+
+    // Relative model paths come back resolved against the session file's folder
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut session: Session = serde_json::from_str(&text).map_err(|e| format!("Invalid session {}: {}", path.display(), e))?;
+        if session.models.is_empty() {
+            return Err(format!("Session {} has no models", path.display()));
+        }
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for model in &mut session.models {
+            model.path = resolve(dir, &model.path);
+            model.compare = model.compare.as_deref().map(|compare| resolve(dir, compare));
+        }
+        Ok(session)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut session = self.clone();
+        for model in &mut session.models {
+            model.path = relative(dir, &model.path);
+            model.compare = model.compare.as_deref().map(|compare| relative(dir, compare));
+        }
+
+        let json = serde_json::to_string_pretty(&session).map_err(|e| format!("Failed to write the session: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+}
+
+fn resolve(dir: &Path, path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        path.to_string()
+    } else {
+        dir.join(path).to_string_lossy().into_owned()
+    }
+}
+
+// Falls back to the absolute path for models outside the session's folder
+fn relative(dir: &Path, path: &str) -> String {
+    let absolute = |p: &Path| -> Option<PathBuf> { p.canonicalize().ok() };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let (Some(dir), Some(file)) = (absolute(dir), absolute(Path::new(path))) else {
+        return path.to_string();
+    };
+
+    match file.strip_prefix(&dir) {
+        Ok(inside) => inside.to_string_lossy().into_owned(),
+        Err(_) => file.to_string_lossy().into_owned(),
+    }
+}
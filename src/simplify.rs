@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use crate::mesh;
+use crate::parse::{Material, Mesh, SubMesh};
+
+// Mesh simplification //
+
+// Meshes above the LOD threshold get up to this many coarser levels, each with about a
+// quarter of the triangles of the one before
+pub const LOD_LEVELS: usize = 3;
+
+// Where the vertex standing in for a grid cell goes
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    // The average of the cell's vertices
+    Mean,
+    // The point closest to the planes of the cell's triangles, which keeps sharp edges
+    // and corners where the average would round them off
+    Quadric,
+}
+
+// Vertex clustering: snaps every vertex to a uniform grid over the bounding box and
+// merges everything in a cell. Quality is rough but it runs in linear time, which is
+// what matters for building a stand-in for a mesh too large to draw interactively.
+pub fn cluster_decimate(mesh: &Mesh, target_triangles: usize) -> Mesh {
+    let (vertices, indices, _) = decimate(&mesh.vertices, &mesh.indices, target_triangles, Placement::Mean);
+    simplified_mesh(vertices, indices, mesh.sources.clone())
+}
+
+// Levels of detail for a large mesh, finest first. Cells are merged with quadric error
+// metrics (Lindstrom's out-of-core simplification), which still runs in linear time on
+// scans with millions of triangles. Triangles stay in file order, so every level keeps
+// the parts and their colors, but not the texture coordinates.
+pub fn build_lods(vertices: &[[f32; 3]], indices: &[u32], submeshes: &[SubMesh]) -> Vec<Mesh> {
+    let mut target = indices.len() / 3;
+    let mut levels: Vec<Mesh> = Vec::with_capacity(LOD_LEVELS);
+    for _ in 0..LOD_LEVELS {
+        target /= 4;
+        let (level_vertices, level_indices, kept) = decimate(vertices, indices, target, Placement::Quadric);
+        // Nothing left to gain once the grid bottoms out
+        if level_indices.is_empty() || levels.last().is_some_and(|last| last.indices.len() <= level_indices.len()) {
+            break;
+        }
+        let mut level = simplified_mesh(level_vertices, level_indices, Vec::new());
+        level.submeshes = remap_submeshes(submeshes, &kept);
+        mesh::generate_normals(&mut level);
+        levels.push(level);
+    }
+    levels
+}
+
+// Positions and indices only; everything else is per vertex and doesn't survive merging
+fn simplified_mesh(vertices: Vec<[f32; 3]>, indices: Vec<u32>, sources: Vec<std::path::PathBuf>) -> Mesh {
+    Mesh {
+        vertices,
+        indices,
+        normals: None,
+        uvs: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        rig: None,
+        submeshes: Vec::new(),
+        textures: Vec::new(),
+        sources,
+    }
+}
+
+// The simplified vertices and indices and, for each triangle left, the one it came from
+type Clustered = (Vec<[f32; 3]>, Vec<u32>, Vec<u32>);
+
+fn decimate(vertices: &[[f32; 3]], indices: &[u32], target_triangles: usize, placement: Placement) -> Clustered {
+    let (min, max) = bounds(vertices);
+    let extent = (0..3).map(|axis| max[axis] - min[axis]).fold(f32::EPSILON, f32::max);
+
+    // A closed surface crosses roughly resolution^2 cells, each yielding about two triangles
+    let mut resolution = ((target_triangles as f32 / 2.0).sqrt() as u32).clamp(8, 4096);
+
+    loop {
+        let simplified = cluster(vertices, indices, min, extent / resolution as f32, placement);
+        if simplified.1.len() / 3 <= target_triangles || resolution <= 8 {
+            return simplified;
+        }
+        resolution = resolution * 3 / 4;
+    }
+}
+
+fn bounds(vertices: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+
+    for vertex in vertices {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vertex[axis]);
+            max[axis] = max[axis].max(vertex[axis]);
+        }
+    }
+
+    (min, max)
+}
+
+fn cluster(positions: &[[f32; 3]], triangles: &[u32], origin: [f32; 3], cell_size: f32, placement: Placement) -> Clustered {
+    let mut cells: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut sums: Vec<([f64; 3], u32)> = Vec::new();
+    let mut remap = Vec::with_capacity(positions.len());
+    let mut keys = Vec::new();
+
+    for vertex in positions {
+        let key = [0, 1, 2].map(|axis| ((vertex[axis] - origin[axis]) / cell_size) as u32);
+        let cell = *cells.entry(key).or_insert_with(|| {
+            sums.push(([0.0; 3], 0));
+            keys.push(key);
+            sums.len() as u32 - 1
+        });
+
+        let (sum, count) = &mut sums[cell as usize];
+        for axis in 0..3 {
+            sum[axis] += vertex[axis] as f64;
+        }
+        *count += 1;
+        remap.push(cell);
+    }
+
+    let means: Vec<[f64; 3]> = sums.iter().map(|(sum, count)| sum.map(|s| s / *count as f64)).collect();
+    let vertices = match placement {
+        Placement::Mean => means.iter().map(|mean| mean.map(|m| m as f32)).collect(),
+        Placement::Quadric => {
+            let quadrics = cell_quadrics(positions, triangles, &remap, sums.len());
+            means
+                .iter()
+                .zip(&quadrics)
+                .zip(&keys)
+                .map(|((&mean, quadric), key)| {
+                    // Kept inside its cell, so nearly parallel planes can't throw it far off
+                    let low = [0, 1, 2].map(|axis| origin[axis] as f64 + key[axis] as f64 * cell_size as f64);
+                    let point = quadric.minimizer().unwrap_or(mean);
+                    [0, 1, 2].map(|axis| point[axis].clamp(low[axis], low[axis] + cell_size as f64) as f32)
+                })
+                .collect()
+        }
+    };
+
+    // Triangles that collapsed into a line or point no longer cover anything
+    let mut indices = Vec::new();
+    let mut kept = Vec::new();
+    for (triangle_index, triangle) in triangles.chunks_exact(3).enumerate() {
+        let [a, b, c] = [0, 1, 2].map(|corner| remap.get(triangle[corner] as usize).copied());
+        if let (Some(a), Some(b), Some(c)) = (a, b, c)
+            && a != b && b != c && a != c {
+            indices.extend_from_slice(&[a, b, c]);
+            kept.push(triangle_index as u32);
+        }
+    }
+
+    (vertices, indices, kept)
+}
+
+// Sum of the squared distances to a set of planes, as x·Ax + 2b·x + c
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+    // Upper triangle of the symmetric A: xx, xy, xz, yy, yz, zz
+    a: [f64; 6],
+    b: [f64; 3],
+}
+
+impl Quadric {
+    // The plane through `point` facing `normal`, weighted by the length of the normal
+    fn add_plane(&mut self, normal: [f64; 3], point: [f64; 3]) {
+        let [x, y, z] = normal;
+        let d = -(x * point[0] + y * point[1] + z * point[2]);
+        let a = [x * x, x * y, x * z, y * y, y * z, z * z];
+        for (sum, term) in self.a.iter_mut().zip(a) {
+            *sum += term;
+        }
+        for (sum, term) in self.b.iter_mut().zip(normal) {
+            *sum += d * term;
+        }
+    }
+
+    // Solves Ax = -b; None where the planes don't pin a single point down, such as on a
+    // flat or gently curved patch, where the mean is as good
+    fn minimizer(&self) -> Option<[f64; 3]> {
+        let [xx, xy, xz, yy, yz, zz] = self.a;
+        let cofactors = [yy * zz - yz * yz, xz * yz - xy * zz, xy * yz - xz * yy];
+        let det = xx * cofactors[0] + xy * cofactors[1] + xz * cofactors[2];
+        // Relative to the planes' total weight, so it doesn't depend on the model's scale
+        let trace = xx + yy + zz;
+        if trace <= 0.0 || det.abs() <= 1e-3 * trace * trace * trace {
+            return None;
+        }
+
+        let inverse = [
+            cofactors[0], cofactors[1], cofactors[2],
+            cofactors[1], xx * zz - xz * xz, xy * xz - xx * yz,
+            cofactors[2], xy * xz - xx * yz, xx * yy - xy * xy,
+        ];
+        let rhs = self.b.map(|b| -b);
+        Some([0, 1, 2].map(|row| (0..3).map(|col| inverse[row * 3 + col] * rhs[col]).sum::<f64>() / det))
+    }
+}
+
+// Each triangle's plane, weighted by its area, added to the cells of its corners
+fn cell_quadrics(positions: &[[f32; 3]], triangles: &[u32], remap: &[u32], cells: usize) -> Vec<Quadric> {
+    let mut quadrics = vec![Quadric::default(); cells];
+    for triangle in triangles.chunks_exact(3) {
+        if triangle.iter().any(|&i| i as usize >= positions.len()) {
+            continue;
+        }
+        let [a, b, c] = [0, 1, 2].map(|corner| positions[triangle[corner] as usize].map(f64::from));
+        let (u, v) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+        // Half of the cross product's length is the area; the plane is scaled by it
+        let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+        let length = (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+        // Unit normal times sqrt(area), so the squared terms come out weighted by area
+        let normal = cross.map(|c| c / length * (length * 0.5).sqrt());
+        for &corner in triangle {
+            quadrics[remap[corner as usize] as usize].add_plane(normal, a);
+        }
+    }
+    quadrics
+}
+
+// The original parts' ranges over the kept triangles, which are still in the same
+// order. Texture slots are dropped along with the texture coordinates.
+fn remap_submeshes(submeshes: &[SubMesh], kept: &[u32]) -> Vec<SubMesh> {
+    submeshes
+        .iter()
+        .map(|submesh| {
+            let first = submesh.first_index / 3;
+            let end = first + submesh.index_count / 3;
+            let start = kept.partition_point(|&triangle| triangle < first);
+            let stop = kept.partition_point(|&triangle| triangle < end);
+            SubMesh {
+                first_index: start as u32 * 3,
+                index_count: (stop - start) as u32 * 3,
+                texture: None,
+                material: Material {
+                    metallic_roughness_texture: None,
+                    normal_texture: None,
+                    occlusion_texture: None,
+                    emissive_texture: None,
+                    ..submesh.material
+                },
+                ..submesh.clone()
+            }
+        })
+        .collect()
+}
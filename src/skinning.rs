@@ -0,0 +1,81 @@
+use cgmath::Matrix4;
+use wgpu::util::StagingBelt;
+
+use crate::upload::DynamicBuffer;
+
+// Joint matrices //
+// The posed joint palette of a skinned mesh, in a storage buffer the skinned vertex
+// shaders index with each vertex's joints. Group 2 of the skinned scene pipelines.
+
+pub struct JointMatrices {
+    buffer: DynamicBuffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    // Size of the buffer the bind group was created for
+    bound_size: wgpu::BufferAddress,
+}
+
+impl JointMatrices {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let matrix_size = std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress;
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Joint Matrices Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(matrix_size),
+                },
+                count: None,
+            }],
+        });
+
+        // Room for the identity entry every palette starts with
+        let buffer = DynamicBuffer::new(device, "Joint Matrices", wgpu::BufferUsages::STORAGE, matrix_size);
+        let bind_group = Self::create_bind_group(device, &layout, &buffer.buffer);
+        let bound_size = buffer.buffer.size();
+
+        Self { buffer, layout, bind_group, bound_size }
+    }
+
+    // Whether the GPU can read storage buffers from vertex shaders, which skinning needs
+    pub fn supported(adapter: &wgpu::Adapter, device: &wgpu::Device) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            && device.limits().max_storage_buffers_per_shader_stage > 0
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Joint Matrices Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        })
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn write(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, matrices: &[Matrix4<f32>]) {
+        let matrices: Vec<[[f32; 4]; 4]> = matrices.iter().map(|&matrix| matrix.into()).collect();
+        if matrices.is_empty() {
+            return;
+        }
+        self.buffer.write(device, encoder, belt, 0, bytemuck::cast_slice(&matrices));
+
+        // Growing replaces the buffer, and the bind group still points at the old one
+        if self.buffer.buffer.size() != self.bound_size {
+            self.bind_group = Self::create_bind_group(device, &self.layout, &self.buffer.buffer);
+            self.bound_size = self.buffer.buffer.size();
+        }
+    }
+
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass<'_>, group: u32) {
+        render_pass.set_bind_group(group, &self.bind_group, &[]);
+    }
+}
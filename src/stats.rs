@@ -0,0 +1,94 @@
+use cgmath::{InnerSpace, Vector3};
+use rayon::prelude::*;
+
+use crate::dimensions::Dimensions;
+use crate::repair;
+use crate::units::Unit;
+
+// Geometry statistics //
+// Surface area, enclosed volume and whether the surface is closed, computed once per
+// loaded mesh. Edges are matched on welded positions, since formats like glTF split
+// vertices along UV seams and that alone would make every mesh look open.
+
+#[derive(Clone, Copy, Debug)]
+pub struct GeometryStats {
+    pub surface_area: f64,
+    // Sum of signed tetrahedra against the origin, only meaningful when watertight
+    pub volume: f64,
+    // Edges used by a single triangle
+    pub boundary_edges: usize,
+    // Edges used by more than two triangles
+    pub non_manifold_edges: usize,
+    // Edges whose two triangles walk them in the same direction, i.e. disagree on winding
+    pub inconsistent_edges: usize,
+    pub dimensions: Dimensions,
+}
+
+impl GeometryStats {
+    pub fn compute(vertices: &[[f32; 3]], indices: &[u32]) -> Self {
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .filter(|t| t.iter().all(|&i| (i as usize) < vertices.len()))
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+
+        let (surface_area, volume) = triangles
+            .par_iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| Vector3::from(vertices[i as usize].map(f64::from)));
+                let area = (b - a).cross(c - a).magnitude() * 0.5;
+                let volume = a.dot(b.cross(c)) / 6.0;
+                (area, volume)
+            })
+            .reduce(|| (0.0, 0.0), |x, y| (x.0 + y.0, x.1 + y.1));
+
+        let (boundary_edges, non_manifold_edges, inconsistent_edges) = Self::count_edges(vertices, &triangles);
+
+        Self {
+            surface_area,
+            volume,
+            boundary_edges,
+            non_manifold_edges,
+            inconsistent_edges,
+            dimensions: Dimensions::compute(vertices),
+        }
+    }
+
+    // Closed and consistently wound: every edge is shared by exactly two triangles in opposite directions
+    pub fn is_watertight(&self) -> bool {
+        self.boundary_edges == 0 && self.non_manifold_edges == 0 && self.inconsistent_edges == 0
+    }
+
+    pub fn summary(&self, unit: Unit) -> String {
+        let closed = if self.is_watertight() {
+            "watertight".to_string()
+        } else {
+            format!("not watertight ({} boundary, {} non-manifold, {} inconsistently wound edges)",
+                    self.boundary_edges, self.non_manifold_edges, self.inconsistent_edges)
+        };
+
+        // The volume's sign only tells the winding, so show its size
+        format!("area {}, volume {}, {}", unit.area(self.surface_area), unit.volume(self.volume.abs()), closed)
+    }
+
+    // Boundary, non-manifold and inconsistently wound edges
+    fn count_edges(vertices: &[[f32; 3]], triangles: &[[u32; 3]]) -> (usize, usize, usize) {
+        let welded = repair::weld_positions(vertices);
+        let edges = repair::sorted_edges(&welded, triangles.as_flattened());
+
+        let mut boundary_edges = 0;
+        let mut non_manifold_edges = 0;
+        let mut inconsistent_edges = 0;
+        for group in edges.chunk_by(|x, y| x.0 == y.0) {
+            let forward = group.iter().filter(|&&(_, _, forward)| forward).count();
+            match (group.len(), forward) {
+                (1, _) => boundary_edges += 1,
+                (2, 1) => {}
+                (2, _) => inconsistent_edges += 1,
+                _ => non_manifold_edges += 1,
+            }
+        }
+
+        (boundary_edges, non_manifold_edges, inconsistent_edges)
+    }
+}
@@ -0,0 +1,565 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use wgpu::util::DeviceExt;
+
+// Textures //
+// KTX2 and DDS images are uploaded in their block-compressed form so they stay small
+// in VRAM. Devices without BC support get BC1-BC3 decoded to RGBA8 on the CPU instead.
+// PNG and JPEG images are decoded to RGBA8 with a box-filtered mip chain.
+
+// Decoded image data, ready to upload
+pub struct TextureImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    // Mip levels from largest to smallest, tightly packed
+    pub levels: Vec<Vec<u8>>,
+    pub sampling: Sampling,
+}
+
+// How the texture is filtered and wrapped, from the glTF sampler that uses it
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sampling {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // False samples only the largest level
+    pub mipmaps: bool,
+}
+
+impl Default for Sampling {
+    // glTF's defaults for a texture without a sampler
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            mipmaps: true,
+        }
+    }
+}
+
+impl Sampling {
+    pub fn create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Material Sampler"),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_max_clamp: if self.mipmaps { 32.0 } else { 0.0 },
+            ..Default::default()
+        })
+    }
+}
+
+pub fn load_texture(path: &Path) -> Result<TextureImage, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read texture: {}", e))?;
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    decode_texture(&data, &extension)
+}
+
+// `kind` is a file extension without the dot
+pub fn decode_texture(data: &[u8], kind: &str) -> Result<TextureImage, String> {
+    match kind {
+        "ktx2" => parse_ktx2(data),
+        "dds" => parse_dds(data),
+        "png" => parse_png(data),
+        "jpg" | "jpeg" => parse_jpeg(data),
+        _ => Err("Unsupported texture format, only PNG, JPEG, KTX2 and DDS textures are supported".to_string()),
+    }
+}
+
+fn parse_ktx2(data: &[u8]) -> Result<TextureImage, String> {
+    use ktx2::{Format, SupercompressionScheme};
+
+    let reader = ktx2::Reader::new(data).map_err(|e| format!("Invalid KTX2 file: {:?}", e))?;
+    let header = reader.header();
+
+    // Basis Universal stores ETC1S as BasisLZ and UASTC without a Vulkan format
+    if header.supercompression_scheme == Some(SupercompressionScheme::BasisLZ) {
+        return Err("Basis Universal ETC1S KTX2 textures need a transcoder, which isn't supported".to_string());
+    }
+    if header.format.is_none() {
+        return Err("Basis Universal UASTC KTX2 textures need a transcoder, which isn't supported".to_string());
+    }
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        return Err("Only 2D KTX2 textures are supported".to_string());
+    }
+    if header.pixel_width == 0 {
+        return Err("KTX2 texture is empty".to_string());
+    }
+
+    let format = match header.format {
+        Some(Format::R8G8B8A8_UNORM) => wgpu::TextureFormat::Rgba8Unorm,
+        Some(Format::R8G8B8A8_SRGB) => wgpu::TextureFormat::Rgba8UnormSrgb,
+        Some(Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK) => wgpu::TextureFormat::Bc1RgbaUnorm,
+        Some(Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK) => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+        Some(Format::BC2_UNORM_BLOCK) => wgpu::TextureFormat::Bc2RgbaUnorm,
+        Some(Format::BC2_SRGB_BLOCK) => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+        Some(Format::BC3_UNORM_BLOCK) => wgpu::TextureFormat::Bc3RgbaUnorm,
+        Some(Format::BC3_SRGB_BLOCK) => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+        Some(Format::BC4_UNORM_BLOCK) => wgpu::TextureFormat::Bc4RUnorm,
+        Some(Format::BC5_UNORM_BLOCK) => wgpu::TextureFormat::Bc5RgUnorm,
+        Some(Format::BC6H_UFLOAT_BLOCK) => wgpu::TextureFormat::Bc6hRgbUfloat,
+        Some(Format::BC7_UNORM_BLOCK) => wgpu::TextureFormat::Bc7RgbaUnorm,
+        Some(Format::BC7_SRGB_BLOCK) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+        other => return Err(format!("Unsupported KTX2 pixel format {:?}", other)),
+    };
+
+    let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+    let mut levels = Vec::new();
+    for (i, level) in reader.levels().take(mip_level_limit(width, height)).enumerate() {
+        // Each level has to hold every block of its size, anything past that is ignored
+        let size = level_size(format, (width >> i).max(1), (height >> i).max(1));
+        let mut bytes = match header.supercompression_scheme {
+            None => level.data.to_vec(),
+            Some(SupercompressionScheme::Zstandard) => {
+                let mut decoded = Vec::with_capacity(size);
+                ruzstd::decoding::StreamingDecoder::new(level.data)
+                    .map_err(|e| e.to_string())
+                    .and_then(|decoder| decoder.take(size as u64).read_to_end(&mut decoded).map_err(|e| e.to_string()))
+                    .map_err(|e| format!("Failed to decompress KTX2 level: {}", e))?;
+                decoded
+            }
+            Some(other) => return Err(format!("Unsupported KTX2 supercompression {:?}", other)),
+        };
+        if bytes.len() < size {
+            return Err(format!("KTX2 file is truncated: level {} has {} of {} bytes", i, bytes.len(), size));
+        }
+        bytes.truncate(size);
+        levels.push(bytes);
+    }
+
+    Ok(TextureImage {
+        format,
+        width,
+        height,
+        levels,
+        sampling: Sampling::default(),
+    })
+}
+
+fn parse_dds(data: &[u8]) -> Result<TextureImage, String> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+
+    let dds = ddsfile::Dds::read(data).map_err(|e| format!("Invalid DDS file: {}", e))?;
+
+    let dxgi_format = dds.get_dxgi_format().and_then(|format| match format {
+        DxgiFormat::R8G8B8A8_UNorm => Some(wgpu::TextureFormat::Rgba8Unorm),
+        DxgiFormat::R8G8B8A8_UNorm_sRGB => Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+        DxgiFormat::BC1_UNorm => Some(wgpu::TextureFormat::Bc1RgbaUnorm),
+        DxgiFormat::BC1_UNorm_sRGB => Some(wgpu::TextureFormat::Bc1RgbaUnormSrgb),
+        DxgiFormat::BC2_UNorm => Some(wgpu::TextureFormat::Bc2RgbaUnorm),
+        DxgiFormat::BC2_UNorm_sRGB => Some(wgpu::TextureFormat::Bc2RgbaUnormSrgb),
+        DxgiFormat::BC3_UNorm => Some(wgpu::TextureFormat::Bc3RgbaUnorm),
+        DxgiFormat::BC3_UNorm_sRGB => Some(wgpu::TextureFormat::Bc3RgbaUnormSrgb),
+        DxgiFormat::BC4_UNorm => Some(wgpu::TextureFormat::Bc4RUnorm),
+        DxgiFormat::BC5_UNorm => Some(wgpu::TextureFormat::Bc5RgUnorm),
+        DxgiFormat::BC6H_UF16 => Some(wgpu::TextureFormat::Bc6hRgbUfloat),
+        DxgiFormat::BC7_UNorm => Some(wgpu::TextureFormat::Bc7RgbaUnorm),
+        DxgiFormat::BC7_UNorm_sRGB => Some(wgpu::TextureFormat::Bc7RgbaUnormSrgb),
+        _ => None,
+    });
+    // Older files without the DX10 header describe the format with a FourCC or bit masks
+    let d3d_format = dds.get_d3d_format().and_then(|format| match format {
+        D3DFormat::A8B8G8R8 => Some(wgpu::TextureFormat::Rgba8Unorm),
+        D3DFormat::A8R8G8B8 => Some(wgpu::TextureFormat::Bgra8Unorm),
+        D3DFormat::DXT1 => Some(wgpu::TextureFormat::Bc1RgbaUnorm),
+        D3DFormat::DXT3 => Some(wgpu::TextureFormat::Bc2RgbaUnorm),
+        D3DFormat::DXT5 => Some(wgpu::TextureFormat::Bc3RgbaUnorm),
+        _ => None,
+    });
+    let format = dxgi_format.or(d3d_format).ok_or("Unsupported DDS pixel format")?;
+
+    if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+        return Err("Only 2D DDS textures are supported".to_string());
+    }
+
+    let (width, height) = (dds.get_width(), dds.get_height());
+    if width == 0 || height == 0 {
+        return Err("DDS texture is empty".to_string());
+    }
+    let mut rest = dds.get_data(0).map_err(|e| format!("Invalid DDS data: {}", e))?;
+    let mut levels = Vec::new();
+
+    let level_count = (dds.get_num_mipmap_levels().max(1) as usize).min(mip_level_limit(width, height));
+    for level in 0..level_count {
+        let size = level_size(format, (width >> level).max(1), (height >> level).max(1));
+        if rest.len() < size {
+            return Err(format!("DDS file is truncated: level {} has {} of {} bytes", level, rest.len(), size));
+        }
+        levels.push(rest[..size].to_vec());
+        rest = &rest[size..];
+    }
+
+    Ok(TextureImage {
+        format,
+        width,
+        height,
+        levels,
+        sampling: Sampling::default(),
+    })
+}
+
+fn parse_png(data: &[u8]) -> Result<TextureImage, String> {
+    use png::{ColorType, Transformations};
+
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+    // Palettes and low bit depths expand to 8 bits per channel, 16 bits get stripped
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| format!("Invalid PNG file: {}", e))?;
+    let size = reader.output_buffer_size().ok_or("PNG image is too large")?;
+    let mut pixels = vec![0; size];
+    let info = reader.next_frame(&mut pixels).map_err(|e| format!("Invalid PNG data: {}", e))?;
+    pixels.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        ColorType::Rgba => pixels,
+        ColorType::Rgb => pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        ColorType::Grayscale => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        ColorType::Indexed => return Err("PNG palette wasn't expanded".to_string()),
+    };
+    Ok(rgba_texture(info.width, info.height, rgba))
+}
+
+fn parse_jpeg(data: &[u8]) -> Result<TextureImage, String> {
+    use jpeg_decoder::PixelFormat;
+
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let pixels = decoder.decode().map_err(|e| format!("Invalid JPEG file: {}", e))?;
+    let info = decoder.info().ok_or("JPEG file has no image")?;
+
+    let rgba = match info.pixel_format {
+        PixelFormat::RGB24 => pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        PixelFormat::L8 => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        // Big-endian samples, the high byte is enough
+        PixelFormat::L16 => pixels.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], 255]).collect(),
+        PixelFormat::CMYK32 => return Err("CMYK JPEG images aren't supported".to_string()),
+    };
+    Ok(rgba_texture(info.width as u32, info.height as u32, rgba))
+}
+
+// Color images in glTF are sRGB; smaller mips average each 2x2 block of the one above
+fn rgba_texture(width: u32, height: u32, pixels: Vec<u8>) -> TextureImage {
+    let mut levels = vec![pixels];
+    let (mut w, mut h) = (width as usize, height as usize);
+    while w > 1 || h > 1 {
+        let above = levels.last().unwrap();
+        let (next_w, next_h) = ((w / 2).max(1), (h / 2).max(1));
+        let mut level = Vec::with_capacity(next_w * next_h * 4);
+        for y in 0..next_h {
+            for x in 0..next_w {
+                let texels = [(x * 2, y * 2), (x * 2 + 1, y * 2), (x * 2, y * 2 + 1), (x * 2 + 1, y * 2 + 1)]
+                    .map(|(tx, ty)| (tx.min(w - 1) + ty.min(h - 1) * w) * 4);
+                for c in 0..4 {
+                    let sum: u32 = texels.iter().map(|&t| above[t + c] as u32).sum();
+                    level.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        levels.push(level);
+        (w, h) = (next_w, next_h);
+    }
+
+    TextureImage {
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width,
+        height,
+        levels,
+        sampling: Sampling::default(),
+    }
+}
+
+fn level_size(format: wgpu::TextureFormat, width: u32, height: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+    width.div_ceil(block_width) as usize * height.div_ceil(block_height) as usize * block_size as usize
+}
+
+// Levels down to 1x1; files that list more than that can't be uploaded as they are
+fn mip_level_limit(width: u32, height: u32) -> usize {
+    (u32::BITS - width.max(height).max(1).leading_zeros()) as usize
+}
+
+// Returns None when the device can't sample the format and it can't be decoded either.
+// Data that isn't a color (normals, occlusion, metallic-roughness) is uploaded without
+// the sRGB format, so it's read as stored.
+pub fn upload_texture(device: &wgpu::Device, queue: &wgpu::Queue, image: &TextureImage, color: bool) -> Option<wgpu::Texture> {
+    // Textures over the device's size limit start from the first mip level that fits
+    let max_size = device.limits().max_texture_dimension_2d;
+    let fits = |level: usize| (image.width >> level).max(1) <= max_size && (image.height >> level).max(1) <= max_size;
+    let Some(first_level) = (0..image.levels.len()).find(|&level| fits(level)) else {
+        eprintln!("Texture: {}x{} is larger than the device's {} limit, drawing untextured", image.width, image.height, max_size);
+        return None;
+    };
+    let smaller;
+    let image = if first_level == 0 {
+        image
+    } else {
+        smaller = TextureImage {
+            format: image.format,
+            width: (image.width >> first_level).max(1),
+            height: (image.height >> first_level).max(1),
+            levels: image.levels[first_level..].to_vec(),
+            sampling: image.sampling,
+        };
+        eprintln!("Texture: {}x{} is larger than the device's {} limit, using it at {}x{}",
+                  image.width, image.height, max_size, smaller.width, smaller.height);
+        &smaller
+    };
+
+    let (block_width, block_height) = image.format.block_dimensions();
+    let supported = device.features().contains(image.format.required_features())
+        // Compressed textures must be a whole number of blocks in size
+        && image.width.is_multiple_of(block_width)
+        && image.height.is_multiple_of(block_height);
+
+    let decoded;
+    let (format, levels) = if supported {
+        (image.format, &image.levels)
+    } else {
+        let Some((format, levels)) = decode_bc(image) else {
+            eprintln!("Texture: {:?} isn't supported on this device, drawing untextured", image.format);
+            return None;
+        };
+        decoded = levels;
+        (format, &decoded)
+    };
+
+    let format = if color { format } else { format.remove_srgb_suffix() };
+    let data: Vec<u8> = levels.concat();
+    Some(device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Material Texture"),
+            size: wgpu::Extent3d {
+                width: image.width,
+                height: image.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &data,
+    ))
+}
+
+pub fn white_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    let image = TextureImage {
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        width: 1,
+        height: 1,
+        levels: vec![vec![255; 4]],
+        sampling: Sampling::default(),
+    };
+    upload_texture(device, queue, &image, true).expect("RGBA8 is always supported")
+}
+
+// The textures of a material, in the order of the shader's bindings
+pub const MATERIAL_SLOTS: usize = 5;
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MaterialSlot {
+    BaseColor,
+    MetallicRoughness,
+    Normal,
+    Occlusion,
+    Emissive,
+}
+
+impl MaterialSlot {
+    pub const ALL: [MaterialSlot; MATERIAL_SLOTS] = [
+        MaterialSlot::BaseColor,
+        MaterialSlot::MetallicRoughness,
+        MaterialSlot::Normal,
+        MaterialSlot::Occlusion,
+        MaterialSlot::Emissive,
+    ];
+
+    // Colors are sRGB, the rest is data that has to be read as stored
+    pub fn is_color(self) -> bool {
+        matches!(self, MaterialSlot::BaseColor | MaterialSlot::Emissive)
+    }
+}
+
+// One texture and sampler pair per material slot, see MaterialSlot
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let entries: Vec<wgpu::BindGroupLayoutEntry> = (0..MATERIAL_SLOTS as u32)
+        .flat_map(|slot| {
+            [
+                wgpu::BindGroupLayoutEntry {
+                    binding: slot * 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: slot * 2 + 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]
+        })
+        .collect();
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Texture Bind Group Layout"),
+        entries: &entries,
+    })
+}
+
+pub fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, slots: [(&wgpu::TextureView, &wgpu::Sampler); MATERIAL_SLOTS]) -> wgpu::BindGroup {
+    let entries: Vec<wgpu::BindGroupEntry> = slots
+        .iter()
+        .enumerate()
+        .flat_map(|(slot, (view, sampler))| {
+            [
+                wgpu::BindGroupEntry {
+                    binding: slot as u32 * 2,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: slot as u32 * 2 + 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ]
+        })
+        .collect();
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Texture Bind Group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+// BC decoding //
+
+// Fills in the alpha channel of a decoded 4x4 block
+type AlphaDecoder = fn(&[u8], &mut [[u8; 4]; 16]);
+
+fn decode_bc(image: &TextureImage) -> Option<(wgpu::TextureFormat, Vec<Vec<u8>>)> {
+    use wgpu::TextureFormat as F;
+
+    let (srgb, block_size, alpha): (bool, usize, AlphaDecoder) = match image.format {
+        F::Bc1RgbaUnorm => (false, 8, |_, _| {}),
+        F::Bc1RgbaUnormSrgb => (true, 8, |_, _| {}),
+        F::Bc2RgbaUnorm => (false, 16, explicit_alpha),
+        F::Bc2RgbaUnormSrgb => (true, 16, explicit_alpha),
+        F::Bc3RgbaUnorm => (false, 16, interpolated_alpha),
+        F::Bc3RgbaUnormSrgb => (true, 16, interpolated_alpha),
+        // BC4-BC7 have no software decoder
+        _ => return None,
+    };
+
+    let levels = image.levels.iter().enumerate().map(|(level, data)| {
+        let width = (image.width >> level).max(1) as usize;
+        let height = (image.height >> level).max(1) as usize;
+        let blocks_wide = width.div_ceil(4);
+        let mut pixels = vec![0u8; width * height * 4];
+
+        for (i, block) in data.chunks_exact(block_size).enumerate() {
+            let (bx, by) = (i % blocks_wide * 4, i / blocks_wide * 4);
+            let mut texels = [[0u8; 4]; 16];
+            // BC2 and BC3 store alpha first, then a BC1 color block without the 1-bit alpha mode
+            let color = &block[block_size - 8..];
+            decode_color_block(color, block_size == 8, &mut texels);
+            alpha(block, &mut texels);
+
+            for (t, texel) in texels.iter().enumerate() {
+                let (x, y) = (bx + t % 4, by + t / 4);
+                if x < width && y < height {
+                    let offset = (y * width + x) * 4;
+                    pixels[offset..offset + 4].copy_from_slice(texel);
+                }
+            }
+        }
+
+        pixels
+    });
+
+    let format = if srgb { F::Rgba8UnormSrgb } else { F::Rgba8Unorm };
+    Some((format, levels.collect()))
+}
+
+fn decode_color_block(block: &[u8], allow_transparent: bool, texels: &mut [[u8; 4]; 16]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let [r0, g0, b0] = rgb565(c0);
+    let [r1, g1, b1] = rgb565(c1);
+
+    let mix = |a: u8, b: u8, wa: u32, wb: u32| ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8;
+    let palette = if c0 > c1 || !allow_transparent {
+        [
+            [r0, g0, b0, 255],
+            [r1, g1, b1, 255],
+            [mix(r0, r1, 2, 1), mix(g0, g1, 2, 1), mix(b0, b1, 2, 1), 255],
+            [mix(r0, r1, 1, 2), mix(g0, g1, 1, 2), mix(b0, b1, 1, 2), 255],
+        ]
+    } else {
+        [
+            [r0, g0, b0, 255],
+            [r1, g1, b1, 255],
+            [mix(r0, r1, 1, 1), mix(g0, g1, 1, 1), mix(b0, b1, 1, 1), 255],
+            [0, 0, 0, 0],
+        ]
+    };
+
+    let bits = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (t, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(bits >> (t * 2) & 3) as usize];
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = (color >> 11 & 31) as u32;
+    let g = (color >> 5 & 63) as u32;
+    let b = (color & 31) as u32;
+    [(r * 255 / 31) as u8, (g * 255 / 63) as u8, (b * 255 / 31) as u8]
+}
+
+// BC2: 4 bits of alpha per texel
+fn explicit_alpha(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let bits = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (t, texel) in texels.iter_mut().enumerate() {
+        texel[3] = (bits >> (t * 4) & 15) as u8 * 17;
+    }
+}
+
+// BC3: two endpoints and a 3-bit index per texel
+fn interpolated_alpha(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let palette: [u32; 8] = if a0 > a1 {
+        [a0, a1, (6 * a0 + a1) / 7, (5 * a0 + 2 * a1) / 7, (4 * a0 + 3 * a1) / 7,
+         (3 * a0 + 4 * a1) / 7, (2 * a0 + 5 * a1) / 7, (a0 + 6 * a1) / 7]
+    } else {
+        [a0, a1, (4 * a0 + a1) / 5, (3 * a0 + 2 * a1) / 5, (2 * a0 + 3 * a1) / 5,
+         (a0 + 4 * a1) / 5, 0, 255]
+    };
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let bits = u64::from_le_bytes(bits);
+    for (t, texel) in texels.iter_mut().enumerate() {
+        texel[3] = palette[(bits >> (t * 3) & 7) as usize] as u8;
+    }
+}
@@ -0,0 +1,81 @@
+use serde::Serialize;
+
+use crate::repair;
+
+// Topology diagnostics //
+// Counts of the mesh's vertices, edges and faces, and of the things in its connectivity
+// that trip up tools further down the line: edges shared by more than two triangles,
+// vertices repeated at the same position, triangles without area and vertices no
+// triangle uses. Shown in the panel and emitted as JSON by `rsview --analyze`.
+
+#[derive(Debug, Serialize)]
+pub struct TopologyReport {
+    pub vertices: usize,
+    // Distinct edges between vertex positions, so seams split for UVs or normals count once
+    pub edges: usize,
+    pub faces: usize,
+    pub non_manifold_edges: usize,
+    // Vertices at exactly the position of an earlier one; glTF splits vertices along
+    // UV and normal seams, so these aren't always mistakes
+    pub duplicate_vertices: usize,
+    pub degenerate_triangles: usize,
+    pub unreferenced_vertices: usize,
+}
+
+impl TopologyReport {
+    // None when an index is out of range, which validation reports instead
+    pub fn analyze(vertices: &[[f32; 3]], indices: &[u32]) -> Option<Self> {
+        let indices = &indices[..indices.len() / 3 * 3];
+        if indices.iter().any(|&i| i as usize >= vertices.len()) {
+            return None;
+        }
+
+        let welded = repair::weld_positions(vertices);
+        let positions = welded.iter().map(|&id| id as usize + 1).max().unwrap_or(0);
+
+        let (mut edges, mut non_manifold_edges) = (0, 0);
+        for group in repair::sorted_edges(&welded, indices).chunk_by(|x, y| x.0 == y.0) {
+            edges += 1;
+            if group.len() > 2 {
+                non_manifold_edges += 1;
+            }
+        }
+
+        let mut referenced = vec![false; vertices.len()];
+        for &i in indices {
+            referenced[i as usize] = true;
+        }
+
+        Some(Self {
+            vertices: vertices.len(),
+            edges,
+            faces: indices.len() / 3,
+            non_manifold_edges,
+            duplicate_vertices: vertices.len() - positions,
+            degenerate_triangles: indices
+                .chunks_exact(3)
+                .filter(|t| repair::is_degenerate(vertices[t[0] as usize], vertices[t[1] as usize], vertices[t[2] as usize]))
+                .count(),
+            unreferenced_vertices: referenced.iter().filter(|&&used| !used).count(),
+        })
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.non_manifold_edges == 0 && self.duplicate_vertices == 0 && self.degenerate_triangles == 0 && self.unreferenced_vertices == 0
+    }
+
+    // The problems found, or "clean"
+    pub fn summary(&self) -> String {
+        let problems: Vec<String> = [
+            (self.non_manifold_edges, "non-manifold edges"),
+            (self.duplicate_vertices, "duplicate vertices"),
+            (self.degenerate_triangles, "degenerate triangles"),
+            (self.unreferenced_vertices, "unreferenced vertices"),
+        ]
+        .into_iter()
+        .filter(|&(count, _)| count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+        if problems.is_empty() { "clean".to_string() } else { problems.join(", ") }
+    }
+}
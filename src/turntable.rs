@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use crate::error::Error;
+use crate::recording::FRAMES_PER_SECOND;
+
+// Turntable export //
+// One full turn of the model written as a video for sharing previews. MP4 and GIF are
+// encoded by piping raw RGBA frames into ffmpeg; an animated PNG (.png or .apng) is
+// written here with the png crate, so it works without ffmpeg and keeps the transparent
+// background.
+
+pub enum TurntableWriter {
+    Ffmpeg(Child),
+    Apng(Box<png::Writer<BufWriter<File>>>),
+}
+
+impl TurntableWriter {
+    pub fn start(path: &Path, width: u32, height: u32, frames: u32) -> Result<Self, Error> {
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "png" | "apng" => Self::start_apng(path, width, height, frames),
+            "mp4" | "gif" => Self::start_ffmpeg(path, width, height, &extension),
+            _ => Err(Error::Io {
+                context: format!("Can't write a turntable to {}", path.display()),
+                source: io::Error::other("use .mp4, .gif, .png or .apng"),
+            }),
+        }
+    }
+
+    fn start_ffmpeg(path: &Path, width: u32, height: u32, extension: &str) -> Result<Self, Error> {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height), "-framerate", &FRAMES_PER_SECOND.to_string(), "-i", "-"]);
+        if extension == "gif" {
+            // A palette made for these frames instead of a generic one, looping forever
+            command.args(["-vf", "split[a][b];[a]palettegen[palette];[b][palette]paletteuse", "-loop", "0"]);
+        } else {
+            // yuv420p needs even dimensions
+            command.args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-c:v", "libx264", "-pix_fmt", "yuv420p"]);
+        }
+        let child = command
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(Error::io("Failed to start ffmpeg, a .png turntable doesn't need it"))?;
+        Ok(TurntableWriter::Ffmpeg(child))
+    }
+
+    fn start_apng(path: &Path, width: u32, height: u32, frames: u32) -> Result<Self, Error> {
+        let context = format!("Failed to write {}", path.display());
+        let file = File::create(path).map_err(Error::io(format!("Failed to create {}", path.display())))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let writer = encoder
+            .set_animated(frames, 0)
+            .and_then(|_| encoder.set_frame_delay(1, FRAMES_PER_SECOND as u16))
+            .and_then(|_| encoder.write_header())
+            .map_err(io::Error::other)
+            .map_err(Error::io(context))?;
+        Ok(TurntableWriter::Apng(Box::new(writer)))
+    }
+
+    // RGBA rows at the size the writer was started with
+    pub fn push(&mut self, frame: &[u8]) -> Result<(), Error> {
+        match self {
+            TurntableWriter::Ffmpeg(child) => child
+                .stdin
+                .as_mut()
+                .ok_or_else(|| io::Error::other("no stdin"))
+                .and_then(|stdin| stdin.write_all(frame))
+                .map_err(Error::io("ffmpeg stopped taking frames")),
+            // One whole frame per call, so each gets its own fcTL and data chunks
+            TurntableWriter::Apng(writer) => writer
+                .write_image_data(frame)
+                .map_err(io::Error::other)
+                .map_err(Error::io("Failed to write a turntable frame")),
+        }
+    }
+
+    // Waits for ffmpeg to write the file, or ends the PNG
+    pub fn finish(self) -> Result<(), Error> {
+        match self {
+            TurntableWriter::Ffmpeg(mut child) => {
+                // Closing stdin lets ffmpeg finish the file
+                drop(child.stdin.take());
+                match child.wait().map_err(Error::io("ffmpeg failed"))? {
+                    status if status.success() => Ok(()),
+                    status => Err(Error::Io { context: "ffmpeg failed".into(), source: io::Error::other(status.to_string()) }),
+                }
+            }
+            TurntableWriter::Apng(writer) => writer
+                .finish()
+                .map_err(io::Error::other)
+                .map_err(Error::io("Failed to finish the turntable")),
+        }
+    }
+}
@@ -0,0 +1,609 @@
+use std::time::Instant;
+
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+
+use crate::background::Background;
+use crate::clip::ClipPlane;
+use crate::debug_view::DebugView;
+use crate::normals::NormalDisplay;
+use crate::views::ViewPreset;
+
+// Overlay panel //
+// An egui window over the scene with what's loaded and where the camera is. egui-winit
+// only supports winit 0.30 with the wgpu version used here, so the few window events
+// the panel needs are translated by hand; it's drawn in its own pass after the scene.
+// The orientation gizmo in the top right corner is part of it too, so it hides with the
+// panel and never shows up in screenshots.
+
+// Weight of the newest frame in the smoothed frame rate
+const FPS_SMOOTHING: f32 = 0.05;
+// Parts end up this many times as far from the middle as they were, plus one
+const EXPLODE_MAX: f32 = 2.0;
+// Largest point cloud point diameter in pixels
+const POINT_SIZE_MAX: f32 = 16.0;
+// Normal segment length as a fraction of the model's size
+const NORMAL_LENGTH_RANGE: std::ops::RangeInclusive<f32> = 0.002..=0.2;
+// Tallest the outliner gets before it scrolls, in points
+const OUTLINER_HEIGHT: f32 = 240.0;
+// Side of the orientation gizmo and how far its axis ends reach from its middle, in points
+const GIZMO_SIZE: f32 = 96.0;
+const GIZMO_REACH: f32 = 34.0;
+const GIZMO_HANDLE_RADIUS: f32 = 8.0;
+const AXIS_COLORS: [egui::Color32; 3] = [
+    egui::Color32::from_rgb(230, 70, 70),
+    egui::Color32::from_rgb(90, 200, 90),
+    egui::Color32::from_rgb(80, 120, 255),
+];
+
+pub struct OverlayStats {
+    pub file: String,
+    pub vertices: usize,
+    pub indices: usize,
+    // Parts outside the view and not drawn, for models with more than one part
+    pub culled_parts: Option<usize>,
+    // The level of detail drawn and its triangle count, for meshes that have levels
+    pub lod: Option<String>,
+    // Copies drawn with --grid and their total triangle count
+    pub grid: Option<String>,
+    // What's being loaded, while the file is still being parsed
+    pub loading: Option<String>,
+    // Average CPU time of the recent frames in milliseconds
+    pub frame_time: f32,
+    // Smoothed GPU time of the scene, when the GPU has timestamp queries
+    pub gpu_time: Option<f32>,
+    pub camera_distance: f32,
+    pub rotation: f32,
+    pub pitch: f32,
+    pub orbit_center: [f32; 3],
+    pub auto_rotate: bool,
+    // File names of the recent models, newest first
+    pub recent: Vec<String>,
+    pub animation: Option<AnimationStats>,
+    pub background: Background,
+    pub clip_plane: ClipPlane,
+    pub normal_display: NormalDisplay,
+    pub debug_view: DebugView,
+    // How far apart the parts are pushed, for models with more than one
+    pub explode: Option<f32>,
+    // Diameter of the points in pixels, for point clouds
+    pub point_size: Option<f32>,
+    // Name of each part in file order and whether it's drawn, for models with more than one
+    pub parts: Vec<(String, bool)>,
+    // From the topology pass once it has run on the file's mesh
+    pub edges: Option<usize>,
+    pub topology: Option<String>,
+    // Width x height x depth of the bounding box while it's shown
+    pub bounds: Option<String>,
+    // The triangle picked with a left click
+    pub selection: Option<SelectionStats>,
+    // Between the last two measurement picks, with the unit
+    pub distance: Option<String>,
+    // The model's X, Y and Z axes in view space: right, up and toward the viewer
+    pub axes: [[f32; 3]; 3],
+}
+
+// The animation the model is playing, for the play button and the scrub bar
+pub struct AnimationStats {
+    pub name: String,
+    pub time: f32,
+    pub duration: f32,
+    pub playing: bool,
+}
+
+// Where the pick ray hit the model
+pub struct SelectionStats {
+    pub triangle: u32,
+    // Name of the part the triangle belongs to, for models with more than one
+    pub part: Option<String>,
+    pub vertex: u32,
+    pub barycentric: [f32; 2],
+    // In file coordinates, with the unit
+    pub point: String,
+}
+
+// What the panel's controls asked for this frame, applied by the viewer
+#[derive(Default)]
+pub struct PanelActions {
+    pub toggle_playback: bool,
+    // Time the scrub bar was dragged to
+    pub seek: Option<f32>,
+    // Picked with the background color buttons
+    pub background: Option<Background>,
+    // Dragged to with the explode slider
+    pub explode: Option<f32>,
+    // Dragged to with the point size slider
+    pub point_size: Option<f32>,
+    // A part whose checkbox was clicked in the outliner, and one whose name was
+    pub toggle_part: Option<usize>,
+    pub focus_part: Option<usize>,
+    // Changed with the clipping controls
+    pub clip_plane: Option<ClipPlane>,
+    // Changed with the normals controls
+    pub normal_display: Option<NormalDisplay>,
+    // Picked in the debug view list
+    pub debug_view: Option<DebugView>,
+    // An axis end clicked on the orientation gizmo
+    pub snap_view: Option<ViewPreset>,
+}
+
+pub struct Overlay {
+    context: egui::Context,
+    renderer: egui_wgpu::Renderer,
+    events: Vec<egui::Event>,
+    pointer: egui::Pos2,
+    pixels_per_point: f32,
+    start: Instant,
+    last_frame: Option<Instant>,
+    fps: f32,
+    actions: PanelActions,
+    pub visible: bool,
+}
+
+// Tessellated panel for one frame, drawn with Overlay::draw
+pub struct OverlayFrame {
+    primitives: Vec<egui::ClippedPrimitive>,
+    screen: egui_wgpu::ScreenDescriptor,
+    free: Vec<egui::TextureId>,
+}
+
+impl Overlay {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, pixels_per_point: f32) -> Self {
+        Self {
+            context: egui::Context::default(),
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+            events: Vec::new(),
+            pointer: egui::Pos2::ZERO,
+            pixels_per_point,
+            start: Instant::now(),
+            last_frame: None,
+            fps: 0.0,
+            actions: PanelActions::default(),
+            visible: true,
+        }
+    }
+
+    // Returns true when the panel took the event, so the viewer shouldn't act on it too
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        if !self.visible {
+            return false;
+        }
+
+        match event {
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.pixels_per_point = *scale_factor as f32;
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer = egui::pos2(position.x as f32, position.y as f32) / self.pixels_per_point;
+                self.events.push(egui::Event::PointerMoved(self.pointer));
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.events.push(egui::Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    _ => return false,
+                };
+                self.events.push(egui::Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: egui::Modifiers::NONE,
+                });
+                self.context.is_pointer_over_area()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (egui::MouseWheelUnit::Line, egui::vec2(*x, *y)),
+                    MouseScrollDelta::PixelDelta(p) => (egui::MouseWheelUnit::Point, egui::vec2(p.x as f32, p.y as f32) / self.pixels_per_point),
+                };
+                self.events.push(egui::Event::MouseWheel { unit, delta, modifiers: egui::Modifiers::NONE });
+                self.context.is_pointer_over_area()
+            }
+            _ => false,
+        }
+    }
+
+    // Lays out the panel and uploads what it needs; None while hidden
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        size: PhysicalSize<u32>,
+        stats: &OverlayStats,
+    ) -> Option<OverlayFrame> {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame.replace(now) {
+            let fps = 1.0 / (now - last).as_secs_f32().max(f32::EPSILON);
+            self.fps = if self.fps == 0.0 { fps } else { self.fps + (fps - self.fps) * FPS_SMOOTHING };
+        }
+        // The loading indicator shows even with the panel hidden
+        if !self.visible && stats.loading.is_none() {
+            self.events.clear();
+            return None;
+        }
+
+        let mut input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(size.width as f32, size.height as f32) / self.pixels_per_point,
+            )),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(self.pixels_per_point);
+
+        let fps = self.fps;
+        let visible = self.visible;
+        let mut actions = PanelActions::default();
+        let output = self.context.run(input, |context| {
+            if let Some(loading) = &stats.loading {
+                loading_indicator(context, loading);
+            }
+            if !visible {
+                return;
+            }
+            egui::Window::new("Model")
+                .default_pos(egui::pos2(10.0, 10.0))
+                .resizable(false)
+                .show(context, |ui| {
+                    stats_grid(ui, stats, fps);
+                    if let Some(animation) = &stats.animation {
+                        animation_controls(ui, animation, &mut actions);
+                    }
+                    background_controls(ui, stats.background, &mut actions);
+                    if let Some(explode) = stats.explode {
+                        explode_slider(ui, explode, &mut actions);
+                    }
+                    if let Some(point_size) = stats.point_size {
+                        point_size_slider(ui, point_size, &mut actions);
+                    }
+                    clip_controls(ui, stats.clip_plane, &mut actions);
+                    normal_controls(ui, stats.normal_display, &mut actions);
+                    debug_view_controls(ui, stats.debug_view, &mut actions);
+                    outliner(ui, &stats.parts, &mut actions);
+                    recent_list(ui, &stats.recent);
+                });
+            orientation_gizmo(context, stats.axes, &mut actions);
+        });
+        self.actions = actions;
+
+        let primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [size.width, size.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        // Callback command buffers only come from paint callbacks, which the panel doesn't use
+        let _ = self.renderer.update_buffers(device, queue, encoder, &primitives, &screen);
+
+        Some(OverlayFrame {
+            primitives,
+            screen,
+            free: output.textures_delta.free,
+        })
+    }
+
+    // The controls used in the last prepared frame, once
+    pub fn take_actions(&mut self) -> PanelActions {
+        std::mem::take(&mut self.actions)
+    }
+
+    // Draws over whatever is already in `view`
+    pub fn draw(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, frame: OverlayFrame) {
+        let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.renderer.render(&mut render_pass.forget_lifetime(), &frame.primitives, &frame.screen);
+
+        for id in &frame.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+fn stats_grid(ui: &mut egui::Ui, stats: &OverlayStats, fps: f32) {
+    egui::Grid::new("model_stats").num_columns(2).show(ui, |ui| {
+        let [x, y, z] = stats.orbit_center;
+        let rows = [
+            ("File", stats.file.clone()),
+            ("Vertices", stats.vertices.to_string()),
+            ("Indices", stats.indices.to_string()),
+            ("Triangles", (stats.indices / 3).to_string()),
+            ("FPS", format!("{:.0}", fps)),
+            ("CPU frame", format!("{:.2} ms", stats.frame_time)),
+            ("GPU frame", stats.gpu_time.map_or_else(|| "unavailable".to_string(), |ms| format!("{:.2} ms", ms))),
+            ("Camera distance", format!("{:.2}", stats.camera_distance)),
+            ("Rotation", format!("{:.1} deg{}", stats.rotation.to_degrees().rem_euclid(360.0), if stats.auto_rotate { " (auto)" } else { "" })),
+            ("Pitch", format!("{:.1} deg", stats.pitch.to_degrees())),
+            ("Orbit center", format!("({:.3}, {:.3}, {:.3})", x, y, z)),
+        ];
+        for (label, value) in rows {
+            ui.label(label);
+            ui.label(value);
+            ui.end_row();
+        }
+        if let (Some(edges), Some(topology)) = (stats.edges, &stats.topology) {
+            ui.label("Edges");
+            ui.label(edges.to_string());
+            ui.end_row();
+            ui.label("Topology");
+            ui.label(topology);
+            ui.end_row();
+        }
+        if let Some(culled) = stats.culled_parts {
+            ui.label("Culled parts");
+            ui.label(format!("{} of {}", culled, stats.parts.len()));
+            ui.end_row();
+        }
+        if let Some(lod) = &stats.lod {
+            ui.label("LOD");
+            ui.label(lod);
+            ui.end_row();
+        }
+        if let Some(grid) = &stats.grid {
+            ui.label("Grid");
+            ui.label(grid);
+            ui.end_row();
+        }
+        if let Some(bounds) = &stats.bounds {
+            ui.label("Bounds");
+            ui.label(bounds);
+            ui.end_row();
+        }
+        if let Some(distance) = &stats.distance {
+            ui.label("Distance");
+            ui.label(distance);
+            ui.end_row();
+        }
+        if let Some(selection) = &stats.selection {
+            let [u, v] = selection.barycentric;
+            let rows = [
+                ("Picked triangle", match &selection.part {
+                    Some(part) => format!("{} in {}", selection.triangle, part),
+                    None => selection.triangle.to_string(),
+                }),
+                ("Nearest vertex", selection.vertex.to_string()),
+                ("Barycentric", format!("({:.3}, {:.3})", u, v)),
+                ("Hit point", selection.point.clone()),
+            ];
+            for (label, value) in rows {
+                ui.label(label);
+                ui.label(value);
+                ui.end_row();
+            }
+        }
+    });
+}
+
+// Play/pause and a scrub bar over the clip; dragging the bar pauses playback
+fn animation_controls(ui: &mut egui::Ui, animation: &AnimationStats, actions: &mut PanelActions) {
+    ui.separator();
+    ui.label(&animation.name);
+    ui.horizontal(|ui| {
+        if ui.button(if animation.playing { "Pause" } else { "Play" }).clicked() {
+            actions.toggle_playback = true;
+        }
+        let mut time = animation.time;
+        let slider = egui::Slider::new(&mut time, 0.0..=animation.duration).suffix(" s").fixed_decimals(2);
+        if ui.add(slider).changed() {
+            actions.seek = Some(time);
+        }
+    });
+}
+
+// One color, or the top and bottom of a gradient
+fn background_controls(ui: &mut egui::Ui, background: Background, actions: &mut PanelActions) {
+    ui.collapsing("Background", |ui| {
+        let mut changed = background;
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgb(&mut changed.top);
+            if let Some(bottom) = &mut changed.bottom {
+                ui.color_edit_button_srgb(bottom);
+            }
+            let mut gradient = changed.bottom.is_some();
+            if ui.checkbox(&mut gradient, "Gradient").changed() {
+                changed = changed.with_gradient(gradient);
+            }
+        });
+        if changed != background {
+            actions.background = Some(changed);
+        }
+    });
+}
+
+// Pushes the parts away from the model's middle to see how an assembly fits together
+fn explode_slider(ui: &mut egui::Ui, explode: f32, actions: &mut PanelActions) {
+    ui.separator();
+    let mut changed = explode;
+    if ui.add(egui::Slider::new(&mut changed, 0.0..=EXPLODE_MAX).text("Explode")).changed() {
+        actions.explode = Some(changed);
+    }
+}
+
+// Sparse clouds read better with bigger points, dense ones with smaller
+fn point_size_slider(ui: &mut egui::Ui, point_size: f32, actions: &mut PanelActions) {
+    ui.separator();
+    let mut changed = point_size;
+    if ui.add(egui::Slider::new(&mut changed, 1.0..=POINT_SIZE_MAX).text("Point size")).changed() {
+        actions.point_size = Some(changed);
+    }
+}
+
+// The plane's direction and where it crosses the model
+fn clip_controls(ui: &mut egui::Ui, clip_plane: ClipPlane, actions: &mut PanelActions) {
+    ui.collapsing("Clipping", |ui| {
+        let mut changed = clip_plane;
+        ui.checkbox(&mut changed.enabled, "Cut the model open");
+        ui.add_enabled_ui(changed.enabled, |ui| {
+            ui.add(egui::Slider::new(&mut changed.angle, -180.0..=180.0).text("Angle").suffix("°"));
+            ui.add(egui::Slider::new(&mut changed.tilt, -90.0..=90.0).text("Tilt").suffix("°"));
+            ui.add(egui::Slider::new(&mut changed.offset, -1.0..=1.0).text("Offset"));
+        });
+        if changed != clip_plane {
+            actions.clip_plane = Some(changed);
+        }
+    });
+}
+
+// Which normals are drawn and how long
+fn normal_controls(ui: &mut egui::Ui, display: NormalDisplay, actions: &mut PanelActions) {
+    ui.collapsing("Normals", |ui| {
+        let mut changed = display;
+        ui.checkbox(&mut changed.vertex, "Vertex normals");
+        ui.checkbox(&mut changed.face, "Face normals");
+        ui.add_enabled_ui(changed.is_shown(), |ui| {
+            ui.add(egui::Slider::new(&mut changed.length, NORMAL_LENGTH_RANGE).logarithmic(true).text("Length"));
+        });
+        if changed != display {
+            actions.normal_display = Some(changed);
+        }
+    });
+}
+
+// Shading the model with the data behind it instead of its materials
+fn debug_view_controls(ui: &mut egui::Ui, view: DebugView, actions: &mut PanelActions) {
+    ui.collapsing("Debug view", |ui| {
+        for option in DebugView::ALL {
+            if ui.radio(view == option, option.name()).clicked() && option != view {
+                actions.debug_view = Some(option);
+            }
+        }
+    });
+}
+
+// The parts as a tree, consecutive ones sharing a name (a glTF mesh's primitives)
+// grouped under it; the checkboxes show and hide parts, the names frame them
+fn outliner(ui: &mut egui::Ui, parts: &[(String, bool)], actions: &mut PanelActions) {
+    if parts.len() < 2 {
+        return;
+    }
+
+    let part_row = |ui: &mut egui::Ui, part: usize, label: &str, actions: &mut PanelActions| {
+        ui.horizontal(|ui| {
+            let mut visible = parts[part].1;
+            if ui.checkbox(&mut visible, "").changed() {
+                actions.toggle_part = Some(part);
+            }
+            if ui.selectable_label(false, label).on_hover_text("Frame this part").clicked() {
+                actions.focus_part = Some(part);
+            }
+        });
+    };
+    ui.collapsing("Outliner", |ui| {
+        egui::ScrollArea::vertical().max_height(OUTLINER_HEIGHT).show(ui, |ui| {
+            for group in (0..parts.len()).collect::<Vec<_>>().chunk_by(|&a, &b| parts[a].0 == parts[b].0) {
+                let name = &parts[group[0]].0;
+                if let [part] = group {
+                    part_row(ui, *part, name, actions);
+                    continue;
+                }
+                egui::CollapsingHeader::new(format!("{} ({})", name, group.len())).id_salt(("outliner", group[0])).show(ui, |ui| {
+                    for (i, &part) in group.iter().enumerate() {
+                        part_row(ui, part, &format!("primitive {}", i + 1), actions);
+                    }
+                });
+            }
+        });
+    });
+}
+
+// Numbered like the keys that open them
+fn recent_list(ui: &mut egui::Ui, recent: &[String]) {
+    if recent.is_empty() {
+        return;
+    }
+
+    ui.collapsing("Recent", |ui| {
+        for (i, name) in recent.iter().enumerate() {
+            ui.label(format!("{}  {}", i + 1, name));
+        }
+    });
+}
+
+// A spinner in the middle of the window until the model's geometry has arrived
+fn loading_indicator(context: &egui::Context, text: &str) {
+    egui::Area::new(egui::Id::new("loading_indicator"))
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .interactable(false)
+        .show(context, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(text);
+                });
+            });
+        });
+}
+
+// An axis triad turning with the view; clicking an axis end looks along it at the model
+fn orientation_gizmo(context: &egui::Context, axes: [[f32; 3]; 3], actions: &mut PanelActions) {
+    egui::Area::new(egui::Id::new("orientation_gizmo"))
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 10.0))
+        .show(context, |ui| {
+            let (rect, response) = ui.allocate_exact_size(egui::vec2(GIZMO_SIZE, GIZMO_SIZE), egui::Sense::click());
+            let painter = ui.painter_at(rect);
+            let center = rect.center();
+            painter.circle_filled(center, GIZMO_SIZE * 0.5, egui::Color32::from_black_alpha(60));
+
+            // Both ends of every axis, the far ones first so the near ones cover them
+            let mut ends: Vec<(usize, bool, egui::Pos2, f32)> = (0..6)
+                .map(|i| {
+                    let (axis, positive) = (i / 2, i % 2 == 0);
+                    let sign = if positive { 1.0 } else { -1.0 };
+                    let [x, y, z] = axes[axis].map(|c| c * sign);
+                    (axis, positive, center + egui::vec2(x, -y) * GIZMO_REACH, z)
+                })
+                .collect();
+            ends.sort_by(|a, b| a.3.total_cmp(&b.3));
+
+            let pointer = response.hover_pos();
+            let hovered = pointer.and_then(|pointer| {
+                ends.iter().rev().find(|end| end.2.distance(pointer) <= GIZMO_HANDLE_RADIUS).map(|end| (end.0, end.1))
+            });
+            for &(axis, positive, position, _) in &ends {
+                let color = AXIS_COLORS[axis];
+                let radius = if hovered == Some((axis, positive)) { GIZMO_HANDLE_RADIUS + 1.5 } else { GIZMO_HANDLE_RADIUS };
+                if positive {
+                    painter.line_segment([center, position], egui::Stroke::new(2.0, color));
+                    painter.circle_filled(position, radius, color);
+                    painter.text(position, egui::Align2::CENTER_CENTER, ["X", "Y", "Z"][axis], egui::FontId::proportional(11.0), egui::Color32::BLACK);
+                } else {
+                    painter.circle(position, radius * 0.8, color.gamma_multiply(0.35), egui::Stroke::new(1.5, color));
+                }
+            }
+
+            if response.clicked() && let Some((axis, positive)) = hovered {
+                // Clicking +X looks from +X at the model, and so on
+                actions.snap_view = Some(match (axis, positive) {
+                    (0, true) => ViewPreset::Right,
+                    (0, false) => ViewPreset::Left,
+                    (1, true) => ViewPreset::Top,
+                    (1, false) => ViewPreset::Bottom,
+                    (2, true) => ViewPreset::Front,
+                    _ => ViewPreset::Back,
+                });
+            }
+        });
+}
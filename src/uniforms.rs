@@ -0,0 +1,95 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+use wgpu::util::StagingBelt;
+
+use crate::upload::DynamicBuffer;
+
+// Per-object uniforms //
+// Every object's uniforms live in one buffer at aligned slots, and a single bind
+// group with a dynamic offset selects the slot at draw time, so adding objects
+// doesn't add bind groups or buffers.
+
+pub struct ObjectUniforms<T: Pod> {
+    buffer: DynamicBuffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    // Bytes between slots, the object size rounded up to the device's offset alignment
+    stride: wgpu::BufferAddress,
+    // Size of the buffer the bind group was created for
+    bound_size: wgpu::BufferAddress,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> ObjectUniforms<T> {
+    pub fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        let size = std::mem::size_of::<T>() as wgpu::BufferAddress;
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let stride = size.div_ceil(alignment) * alignment;
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // The fragment stage reads the light and camera for shading
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(size),
+                },
+                count: None,
+            }],
+        });
+
+        let buffer = DynamicBuffer::new(device, label, wgpu::BufferUsages::UNIFORM, stride);
+        let bind_group = Self::create_bind_group(device, &layout, &buffer.buffer);
+        let bound_size = buffer.buffer.size();
+
+        Self {
+            buffer,
+            layout,
+            bind_group,
+            stride,
+            bound_size,
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Object Uniforms Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<T>() as wgpu::BufferAddress),
+                }),
+            }],
+        })
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    // Writes the uniforms of object `index`, growing the buffer when needed
+    pub fn write(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, index: u32, uniforms: &T) {
+        let offset = index as wgpu::BufferAddress * self.stride;
+        self.buffer.write(device, encoder, belt, offset, bytemuck::bytes_of(uniforms));
+
+        // Growing replaces the buffer, and the bind group still points at the old one
+        if self.buffer.buffer.size() != self.bound_size {
+            self.bind_group = Self::create_bind_group(device, &self.layout, &self.buffer.buffer);
+            self.bound_size = self.buffer.buffer.size();
+        }
+    }
+
+    // Binds the uniforms of object `index`
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass<'_>, group: u32, index: u32) {
+        let offset = index as wgpu::BufferAddress * self.stride;
+        render_pass.set_bind_group(group, &self.bind_group, &[offset as wgpu::DynamicOffset]);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+// Units //
+// OBJ and most scan formats carry no units, so the user says what one model unit is
+// (after --unit-scale) and every readout is labelled with it.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    // Nothing was specified, values are shown bare
+    #[default]
+    Model,
+    Millimeter,
+    Centimeter,
+    Meter,
+    Inch,
+}
+
+impl Unit {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mm" => Some(Unit::Millimeter),
+            "cm" => Some(Unit::Centimeter),
+            "m" => Some(Unit::Meter),
+            "in" => Some(Unit::Inch),
+            _ => None,
+        }
+    }
+
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Unit::Model => "",
+            Unit::Millimeter => " mm",
+            Unit::Centimeter => " cm",
+            Unit::Meter => " m",
+            Unit::Inch => " in",
+        }
+    }
+
+    pub fn length(self, value: f64) -> String {
+        format!("{:.4}{}", value, self.suffix())
+    }
+
+    pub fn area(self, value: f64) -> String {
+        match self {
+            Unit::Model => format!("{:.4}", value),
+            _ => format!("{:.4}{}²", value, self.suffix()),
+        }
+    }
+
+    pub fn volume(self, value: f64) -> String {
+        match self {
+            Unit::Model => format!("{:.4}", value),
+            _ => format!("{:.4}{}³", value, self.suffix()),
+        }
+    }
+
+    // Width x height x depth, with the unit once at the end
+    pub fn size(self, size: [f64; 3]) -> String {
+        format!("{:.4} x {:.4} x {:.4}{}", size[0], size[1], size[2], self.suffix())
+    }
+}
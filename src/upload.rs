@@ -0,0 +1,94 @@
+use wgpu::util::StagingBelt;
+
+// GPU uploads //
+// All buffer writes go through one staging belt so repeated uploads reuse the same
+// mapped memory instead of allocating fresh staging buffers every time.
+
+pub const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 4 * 1024 * 1024;
+
+// A buffer that is reused across uploads and only reallocated when it has to grow
+pub struct DynamicBuffer {
+    pub buffer: wgpu::Buffer,
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    capacity: wgpu::BufferAddress,
+}
+
+impl DynamicBuffer {
+    pub fn new(device: &wgpu::Device, label: &'static str, usage: wgpu::BufferUsages, capacity: wgpu::BufferAddress) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let capacity = align(capacity.max(wgpu::COPY_BUFFER_ALIGNMENT));
+
+        Self {
+            buffer: Self::allocate(device, label, usage, capacity),
+            label,
+            usage,
+            capacity,
+        }
+    }
+
+    fn allocate(device: &wgpu::Device, label: &'static str, usage: wgpu::BufferUsages, size: wgpu::BufferAddress) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Grows the buffer to hold at least `size` bytes, copying over the first `keep` bytes
+    pub fn reserve(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, size: wgpu::BufferAddress, keep: wgpu::BufferAddress) {
+        if size <= self.capacity {
+            return;
+        }
+
+        // Grow geometrically so a stream of appends doesn't reallocate every time
+        let capacity = align(size.max(self.capacity * 2));
+        let buffer = Self::allocate(device, self.label, self.usage, capacity);
+
+        let keep = align(keep.min(self.capacity));
+        if keep > 0 {
+            encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, keep);
+        }
+
+        self.buffer = buffer;
+        self.capacity = capacity;
+    }
+
+    // Writes `data` at `offset`, keeping whatever was already stored before it
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut StagingBelt,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        self.reserve(device, encoder, offset + data.len() as wgpu::BufferAddress, offset);
+        write_buffer(device, encoder, belt, &self.buffer, offset, data);
+    }
+}
+
+// Copies data into a buffer through the belt, split so no single write exceeds a belt chunk
+pub fn write_buffer(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    belt: &mut StagingBelt,
+    buffer: &wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    data: &[u8],
+) {
+    for (i, piece) in data.chunks(STAGING_CHUNK_SIZE as usize).enumerate() {
+        let Some(size) = wgpu::BufferSize::new(piece.len() as wgpu::BufferAddress) else {
+            continue;
+        };
+
+        let piece_offset = offset + i as wgpu::BufferAddress * STAGING_CHUNK_SIZE;
+        belt.write_buffer(encoder, buffer, piece_offset, size, device)
+            .copy_from_slice(piece);
+    }
+}
+
+fn align(size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    size.div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT
+}
@@ -0,0 +1,543 @@
+use cgmath::{Deg, Matrix4, One, SquareMatrix, Vector3};
+use flate2::read::DeflateDecoder;
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::batch::DEFAULT_COLOR;
+use crate::error::Error;
+use crate::parse::{map_file, transform_vertices, Material, Mesh, SubMesh};
+
+// USD parser //
+// Text USD layers (.usda) and USDZ packages with a text root layer, as exported for AR
+// previews. Mesh prims give their points and faces, placed by the xformOps of the prims
+// above them and their own; everything else in the layer (materials, references,
+// variants, time samples) is read past. Binary crate layers (.usdc) aren't supported.
+
+const CRATE_MAGIC: &[u8] = b"PXR-USDC";
+// Far deeper than any real scene hierarchy or value
+const MAX_NESTING: usize = 256;
+// Zip record signatures
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+pub fn parse_usd(file_path: &str) -> Result<Mesh, Error> {
+    let mmap = map_file(file_path)?;
+    let data = mmap.as_deref().unwrap_or(&[]);
+    let layer = if data.starts_with(&LOCAL_HEADER.to_le_bytes()) {
+        let layer = root_layer(data).map_err(Error::UsdParse)?;
+        // What Reality Converter and usdzip write, so most AR assets end up here
+        if layer.starts_with(CRATE_MAGIC) {
+            return Err(Error::UsdParse("This USDZ package's root layer is binary USD (usdc), which isn't supported; only packages with a usda text root layer load. Unpack it and convert the layer with usdcat".into()));
+        }
+        layer
+    } else {
+        data.to_vec()
+    };
+    parse_usda(&layer).map_err(Error::UsdParse)
+}
+
+// USDZ packages are zip archives whose first USD file is the one to open. Files are
+// meant to be stored uncompressed, but deflated ones are inflated anyway.
+fn root_layer(data: &[u8]) -> Result<Vec<u8>, String> {
+    let u16_at = |at: usize| data.get(at..at + 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()) as usize).ok_or("USDZ archive is truncated");
+    let u32_at = |at: usize| data.get(at..at + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).ok_or("USDZ archive is truncated");
+
+    // The end record sits in the last 64 KB, after a comment of unknown length
+    let end = (data.len().saturating_sub(22 + 0xFFFF)..data.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(at) == Ok(END_OF_CENTRAL_DIRECTORY))
+        .ok_or("USDZ archive has no central directory")?;
+    let entries = u16_at(end + 10)?;
+    let mut at = u32_at(end + 16)? as usize;
+
+    for _ in 0..entries {
+        if u32_at(at)? != CENTRAL_HEADER {
+            return Err("USDZ archive has a broken central directory".into());
+        }
+        let method = u16_at(at + 10)?;
+        let stored_size = u32_at(at + 20)? as usize;
+        let name_length = u16_at(at + 28)?;
+        let local = u32_at(at + 42)? as usize;
+        let name = String::from_utf8_lossy(data.get(at + 46..at + 46 + name_length).ok_or("USDZ archive is truncated")?).to_lowercase();
+        at += 46 + name_length + u16_at(at + 30)? + u16_at(at + 32)?;
+        if !(name.ends_with(".usda") || name.ends_with(".usdc") || name.ends_with(".usd")) {
+            continue;
+        }
+
+        if u32_at(local)? != LOCAL_HEADER {
+            return Err(format!("USDZ archive has a broken entry for {}", name));
+        }
+        let start = local + 30 + u16_at(local + 26)? + u16_at(local + 28)?;
+        let stored = data.get(start..start + stored_size).ok_or("USDZ archive is truncated")?;
+        return match method {
+            0 => Ok(stored.to_vec()),
+            8 => {
+                let mut layer = Vec::new();
+                DeflateDecoder::new(stored).read_to_end(&mut layer).map_err(|e| format!("Invalid compressed USDZ entry {}: {}", name, e))?;
+                Ok(layer)
+            }
+            _ => Err(format!("USDZ entry {} uses unsupported compression {}", name, method)),
+        };
+    }
+    Err("USDZ archive has no USD layer".into())
+}
+
+// Tokens //
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    // Keywords, type names and property names, including namespaced ones like xformOp:translate
+    Word(String),
+    Number(f64),
+    String(String),
+    // <paths> and @asset paths@, which nothing here follows
+    Path,
+    Punct(u8),
+}
+
+fn tokenize(text: &[u8]) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut at = 0;
+    while at < text.len() {
+        let c = text[at];
+        match c {
+            _ if c.is_ascii_whitespace() => at += 1,
+            b'#' => at = text[at..].iter().position(|&b| b == b'\n').map_or(text.len(), |newline| at + newline),
+            b'"' | b'\'' => {
+                // Triple-quoted strings can span lines
+                let quote: &[u8] = if text[at..].starts_with(&[c; 3]) { &text[at..at + 3] } else { &text[at..at + 1] };
+                let start = at + quote.len();
+                let mut end = start;
+                while end < text.len() && !text[end..].starts_with(quote) {
+                    end += if text[end] == b'\\' { 2 } else { 1 };
+                }
+                if end >= text.len() {
+                    return Err("Unterminated string in USD layer".into());
+                }
+                tokens.push(Token::String(String::from_utf8_lossy(&text[start..end]).into_owned()));
+                at = end + quote.len();
+            }
+            b'<' => {
+                at += text[at..].iter().position(|&b| b == b'>').ok_or("Unterminated path in USD layer")? + 1;
+                tokens.push(Token::Path);
+            }
+            b'@' => {
+                let quote: &[u8] = if text[at..].starts_with(b"@@@") { b"@@@" } else { b"@" };
+                let start = at + quote.len();
+                let length = text[start..].windows(quote.len()).position(|window| window == quote).ok_or("Unterminated asset path in USD layer")?;
+                tokens.push(Token::Path);
+                at = start + length + quote.len();
+            }
+            b'(' | b')' | b'[' | b']' | b'{' | b'}' | b'=' | b',' | b';' => {
+                tokens.push(Token::Punct(c));
+                at += 1;
+            }
+            _ => {
+                // Numbers also end at the colon of a time sample, like `10: (1, 2, 3)`
+                let number = c.is_ascii_digit() || c == b'-' || c == b'+' || c == b'.';
+                let length = text[at..]
+                    .iter()
+                    .position(|&b| b.is_ascii_whitespace() || b"()[]{}=,;<>\"'#@".contains(&b) || (number && b == b':'))
+                    .unwrap_or(text.len() - at)
+                    .max(1);
+                let word = &text[at..at + length];
+                at += length;
+                if number {
+                    tokens.push(Token::Number(fast_float2::parse(word).map_err(|_| format!("Invalid number {} in USD layer", String::from_utf8_lossy(word)))?));
+                } else {
+                    tokens.push(Token::Word(String::from_utf8_lossy(word).into_owned()));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Values and prims //
+
+#[derive(Debug)]
+enum Value {
+    Number(f64),
+    String(String),
+    // Both (tuples) and [arrays]
+    List(Vec<Value>),
+    Other,
+}
+
+impl Value {
+    // Every number in the value, flattened, so points come out as x, y, z, x, ...
+    fn numbers(&self) -> Vec<f64> {
+        let mut numbers = Vec::new();
+        self.collect_numbers(&mut numbers);
+        numbers
+    }
+
+    fn collect_numbers(&self, numbers: &mut Vec<f64>) {
+        match self {
+            Value::Number(value) => numbers.push(*value),
+            Value::List(items) => items.iter().for_each(|item| item.collect_numbers(numbers)),
+            _ => {}
+        }
+    }
+
+    fn strings(&self) -> Vec<&str> {
+        match self {
+            Value::String(value) => vec![value.as_str()],
+            Value::List(items) => items.iter().flat_map(Value::strings).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+struct Prim {
+    kind: String,
+    name: String,
+    attributes: HashMap<String, Value>,
+    children: Vec<Prim>,
+}
+
+struct UsdaParser {
+    tokens: Vec<Token>,
+    at: usize,
+    // Lists and prims currently open, so deeply nested layers fail instead of overflowing the stack
+    depth: usize,
+}
+
+impl UsdaParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.at).cloned().ok_or("Unexpected end of USD layer")?;
+        self.at += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, punct: u8) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: u8) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' in USD layer, found {:?}", punct as char, self.peek()))
+        }
+    }
+
+    // Past a balanced (...), [...] or {...}, starting at its opening bracket
+    fn skip_group(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Token::Punct(b'(' | b'[' | b'{') => depth += 1,
+                Token::Punct(b')' | b']' | b'}') => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Runs `parse` one nesting level deeper
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.depth >= MAX_NESTING {
+            return Err(format!("USD layer nests more than {} levels deep", MAX_NESTING));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn skip_metadata(&mut self) -> Result<(), String> {
+        if self.peek() == Some(&Token::Punct(b'(')) {
+            self.skip_group()?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        Ok(match self.next()? {
+            Token::Number(value) => Value::Number(value),
+            Token::String(value) => Value::String(value),
+            Token::Word(word) => match word.as_str() {
+                "inf" => Value::Number(f64::INFINITY),
+                "-inf" => Value::Number(f64::NEG_INFINITY),
+                "nan" => Value::Number(f64::NAN),
+                // Tokens in arrays, like xformOpOrder's, are written as strings anyway
+                _ => Value::Other,
+            },
+            Token::Punct(open @ (b'(' | b'[')) => {
+                let close = if open == b'(' { b')' } else { b']' };
+                self.nested(|parser| {
+                    let mut items = Vec::new();
+                    while !parser.eat(close) {
+                        items.push(parser.value()?);
+                        parser.eat(b',');
+                    }
+                    Ok(Value::List(items))
+                })?
+            }
+            Token::Punct(b'{') => {
+                self.at -= 1;
+                self.skip_group()?;
+                Value::Other
+            }
+            Token::Path => Value::Other,
+            token => return Err(format!("Unexpected {:?} in USD value", token)),
+        })
+    }
+
+    // `def Mesh "name" (metadata) { ... }`, with the specifier already read
+    fn prim(&mut self) -> Result<Prim, String> {
+        let (kind, name) = match self.next()? {
+            Token::Word(kind) => match self.next()? {
+                Token::String(name) => (kind, name),
+                _ => return Err(format!("USD {} prim has no name", kind)),
+            },
+            // Typeless prims go straight to their name
+            Token::String(name) => (String::new(), name),
+            token => return Err(format!("Unexpected {:?} in USD prim", token)),
+        };
+        self.skip_metadata()?;
+        self.expect(b'{')?;
+
+        let mut prim = Prim { kind, name, attributes: HashMap::new(), children: Vec::new() };
+        self.nested(|parser| {
+            while !parser.eat(b'}') {
+                parser.statement(&mut prim)?;
+            }
+            Ok(prim)
+        })
+    }
+
+    // One prim or property inside a prim's body, or at the top of the layer
+    fn statement(&mut self, parent: &mut Prim) -> Result<(), String> {
+        let Token::Word(word) = self.next()? else {
+            // Stray separators
+            return Ok(());
+        };
+        match word.as_str() {
+            "def" | "over" => parent.children.push(self.prim()?),
+            // Classes are only templates for other prims
+            "class" => {
+                self.prim()?;
+            }
+            "variantSet" => {
+                self.next()?;
+                self.expect(b'=')?;
+                self.skip_group()?;
+            }
+            "reorder" => {
+                self.next()?;
+                self.expect(b'=')?;
+                self.value()?;
+            }
+            _ => {
+                // Qualifiers, then the type (maybe an array), then the name
+                let mut words = vec![word];
+                loop {
+                    if self.eat(b'[') {
+                        self.expect(b']')?;
+                    }
+                    match self.peek().cloned() {
+                        Some(Token::Word(word)) => {
+                            self.at += 1;
+                            words.push(word);
+                        }
+                        _ => break,
+                    }
+                }
+                let name = words.pop().unwrap_or_default();
+                if self.eat(b'=') {
+                    let value = self.value()?;
+                    parent.attributes.insert(name, value);
+                }
+                self.skip_metadata()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_usda(data: &[u8]) -> Result<Mesh, String> {
+    if data.starts_with(CRATE_MAGIC) {
+        return Err("Binary USD (usdc) layers aren't supported, only usda text; convert them with usdcat".into());
+    }
+    if !data.starts_with(b"#usda") {
+        return Err("Not a USD text layer".into());
+    }
+
+    let mut parser = UsdaParser { tokens: tokenize(data)?, at: 0, depth: 0 };
+    // Layer metadata, which holds the up axis
+    let mut up_axis = None;
+    if parser.peek() == Some(&Token::Punct(b'(')) {
+        let start = parser.at;
+        parser.skip_group()?;
+        let metadata = &parser.tokens[start..parser.at];
+        up_axis = metadata
+            .windows(3)
+            .find(|window| window[0] == Token::Word("upAxis".into()) && window[1] == Token::Punct(b'='))
+            .and_then(|window| match &window[2] {
+                Token::String(axis) => Some(axis.clone()),
+                _ => None,
+            });
+    }
+    let mut root = Prim { kind: String::new(), name: String::new(), attributes: HashMap::new(), children: Vec::new() };
+    while parser.peek().is_some() {
+        parser.statement(&mut root)?;
+    }
+
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        normals: None,
+        uvs: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        rig: None,
+        submeshes: Vec::new(),
+        textures: Vec::new(),
+        sources: Vec::new(),
+    };
+    // Z-up stages are turned to the viewer's Y up
+    let stage = match up_axis.as_deref() {
+        Some("Z") => Matrix4::from_angle_x(Deg(-90.0)),
+        _ => Matrix4::one(),
+    };
+    for prim in &root.children {
+        append_prim(&mut mesh, prim, stage)?;
+    }
+    if mesh.submeshes.len() == 1 {
+        mesh.submeshes.clear();
+    }
+
+    eprintln!("USD Parser: Loaded {} vertices, {} indices ({} triangles){}",
+             mesh.vertices.len(), mesh.indices.len(), mesh.indices.len() / 3,
+             if up_axis.as_deref() == Some("Z") { ", turned from Z up" } else { "" });
+    Ok(mesh)
+}
+
+// The prim's xformOps, in xformOpOrder with the first one outermost
+fn local_transform(prim: &Prim) -> Matrix4<f32> {
+    let Some(order) = prim.attributes.get("xformOpOrder") else {
+        return Matrix4::one();
+    };
+    let mut transform = Matrix4::one();
+    for op in order.strings() {
+        let (inverted, name) = match op.strip_prefix("!invert!") {
+            Some(name) => (true, name),
+            None => (false, op),
+        };
+        let Some(value) = prim.attributes.get(name) else {
+            continue;
+        };
+        let numbers: Vec<f32> = value.numbers().into_iter().map(|value| value as f32).collect();
+        let vector = || Vector3::new(numbers[0], numbers[1], numbers[2]);
+        // xformOp:<kind>, optionally with a suffix like :pivot
+        let kind = name.split(':').nth(1).unwrap_or_default();
+        let matrix = match (kind, numbers.len()) {
+            ("translate", 3) => Matrix4::from_translation(vector()),
+            ("scale", 3) => Matrix4::from_nonuniform_scale(numbers[0], numbers[1], numbers[2]),
+            ("scale", 1) => Matrix4::from_scale(numbers[0]),
+            ("rotateX", 1) => Matrix4::from_angle_x(Deg(numbers[0])),
+            ("rotateY", 1) => Matrix4::from_angle_y(Deg(numbers[0])),
+            ("rotateZ", 1) => Matrix4::from_angle_z(Deg(numbers[0])),
+            // rotateXYZ turns about X first, so its matrix is Z * Y * X
+            (kind, 3) if kind.len() == 9 && kind.starts_with("rotate") => kind[6..]
+                .bytes()
+                .zip(numbers.iter())
+                .fold(Matrix4::one(), |matrix, (axis, &degrees)| {
+                    let rotation = match axis {
+                        b'X' => Matrix4::from_angle_x(Deg(degrees)),
+                        b'Y' => Matrix4::from_angle_y(Deg(degrees)),
+                        _ => Matrix4::from_angle_z(Deg(degrees)),
+                    };
+                    rotation * matrix
+                }),
+            // Rows in USD's row-vector convention are columns here
+            ("transform", 16) => Matrix4::from(std::array::from_fn::<[f32; 4], 4, _>(|row| std::array::from_fn(|column| numbers[row * 4 + column]))),
+            _ => continue,
+        };
+        transform = transform * if inverted { matrix.invert().unwrap_or(matrix) } else { matrix };
+    }
+    transform
+}
+
+fn append_prim(mesh: &mut Mesh, prim: &Prim, parent: Matrix4<f32>) -> Result<(), String> {
+    let resets = prim.attributes.get("xformOpOrder").is_some_and(|order| order.strings().contains(&"!resetXformStack!"));
+    let transform = if resets { Matrix4::one() } else { parent } * local_transform(prim);
+    if prim.kind == "Mesh" {
+        append_mesh(mesh, prim, transform)?;
+    }
+    for child in &prim.children {
+        append_prim(mesh, child, transform)?;
+    }
+    Ok(())
+}
+
+// A Mesh prim's faces as fans, as a part of its own
+fn append_mesh(mesh: &mut Mesh, prim: &Prim, transform: Matrix4<f32>) -> Result<(), String> {
+    let numbers = |name: &str| prim.attributes.get(name).map_or_else(Vec::new, Value::numbers);
+    let mut vertices: Vec<[f32; 3]> = numbers("points").chunks_exact(3).map(|p| [p[0] as f32, p[1] as f32, p[2] as f32]).collect();
+    let counts = numbers("faceVertexCounts");
+    let corners = numbers("faceVertexIndices");
+    let name = &prim.name;
+    if vertices.is_empty() || corners.is_empty() {
+        return Ok(());
+    }
+
+    transform_vertices(&transform, &mut vertices, &mut []);
+    // Left-handed meshes and mirroring transforms both flip the winding
+    let left_handed = prim.attributes.get("orientation").is_some_and(|orientation| orientation.strings() == ["leftHanded"]);
+    let flipped = left_handed != (transform.determinant() < 0.0);
+
+    let base = mesh.vertices.len() as u32;
+    let first_index = mesh.indices.len() as u32;
+    let mut next = 0usize;
+    for &count in &counts {
+        // Counts and indices are ints in USD but read as numbers; anything else is rejected
+        // before it's turned into a usize, where NaN would become 0 and huge values saturate
+        if count < 0.0 || count.fract() != 0.0 || count > corners.len() as f64 {
+            return Err(format!("USD mesh {} has an invalid faceVertexCount {}", name, count));
+        }
+        let face = next
+            .checked_add(count as usize)
+            .and_then(|end| corners.get(next..end))
+            .ok_or_else(|| format!("USD mesh {} has fewer faceVertexIndices than its faceVertexCounts", name))?;
+        next += count as usize;
+        if face.iter().any(|&index| index.fract() != 0.0 || !(0.0..vertices.len() as f64).contains(&index)) {
+            return Err(format!("USD mesh {} has a face vertex out of range", name));
+        }
+        for i in 1..face.len().saturating_sub(1) {
+            let [a, b, c] = [face[0], face[i], face[i + 1]].map(|index| base + index as u32);
+            let triangle = if flipped { [a, c, b] } else { [a, b, c] };
+            mesh.indices.extend_from_slice(&triangle);
+        }
+    }
+    mesh.vertices.extend(vertices);
+
+    // A single display color tints the whole mesh
+    let color = match numbers("primvars:displayColor").as_slice() {
+        [r, g, b] => [*r as f32, *g as f32, *b as f32, 1.0],
+        _ => DEFAULT_COLOR,
+    };
+    mesh.submeshes.push(SubMesh {
+        first_index,
+        index_count: mesh.indices.len() as u32 - first_index,
+        color,
+        texture: None,
+        material: Material::default(),
+        name: name.clone(),
+    });
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use cgmath::Matrix4;
+use wgpu::util::StagingBelt;
+
+use crate::lines::{LineVertex, Lines};
+
+// UV layout //
+// The mesh's texture coordinates drawn flat over a checker in 0-1 space, with
+// triangles that are mirrored against the rest of the layout or that overlap other
+// triangles highlighted. UVs are shown the way glTF stores them, v = 0 at the top.
+
+const EDGE_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.8];
+const FLIPPED_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const OVERLAP_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+// Intervals closer than this count as touching rather than overlapping
+const OVERLAP_EPSILON: f32 = 1e-6;
+// Fraction of the shorter window side the 0-1 square fills
+const VIEW_FILL: f32 = 0.9;
+
+pub struct UvLayout {
+    pub triangles: Vec<[[f32; 2]; 3]>,
+    // Wound the other way from most of the layout
+    pub flipped: Vec<bool>,
+    // Covering part of another triangle
+    pub overlapping: Vec<bool>,
+}
+
+impl UvLayout {
+    // None when some index has no UV
+    pub fn analyze(uvs: &[[f32; 2]], indices: &[u32]) -> Option<Self> {
+        if uvs.is_empty() || indices.iter().any(|&i| i as usize >= uvs.len()) {
+            return None;
+        }
+
+        let triangles: Vec<[[f32; 2]; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [uvs[t[0] as usize], uvs[t[1] as usize], uvs[t[2] as usize]])
+            .collect();
+
+        // Degenerate triangles have no winding and cover nothing
+        let areas: Vec<f32> = triangles.iter().map(|&t| signed_area(t)).collect();
+        let positive = areas.iter().filter(|&&a| a > 0.0).count();
+        let negative = areas.iter().filter(|&&a| a < 0.0).count();
+        let majority = if positive >= negative { 1.0 } else { -1.0 };
+        let flipped = areas.iter().map(|&a| a * majority < 0.0).collect();
+
+        let overlapping = find_overlaps(&triangles, &areas);
+
+        Some(Self {
+            triangles,
+            flipped,
+            overlapping,
+        })
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} triangles, {} flipped, {} overlapping",
+                self.triangles.len(),
+                self.flipped.iter().filter(|&&f| f).count(),
+                self.overlapping.iter().filter(|&&o| o).count())
+    }
+
+    // Triangle edges as line segments, at z = 0 in UV space
+    fn line_vertices(&self) -> Vec<LineVertex> {
+        let mut vertices = Vec::with_capacity(self.triangles.len() * 6);
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            let color = if self.flipped[i] {
+                FLIPPED_COLOR
+            } else if self.overlapping[i] {
+                OVERLAP_COLOR
+            } else {
+                EDGE_COLOR
+            };
+
+            for corner in 0..3 {
+                for [u, v] in [triangle[corner], triangle[(corner + 1) % 3]] {
+                    vertices.push(LineVertex { position: [u, v, 0.0], color });
+                }
+            }
+        }
+        vertices
+    }
+}
+
+fn signed_area([a, b, c]: [[f32; 2]; 3]) -> f32 {
+    ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])) * 0.5
+}
+
+// Bins triangles into a grid over the layout and tests pairs that share a cell
+fn find_overlaps(triangles: &[[[f32; 2]; 3]], areas: &[f32]) -> Vec<bool> {
+    let mut overlapping = vec![false; triangles.len()];
+    let live: Vec<usize> = (0..triangles.len()).filter(|&t| areas[t] != 0.0).collect();
+    if live.len() < 2 {
+        return overlapping;
+    }
+
+    let bounds: Vec<([f32; 2], [f32; 2])> = triangles.iter().map(|t| triangle_bounds(*t)).collect();
+    let mut min = [f32::MAX; 2];
+    let mut max = [f32::MIN; 2];
+    for &t in &live {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(bounds[t].0[axis]);
+            max[axis] = max[axis].max(bounds[t].1[axis]);
+        }
+    }
+
+    // About one triangle per cell on average
+    let cells = ((live.len() as f32).sqrt() as usize).clamp(1, 1024);
+    let cell_size = [0, 1].map(|axis| ((max[axis] - min[axis]) / cells as f32).max(f32::MIN_POSITIVE));
+    let cell_of = |p: [f32; 2], axis: usize| (((p[axis] - min[axis]) / cell_size[axis]) as usize).min(cells - 1);
+
+    let mut grid: Vec<Vec<u32>> = vec![Vec::new(); cells * cells];
+    for &t in &live {
+        let (low, high) = bounds[t];
+        for y in cell_of(low, 1)..=cell_of(high, 1) {
+            for x in cell_of(low, 0)..=cell_of(high, 0) {
+                grid[y * cells + x].push(t as u32);
+            }
+        }
+    }
+
+    for (cell, members) in grid.iter().enumerate() {
+        for (i, &a) in members.iter().enumerate() {
+            for &b in &members[i + 1..] {
+                let (a, b) = (a as usize, b as usize);
+                // Pairs sharing several cells are only tested in the first cell of their common bounds
+                let common = [0, 1].map(|axis| bounds[a].0[axis].max(bounds[b].0[axis]));
+                if common[0] > bounds[a].1[0].min(bounds[b].1[0]) || common[1] > bounds[a].1[1].min(bounds[b].1[1]) {
+                    continue;
+                }
+                if cell_of(common, 1) * cells + cell_of(common, 0) != cell {
+                    continue;
+                }
+
+                if triangles_overlap(triangles[a], triangles[b]) {
+                    overlapping[a] = true;
+                    overlapping[b] = true;
+                }
+            }
+        }
+    }
+
+    overlapping
+}
+
+fn triangle_bounds(triangle: [[f32; 2]; 3]) -> ([f32; 2], [f32; 2]) {
+    let mut min = [f32::MAX; 2];
+    let mut max = [f32::MIN; 2];
+    for p in triangle {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    (min, max)
+}
+
+// Separating axis test over the six edge normals; touching edges don't count
+fn triangles_overlap(a: [[f32; 2]; 3], b: [[f32; 2]; 3]) -> bool {
+    for triangle in [a, b] {
+        for corner in 0..3 {
+            let p = triangle[corner];
+            let q = triangle[(corner + 1) % 3];
+            let axis = [q[1] - p[1], p[0] - q[0]];
+
+            let project = |t: [[f32; 2]; 3]| {
+                let d = t.map(|v| v[0] * axis[0] + v[1] * axis[1]);
+                (d[0].min(d[1]).min(d[2]), d[0].max(d[1]).max(d[2]))
+            };
+            let (a_min, a_max) = project(a);
+            let (b_min, b_max) = project(b);
+
+            let epsilon = OVERLAP_EPSILON * (axis[0].abs() + axis[1].abs());
+            if a_max <= b_min + epsilon || b_max <= a_min + epsilon {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Draws the layout in place of the scene
+pub struct UvView {
+    checker_pipeline: wgpu::RenderPipeline,
+    lines: Lines,
+}
+
+impl UvView {
+    pub fn new(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../uv.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("UV Checker Pipeline Layout"),
+            bind_group_layouts: &[uniform_layout],
+            push_constant_ranges: &[],
+        });
+
+        let checker_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("UV Checker Pipeline"),
+            layout: Some(&layout),
+            cache,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_checker"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_checker"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self {
+            checker_pipeline,
+            lines: Lines::new(device, uniform_layout, color_format, depth_format, sample_count, cache),
+        }
+    }
+
+    pub fn set(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, belt: &mut StagingBelt, layout: Option<&UvLayout>) {
+        let vertices = layout.map(UvLayout::line_vertices).unwrap_or_default();
+        self.lines.set(device, encoder, belt, &vertices);
+    }
+
+    // Maps the 0-1 square to a centered square in clip space, v growing downwards
+    pub fn view_projection(width: u32, height: u32) -> Matrix4<f32> {
+        let aspect = width as f32 / height.max(1) as f32;
+        let (scale_x, scale_y) = if aspect >= 1.0 {
+            (2.0 * VIEW_FILL / aspect, 2.0 * VIEW_FILL)
+        } else {
+            (2.0 * VIEW_FILL, 2.0 * VIEW_FILL * aspect)
+        };
+
+        Matrix4::from_nonuniform_scale(scale_x, -scale_y, 1.0) * Matrix4::from_translation(cgmath::Vector3::new(-0.5, -0.5, 0.0))
+    }
+
+    // Expects the UV view's uniforms to be bound at group 0
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(&self.checker_pipeline);
+        render_pass.draw(0..4, 0..1);
+        self.lines.draw(render_pass);
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+
+use crate::manifold::ManifoldReport;
+use crate::parse::{gltf_unreferenced, Mesh};
+
+// Mesh validation //
+// Problems with the data in a file, as opposed to the shape it describes: values
+// that aren't numbers, indices past the end of the vertex list, normals that point
+// nowhere and, for glTF, data that nothing uses. Printed on load and emitted as JSON
+// by `rsview check`.
+
+// Offending items listed per issue, the count covers the rest
+const MAX_EXAMPLES: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub kind: &'static str,
+    pub count: usize,
+    // Indices of the first few offending items
+    pub examples: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub file: String,
+    pub vertices: usize,
+    pub triangles: usize,
+    pub issues: Vec<Issue>,
+    // Holes and non-manifold edges; these matter for printing but don't count as issues
+    pub manifold: Option<ManifoldReport>,
+}
+
+impl ValidationReport {
+    pub fn check(file: &str, mesh: &Mesh) -> Self {
+        let mut issues = Vec::new();
+        issues.extend(issue("non_finite_vertices", positions(&mesh.vertices, |v| v.iter().any(|c| !c.is_finite()))));
+        issues.extend(issue("out_of_range_indices", positions(&mesh.indices, |&i| i as usize >= mesh.vertices.len())));
+        if let Some(normals) = &mesh.normals {
+            issues.extend(issue("zero_length_normals", positions(normals, |n| n.iter().all(|&c| c == 0.0))));
+        }
+
+        let lower = file.to_lowercase();
+        if lower.ends_with(".gltf") || lower.ends_with(".glb") {
+            match gltf_unreferenced(file) {
+                Ok(unreferenced) => {
+                    issues.extend(issue("unreferenced_accessors", unreferenced.accessors.into_iter()));
+                    issues.extend(issue("unreferenced_buffer_views", unreferenced.buffer_views.into_iter()));
+                    issues.extend(issue("unreferenced_buffers", unreferenced.buffers.into_iter()));
+                }
+                Err(e) => eprintln!("Validation: Skipping glTF references: {}", e),
+            }
+        }
+
+        Self {
+            file: file.to_string(),
+            vertices: mesh.vertices.len(),
+            triangles: mesh.indices.len() / 3,
+            manifold: mesh.indices
+                .iter()
+                .all(|&i| (i as usize) < mesh.vertices.len())
+                .then(|| ManifoldReport::analyze(&mesh.vertices, &mesh.indices)),
+            issues,
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_clean() {
+            return "no issues".to_string();
+        }
+        self.issues
+            .iter()
+            .map(|issue| format!("{} {}", issue.count, issue.kind.replace('_', " ")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// None when nothing offends
+fn issue(kind: &'static str, offenders: impl Iterator<Item = usize>) -> Option<Issue> {
+    let mut examples = Vec::new();
+    let mut count = 0;
+    for offender in offenders {
+        if examples.len() < MAX_EXAMPLES {
+            examples.push(offender);
+        }
+        count += 1;
+    }
+
+    (count > 0).then_some(Issue { kind, count, examples })
+}
+
+fn positions<T>(items: &[T], offends: impl Fn(&T) -> bool) -> impl Iterator<Item = usize> {
+    items.iter().enumerate().filter(move |(_, item)| offends(item)).map(|(i, _)| i)
+}
@@ -0,0 +1,3051 @@
+use winit:: {
+    dpi::PhysicalPosition,
+    event::WindowEvent,
+    keyboard::KeyCode,
+};
+use bytemuck::*;
+use std::collections::HashSet;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use cgmath::*;
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI, TAU};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::parse::{Mesh, MeshChunk, SubMesh};
+use crate::texture::TextureImage;
+use crate::uniforms::ObjectUniforms;
+use crate::upload::{self, DynamicBuffer};
+use crate::profiling::{FrameHistory, FrameTiming, GpuTimer, ProfileLog};
+use crate::overlay::FrameGraph;
+use crate::pipeline_cache::DiskPipelineCache;
+use crate::framebuffer::Framebuffer;
+use crate::batch::{self, DrawBatch};
+use crate::scene::{Hit, Scene};
+use crate::components::Components;
+use crate::manifold::ManifoldReport;
+use crate::screenshot::{self, Capture};
+use crate::recording::{self, Recorder};
+use crate::validate::ValidationReport;
+use crate::topology::TopologyReport;
+use crate::session::{self, Annotation, Camera, RenderSettings, Session, SessionModel};
+use crate::stats::GeometryStats;
+use crate::measure::{Measurements, Pick};
+use crate::deviation::Deviation;
+use crate::error::Error;
+use crate::units::Unit;
+use crate::config::Config;
+use crate::section::Section;
+use crate::clip::ClipPlane;
+use crate::curvature::Curvature;
+use crate::debug_view::DebugView;
+use crate::normals::{NormalDisplay, NormalSegments};
+use crate::lines::{self, LineVertex, Lines};
+use crate::points::{PointVertex, Points};
+use crate::grid;
+use crate::frustum::Frustum;
+use crate::uv_layout::{UvLayout, UvView};
+use crate::ui::{AnimationStats, Overlay, OverlayStats, SelectionStats};
+use crate::loader::{spawn_loader, spawn_side_loader, LoadEvent, LoadOptions, Waker};
+use crate::watch::ModelWatcher;
+use crate::recent::RecentFiles;
+use crate::animation::{Clip, Rig};
+use crate::skinning::JointMatrices;
+use crate::views::{self, CameraPose, CameraTransition, ViewPreset};
+use crate::background::{Background, BackgroundPass};
+use crate::environment::{self, EnvironmentPass};
+
+// Viewer //
+// Everything the window shows: the loaded model and its GPU buffers, the camera, the
+// analysis overlays and the panel. A viewer made without a window renders only
+// through capture_view, for thumbnails.
+
+// How long resize events must stop arriving before the surface is reconfigured
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
+// Upload budget for swapping a full-resolution mesh in behind its preview
+const UPLOAD_BYTES_PER_FRAME: usize = 32 * 1024 * 1024;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// Slot of the loaded model in the per-object uniforms
+const MODEL_OBJECT: u32 = 0;
+// Slot of the line overlays, which are in model space but never quantized
+const OVERLAY_OBJECT: u32 = 1;
+// Slot of the flat UV layout view
+const UV_OBJECT: u32 = 2;
+// Slot of the A/B splitter, which is in clip space
+const SPLITTER_OBJECT: u32 = 3;
+// Slot of the main model's point cloud, which is never quantized
+const POINTS_OBJECT: u32 = 4;
+// Slot of the first model beside the main one, the others follow
+const FIRST_SIDE_OBJECT: u32 = 5;
+// Models side by side sit this far apart, enough for a model fitted to the 2-unit cube
+// to turn without touching its neighbours
+const MODEL_SPACING: f32 = 3.0;
+// Past this camera distance the main model is drawn with its first level of detail, and
+// with the next one every time the distance doubles: the model then covers a quarter of
+// the screen area, and each level has about a quarter of the triangles
+const LOD_DISTANCE: f32 = 6.0;
+// Diameter of point cloud points in pixels until changed in the panel, at the starting
+// camera distance; they shrink farther away and grow closer
+const POINT_SIZE: f32 = 3.0;
+const POINT_SIZE_DISTANCE: f32 = 3.0;
+// The row runs across the view; the camera looks down the diagonal between X and Z
+const ROW_DIRECTION: Vector3<f32> = Vector3::new(FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2);
+// Auto-rotation in radians per second, and how much + and - change it per press
+const ROTATION_SPEED: f32 = 0.6;
+const ROTATION_SPEED_FACTOR: f32 = 1.5;
+// Longest step animation takes in one frame, so a frame after a stall or a power-save
+// sleep doesn't jump
+const MAX_FRAME_STEP: Duration = Duration::from_millis(100);
+// Arrow keys turn the paused model this far per press, in radians
+const ROTATION_STEP: f32 = 5.0 * PI / 180.0;
+// Section plane moves this fraction of the model's extent per key press
+const SECTION_STEP: f32 = 0.01;
+const SECTION_COLOR: [f32; 4] = [1.0, 0.85, 0.1, 1.0];
+const SPLITTER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// Squares of the UV checker across one unit of U or V
+const CHECKER_SQUARES: f32 = 8.0;
+const BOUNDS_COLOR: [f32; 4] = [0.2, 0.9, 1.0, 1.0];
+const SELECTION_COLOR: [f32; 4] = [1.0, 0.3, 0.8, 1.0];
+const SELECTION_VERTEX_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// Half the length of the crosses on the picked vertex and the measurement picks, as a
+// fraction of the model's size
+const MARKER_SIZE: f32 = 0.01;
+// How close to the splitter, in pixels, a click has to be to grab it
+const SPLITTER_GRAB: f64 = 8.0;
+// Toward the light in world space from the default view, above and to the right of the
+// camera so the model shows its shape as it turns; it moves with the camera
+const LIGHT_DIRECTION: Vector3<f32> = Vector3::new(0.5, 1.0, 0.3);
+// The camera is this many times camera_distance from the origin, which puts it at
+// (d, d/2, d) in the default view
+const CAMERA_DISTANCE_FACTOR: f32 = 1.5;
+// Framing a part gets no closer than for one this fraction of the model's size, so tiny
+// parts don't put the camera inside the near plane
+const MIN_FOCUS_RADIUS: f32 = 0.1;
+
+// What to open and how to load it
+#[derive(Clone)]
+pub struct ViewerOptions {
+    // The built-in default model when None
+    pub file: Option<String>,
+    // Further models shown in a row beside the first, each scaled to the same size
+    pub side_by_side: Vec<String>,
+    pub optimize: bool,
+    pub clean: bool,
+    pub fix_winding: bool,
+    // Reference model for the deviation heatmap
+    pub compare: Option<String>,
+    // Show the reference right of a splitter, with the model left of it
+    pub split: bool,
+    pub unit_scale: f32,
+    pub unit: Unit,
+    // Shells with fewer triangles are dropped on load
+    pub min_shell_triangles: usize,
+    pub preview_triangles: usize,
+    // Meshes with more triangles get levels of detail for when the camera is far off, 0 for none
+    pub lod_triangles: usize,
+    pub compact_vertices: bool,
+    // Multisampling of the scene, lowered to what the GPU supports
+    pub msaa_samples: u32,
+    pub background: Background,
+    // Equirectangular image drawn around the model and lighting it, instead of the background
+    pub environment: Option<String>,
+    // Falls back to Fifo when the surface doesn't offer it
+    pub present_mode: wgpu::PresentMode,
+    // CSV file that gets a row of frame timings per frame
+    pub profile: Option<String>,
+    // Copies of the main model drawn along X and Z, for benchmarking and tiling
+    pub grid: (u32, u32),
+    // Starting camera view, which also stops the turning; the default three-quarter view when None
+    pub view: Option<ViewPreset>,
+}
+
+impl Default for ViewerOptions {
+    fn default() -> Self {
+        Self {
+            file: None,
+            side_by_side: Vec::new(),
+            optimize: false,
+            clean: false,
+            fix_winding: false,
+            compare: None,
+            split: false,
+            unit_scale: 1.0,
+            unit: Unit::Model,
+            min_shell_triangles: 0,
+            preview_triangles: 20_000_000,
+            lod_triangles: 1_000_000,
+            compact_vertices: false,
+            msaa_samples: 4,
+            background: Background::default(),
+            environment: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            profile: None,
+            grid: (1, 1),
+            view: None,
+        }
+    }
+}
+
+// Indexed by [skinned][material, textured or vertex colors][compact vertices]; the
+// skinned variants are missing when the GPU can't skin
+type ScenePipelines = Vec<[[wgpu::RenderPipeline; 2]; 3]>;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Uniforms {
+    mvp: [[f32; 4]; 4],
+    // Applied to vertex positions before the mvp, undoes compact vertex quantization
+    position_scale: [f32; 4],
+    position_offset: [f32; 4],
+    // Both in model space, so shading works on undecoded normals and positions. The
+    // light's w is 1 to shade with face normals instead of the vertices'.
+    light_direction: [f32; 4],
+    camera_position: [f32; 4],
+    // Model space to the environment's, and the ambient light as spherical harmonics
+    environment_rotation: [[f32; 4]; 3],
+    ambient: [[f32; 4]; 9],
+    // Normal and distance of the clipping plane in model space, zero to keep everything
+    clip_plane: [f32; 4],
+    // Checker squares per UV unit in x, zero for the material's colors; the debug view's
+    // shader mode in y, and the depth view's range from the camera in z and w
+    display: [f32; 4],
+}
+
+// Full-resolution geometry written a slice per frame while its preview stays on screen
+struct BackgroundUpload {
+    vertex_data: Vec<u8>,
+    quantization: Option<Quantization>,
+    index_data: Vec<u8>,
+    index_format: wgpu::IndexFormat,
+    uv_data: Option<Vec<u8>>,
+    skin_data: Option<Vec<u8>>,
+    rig: Option<Rig>,
+    vertex_count: usize,
+    index_count: usize,
+    colors: Option<Vec<[u8; 4]>>,
+    submeshes: Vec<SubMesh>,
+    part_bounds: Vec<batch::PartBounds>,
+    textures: Vec<TextureImage>,
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    uv_buffer: DynamicBuffer,
+    skin_buffer: DynamicBuffer,
+    vertex_bytes_written: usize,
+    index_bytes_written: usize,
+    uv_bytes_written: usize,
+    skin_bytes_written: usize,
+}
+
+// A model opened beside the main one; it's drawn at the same size but not analyzed
+struct DrawItem {
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    index_format: wgpu::IndexFormat,
+    uv_buffer: DynamicBuffer,
+    num_indices: u32,
+    textured: bool,
+    quantization: Option<Quantization>,
+    draw_batch: DrawBatch,
+    // Fits the model in the 2-unit cube, like fit_model does for the main one
+    center: Vector3<f32>,
+    scale: f32,
+}
+
+pub struct Viewer<'a> {
+    // None when rendering headless with --render-to
+    surface: Option<wgpu::Surface<'a>>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    // Present modes the surface supports, cycled with V
+    present_modes: Vec<wgpu::PresentMode>,
+    size: winit::dpi::PhysicalSize<u32>,
+    // Latest size from the window and when it arrived, applied once resizing settles
+    pending_resize: Option<(winit::dpi::PhysicalSize<u32>, Instant)>,
+    // Per polygon mode the GPU supports (fill always comes first), indexed by [clipped]
+    // then as ScenePipelines. Clipped ones draw back faces and discard what the clipping
+    // plane cuts away.
+    scene_pipelines: Vec<(wgpu::PolygonMode, [ScenePipelines; 2])>,
+    // Index into scene_pipelines, W cycles it
+    polygon_mode: usize,
+    compact_vertices: bool,
+    // Set while the vertex buffer holds CompactVertex data
+    quantization: Option<Quantization>,
+    staging_belt: wgpu::util::StagingBelt,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    // Uint16 for meshes whose indices all fit; streamed geometry is always Uint32
+    index_format: wgpu::IndexFormat,
+    uv_buffer: DynamicBuffer,
+    // The current mesh has UVs and textures, so it's drawn with the textured pipelines
+    textured: bool,
+    // Joints and weights of a skinned mesh, posed by `rig`
+    skin_buffer: DynamicBuffer,
+    skinned: bool,
+    rig: Option<Rig>,
+    // None when the GPU can't read storage buffers in vertex shaders
+    joint_matrices: Option<JointMatrices>,
+    // Playback of the rig's first animation, P pauses and the panel scrubs
+    animation_time: f32,
+    animation_playing: bool,
+    // Heatmap colors, one per vertex of the current mesh
+    color_buffer: DynamicBuffer,
+    // Colors stored in the file itself, shown when nothing else colors the mesh
+    file_colors: Option<Vec<[u8; 4]>>,
+    // Distances to the --compare reference; the colors are uploaded once the full mesh is on the GPU
+    deviation: Option<Deviation>,
+    show_deviation: bool,
+    components: Option<Components>,
+    // K colors each shell differently
+    show_components: bool,
+    // Shells of the model before isolating one, I cycles through them
+    shell_count: usize,
+    // What the color buffer currently holds
+    vertex_colors: Option<VertexColors>,
+    // G colors each object/group/primitive differently, over textures and vertex colors
+    show_parts: bool,
+    // F shades with face normals, so the facets show
+    flat_shading: bool,
+    // T shades models with texture coordinates with a checker mapped by them
+    uv_checker: bool,
+    // Y cycles the main model through the debug views
+    debug_view: DebugView,
+    curvature: Option<Curvature>,
+    // Set from the panel, cuts the main model open
+    clip_plane: ClipPlane,
+    // How far the main model's parts are pushed apart, as a multiple of their distance
+    // from its middle; set with the panel's slider
+    explode: f32,
+    // Parts of the main model unchecked in the outliner, by their position in the file
+    hidden_parts: HashSet<usize>,
+    // The main model's vertices when it has no faces, and their diameter in pixels
+    points: Points,
+    point_size: f32,
+    // View and picks from a session file, restored once the model has finished loading
+    pending_session: Option<Session>,
+    // Indices that are ready to draw; while streaming this grows as vertices arrive
+    num_indices: u32,
+    num_vertices: usize,
+    draw_batch: DrawBatch,
+    loader: Option<Receiver<LoadEvent>>,
+    // Models beside the main one in command line order, None until loaded or when they failed
+    side_paths: Vec<String>,
+    draw_list: Vec<Option<DrawItem>>,
+    // Coarser versions of the main model, finest first, drawn in its place from far off
+    lods: Vec<DrawItem>,
+    // How many of the main model's parts were outside the view last frame
+    culled_parts: usize,
+    // Copies of the main model along X and Z, and how far apart they are in model units
+    grid: (u32, u32),
+    grid_spacing: f32,
+    side_loader: Option<Receiver<(usize, Mesh)>>,
+    // In an A/B comparison, where the splitter sits as a fraction of the width. The main
+    // model shows left of it and the first side model, at the same place, right of it.
+    split: Option<f32>,
+    dragging_splitter: bool,
+    splitter: Lines,
+    // Kept to load the model again, fitted to the budget
+    model_path: String,
+    load_options: LoadOptions,
+    waker: Waker,
+    // Reloads the model when its files change on disk, only with a window
+    watcher: Option<ModelWatcher>,
+    // Models opened in a window, 1-9 open them again
+    recent: RecentFiles,
+    // Set when the file failed and the default model is loading in its place
+    model_failed: bool,
+    // Set while the loader is still parsing the file, for the loading indicator
+    parsing_since: Option<Instant>,
+    // Set when the model's file has data problems
+    validation: Option<ValidationReport>,
+    topology: Option<TopologyReport>,
+    // Budget limits the current model exceeds, B fits it to them
+    budget_violations: Vec<String>,
+    streamed_vertices: usize,
+    pending_indices: Vec<u32>,
+    pending_max_index: u32,
+    stream_min: Vector3<f32>,
+    stream_max: Vector3<f32>,
+    background_upload: Option<BackgroundUpload>,
+    showing_preview: bool,
+    // Ray queries against the full-resolution mesh, arrives shortly after it loads
+    scene: Option<Scene>,
+    geometry_stats: Option<GeometryStats>,
+    // Include the PCA-oriented box in the dimensions readout
+    show_oriented_box: bool,
+    unit: Unit,
+    cursor: PhysicalPosition<f64>,
+    measurements: Measurements,
+    measurement_lines: Lines,
+    // Triangle under the last left click, outlined with its nearest vertex marked
+    selection: Option<Hit>,
+    selection_lines: Lines,
+    // The selection or the measurements changed since their lines were last built
+    picks_dirty: bool,
+    // Axis of the section plane and where along it the plane sits, None when hidden
+    section_plane: Option<(usize, f32)>,
+    section: Option<Section>,
+    section_dirty: bool,
+    lines: Lines,
+    manifold: Option<ManifoldReport>,
+    // Boundary and non-manifold edges, shown with E
+    edge_lines: Lines,
+    show_manifold_edges: bool,
+    // Ground grid under the model's bounds and the axes at its origin, shown with A
+    grid_lines: Lines,
+    axis_lines: Lines,
+    show_grid: bool,
+    // The model's axis-aligned box, shown with L along with its size in the panel
+    bounds_lines: Lines,
+    show_bounds: bool,
+    // The bounds changed since the grid and box were last built
+    bounds_dirty: bool,
+    // Vertex and face normals, cycled with N and set from the panel
+    normal_segments: Option<NormalSegments>,
+    normal_lines: Lines,
+    normal_display: NormalDisplay,
+    normals_dirty: bool,
+    model_bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+    uv_layout: Option<UvLayout>,
+    uv_view: UvView,
+    // U swaps the model for its UV layout
+    show_uv_layout: bool,
+    object_uniforms: ObjectUniforms<Uniforms>,
+    // Turn around the vertical axis, then tilt toward the camera, in radians
+    rotation: f32,
+    pitch: f32,
+    // Space pauses and resumes the turning, + and - change its speed
+    auto_rotate: bool,
+    rotation_speed: f32,
+    // When the last frame was updated, animation advances by the time since
+    last_update: Option<Instant>,
+    gpu_timer: Option<GpuTimer>,
+    frame_history: FrameHistory,
+    // --profile's CSV, dropped after a failed write
+    profile: Option<ProfileLog>,
+    // Time spent handling window events since the last frame
+    event_time: Duration,
+    frame_graph: FrameGraph,
+    show_frame_graph: bool,
+    // Behind the model in the window and in captures that aren't transparent
+    background: Background,
+    background_pass: BackgroundPass,
+    environment: Option<EnvironmentPass>,
+    // Model statistics panel, Tab hides it
+    overlay: Overlay,
+    framebuffer: Framebuffer,
+    // Set while F9 is recording the session
+    recorder: Option<Recorder>,
+    last_recorded_frame: Option<Instant>,
+    model_scale: f32,
+    model_center: Vector3<f32>,
+    camera_distance: f32,
+    // Where the camera orbits the origin, in radians; the numpad presets move it
+    view_yaw: f32,
+    view_elevation: f32,
+    // Center and camera distance that frame the whole model, which the presets go back to
+    fit_center: Vector3<f32>,
+    fit_distance: f32,
+    camera_transition: Option<CameraTransition>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct Vertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+impl Vertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 3 => Float32x3];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }    
+}
+
+// Position as snorm16 within the mesh bounds and normal as snorm8 (w is padding in
+// both), 12 bytes instead of 24
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct CompactVertex {
+    position: [i16; 4],
+    normal: [i8; 4],
+}
+
+impl CompactVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Snorm16x4, 3 => Snorm8x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CompactVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// Texture coordinates live in their own buffer so untextured meshes don't pay for them
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct TexCoord {
+    uv: [f32; 2],
+}
+
+impl TexCoord {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] =
+        wgpu::vertex_attr_array![2 => Float32x2];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TexCoord>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct VertexColor {
+    color: [u8; 4],
+}
+
+impl VertexColor {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] =
+        wgpu::vertex_attr_array![2 => Unorm8x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<VertexColor>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// Joints into the rig's palette and their weights, right after the other vertex buffers
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SkinVertex {
+    joints: [u16; 4],
+    weights: [f32; 4],
+}
+
+impl SkinVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![4 => Uint16x4, 5 => Float32x4];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SkinVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// Per-vertex colors from the file, when there's one for every vertex
+fn vertex_colors(mesh: &Mesh) -> Option<Vec<[u8; 4]>> {
+    mesh.colors.clone().filter(|colors| colors.len() == mesh.vertices.len())
+}
+
+// Every vertex of a mesh without faces as a point, in the file's colors when it has them
+fn point_vertices(mesh: &Mesh) -> Vec<PointVertex> {
+    if !mesh.indices.is_empty() {
+        return Vec::new();
+    }
+    let colors = vertex_colors(mesh);
+    let default_color = batch::DEFAULT_COLOR.map(|channel| (channel * 255.0) as u8);
+    mesh.vertices
+        .iter()
+        .enumerate()
+        .map(|(i, &position)| PointVertex {
+            position,
+            color: colors.as_ref().map_or(default_color, |colors| colors[i]),
+        })
+        .collect()
+}
+
+// Index buffer contents, 16-bit when every index fits, which halves the buffer for
+// most models. Padded to the copy alignment since three 16-bit indices aren't.
+fn encode_indices(indices: &[u32]) -> (Vec<u8>, wgpu::IndexFormat) {
+    // 0xFFFF is left out, it restarts strips on some backends
+    if indices.iter().all(|&index| index < u16::MAX as u32) {
+        let short: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+        let mut data = bytemuck::cast_slice(&short).to_vec();
+        data.resize(data.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+        (data, wgpu::IndexFormat::Uint16)
+    } else {
+        (bytemuck::cast_slice(indices).to_vec(), wgpu::IndexFormat::Uint32)
+    }
+}
+
+// UV buffer contents when the mesh has something to texture with them
+fn encode_uvs(mesh: &Mesh) -> Option<Vec<u8>> {
+    let uvs = mesh.uvs.as_ref().filter(|uvs| uvs.len() == mesh.vertices.len() && !mesh.textures.is_empty())?;
+    Some(bytemuck::cast_slice(uvs).to_vec())
+}
+
+// Skin buffer contents when the mesh has a rig to pose it with
+fn encode_skin(mesh: &Mesh) -> Option<Vec<u8>> {
+    mesh.rig.as_ref()?;
+    let (joints, weights) = (mesh.joints.as_ref()?, mesh.weights.as_ref()?);
+    if joints.len() != mesh.vertices.len() || weights.len() != mesh.vertices.len() {
+        return None;
+    }
+    let skin: Vec<SkinVertex> = joints.iter().zip(weights).map(|(&joints, &weights)| SkinVertex { joints, weights }).collect();
+    Some(bytemuck::cast_slice(&skin).to_vec())
+}
+
+// The highest sample count up to `requested` that every format can render with. 1 and 4
+// always work; other counts need adapter-specific format features.
+fn supported_sample_count(adapter: &wgpu::Adapter, features: wgpu::Features, formats: &[wgpu::TextureFormat], requested: u32) -> u32 {
+    let adapter_specific = features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    [8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| if adapter_specific {
+            formats.iter().all(|&format| adapter.get_texture_format_features(format).flags.sample_count_supported(count))
+        } else {
+            count == 4
+        })
+        .unwrap_or(1)
+}
+
+// Maps compact positions back to model space: position = stored * scale + offset
+#[derive(Copy, Clone)]
+struct Quantization {
+    scale: Vector3<f32>,
+    offset: Vector3<f32>,
+}
+
+// Packs positions for the GPU, quantized when compact and full precision otherwise
+// Missing normals are written as zero, which the shader replaces with the face normal
+fn encode_vertices(positions: &[[f32; 3]], normals: Option<&[[f32; 3]]>, compact: bool) -> (Vec<u8>, Option<Quantization>) {
+    let normals = normals.filter(|normals| normals.len() == positions.len());
+    let normal = |i: usize| normals.map_or([0.0; 3], |normals| normals[i]);
+
+    if !compact {
+        let vertices: Vec<Vertex> = positions.iter().enumerate().map(|(i, &p)| Vertex { position: p, normal: normal(i) }).collect();
+        return (bytemuck::cast_slice(&vertices).to_vec(), None);
+    }
+
+    let (min, max, center, _) = Viewer::calculate_model_bounds(positions);
+    // Flat axes still need a non-zero scale to divide by
+    let half_extent = ((max - min) / 2.0).map(|h| h.max(f32::MIN_POSITIVE));
+
+    let vertices: Vec<CompactVertex> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let q = [0, 1, 2].map(|axis| {
+                let normalized = ((p[axis] - center[axis]) / half_extent[axis]).clamp(-1.0, 1.0);
+                (normalized * i16::MAX as f32).round() as i16
+            });
+            let n = normal(i).map(|c| (c.clamp(-1.0, 1.0) * i8::MAX as f32).round() as i8);
+            CompactVertex { position: [q[0], q[1], q[2], 0], normal: [n[0], n[1], n[2], 0] }
+        })
+        .collect();
+
+    let quantization = Quantization {
+        scale: half_extent,
+        offset: center,
+    };
+    (bytemuck::cast_slice(&vertices).to_vec(), Some(quantization))
+}
+
+impl<'a> Viewer<'a> {
+    fn calculate_model_bounds(vertices: &[[f32; 3]]) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>, f32) {
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+
+        for vertex in vertices {
+            min.x = min.x.min(vertex[0]);
+            min.y = min.y.min(vertex[1]);
+            min.z = min.z.min(vertex[2]);
+            
+            max.x = max.x.max(vertex[0]);
+            max.y = max.y.max(vertex[1]);
+            max.z = max.z.max(vertex[2]);
+        }
+
+        let center = (min + max) / 2.0;
+        let size = max - min;
+        let max_dimension = size.x.max(size.y).max(size.z);
+
+        (min, max, center, max_dimension)
+    }
+
+    // Without a window everything is set up the same, but only capture_view has somewhere to draw
+    pub async fn new(window: Option<&'a winit::window::Window>, size: winit::dpi::PhysicalSize<u32>, options: &ViewerOptions, config: &Config, session: Option<Session>, waker: Waker) -> Self {
+        let instance = wgpu::Instance::default();
+        let surface = window.map(|window| instance.create_surface(window).unwrap());
+
+        let mut file_to_load = options.file.clone().unwrap_or_else(|| "test_files/cows".to_string());
+        let mut side_paths = options.side_by_side.clone();
+        let split = options.compare.clone().filter(|_| options.split);
+        if let Some(reference) = &split {
+            side_paths = vec![reference.clone()];
+        }
+
+        // Parsing runs alongside GPU setup and keeps going after the window is up
+        let mut load_options = LoadOptions {
+            optimize: options.optimize,
+            clean: options.clean,
+            fix_winding: options.fix_winding,
+            unit_scale: options.unit_scale,
+            unit: options.unit,
+            min_shell_triangles: options.min_shell_triangles,
+            isolate_shell: None,
+            budget: config.budget,
+            fit_budget: false,
+            compare: options.compare.clone(),
+            preview_triangles: options.preview_triangles,
+            lod_triangles: options.lod_triangles,
+        };
+        // A session brings its own model and the options it was loaded with
+        if let Some(session) = &session {
+            let model = &session.models[0];
+            file_to_load = model.path.clone();
+            load_options.optimize = model.optimize;
+            load_options.clean = model.clean;
+            load_options.fix_winding = model.fix_winding;
+            load_options.unit_scale = model.unit_scale;
+            load_options.unit = session.render.unit;
+            load_options.min_shell_triangles = model.min_shell_triangles;
+            load_options.isolate_shell = model.isolate_shell;
+            load_options.compare = model.compare.clone();
+            side_paths = session.models[1..].iter().map(|model| model.path.clone()).collect();
+        }
+        let loader = spawn_loader(file_to_load.clone(), load_options.clone(), waker.clone());
+        let side_loader = (!side_paths.is_empty()).then(|| spawn_side_loader(side_paths.clone(), waker.clone()));
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: surface.as_ref(),
+                force_fallback_adapter: false,
+            })
+            .await
+            .expect("Failed to find an appropriate adapter");
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Device"),
+                    // Timestamps, multi-draw, pipeline caching and wireframes are optional, all have fallbacks without them
+                    // Adapter-specific format features allow sample counts other than 1 and 4
+                    required_features: adapter.features()
+                        & (wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::PIPELINE_CACHE | DrawBatch::features()
+                            | wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::POLYGON_MODE_POINT
+                            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
+                    required_limits: wgpu::Limits::default(),
+                    memory_hints: Default::default(),
+                    trace: wgpu::Trace::default(),
+            },
+        )
+        .await
+        .expect("Failed to create device");
+
+        let surface_caps = match &surface {
+            Some(surface) => surface.get_capabilities(&adapter),
+            None => wgpu::SurfaceCapabilities {
+                formats: vec![wgpu::TextureFormat::Rgba8UnormSrgb],
+                present_modes: vec![wgpu::PresentMode::Fifo],
+                ..Default::default()
+            },
+        };
+        let surface_format = surface_caps.formats[0];
+        let present_modes: Vec<wgpu::PresentMode> = [
+            wgpu::PresentMode::Fifo,
+            wgpu::PresentMode::Mailbox,
+            wgpu::PresentMode::Immediate,
+        ]
+        .into_iter()
+        .filter(|mode| surface_caps.present_modes.contains(mode))
+        .collect();
+        let present_mode = if present_modes.contains(&options.present_mode) {
+            options.present_mode
+        } else {
+            println!("Present mode: {:?} isn't supported, using Fifo", options.present_mode);
+            wgpu::PresentMode::Fifo
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![surface_format],
+            desired_maximum_frame_latency: 2,
+        };
+
+        // Geometry arrives from the loader thread; start with an empty placeholder
+        let vertex_buffer = DynamicBuffer::new(&device, "Vertex Buffer", wgpu::BufferUsages::VERTEX, 0);
+        let index_buffer = DynamicBuffer::new(&device, "Index Buffer", wgpu::BufferUsages::INDEX, 0);
+        let uv_buffer = DynamicBuffer::new(&device, "UV Buffer", wgpu::BufferUsages::VERTEX, 0);
+        let color_buffer = DynamicBuffer::new(&device, "Vertex Color Buffer", wgpu::BufferUsages::VERTEX, 0);
+        let skin_buffer = DynamicBuffer::new(&device, "Skin Buffer", wgpu::BufferUsages::VERTEX, 0);
+        let model_scale = 1.0;
+        let center = Vector3::zero();
+        // Adjust this to zoom in/out; a row of models backs off to fit all of them
+        let camera_distance = if split.is_some() { 3.0 } else { 3.0 + MODEL_SPACING * side_paths.len() as f32 };
+
+        let object_uniforms = ObjectUniforms::new(&device, "Object Uniforms");
+
+        if let Some(surface) = &surface {
+            surface.configure(&device, &config);
+        }
+
+        let sample_count = supported_sample_count(&adapter, device.features(), &[config.format, DEPTH_FORMAT], options.msaa_samples);
+        if sample_count != options.msaa_samples {
+            println!("MSAA: {}x isn't supported, using {}x", options.msaa_samples, sample_count);
+        }
+        let framebuffer = Framebuffer::new(config.format, DEPTH_FORMAT, sample_count, config.width, config.height);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader.wgsl"));
+        let pipeline_cache = DiskPipelineCache::load(&device, &adapter.get_info());
+
+        let draw_batch = DrawBatch::new(&device, &queue);
+        let joint_matrices = JointMatrices::supported(&adapter, &device).then(|| JointMatrices::new(&device));
+
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[object_uniforms.layout(), draw_batch.texture_layout()],
+            push_constant_ranges: &[],
+        });
+        let skinned_pipeline_layout = joint_matrices.as_ref().map(|joint_matrices| device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skinned Render Pipeline Layout"),
+            bind_group_layouts: &[object_uniforms.layout(), draw_batch.texture_layout(), joint_matrices.layout()],
+            push_constant_ranges: &[],
+        }));
+
+        let create_pipeline = |label: &str, layout: &wgpu::PipelineLayout, buffers: &[wgpu::VertexBufferLayout], vertex_entry: &str, fragment_entry: &str, polygon_mode: wgpu::PolygonMode, cull_mode: Option<wgpu::Face>| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            cache: pipeline_cache.as_ref().map(|c| c.cache()),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some(vertex_entry),
+                buffers,
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(fragment_entry),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode,
+                unclipped_depth: false,
+                polygon_mode,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        let mut polygon_modes = vec![wgpu::PolygonMode::Fill];
+        if device.features().contains(wgpu::Features::POLYGON_MODE_LINE) {
+            polygon_modes.push(wgpu::PolygonMode::Line);
+        }
+        if device.features().contains(wgpu::Features::POLYGON_MODE_POINT) {
+            polygon_modes.push(wgpu::PolygonMode::Point);
+        }
+        // Skinned variants read the joints and weights from the buffer after the others
+        let color_sources = |mode: wgpu::PolygonMode, clipped: bool, layout: &wgpu::PipelineLayout, skin: Option<wgpu::VertexBufferLayout<'static>>, vertex_entries: [&str; 3]| {
+            let pipeline = |label: &str, buffers: &[wgpu::VertexBufferLayout], vertex_entry: &str, fragment_entry: &str| {
+                let buffers: Vec<wgpu::VertexBufferLayout> = buffers.iter().cloned().chain(skin.clone()).collect();
+                let label = if skin.is_some() { format!("Skinned {}", label) } else { label.to_string() };
+                match clipped {
+                    true => create_pipeline(&format!("Clipped {}", label), layout, &buffers, vertex_entry, &format!("{}_clipped", fragment_entry), mode, None),
+                    false => create_pipeline(&label, layout, &buffers, vertex_entry, fragment_entry, mode, Some(wgpu::Face::Back)),
+                }
+            };
+            [
+                [
+                    pipeline("Render Pipeline", &[Vertex::desc(), batch::DrawData::desc()], vertex_entries[0], "fs_main"),
+                    pipeline("Compact Render Pipeline", &[CompactVertex::desc(), batch::DrawData::desc()], vertex_entries[0], "fs_main"),
+                ],
+                [
+                    pipeline("Textured Render Pipeline", &[Vertex::desc(), batch::DrawData::desc(), TexCoord::desc()], vertex_entries[1], "fs_textured"),
+                    pipeline("Compact Textured Render Pipeline", &[CompactVertex::desc(), batch::DrawData::desc(), TexCoord::desc()], vertex_entries[1], "fs_textured"),
+                ],
+                [
+                    pipeline("Vertex Color Render Pipeline", &[Vertex::desc(), batch::DrawData::desc(), VertexColor::desc()], vertex_entries[2], "fs_main"),
+                    pipeline("Compact Vertex Color Render Pipeline", &[CompactVertex::desc(), batch::DrawData::desc(), VertexColor::desc()], vertex_entries[2], "fs_main"),
+                ],
+            ]
+        };
+        let scene_pipelines = polygon_modes
+            .into_iter()
+            .map(|mode| {
+                let pipelines = [false, true].map(|clipped| {
+                    let mut pipelines = vec![color_sources(mode, clipped, &render_pipeline_layout, None, ["vs_main", "vs_textured", "vs_vertex_color"])];
+                    if let Some(layout) = &skinned_pipeline_layout {
+                        pipelines.push(color_sources(mode, clipped, layout, Some(SkinVertex::desc()), ["vs_skinned", "vs_textured_skinned", "vs_vertex_color_skinned"]));
+                    }
+                    pipelines
+                });
+                (mode, pipelines)
+            })
+            .collect();
+        let gpu_timer = GpuTimer::new(&device, &queue, &["Scene"]);
+        let profile = options.profile.as_ref().and_then(|path| {
+            let gpu_passes = gpu_timer.as_ref().map_or(&[][..], GpuTimer::pass_names);
+            match ProfileLog::create(Path::new(path), gpu_passes) {
+                Ok(log) => {
+                    println!("Profiling frames to {}", path);
+                    Some(log)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            }
+        });
+        let overlay = Overlay::new(&device, config.format, window.map_or(1.0, |window| window.scale_factor() as f32));
+        let frame_graph = FrameGraph::new(&device, config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let background_pass = BackgroundPass::new(&device, config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let environment = options.environment.as_ref().and_then(|path| {
+            match environment::load_environment(Path::new(path), device.limits().max_texture_dimension_2d) {
+                Ok(image) => {
+                    println!("Environment: {} ({}x{})", path, image.width, image.height);
+                    Some(EnvironmentPass::new(&device, &queue, &image, config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache())))
+                }
+                Err(e) => {
+                    eprintln!("Failed to load environment {}: {}", path, e);
+                    None
+                }
+            }
+        });
+        let lines = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let edge_lines = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let normal_lines = Lines::depth_tested(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let grid_lines = Lines::depth_tested(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let measurement_lines = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let selection_lines = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let bounds_lines = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let axis_lines = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let splitter = Lines::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let points = Points::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        let uv_view = UvView::new(&device, object_uniforms.layout(), config.format, DEPTH_FORMAT, sample_count, pipeline_cache.as_ref().map(|c| c.cache()));
+        if let Some(pipeline_cache) = &pipeline_cache {
+            pipeline_cache.save();
+        }
+
+        let (view_yaw, view_elevation) = options.view.map_or(views::DEFAULT_VIEW, ViewPreset::angles);
+        let mut state = Self {
+            surface,
+            device,
+            queue,
+            config,
+            present_modes,
+            size,
+            pending_resize: None,
+            scene_pipelines,
+            polygon_mode: 0,
+            compact_vertices: options.compact_vertices,
+            quantization: None,
+            staging_belt: wgpu::util::StagingBelt::new(upload::STAGING_CHUNK_SIZE),
+            vertex_buffer,
+            index_buffer,
+            index_format: wgpu::IndexFormat::Uint32,
+            uv_buffer,
+            textured: false,
+            skin_buffer,
+            skinned: false,
+            rig: None,
+            joint_matrices,
+            animation_time: 0.0,
+            animation_playing: false,
+            color_buffer,
+            file_colors: None,
+            deviation: None,
+            // Side by side, the model's own shading is what's being compared
+            show_deviation: !options.split,
+            components: None,
+            show_components: false,
+            shell_count: 0,
+            vertex_colors: None,
+            show_parts: false,
+            flat_shading: false,
+            uv_checker: false,
+            debug_view: DebugView::Off,
+            curvature: None,
+            clip_plane: ClipPlane::default(),
+            explode: 0.0,
+            points,
+            point_size: POINT_SIZE,
+            hidden_parts: HashSet::new(),
+            pending_session: None,
+            num_indices: 0,
+            num_vertices: 0,
+            draw_batch,
+            loader: Some(loader),
+            draw_list: side_paths.iter().map(|_| None).collect(),
+            lods: Vec::new(),
+            culled_parts: 0,
+            grid: options.grid,
+            grid_spacing: 0.0,
+            side_paths,
+            side_loader,
+            split: split.map(|_| 0.5),
+            dragging_splitter: false,
+            splitter,
+            model_path: file_to_load,
+            load_options,
+            waker,
+            watcher: None,
+            recent: RecentFiles::load(),
+            model_failed: false,
+            parsing_since: Some(Instant::now()),
+            validation: None,
+            topology: None,
+            budget_violations: Vec::new(),
+            streamed_vertices: 0,
+            pending_indices: Vec::new(),
+            pending_max_index: 0,
+            stream_min: Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+            stream_max: Vector3::new(f32::MIN, f32::MIN, f32::MIN),
+            background_upload: None,
+            showing_preview: false,
+            object_uniforms,
+            scene: None,
+            geometry_stats: None,
+            show_oriented_box: false,
+            unit: options.unit,
+            measurements: Measurements::default(),
+            section_plane: None,
+            section: None,
+            section_dirty: false,
+            lines,
+            manifold: None,
+            edge_lines,
+            show_manifold_edges: false,
+            grid_lines,
+            axis_lines,
+            show_grid: false,
+            bounds_lines,
+            measurement_lines,
+            selection: None,
+            selection_lines,
+            picks_dirty: false,
+            show_bounds: false,
+            normal_segments: None,
+            normal_lines,
+            normal_display: NormalDisplay::default(),
+            normals_dirty: false,
+            bounds_dirty: false,
+            model_bounds: None,
+            uv_layout: None,
+            uv_view,
+            show_uv_layout: false,
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            rotation: 0.0,
+            pitch: 0.0,
+            auto_rotate: options.view.is_none(),
+            rotation_speed: ROTATION_SPEED,
+            last_update: None,
+            gpu_timer,
+            frame_history: FrameHistory::default(),
+            profile,
+            event_time: Duration::ZERO,
+            frame_graph,
+            overlay,
+            show_frame_graph: false,
+            background: options.background,
+            background_pass,
+            environment,
+            framebuffer,
+            recorder: None,
+            last_recorded_frame: None,
+            model_scale,
+            model_center: center,
+            camera_distance,
+            view_yaw,
+            view_elevation,
+            fit_center: center,
+            fit_distance: camera_distance,
+            camera_transition: None,
+        };
+
+        if let Some(session) = session {
+            state.apply_render_settings(session.render);
+            state.pending_session = Some(session);
+        }
+        state
+    }
+
+    fn fit_model(&mut self, min: Vector3<f32>, max: Vector3<f32>) {
+        let size = max - min;
+        // Copies in a grid leave a quarter of the model's size between them, and the
+        // whole grid is fitted
+        self.grid_spacing = size.x.max(size.y).max(size.z) * 1.25;
+        let grid_max = max + Vector3::new((self.grid.0 - 1) as f32, 0.0, (self.grid.1 - 1) as f32) * self.grid_spacing;
+        let grid_size = grid_max - min;
+        let max_dimension = grid_size.x.max(grid_size.y).max(grid_size.z);
+
+        self.model_center = (min + grid_max) / 2.0;
+        self.fit_center = self.model_center;
+        self.model_scale = 2.0 / max_dimension; // Scale to fit in a 2-unit cube
+        self.model_bounds = Some((min, max));
+        self.bounds_dirty = true;
+    }
+
+    fn poll_loader(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(loader) = &self.loader else {
+            return;
+        };
+
+        let events: Vec<LoadEvent> = loader.try_iter().collect();
+        for event in events {
+            match event {
+                LoadEvent::Chunk(chunk) => self.append_chunk(encoder, chunk),
+                LoadEvent::Fallback(_) => self.model_failed = true,
+                LoadEvent::Preview(mesh) => {
+                    self.parsing_since = None;
+                    self.set_mesh(encoder, mesh);
+                    self.showing_preview = true;
+                }
+                LoadEvent::Loaded(mesh) if self.showing_preview => {
+                    self.parsing_since = None;
+                    self.remember_model();
+                    self.watch_model(&mesh);
+                    self.begin_background_upload(mesh);
+                }
+                LoadEvent::Loaded(mesh) => {
+                    self.parsing_since = None;
+                    self.remember_model();
+                    self.watch_model(&mesh);
+                    self.set_mesh(encoder, mesh);
+                }
+                LoadEvent::Lods(lods) => {
+                    self.lods = lods.iter().map(|lod| self.upload_draw_item(encoder, lod)).collect();
+                }
+                LoadEvent::Validation(report) => {
+                    self.validation = Some(report);
+                }
+                LoadEvent::Topology(report) => {
+                    self.topology = Some(report);
+                }
+                LoadEvent::OverBudget(violations) => {
+                    println!("Budget: Press B to simplify the model to the budget");
+                    self.budget_violations = violations;
+                }
+                LoadEvent::Stats(stats) => {
+                    self.geometry_stats = Some(stats);
+                }
+                LoadEvent::Manifold(manifold) => {
+                    self.edge_lines.set(&self.device, encoder, &mut self.staging_belt, &manifold.line_vertices());
+                    self.manifold = Some(manifold);
+                }
+                LoadEvent::Curvature(curvature) => {
+                    self.curvature = Some(curvature);
+                }
+                LoadEvent::Normals(segments) => {
+                    self.normal_segments = Some(segments);
+                    self.normals_dirty = true;
+                }
+                LoadEvent::Components(components) => {
+                    if self.load_options.isolate_shell.is_none() {
+                        self.shell_count = components.count();
+                    }
+                    self.components = Some(components);
+                }
+                LoadEvent::UvLayout(layout) => {
+                    self.uv_view.set(&self.device, encoder, &mut self.staging_belt, Some(&layout));
+                    self.uv_layout = Some(layout);
+                }
+                LoadEvent::Deviation(deviation) => {
+                    self.deviation = Some(deviation);
+                }
+                LoadEvent::Scene(scene) => {
+                    self.scene = Some(scene);
+                    self.section_dirty = true;
+                    self.loader = None;
+                }
+                LoadEvent::Failed(e) => {
+                    eprintln!("Failed to load default model: {}", e);
+                    self.parsing_since = None;
+                    self.loader = None;
+                }
+            }
+        }
+    }
+
+    // Uploads the models beside the main one as they finish loading
+    fn poll_side_loader(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(loader) = &self.side_loader else {
+            return;
+        };
+
+        let mut meshes = Vec::new();
+        loop {
+            match loader.try_recv() {
+                Ok(loaded) => meshes.push(loaded),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.side_loader = None;
+                    break;
+                }
+            }
+        }
+
+        for (index, mesh) in meshes {
+            println!("Side by side: {} with {} triangles", self.side_paths[index], mesh.indices.len() / 3);
+            let item = self.upload_draw_item(encoder, &mesh);
+            self.draw_list[index] = Some(item);
+        }
+    }
+
+    fn upload_draw_item(&mut self, encoder: &mut wgpu::CommandEncoder, mesh: &Mesh) -> DrawItem {
+        let (_, _, center, max_dimension) = Self::calculate_model_bounds(&mesh.vertices);
+        let (vertex_data, quantization) = encode_vertices(&mesh.vertices, mesh.normals.as_deref(), self.compact_vertices);
+        let uv_data = encode_uvs(mesh);
+
+        let mut vertex_buffer = DynamicBuffer::new(&self.device, "Vertex Buffer", wgpu::BufferUsages::VERTEX, 0);
+        vertex_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, &vertex_data);
+        let (index_data, index_format) = encode_indices(&mesh.indices);
+        let mut index_buffer = DynamicBuffer::new(&self.device, "Index Buffer", wgpu::BufferUsages::INDEX, 0);
+        index_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, &index_data);
+        let mut uv_buffer = DynamicBuffer::new(&self.device, "UV Buffer", wgpu::BufferUsages::VERTEX, 0);
+        if let Some(uv_data) = &uv_data {
+            uv_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, uv_data);
+        }
+        let mut draw_batch = DrawBatch::new(&self.device, &self.queue);
+        draw_batch.set(&self.device, &self.queue, encoder, &mut self.staging_belt, &mesh.submeshes, &mesh.textures);
+
+        DrawItem {
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            uv_buffer,
+            num_indices: mesh.indices.len() as u32,
+            textured: uv_data.is_some(),
+            quantization,
+            draw_batch,
+            center,
+            scale: 2.0 / max_dimension,
+        }
+    }
+
+    // Uploads a partially parsed piece of the model so it shows up before loading finishes
+    fn append_chunk(&mut self, encoder: &mut wgpu::CommandEncoder, chunk: MeshChunk) {
+        let vertex_size = std::mem::size_of::<Vertex>() as wgpu::BufferAddress;
+
+        if self.streamed_vertices == 0 {
+            // Streamed vertices stay full precision since the final bounds aren't known yet
+            self.quantization = None;
+            // The previous mesh's indices may be 16-bit, and refer to vertices being overwritten
+            self.index_format = wgpu::IndexFormat::Uint32;
+            self.num_indices = 0;
+            self.lods.clear();
+            self.textured = false;
+            self.skinned = false;
+            self.draw_batch.set(&self.device, &self.queue, encoder, &mut self.staging_belt, &[], &[]);
+            // The vertex total is known up front, so size the buffer once for the whole stream
+            self.vertex_buffer.reserve(&self.device, encoder, chunk.total_vertices as wgpu::BufferAddress * vertex_size, 0);
+        }
+
+        if !chunk.vertices.is_empty() {
+            // Normals need the faces around each vertex, which haven't all arrived yet
+            let vertices: Vec<Vertex> = chunk.vertices.iter().map(|&p| Vertex { position: p, normal: [0.0; 3] }).collect();
+            let offset = chunk.vertex_base as wgpu::BufferAddress * vertex_size;
+            self.vertex_buffer.write(&self.device, encoder, &mut self.staging_belt, offset, bytemuck::cast_slice(&vertices));
+
+            let (min, max, _, _) = Self::calculate_model_bounds(&chunk.vertices);
+            self.stream_min = Vector3::new(self.stream_min.x.min(min.x), self.stream_min.y.min(min.y), self.stream_min.z.min(min.z));
+            self.stream_max = Vector3::new(self.stream_max.x.max(max.x), self.stream_max.y.max(max.y), self.stream_max.z.max(max.z));
+            self.fit_model(self.stream_min, self.stream_max);
+
+            self.streamed_vertices = chunk.vertex_base + chunk.vertices.len();
+            self.num_vertices = self.streamed_vertices;
+        }
+
+        // Faces can only be drawn once every vertex they use has been uploaded
+        self.pending_max_index = chunk.indices.iter().copied().fold(self.pending_max_index, u32::max);
+        self.pending_indices.extend(chunk.indices);
+
+        if !self.pending_indices.is_empty() && (self.pending_max_index as usize) < self.streamed_vertices {
+            let offset = self.num_indices as wgpu::BufferAddress * 4;
+            self.index_buffer.write(&self.device, encoder, &mut self.staging_belt, offset, bytemuck::cast_slice(&self.pending_indices));
+            self.num_indices += self.pending_indices.len() as u32;
+            self.pending_indices.clear();
+            self.pending_max_index = 0;
+        }
+    }
+
+    // Replaces any streamed geometry with the final, fully processed mesh
+    fn set_mesh(&mut self, encoder: &mut wgpu::CommandEncoder, mesh: Mesh) {
+        let (min, max, _, _) = Self::calculate_model_bounds(&mesh.vertices);
+        self.fit_model(min, max);
+        self.hidden_parts.clear();
+
+        let (vertex_data, quantization) = encode_vertices(&mesh.vertices, mesh.normals.as_deref(), self.compact_vertices);
+        self.vertex_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, &vertex_data);
+        self.quantization = quantization;
+        let (index_data, index_format) = encode_indices(&mesh.indices);
+        self.index_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, &index_data);
+        self.index_format = index_format;
+        self.num_indices = mesh.indices.len() as u32;
+        self.num_vertices = mesh.vertices.len();
+
+        let uv_data = encode_uvs(&mesh);
+        if let Some(uv_data) = &uv_data {
+            self.uv_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, uv_data);
+        }
+        self.textured = uv_data.is_some();
+        let skin_data = encode_skin(&mesh);
+        if let Some(skin_data) = &skin_data {
+            self.skin_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, skin_data);
+        }
+        self.skinned = skin_data.is_some();
+        self.draw_batch.set_part_bounds(batch::part_bounds(&mesh));
+        self.draw_batch.set(&self.device, &self.queue, encoder, &mut self.staging_belt, &mesh.submeshes, &mesh.textures);
+        self.file_colors = vertex_colors(&mesh);
+        self.points.set(point_vertices(&mesh));
+        self.set_rig(mesh.rig.filter(|_| skin_data.is_some()));
+
+        self.lods.clear();
+        self.streamed_vertices = 0;
+        self.pending_indices = Vec::new();
+        self.pending_max_index = 0;
+        self.scene = None;
+        self.geometry_stats = None;
+        self.measurements.clear();
+        self.selection = None;
+        self.picks_dirty = true;
+        self.validation = None;
+        self.topology = None;
+        self.budget_violations = Vec::new();
+        self.deviation = None;
+        self.components = None;
+        self.vertex_colors = None;
+        self.manifold = None;
+        self.normal_segments = None;
+        self.normals_dirty = true;
+        self.curvature = None;
+        self.edge_lines.set(&self.device, encoder, &mut self.staging_belt, &[]);
+        self.section_dirty = true;
+        self.uv_layout = None;
+        self.uv_view.set(&self.device, encoder, &mut self.staging_belt, None);
+    }
+
+    fn begin_background_upload(&mut self, mesh: Mesh) {
+        let (min, max, _, _) = Self::calculate_model_bounds(&mesh.vertices);
+        let (vertex_data, quantization) = encode_vertices(&mesh.vertices, mesh.normals.as_deref(), self.compact_vertices);
+        if self.compact_vertices {
+            println!("Vertices: {:.1} MB compact instead of {:.1} MB",
+                     vertex_data.len() as f64 / 1_048_576.0,
+                     (mesh.vertices.len() * std::mem::size_of::<Vertex>()) as f64 / 1_048_576.0);
+        }
+
+        let uv_data = encode_uvs(&mesh);
+        let skin_data = encode_skin(&mesh);
+        let colors = vertex_colors(&mesh);
+        let part_bounds = batch::part_bounds(&mesh);
+
+        let vertex_bytes = vertex_data.len() as wgpu::BufferAddress;
+        let (index_data, index_format) = encode_indices(&mesh.indices);
+        let index_bytes = index_data.len() as wgpu::BufferAddress;
+        let uv_bytes = uv_data.as_ref().map_or(0, |uv_data| uv_data.len()) as wgpu::BufferAddress;
+        let skin_bytes = skin_data.as_ref().map_or(0, |skin_data| skin_data.len()) as wgpu::BufferAddress;
+
+        self.background_upload = Some(BackgroundUpload {
+            vertex_data,
+            quantization,
+            index_data,
+            index_format,
+            rig: mesh.rig.filter(|_| skin_data.is_some()),
+            uv_data,
+            skin_data,
+            vertex_count: mesh.vertices.len(),
+            index_count: mesh.indices.len(),
+            colors,
+            submeshes: mesh.submeshes,
+            part_bounds,
+            textures: mesh.textures,
+            min,
+            max,
+            vertex_buffer: DynamicBuffer::new(&self.device, "Vertex Buffer", wgpu::BufferUsages::VERTEX, vertex_bytes),
+            index_buffer: DynamicBuffer::new(&self.device, "Index Buffer", wgpu::BufferUsages::INDEX, index_bytes),
+            uv_buffer: DynamicBuffer::new(&self.device, "UV Buffer", wgpu::BufferUsages::VERTEX, uv_bytes),
+            skin_buffer: DynamicBuffer::new(&self.device, "Skin Buffer", wgpu::BufferUsages::VERTEX, skin_bytes),
+            vertex_bytes_written: 0,
+            index_bytes_written: 0,
+            uv_bytes_written: 0,
+            skin_bytes_written: 0,
+        });
+    }
+
+    // Continues the full-resolution upload and swaps it in once everything is on the GPU
+    fn step_background_upload(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(upload) = &mut self.background_upload else {
+            return;
+        };
+
+        let mut budget = UPLOAD_BYTES_PER_FRAME;
+        for (data, written, buffer) in [
+            (upload.vertex_data.as_slice(), &mut upload.vertex_bytes_written, &mut upload.vertex_buffer),
+            (upload.index_data.as_slice(), &mut upload.index_bytes_written, &mut upload.index_buffer),
+            (upload.uv_data.as_deref().unwrap_or(&[]), &mut upload.uv_bytes_written, &mut upload.uv_buffer),
+            (upload.skin_data.as_deref().unwrap_or(&[]), &mut upload.skin_bytes_written, &mut upload.skin_buffer),
+        ] {
+            let end = data.len().min(*written + budget);
+            if end > *written {
+                buffer.write(&self.device, encoder, &mut self.staging_belt, *written as wgpu::BufferAddress, &data[*written..end]);
+                budget -= end - *written;
+                *written = end;
+            }
+        }
+
+        if upload.vertex_bytes_written < upload.vertex_data.len()
+            || upload.index_bytes_written < upload.index_data.len()
+            || upload.uv_bytes_written < upload.uv_data.as_ref().map_or(0, |uv_data| uv_data.len())
+            || upload.skin_bytes_written < upload.skin_data.as_ref().map_or(0, |skin_data| skin_data.len()) {
+            return;
+        }
+
+        if let Some(upload) = self.background_upload.take() {
+            self.fit_model(upload.min, upload.max);
+            self.vertex_buffer = upload.vertex_buffer;
+            self.index_buffer = upload.index_buffer;
+            self.index_format = upload.index_format;
+            self.num_indices = upload.index_count as u32;
+            self.num_vertices = upload.vertex_count;
+            self.quantization = upload.quantization;
+            self.uv_buffer = upload.uv_buffer;
+            self.textured = upload.uv_data.is_some();
+            self.skin_buffer = upload.skin_buffer;
+            self.skinned = upload.skin_data.is_some();
+            self.set_rig(upload.rig);
+            self.file_colors = upload.colors;
+            self.draw_batch.set_part_bounds(upload.part_bounds);
+            self.draw_batch.set(&self.device, &self.queue, encoder, &mut self.staging_belt, &upload.submeshes, &upload.textures);
+            self.showing_preview = false;
+            println!("Preview: Swapped in the full resolution mesh");
+        }
+    }
+
+    // Leaves the main model's parts that are outside the view out of its draws, and out
+    // of its levels of detail's, which have the same parts. Skinned parts move outside
+    // their bounds, and parts in a grid have copies elsewhere, so neither is culled.
+    fn cull_parts(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let culled = if self.skinned || self.draw_batch.part_count() < 2 || self.grid != (1, 1) {
+            HashSet::new()
+        } else {
+            self.draw_batch.parts_outside(&Frustum::new(self.model_view_projection()))
+        };
+        self.draw_batch.set_culled(&self.device, encoder, &mut self.staging_belt, &culled);
+        for lod in &mut self.lods {
+            lod.draw_batch.set_culled(&self.device, encoder, &mut self.staging_belt, &culled);
+        }
+        self.culled_parts = culled.len();
+    }
+
+    // The level of detail the main model is drawn with at the current distance, numbered
+    // from 1, or None for the full mesh. Levels only carry positions, normals and part
+    // colors, so anything that needs more of the mesh draws it in full.
+    fn active_lod(&self) -> Option<(usize, &DrawItem)> {
+        if self.lods.is_empty() || self.camera_distance < LOD_DISTANCE || self.showing_preview {
+            return None;
+        }
+        let needs_full_mesh = self.textured
+            || self.skinned
+            || self.show_parts
+            || self.uv_checker
+            || self.wanted_vertex_colors().is_some()
+            || self.explode > 0.0
+            || !self.hidden_parts.is_empty();
+        if needs_full_mesh {
+            return None;
+        }
+
+        let level = ((self.camera_distance / LOD_DISTANCE).log2().floor() as usize + 1).min(self.lods.len());
+        Some((level, &self.lods[level - 1]))
+    }
+
+    // Shell colors win over the heatmap when both are on, and both over the file's colors
+    fn wanted_vertex_colors(&self) -> Option<VertexColors> {
+        if self.debug_view == DebugView::Curvature && self.curvature.is_some() {
+            Some(VertexColors::Curvature)
+        } else if self.show_components && self.components.is_some() {
+            Some(VertexColors::Components)
+        } else if self.show_deviation && self.deviation.is_some() {
+            Some(VertexColors::Deviation)
+        } else if self.file_colors.is_some() {
+            Some(VertexColors::File)
+        } else {
+            None
+        }
+    }
+
+    // Colors need the vertex buffer they color, so they wait for a preview swap to finish
+    fn upload_vertex_colors(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let wanted = self.wanted_vertex_colors();
+        if wanted.is_none() || wanted == self.vertex_colors || self.background_upload.is_some() {
+            return;
+        }
+
+        let colors = match wanted {
+            Some(VertexColors::Components) => self.components.as_ref().map(Components::colors),
+            Some(VertexColors::Deviation) => self.deviation.as_ref().map(Deviation::colors),
+            Some(VertexColors::Curvature) => self.curvature.as_ref().map(Curvature::colors),
+            Some(VertexColors::File) => self.file_colors.clone(),
+            None => None,
+        };
+        if let Some(colors) = colors {
+            self.color_buffer.write(&self.device, encoder, &mut self.staging_belt, 0, bytemuck::cast_slice(&colors));
+            self.vertex_colors = wanted;
+        }
+    }
+
+    // Whether the next frame will look different even without any input
+    pub fn is_animating(&self) -> bool {
+        self.auto_rotate || self.background_upload.is_some() || self.points.is_uploading() || self.animation_playing || self.camera_transition.is_some() || self.parsing_since.is_some()
+    }
+
+    // A new skeleton starts its first animation from the beginning
+    fn set_rig(&mut self, rig: Option<Rig>) {
+        if rig.is_some() && self.joint_matrices.is_none() {
+            println!("Animation: The GPU can't skin, showing the bind pose");
+        }
+        let rig = rig.filter(|_| self.joint_matrices.is_some());
+        if let Some(rig) = &rig {
+            let clip = rig.clips.first();
+            println!("Animation: {} joints, {}", rig.joint_count(),
+                     clip.map_or_else(|| "no animations".to_string(), |clip| format!("playing {} ({:.2} s)", clip.name, clip.duration())));
+        }
+        self.animation_time = 0.0;
+        self.animation_playing = rig.as_ref().is_some_and(|rig| !rig.clips.is_empty());
+        self.rig = rig;
+    }
+
+    // The first animation's length, when the model has one
+    fn animation_duration(&self) -> Option<f32> {
+        self.rig.as_ref()?.clips.first().map(Clip::duration)
+    }
+
+    // P pauses and resumes the animation
+    fn toggle_animation(&mut self) {
+        if self.animation_duration().is_none() {
+            return;
+        }
+        self.animation_playing = !self.animation_playing;
+        println!("Animation: {}", if self.animation_playing { "Playing" } else { "Paused" });
+    }
+
+    // Jumps to a time in the animation from the panel's scrub bar, which pauses it
+    fn seek_animation(&mut self, time: f32) {
+        if let Some(duration) = self.animation_duration() {
+            self.animation_time = time.clamp(0.0, duration);
+            self.animation_playing = false;
+        }
+    }
+
+    fn update(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.reload_if_changed();
+        self.poll_loader(encoder);
+        self.poll_side_loader(encoder);
+        self.step_background_upload(encoder);
+        self.points.step_upload(&self.device, encoder, &mut self.staging_belt);
+        self.upload_vertex_colors(encoder);
+        self.restore_session();
+        self.draw_batch.color_by_part(&self.device, encoder, &mut self.staging_belt, self.show_parts);
+        self.draw_batch.set_explode(&self.device, encoder, &mut self.staging_belt, self.explode);
+        self.draw_batch.set_hidden(&self.device, encoder, &mut self.staging_belt, &self.hidden_parts);
+        self.draw_batch.set_grid(&self.device, encoder, &mut self.staging_belt, self.grid, self.grid_spacing);
+        for lod in &mut self.lods {
+            lod.draw_batch.set_grid(&self.device, encoder, &mut self.staging_belt, self.grid, self.grid_spacing);
+        }
+        self.cull_parts(encoder);
+        for item in self.draw_list.iter_mut().flatten() {
+            item.draw_batch.color_by_part(&self.device, encoder, &mut self.staging_belt, self.show_parts);
+        }
+        if self.section_dirty {
+            self.update_section(encoder);
+        }
+        if self.picks_dirty {
+            self.update_pick_lines(encoder);
+        }
+        if self.bounds_dirty && (self.show_grid || self.show_bounds) {
+            self.update_bounds_lines(encoder);
+        }
+        if self.normals_dirty && self.normal_display.is_shown() {
+            self.update_normal_lines(encoder);
+        }
+
+        let now = Instant::now();
+        let delta = self.last_update.map_or(Duration::ZERO, |last| (now - last).min(MAX_FRAME_STEP)).as_secs_f32();
+        self.last_update = Some(now);
+
+        if self.auto_rotate {
+            self.rotation += self.rotation_speed * delta;
+        }
+        if let Some(transition) = &mut self.camera_transition {
+            let (pose, done) = transition.step(delta);
+            self.set_camera_pose(pose);
+            if done {
+                self.camera_transition = None;
+            }
+        }
+        if self.animation_playing && let Some(duration) = self.animation_duration() {
+            self.animation_time = if duration > 0.0 { (self.animation_time + delta) % duration } else { 0.0 };
+        }
+        if self.skinned && let (Some(rig), Some(joint_matrices)) = (&self.rig, &mut self.joint_matrices) {
+            let matrices = rig.joint_matrices(rig.clips.first(), self.animation_time);
+            joint_matrices.write(&self.device, encoder, &mut self.staging_belt, &matrices);
+        }
+
+        self.background_pass.set(&self.device, encoder, &mut self.staging_belt, self.background);
+        if let Some(ray_transform) = (self.projection() * Matrix4::from(Self::camera_rotation(self.view_yaw, self.view_elevation))).invert()
+            && let Some(environment) = &mut self.environment
+        {
+            environment.set(&self.device, encoder, &mut self.staging_belt, ray_transform);
+        }
+
+        let quantization = self.active_lod().map_or(self.quantization, |(_, lod)| lod.quantization);
+        let model_uniforms = self.model_uniforms(self.model_matrix(), quantization);
+        let uniforms = Uniforms {
+            clip_plane: self.model_bounds.map_or([0.0; 4], |(min, max)| self.clip_plane.equation(min, max)),
+            display: self.display_uniform(model_uniforms.camera_position),
+            ..model_uniforms
+        };
+        self.object_uniforms.write(&self.device, encoder, &mut self.staging_belt, MODEL_OBJECT, &uniforms);
+
+        let side_uniforms: Vec<(u32, Uniforms)> = self.draw_list
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let item = item.as_ref()?;
+                // Compared models share the main model's fit, so they line up
+                let model = match self.split {
+                    Some(_) => self.model_matrix(),
+                    None => self.placement(index + 1, item.scale, item.center),
+                };
+                Some((FIRST_SIDE_OBJECT + index as u32, self.model_uniforms(model, item.quantization)))
+            })
+            .collect();
+        for (slot, side_uniforms) in &side_uniforms {
+            self.object_uniforms.write(&self.device, encoder, &mut self.staging_belt, *slot, side_uniforms);
+        }
+
+        let overlay_uniforms = Uniforms {
+            position_scale: [1.0; 4],
+            position_offset: [0.0; 4],
+            ..uniforms
+        };
+        self.object_uniforms.write(&self.device, encoder, &mut self.staging_belt, OVERLAY_OBJECT, &overlay_uniforms);
+
+        let point_uniforms = Uniforms {
+            display: [self.point_size, self.size.width as f32, self.size.height as f32, POINT_SIZE_DISTANCE],
+            ..overlay_uniforms
+        };
+        self.object_uniforms.write(&self.device, encoder, &mut self.staging_belt, POINTS_OBJECT, &point_uniforms);
+
+        let uv_uniforms = Uniforms {
+            mvp: UvView::view_projection(self.size.width, self.size.height).into(),
+            ..overlay_uniforms
+        };
+        self.object_uniforms.write(&self.device, encoder, &mut self.staging_belt, UV_OBJECT, &uv_uniforms);
+
+        if let Some(split) = self.split {
+            let x = split * 2.0 - 1.0;
+            let vertices = [-1.0, 1.0].map(|y| LineVertex { position: [x, y, 0.5], color: SPLITTER_COLOR });
+            self.splitter.set(&self.device, encoder, &mut self.staging_belt, &vertices);
+            let splitter_uniforms = Uniforms {
+                mvp: Matrix4::identity().into(),
+                ..overlay_uniforms
+            };
+            self.object_uniforms.write(&self.device, encoder, &mut self.staging_belt, SPLITTER_OBJECT, &splitter_uniforms);
+        }
+    }
+
+    // X cycles the section plane through the three axes and off, starting at the model's middle
+    fn cycle_section_axis(&mut self) {
+        let Some((min, max)) = self.scene.as_ref().and_then(|scene| scene.bvh().bounds()) else {
+            return;
+        };
+
+        self.section_plane = match self.section_plane {
+            None => Some((0, (min.x + max.x) * 0.5)),
+            Some((2, _)) => None,
+            Some((axis, _)) => Some((axis + 1, (min[axis + 1] + max[axis + 1]) * 0.5)),
+        };
+        self.section_dirty = true;
+    }
+
+    fn move_section_plane(&mut self, steps: f32) {
+        let (Some((axis, position)), Some((min, max))) = (self.section_plane, self.scene.as_ref().and_then(|scene| scene.bvh().bounds())) else {
+            return;
+        };
+
+        let position = (position + steps * SECTION_STEP * (max[axis] - min[axis])).clamp(min[axis], max[axis]);
+        self.section_plane = Some((axis, position));
+        self.section_dirty = true;
+    }
+
+    // Recuts the mesh after the plane or the mesh changed
+    fn update_section(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.section_dirty = false;
+        self.section = match (self.section_plane, &self.scene) {
+            (Some((axis, position)), Some(scene)) => Some(Section::cut(scene.bvh(), axis, position)),
+            _ => None,
+        };
+
+        let vertices: Vec<LineVertex> = self.section
+            .iter()
+            .flat_map(|section| section.segments.iter().flatten())
+            .map(|&p| LineVertex { position: p.into(), color: SECTION_COLOR })
+            .collect();
+        self.lines.set(&self.device, encoder, &mut self.staging_belt, &vertices);
+
+        if let Some(section) = &self.section {
+            println!("Section: {}", section.summary(self.unit));
+        }
+    }
+
+    // Rebuilds the grid, axes and box for the current bounds; only while one is shown,
+    // since streaming moves the bounds with every chunk
+    fn update_bounds_lines(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.bounds_dirty = false;
+        let (grid, axes, bounds) = match self.model_bounds {
+            Some((min, max)) => (grid::grid_vertices(min, max), grid::axis_vertices(min, max), lines::box_vertices(min.into(), max.into(), BOUNDS_COLOR)),
+            None => (Vec::new(), Vec::new(), Vec::new()),
+        };
+        self.grid_lines.set(&self.device, encoder, &mut self.staging_belt, &grid);
+        self.axis_lines.set(&self.device, encoder, &mut self.staging_belt, &axes);
+        self.bounds_lines.set(&self.device, encoder, &mut self.staging_belt, &bounds);
+    }
+
+    fn set_normal_display(&mut self, display: NormalDisplay) {
+        if display != self.normal_display {
+            self.normal_display = display;
+            self.normals_dirty = true;
+        }
+    }
+
+    // Rebuilds the normal segments at the current length; only while they're shown, as
+    // a large mesh makes a lot of them
+    fn update_normal_lines(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.normals_dirty = false;
+        let vertices = match (&self.normal_segments, self.model_bounds) {
+            (Some(segments), Some((min, max))) => {
+                let size = (max - min).x.max((max - min).y).max((max - min).z);
+                segments.line_vertices(&self.normal_display, self.normal_display.length * size)
+            }
+            _ => Vec::new(),
+        };
+        self.normal_lines.set(&self.device, encoder, &mut self.staging_belt, &vertices);
+    }
+
+    // Uniforms for drawing a model with this model matrix
+    fn model_uniforms(&self, model: Matrix4<f32>, quantization: Option<Quantization>) -> Uniforms {
+        let (scale, offset) = match quantization {
+            Some(q) => (q.scale.extend(1.0).into(), q.offset.extend(0.0).into()),
+            None => ([1.0; 4], [0.0; 4]),
+        };
+        // The model matrix only rotates and scales uniformly, so undoing the rotation is
+        // enough to bring the light into model space
+        let (default_yaw, default_elevation) = views::DEFAULT_VIEW;
+        let light = Self::camera_rotation(self.view_yaw, self.view_elevation).transpose()
+            * Self::camera_rotation(default_yaw, default_elevation)
+            * LIGHT_DIRECTION.normalize();
+        // The environment is fixed in the world, so it's the model that turns in it
+        let orientation = self.orientation();
+        let light = orientation.transpose() * light;
+        let camera = model.invert().map_or(Vector4::zero(), |inverse| inverse * self.camera_position().to_homogeneous());
+        Uniforms {
+            mvp: (self.view_projection() * model).into(),
+            position_scale: scale,
+            position_offset: offset,
+            light_direction: light.extend(if self.flat_shading { 1.0 } else { 0.0 }).into(),
+            camera_position: camera.into(),
+            environment_rotation: [orientation.x, orientation.y, orientation.z].map(|column| column.extend(0.0).into()),
+            ambient: self.environment.as_ref().map_or_else(environment::flat_ambient, EnvironmentPass::ambient),
+            clip_plane: [0.0; 4],
+            display: [0.0; 4],
+        }
+    }
+
+    // The checker and debug view for the main model, with the depth view spanning its
+    // bounds as seen from the camera, in model space
+    fn display_uniform(&self, camera: [f32; 4]) -> [f32; 4] {
+        let checker = if self.uv_checker { CHECKER_SQUARES } else { 0.0 };
+        let (near, far) = match self.model_bounds {
+            Some((min, max)) => {
+                let distance = (Vector3::new(camera[0], camera[1], camera[2]) - (min + max) * 0.5).magnitude();
+                let radius = (max - min).magnitude() * 0.5;
+                ((distance - radius).max(0.0), distance + radius)
+            }
+            None => (0.0, 1.0),
+        };
+        [checker, self.debug_view.shader_mode(), near, far]
+    }
+
+    fn orientation(&self) -> Matrix3<f32> {
+        Matrix3::from_angle_x(Rad(self.pitch)) * Matrix3::from_angle_y(Rad(self.rotation))
+    }
+
+    fn model_matrix(&self) -> Matrix4<f32> {
+        self.placement(0, self.model_scale, self.model_center)
+    }
+
+    // Model matrix for the model at `slot` in the row, 0 being the main one. Every model
+    // turns around its own center, which the row spreads across the view; an A/B
+    // comparison has no row.
+    fn placement(&self, slot: usize, scale: f32, center: Vector3<f32>) -> Matrix4<f32> {
+        let position = match self.split {
+            Some(_) => 0.0,
+            None => (slot as f32 - self.draw_list.len() as f32 / 2.0) * MODEL_SPACING,
+        };
+        Matrix4::from_translation(ROW_DIRECTION * position) *
+        Matrix4::from(self.orientation()) * 
+        Matrix4::from_scale(scale) * 
+        Matrix4::from_translation(-center)
+    }
+
+    fn camera_position(&self) -> Point3<f32> {
+        let (yaw, elevation) = (self.view_yaw, self.view_elevation);
+        let direction = Vector3::new(elevation.cos() * yaw.sin(), elevation.sin(), elevation.cos() * yaw.cos());
+        Point3::from_vec(direction * self.camera_distance * CAMERA_DISTANCE_FACTOR)
+    }
+
+    // World to view, without the translation. Built from the angles rather than looking
+    // at the origin, so straight down and straight up work too.
+    fn camera_rotation(yaw: f32, elevation: f32) -> Matrix3<f32> {
+        Matrix3::from_angle_x(Rad(elevation)) * Matrix3::from_angle_y(Rad(-yaw))
+    }
+
+    fn model_view_projection(&self) -> Matrix4<f32> {
+        self.view_projection() * self.model_matrix()
+    }
+
+    fn view_projection(&self) -> Matrix4<f32> {
+        // Orbiting the origin, where the model is centered
+        let view = Matrix4::from_translation(-Vector3::unit_z() * self.camera_distance * CAMERA_DISTANCE_FACTOR)
+            * Matrix4::from(Self::camera_rotation(self.view_yaw, self.view_elevation));
+        
+        self.projection() * view
+    }
+
+    fn projection(&self) -> Matrix4<f32> {
+        let aspect_ratio = self.size.width as f32 / self.size.height as f32;
+        perspective(Rad(std::f32::consts::FRAC_PI_4), aspect_ratio, 0.1, 100.0)
+    }
+
+    // Re-centers the orbit on the surface point under the cursor
+    // Casts a ray from the cursor into the model, once the BVH is ready
+    fn pick_at_cursor(&self) -> Option<(&Scene, Hit)> {
+        let scene = self.scene.as_ref()?;
+        let inverse = self.model_view_projection().invert()?;
+
+        // Unproject two depths under the cursor to get a ray in model space
+        let ndc_x = (2.0 * self.cursor.x / self.size.width as f64 - 1.0) as f32;
+        let ndc_y = (1.0 - 2.0 * self.cursor.y / self.size.height as f64) as f32;
+        let near = inverse * Vector4::new(ndc_x, ndc_y, 0.0, 1.0);
+        let far = inverse * Vector4::new(ndc_x, ndc_y, 1.0, 1.0);
+        let origin = near.truncate() / near.w;
+        let direction = far.truncate() / far.w - origin;
+
+        scene.raycast(origin, direction).map(|hit| (scene, hit))
+    }
+
+    pub fn focus_at_cursor(&mut self) {
+        if let Some((_, hit)) = self.pick_at_cursor() {
+            self.camera_transition = None;
+            self.model_center = hit.point;
+        }
+    }
+
+    // Picks the triangle under the cursor, or clears the pick when there's none
+    pub fn select_at_cursor(&mut self) {
+        self.selection = self.pick_at_cursor().map(|(_, hit)| hit);
+        self.picks_dirty = true;
+        if let (Some(hit), Some(scene)) = (&self.selection, &self.scene) {
+            let [u, v] = hit.barycentric;
+            println!("Picked: triangle {} (vertex {}), barycentric ({:.3}, {:.3}), at ({:.4}, {:.4}, {:.4}){}",
+                     hit.triangle, scene.nearest_vertex(hit), u, v, hit.point.x, hit.point.y, hit.point.z, self.unit.suffix());
+        }
+    }
+
+    fn update_pick_lines(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.picks_dirty = false;
+        // Crosses are sized to the model
+        let marker = self.model_bounds.map_or(0.0, |(min, max)| (max - min).x.max((max - min).y).max((max - min).z) * MARKER_SIZE);
+        let vertices = match (&self.selection, &self.scene) {
+            (Some(hit), Some(scene)) => {
+                let [a, b, c] = scene.bvh().triangle(hit.triangle);
+                let mut vertices: Vec<LineVertex> = [(a, b), (b, c), (c, a)]
+                    .into_iter()
+                    .flat_map(|(start, end)| [start, end])
+                    .map(|p| LineVertex { position: p.into(), color: SELECTION_COLOR })
+                    .collect();
+                let corner = Vector3::from(scene.bvh().vertex(scene.nearest_vertex(hit)));
+                for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+                    for p in [corner - axis * marker, corner + axis * marker] {
+                        vertices.push(LineVertex { position: p.into(), color: SELECTION_VERTEX_COLOR });
+                    }
+                }
+                vertices
+            }
+            _ => Vec::new(),
+        };
+        self.selection_lines.set(&self.device, encoder, &mut self.staging_belt, &vertices);
+        self.measurement_lines.set(&self.device, encoder, &mut self.staging_belt, &self.measurements.line_vertices(marker));
+    }
+
+    // Adds the surface point under the cursor to the measurements
+    pub fn measure_at_cursor(&mut self) {
+        let Some((scene, hit)) = self.pick_at_cursor() else {
+            return;
+        };
+
+        let pick = Pick::from_hit(scene, &hit);
+        self.measurements.add(pick);
+        self.picks_dirty = true;
+        print!("{}", self.measurements.report(self.unit));
+    }
+
+    fn clear_measurements(&mut self) {
+        self.measurements.clear();
+        self.picks_dirty = true;
+    }
+
+    fn copy_measurements(&self) {
+        if !self.measurements.is_empty() {
+            copy_to_clipboard("measurements", self.measurements.report(self.unit));
+        }
+    }
+
+    fn copy_dimensions(&self) {
+        if let Some(stats) = &self.geometry_stats {
+            copy_to_clipboard("dimensions", stats.dimensions.report(self.unit));
+        }
+    }
+
+    // Replaces the model with another file, loaded with the same options minus the
+    // per-model ones (shell isolation, budget fitting)
+    pub fn open(&mut self, path: String) {
+        self.model_path = path;
+        self.load_options.isolate_shell = None;
+        self.load_options.fit_budget = false;
+        self.pending_session = None;
+        self.reload();
+    }
+
+    // Loads the model again with the budget applied
+    fn fit_to_budget(&mut self) {
+        if self.budget_violations.is_empty() || self.loader.is_some() {
+            return;
+        }
+
+        self.load_options.fit_budget = true;
+        self.reload();
+    }
+
+    // Removes shells under DEBRIS_FRACTION of the largest one
+    fn remove_debris(&mut self) {
+        let Some(components) = &self.components else {
+            return;
+        };
+        if self.loader.is_some() || components.count() < 2 {
+            return;
+        }
+
+        self.load_options.min_shell_triangles = components.debris_threshold();
+        self.reload();
+    }
+
+    // Shows the shells one at a time, largest first, then all of them again
+    fn isolate_next_shell(&mut self) {
+        if self.loader.is_some() || self.shell_count < 2 {
+            return;
+        }
+
+        self.load_options.isolate_shell = match self.load_options.isolate_shell {
+            None => Some(0),
+            Some(shell) if shell + 1 < self.shell_count => Some(shell + 1),
+            Some(_) => None,
+        };
+        self.reload();
+    }
+
+    // Loads the model again with the current options; the current one stays up meanwhile
+    fn save_session(&self) {
+        let path = PathBuf::from(format!("rsview-{}.{}", timestamp(), session::EXTENSION));
+        match self.session().save(&path) {
+            Ok(()) => println!("Saved session to {}", path.display()),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    fn session(&self) -> Session {
+        let options = &self.load_options;
+        let mut models = vec![SessionModel {
+            path: self.model_path.clone(),
+            optimize: options.optimize,
+            clean: options.clean,
+            fix_winding: options.fix_winding,
+            unit_scale: options.unit_scale,
+            min_shell_triangles: options.min_shell_triangles,
+            isolate_shell: options.isolate_shell,
+            compare: options.compare.clone(),
+            rotation: self.rotation,
+            pitch: self.pitch,
+            center: self.model_center.into(),
+        }];
+        // The models beside it are loaded as they are, turned with the main one
+        models.extend(self.side_paths.iter().map(|path| SessionModel {
+            path: path.clone(),
+            optimize: false,
+            clean: false,
+            fix_winding: false,
+            unit_scale: 1.0,
+            min_shell_triangles: 0,
+            isolate_shell: None,
+            compare: None,
+            rotation: self.rotation,
+            pitch: self.pitch,
+            center: [0.0; 3],
+        }));
+
+        Session {
+            models,
+            camera: Camera {
+                distance: self.camera_distance,
+                auto_rotate: self.auto_rotate,
+                view: Some((self.view_yaw, self.view_elevation)),
+            },
+            render: RenderSettings {
+                unit: self.unit,
+                show_oriented_box: self.show_oriented_box,
+                show_deviation: self.show_deviation,
+                show_components: self.show_components,
+                show_manifold_edges: self.show_manifold_edges,
+                show_parts: self.show_parts,
+                show_grid: self.show_grid,
+                show_bounds: self.show_bounds,
+                flat_shading: self.flat_shading,
+                uv_checker: self.uv_checker,
+                debug_view: self.debug_view,
+                clip_plane: self.clip_plane,
+                normal_display: self.normal_display,
+                explode: self.explode,
+                show_uv_layout: self.show_uv_layout,
+                section_plane: self.section_plane,
+                split: self.split,
+                background: Some(self.background),
+            },
+            annotations: self
+                .measurements
+                .picks()
+                .iter()
+                .map(|pick| Annotation {
+                    point: pick.point.into(),
+                    normal: pick.normal.into(),
+                    edge_length: pick.edge_length,
+                })
+                .collect(),
+        }
+    }
+
+    fn apply_render_settings(&mut self, render: RenderSettings) {
+        self.unit = render.unit;
+        self.show_oriented_box = render.show_oriented_box;
+        self.show_deviation = render.show_deviation;
+        self.show_components = render.show_components;
+        self.show_manifold_edges = render.show_manifold_edges;
+        self.show_parts = render.show_parts;
+        self.show_grid = render.show_grid;
+        self.show_bounds = render.show_bounds;
+        self.flat_shading = render.flat_shading;
+        self.uv_checker = render.uv_checker;
+        self.debug_view = render.debug_view;
+        self.clip_plane = render.clip_plane;
+        self.set_normal_display(render.normal_display);
+        self.explode = render.explode;
+        self.show_uv_layout = render.show_uv_layout;
+        self.section_plane = render.section_plane;
+        self.section_dirty = true;
+        self.split = render.split;
+        if let Some(background) = render.background {
+            self.background = background;
+        }
+    }
+
+    // Loading the model fits the view to it, so the saved view waits until it's done
+    fn restore_session(&mut self) {
+        if self.loader.is_some() || self.background_upload.is_some() {
+            return;
+        }
+        let Some(session) = self.pending_session.take() else {
+            return;
+        };
+
+        let model = &session.models[0];
+        self.rotation = model.rotation;
+        self.pitch = model.pitch;
+        self.model_center = model.center.into();
+        self.camera_distance = session.camera.distance;
+        self.auto_rotate = session.camera.auto_rotate;
+        (self.view_yaw, self.view_elevation) = session.camera.view.unwrap_or(views::DEFAULT_VIEW);
+        self.camera_transition = None;
+        for annotation in &session.annotations {
+            self.measurements.add(Pick {
+                point: annotation.point.into(),
+                normal: annotation.normal.into(),
+                edge_length: annotation.edge_length,
+            });
+        }
+        self.picks_dirty = true;
+    }
+
+    // Watches the model file and the files it refers to, replacing the previous watch.
+    // Thumbnails are rendered once, so there's nothing to reload for without a window.
+    fn watch_model(&mut self, mesh: &Mesh) {
+        if self.surface.is_none() {
+            return;
+        }
+
+        let files = std::iter::once(PathBuf::from(&self.model_path)).chain(mesh.sources.iter().cloned());
+        self.watcher = ModelWatcher::new(files, self.waker.clone())
+            .map_err(|e| eprintln!("Watch: Not reloading on changes: {}", e))
+            .ok();
+    }
+
+    // Thumbnails don't count as opening a model
+    fn remember_model(&mut self) {
+        if self.surface.is_some() && !self.model_failed {
+            self.recent.add(&self.model_path);
+        }
+    }
+
+    fn open_recent(&mut self, index: usize) {
+        if let Some(path) = self.recent.get(index) {
+            self.open(path.to_string());
+        }
+    }
+
+    fn reload_if_changed(&mut self) {
+        if self.watcher.as_ref().is_some_and(ModelWatcher::changed) {
+            println!("Watch: {} changed, reloading", self.model_path);
+            self.reload();
+        }
+    }
+
+    fn reload(&mut self) {
+        self.model_failed = false;
+        self.parsing_since = Some(Instant::now());
+        self.loader = Some(spawn_loader(self.model_path.clone(), self.load_options.clone(), self.waker.clone()));
+    }
+
+    pub fn cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor = position;
+        if self.dragging_splitter && self.size.width > 0 {
+            self.split = Some((position.x / self.size.width as f64).clamp(0.0, 1.0) as f32);
+        }
+    }
+
+    // Starts dragging the A/B splitter when the cursor is on it
+    pub fn grab_splitter(&mut self) {
+        if let Some(split) = self.split {
+            self.dragging_splitter = (self.cursor.x - split as f64 * self.size.width as f64).abs() <= SPLITTER_GRAB;
+        }
+    }
+
+    pub fn release_splitter(&mut self) {
+        self.dragging_splitter = false;
+    }
+
+    pub fn is_dragging_splitter(&self) -> bool {
+        self.dragging_splitter
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        // Minimizing reports a zero size; free the render targets until the window comes back
+        if new_size.width == 0 || new_size.height == 0 {
+            self.pending_resize = None;
+            self.framebuffer.resize(0, 0);
+            return;
+        }
+
+        // Dragging a window edge fires a storm of these, so only remember the latest one
+        self.pending_resize = Some((new_size, Instant::now()));
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.framebuffer.is_empty()
+    }
+
+    // When the pending resize should be applied, if there is one
+    pub fn resize_deadline(&self) -> Option<Instant> {
+        self.pending_resize.map(|(_, at)| at + RESIZE_DEBOUNCE)
+    }
+
+    fn apply_pending_resize(&mut self, force: bool) {
+        let Some((new_size, at)) = self.pending_resize else {
+            return;
+        };
+
+        if !force && at.elapsed() < RESIZE_DEBOUNCE {
+            return;
+        }
+
+        self.pending_resize = None;
+        // Coming back from minimized at the old size only needs the targets again
+        self.framebuffer.resize(new_size.width, new_size.height);
+        if new_size == self.size {
+            return;
+        }
+
+        self.size = new_size;
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+        self.configure_surface();
+    }
+
+    // Held arrow keys keep turning the model, everything else acts once per press
+    pub fn key_pressed(&mut self, key: KeyCode, repeat: bool) {
+        let arrow = matches!(key, KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::ArrowUp | KeyCode::ArrowDown);
+        if repeat && !arrow {
+            return;
+        }
+
+        match key {
+            KeyCode::KeyV => self.cycle_present_mode(),
+            KeyCode::KeyW => self.cycle_polygon_mode(),
+            KeyCode::F12 => self.save_screenshot(),
+            KeyCode::F11 => self.copy_screenshot(),
+            KeyCode::F9 => self.toggle_recording(),
+            KeyCode::F3 => self.show_frame_graph = !self.show_frame_graph,
+            KeyCode::Tab => self.overlay.visible = !self.overlay.visible,
+            KeyCode::KeyH => self.show_deviation = !self.show_deviation,
+            KeyCode::KeyE => self.show_manifold_edges = !self.show_manifold_edges,
+            KeyCode::KeyK => self.show_components = !self.show_components,
+            KeyCode::KeyG => self.toggle_parts(),
+            KeyCode::KeyA => self.show_grid = !self.show_grid,
+            KeyCode::KeyL => self.show_bounds = !self.show_bounds,
+            KeyCode::KeyF => self.flat_shading = !self.flat_shading,
+            KeyCode::KeyT => self.toggle_uv_checker(),
+            KeyCode::KeyY => self.set_debug_view(self.debug_view.next()),
+            KeyCode::KeyN => self.set_normal_display(self.normal_display.cycled()),
+            KeyCode::KeyS => self.save_session(),
+            KeyCode::KeyI => self.isolate_next_shell(),
+            KeyCode::Backspace => self.remove_debris(),
+            KeyCode::KeyM => self.measure_at_cursor(),
+            KeyCode::KeyC => self.copy_measurements(),
+            KeyCode::KeyD => self.copy_dimensions(),
+            KeyCode::KeyO => self.show_oriented_box = !self.show_oriented_box,
+            KeyCode::Delete => self.clear_measurements(),
+            KeyCode::KeyB => self.fit_to_budget(),
+            KeyCode::KeyU => self.show_uv_layout = !self.show_uv_layout,
+            KeyCode::KeyX => self.cycle_section_axis(),
+            KeyCode::BracketLeft => self.move_section_plane(-1.0),
+            KeyCode::BracketRight => self.move_section_plane(1.0),
+            KeyCode::Space => self.toggle_auto_rotate(),
+            KeyCode::KeyP => self.toggle_animation(),
+            KeyCode::Equal | KeyCode::NumpadAdd => self.change_rotation_speed(ROTATION_SPEED_FACTOR),
+            KeyCode::Minus | KeyCode::NumpadSubtract => self.change_rotation_speed(1.0 / ROTATION_SPEED_FACTOR),
+            KeyCode::KeyR => self.reset_orientation(),
+            KeyCode::ArrowLeft => self.turn(-ROTATION_STEP, 0.0),
+            KeyCode::ArrowRight => self.turn(ROTATION_STEP, 0.0),
+            KeyCode::ArrowUp => self.turn(0.0, -ROTATION_STEP),
+            KeyCode::ArrowDown => self.turn(0.0, ROTATION_STEP),
+            KeyCode::Digit1 => self.open_recent(0),
+            KeyCode::Digit2 => self.open_recent(1),
+            KeyCode::Digit3 => self.open_recent(2),
+            KeyCode::Digit4 => self.open_recent(3),
+            KeyCode::Digit5 => self.open_recent(4),
+            KeyCode::Digit6 => self.open_recent(5),
+            KeyCode::Digit7 => self.open_recent(6),
+            KeyCode::Digit8 => self.open_recent(7),
+            KeyCode::Digit9 => self.open_recent(8),
+            _ => {}
+        }
+    }
+
+    // Moves the camera to a preset view around the whole model, pausing the rotation so
+    // it stays there
+    pub fn snap_to_view(&mut self, preset: ViewPreset) {
+        let (yaw, elevation) = preset.angles();
+        let target = CameraPose {
+            yaw,
+            elevation,
+            rotation: 0.0,
+            pitch: 0.0,
+            center: self.fit_center,
+            distance: self.fit_distance,
+        };
+        self.camera_transition = Some(CameraTransition::new(self.camera_pose(), target));
+        self.auto_rotate = false;
+        println!("View: {}", preset.name());
+    }
+
+    // Moves the camera in on one part of the main model from where it's looking now,
+    // turning around the part's middle from then on
+    fn focus_part(&mut self, part: usize) {
+        let Some((center, size)) = self.draw_batch.part_frame(part) else {
+            return;
+        };
+        // fit_distance frames the whole model, which is scaled to 2 units across
+        let radius = (size * self.model_scale * 0.5).max(MIN_FOCUS_RADIUS);
+        let target = CameraPose {
+            center: Vector3::from(center),
+            distance: self.fit_distance * radius,
+            ..self.camera_pose()
+        };
+        self.camera_transition = Some(CameraTransition::new(self.camera_pose(), target));
+        self.auto_rotate = false;
+        if let Some((name, _)) = self.draw_batch.legend().get(part) {
+            println!("Focus: {}", name);
+        }
+    }
+
+    fn camera_pose(&self) -> CameraPose {
+        CameraPose {
+            yaw: self.view_yaw,
+            elevation: self.view_elevation,
+            rotation: self.rotation,
+            pitch: self.pitch,
+            center: self.model_center,
+            distance: self.camera_distance,
+        }
+    }
+
+    fn set_camera_pose(&mut self, pose: CameraPose) {
+        self.view_yaw = pose.yaw;
+        self.view_elevation = pose.elevation;
+        self.rotation = pose.rotation;
+        self.pitch = pose.pitch;
+        self.model_center = pose.center;
+        self.camera_distance = pose.distance;
+    }
+
+    fn toggle_auto_rotate(&mut self) {
+        self.camera_transition = None;
+        self.auto_rotate = !self.auto_rotate;
+        println!("Rotation: {}", if self.auto_rotate { "Resumed" } else { "Paused, arrow keys turn the model" });
+    }
+
+    fn change_rotation_speed(&mut self, factor: f32) {
+        self.rotation_speed = (self.rotation_speed * factor).clamp(ROTATION_SPEED / 16.0, ROTATION_SPEED * 16.0);
+        println!("Rotation: {:.1}x speed", self.rotation_speed / ROTATION_SPEED);
+    }
+
+    fn reset_orientation(&mut self) {
+        self.rotation = 0.0;
+        self.pitch = 0.0;
+    }
+
+    // Manual turning only while paused, so it doesn't fight the auto-rotation
+    fn turn(&mut self, yaw: f32, pitch: f32) {
+        if self.auto_rotate {
+            return;
+        }
+        self.camera_transition = None;
+        self.rotation += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-FRAC_PI_2, FRAC_PI_2);
+    }
+
+    pub fn record_event_time(&mut self, elapsed: Duration) {
+        self.event_time += elapsed;
+    }
+
+    fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_view = view;
+        println!("Debug view: {}", view.name());
+        if view == DebugView::Curvature && let Some(curvature) = &self.curvature {
+            println!("Curvature: {}", curvature.summary());
+        }
+    }
+
+    fn toggle_uv_checker(&mut self) {
+        self.uv_checker = !self.uv_checker;
+        if self.uv_checker && !self.textured {
+            println!("UV checker: The model has no texture coordinates");
+        }
+    }
+
+    // Prints which color went to which part when turning part colors on
+    fn toggle_parts(&mut self) {
+        self.show_parts = !self.show_parts;
+        if !self.show_parts {
+            return;
+        }
+
+        for (name, color) in self.draw_batch.legend() {
+            let [r, g, b] = color.map(|c| (c * 255.0).round() as u8);
+            println!("#{:02x}{:02x}{:02x} {}", r, g, b, name);
+        }
+    }
+
+    // Switches between vsync (Fifo) and the lower latency modes the surface offers
+    fn cycle_present_mode(&mut self) {
+        if self.present_modes.len() < 2 {
+            return;
+        }
+
+        let current = self.present_modes.iter().position(|&mode| mode == self.config.present_mode).unwrap_or(0);
+        self.config.present_mode = self.present_modes[(current + 1) % self.present_modes.len()];
+        self.configure_surface();
+
+        println!("Present mode: {:?}", self.config.present_mode);
+    }
+
+    // Solid, wireframe and points, as far as the GPU can draw them
+    fn cycle_polygon_mode(&mut self) {
+        if self.scene_pipelines.len() < 2 {
+            println!("Polygon mode: Wireframe and points aren't supported by this GPU");
+            return;
+        }
+
+        self.polygon_mode = (self.polygon_mode + 1) % self.scene_pipelines.len();
+        println!("Polygon mode: {:?}", self.scene_pipelines[self.polygon_mode].0);
+    }
+
+    // Called after the surface reported Outdated or Lost
+    pub fn recover_surface(&mut self) {
+        if self.pending_resize.is_some() {
+            self.apply_pending_resize(true);
+        } else {
+            self.configure_surface();
+        }
+    }
+
+    fn configure_surface(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    // The environment when there is one, otherwise a gradient if the background has one
+    fn draw_background(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        match &self.environment {
+            Some(environment) => environment.draw(render_pass),
+            None => self.background_pass.draw(render_pass),
+        }
+    }
+
+    // Everything but the frame graph, so screenshots match the window without the debug overlay
+    fn draw_scene(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.show_uv_layout && self.uv_layout.is_some() {
+            self.object_uniforms.bind(render_pass, 0, UV_OBJECT);
+            self.uv_view.draw(render_pass);
+            return;
+        }
+
+        // In an A/B comparison the main model and its overlays stay left of the splitter
+        let split_x = self.split_x();
+        if let Some(x) = split_x {
+            render_pass.set_scissor_rect(0, 0, x, self.config.height);
+        }
+
+        // The checker needs the UVs, so it wins over part and vertex colors
+        let checker = self.uv_checker && self.textured;
+        // Like the checker, the curvature heatmap wins over part colors
+        let heatmap = self.debug_view == DebugView::Curvature;
+        let vertex_colors = !checker && (heatmap || !self.show_parts) && self.vertex_colors.is_some() && self.vertex_colors == self.wanted_vertex_colors();
+        let textured = checker || (!self.show_parts && self.textured);
+        let color_source = if vertex_colors { 2 } else { textured as usize };
+        // Skinned pipelines only exist when the GPU can skin, and joints follow any colors or UVs
+        let skinned = self.skinned && self.joint_matrices.is_some();
+        let clipped = self.clip_plane.enabled;
+        // Only ever with the material colors, so nothing else needs binding
+        if let Some((_, lod)) = self.active_lod() {
+            render_pass.set_pipeline(&self.scene_pipelines[self.polygon_mode].1[clipped as usize][0][0][lod.quantization.is_some() as usize]);
+            self.object_uniforms.bind(render_pass, 0, MODEL_OBJECT);
+            render_pass.set_vertex_buffer(0, lod.vertex_buffer.buffer.slice(..));
+            render_pass.set_index_buffer(lod.index_buffer.buffer.slice(..), lod.index_format);
+            lod.draw_batch.draw(render_pass, lod.num_indices);
+        } else {
+            render_pass.set_pipeline(&self.scene_pipelines[self.polygon_mode].1[clipped as usize][skinned as usize][color_source][self.quantization.is_some() as usize]);
+            self.object_uniforms.bind(render_pass, 0, MODEL_OBJECT);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.buffer.slice(..), self.index_format);
+            if vertex_colors {
+                render_pass.set_vertex_buffer(2, self.color_buffer.buffer.slice(..));
+            } else if textured {
+                render_pass.set_vertex_buffer(2, self.uv_buffer.buffer.slice(..));
+            }
+            if skinned && let Some(joint_matrices) = &self.joint_matrices {
+                joint_matrices.bind(render_pass, 2);
+                let slot = if color_source == 0 { 2 } else { 3 };
+                render_pass.set_vertex_buffer(slot, self.skin_buffer.buffer.slice(..));
+            }
+            self.draw_batch.draw(render_pass, self.num_indices);
+        }
+        self.object_uniforms.bind(render_pass, 0, POINTS_OBJECT);
+        self.points.draw(render_pass);
+
+        self.object_uniforms.bind(render_pass, 0, OVERLAY_OBJECT);
+        if self.show_grid {
+            self.grid_lines.draw(render_pass);
+            self.axis_lines.draw(render_pass);
+        }
+        if self.show_bounds {
+            self.bounds_lines.draw(render_pass);
+        }
+        if self.normal_display.is_shown() {
+            self.normal_lines.draw(render_pass);
+        }
+        self.lines.draw(render_pass);
+        self.selection_lines.draw(render_pass);
+        self.measurement_lines.draw(render_pass);
+        if self.show_manifold_edges {
+            self.edge_lines.draw(render_pass);
+        }
+
+        if let Some(x) = split_x {
+            render_pass.set_scissor_rect(x, 0, self.config.width - x, self.config.height);
+        }
+        // Models beside the main one have neither vertex colors, overlays nor clipping
+        for (index, item) in self.draw_list.iter().enumerate() {
+            let Some(item) = item else {
+                continue;
+            };
+            let textured = !self.show_parts && item.textured;
+            render_pass.set_pipeline(&self.scene_pipelines[self.polygon_mode].1[0][0][textured as usize][item.quantization.is_some() as usize]);
+            self.object_uniforms.bind(render_pass, 0, FIRST_SIDE_OBJECT + index as u32);
+            render_pass.set_vertex_buffer(0, item.vertex_buffer.buffer.slice(..));
+            render_pass.set_index_buffer(item.index_buffer.buffer.slice(..), item.index_format);
+            if textured {
+                render_pass.set_vertex_buffer(2, item.uv_buffer.buffer.slice(..));
+            }
+            item.draw_batch.draw(render_pass, item.num_indices);
+        }
+
+        if split_x.is_some() {
+            render_pass.set_scissor_rect(0, 0, self.config.width, self.config.height);
+            self.object_uniforms.bind(render_pass, 0, SPLITTER_OBJECT);
+            self.splitter.draw(render_pass);
+        }
+    }
+
+    // The splitter's column in the render targets, in an A/B comparison
+    fn split_x(&self) -> Option<u32> {
+        self.split.map(|split| ((split * self.config.width as f32).round() as u32).min(self.config.width))
+    }
+
+    // Renders the current view again offscreen and reads it back as RGBA at the window
+    // size, over the background or a transparent one
+    pub fn capture_view(&mut self, with_background: bool) -> Result<Vec<u8>, Error> {
+        // A headless --size can ask for more than the device renders to
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        if self.config.width > max_size || self.config.height > max_size {
+            return Err(Error::Gpu(format!("Can't render at {}x{}, this device's limit is {}x{}",
+                                          self.config.width, self.config.height, max_size, max_size)));
+        }
+        let clear = if with_background { self.background.clear_color(self.config.format) } else { wgpu::Color::TRANSPARENT };
+        let targets = self.framebuffer.targets(&self.device).cloned().ok_or_else(|| Error::Gpu("Nothing to capture while minimized".into()))?;
+        let capture = Capture::new(&self.device, self.config.format, self.config.width, self.config.height).map_err(Error::Gpu)?;
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Screenshot Encoder")
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Screenshot Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: targets.msaa_color.as_ref().unwrap_or(capture.view()),
+                    resolve_target: targets.msaa_color.as_ref().map(|_| capture.view()),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if with_background {
+                self.draw_background(&mut render_pass);
+            }
+            self.draw_scene(&mut render_pass);
+        }
+        capture.copy(&mut encoder);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        capture.read(&self.device).map_err(Error::Gpu)
+    }
+
+    // Writes the current view over a transparent background as a PNG
+    pub fn save_png(&mut self, path: &Path) -> Result<(), Error> {
+        let rgba = self.capture_view(false)?;
+        screenshot::save_png(path, self.config.width, self.config.height, &rgba)
+    }
+
+    // Renders the model turned `angle` radians from the front view and reads it back, over
+    // the window background or a transparent one
+    pub fn turntable_frame(&mut self, angle: f32, transparent: bool) -> Result<Vec<u8>, Error> {
+        self.rotation = angle;
+        // An animation plays through once per turn, so the video loops cleanly
+        if let Some(duration) = self.animation_duration() {
+            self.animation_playing = false;
+            self.animation_time = duration * angle.rem_euclid(TAU) / TAU;
+        }
+        // Brings the uniforms up to date with the new angle
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Turntable Encoder")
+        });
+        self.update(&mut encoder);
+        self.submit(encoder);
+
+        self.capture_view(!transparent)
+    }
+
+    // Saves the current view over a transparent background as a PNG next to the model,
+    // named after it
+    fn save_screenshot(&mut self) {
+        let model = Path::new(&self.model_path);
+        let name = model.file_stem().map_or_else(|| "rsview".into(), |stem| stem.to_string_lossy());
+        let path = model.with_file_name(format!("{}-{}.png", name, timestamp()));
+        match self.save_png(&path) {
+            Ok(()) => println!("Saved screenshot to {}", path.display()),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    // Puts the current view on the clipboard with the window background, since not every
+    // app that images get pasted into handles transparency
+    fn copy_screenshot(&mut self) {
+        let rgba = match self.capture_view(true) {
+            Ok(rgba) => rgba,
+            Err(e) => {
+                eprintln!("Failed to copy the view: {}", e);
+                return;
+            }
+        };
+
+        let image = arboard::ImageData {
+            width: self.config.width as usize,
+            height: self.config.height as usize,
+            bytes: rgba.into(),
+        };
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_image(image)) {
+            Ok(()) => println!("Copied the view to the clipboard"),
+            Err(e) => eprintln!("Failed to copy the view: {}", e),
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            self.stop_recording();
+            return;
+        }
+
+        match Recorder::start(&format!("rsview-{}", timestamp()), self.config.width, self.config.height) {
+            Ok(recorder) => {
+                println!("Recording: Started, F9 again stops it");
+                self.recorder = Some(recorder);
+                self.last_recorded_frame = None;
+            }
+            Err(e) => eprintln!("Recording: {}", e),
+        }
+    }
+
+    // Lets the encoder finish the file; also needed on exit
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish();
+        }
+    }
+
+    // Flushes the --profile CSV, called on exit
+    pub fn stop_profiling(&mut self) {
+        if let Some(profile) = self.profile.take()
+            && let Err(e) = profile.finish()
+        {
+            eprintln!("Failed to write the profile: {}", e);
+        }
+    }
+
+    // Captures a frame for the recording at most FRAMES_PER_SECOND times a second
+    fn record_frame(&mut self) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        let interval = Duration::from_secs(1) / recording::FRAMES_PER_SECOND;
+        if self.last_recorded_frame.is_some_and(|last| last.elapsed() < interval) {
+            return;
+        }
+        if recorder.size() != (self.config.width, self.config.height) {
+            println!("Recording: The window was resized, stopping");
+            self.stop_recording();
+            return;
+        }
+
+        self.last_recorded_frame = Some(Instant::now());
+        match self.capture_view(true) {
+            Ok(rgba) => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.push(rgba);
+                }
+            }
+            Err(e) => eprintln!("Recording: {}", e),
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.apply_pending_resize(false);
+        if self.is_minimized() {
+            return Ok(());
+        }
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.collect(&self.device);
+        }
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder")
+        });
+
+        let update_start = Instant::now();
+        self.update(&mut encoder);
+        if self.show_frame_graph {
+            self.frame_graph.prepare(&self.device, &mut encoder, &mut self.staging_belt, &self.frame_history);
+        }
+        let update_time = update_start.elapsed();
+
+        let acquire_start = Instant::now();
+        // Headless states only draw through capture_view
+        let Some(surface) = &self.surface else {
+            self.submit(encoder);
+            return Ok(());
+        };
+        let output = match surface.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                // Still submit the uploads recorded this frame so streamed data isn't lost
+                self.submit(encoder);
+                return Err(e);
+            }
+        };
+        let acquire_time = acquire_start.elapsed();
+
+        let encode_start = Instant::now();
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let targets = self.framebuffer.targets(&self.device).cloned().expect("Framebuffer targets while not minimized");
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: targets.msaa_color.as_ref().unwrap_or(&view),
+                    resolve_target: targets.msaa_color.as_ref().map(|_| &view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background.clear_color(self.config.format)),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: self.gpu_timer.as_ref().map(|timer| timer.timestamp_writes(0)),
+                occlusion_query_set: None,
+            });
+
+            self.draw_background(&mut render_pass);
+            self.draw_scene(&mut render_pass);
+
+            if self.show_frame_graph {
+                self.frame_graph.draw(&mut render_pass);
+            }
+        }
+
+        let overlay_stats = self.overlay_stats();
+        if let Some(frame) = self.overlay.prepare(&self.device, &self.queue, &mut encoder, self.size, &overlay_stats) {
+            self.overlay.draw(&mut encoder, &view, frame);
+        }
+        // Applied from the next frame on, this one is already encoded
+        let actions = self.overlay.take_actions();
+        if actions.toggle_playback {
+            self.toggle_animation();
+        }
+        if let Some(time) = actions.seek {
+            self.seek_animation(time);
+        }
+        if let Some(background) = actions.background {
+            self.background = background;
+        }
+        if let Some(explode) = actions.explode {
+            self.explode = explode;
+        }
+        if let Some(point_size) = actions.point_size {
+            self.point_size = point_size;
+        }
+        if let Some(clip_plane) = actions.clip_plane {
+            self.clip_plane = clip_plane;
+        }
+        if let Some(display) = actions.normal_display {
+            self.set_normal_display(display);
+        }
+        if let Some(view) = actions.debug_view {
+            self.set_debug_view(view);
+        }
+        if let Some(preset) = actions.snap_view {
+            self.snap_to_view(preset);
+        }
+        if let Some(part) = actions.toggle_part && !self.hidden_parts.remove(&part) {
+            self.hidden_parts.insert(part);
+        }
+        if let Some(part) = actions.focus_part {
+            self.focus_part(part);
+        }
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.resolve(&mut encoder);
+        }
+
+        self.submit(encoder);
+        let encode_time = encode_start.elapsed();
+
+        let present_start = Instant::now();
+        output.present();
+        let present_time = acquire_time + present_start.elapsed();
+
+        let timing = FrameTiming {
+            events: std::mem::take(&mut self.event_time).as_secs_f32() * 1000.0,
+            update: update_time.as_secs_f32() * 1000.0,
+            encode: encode_time.as_secs_f32() * 1000.0,
+            present: present_time.as_secs_f32() * 1000.0,
+        };
+        self.frame_history.push(timing);
+        if let Some(profile) = &mut self.profile {
+            let gpu = self.gpu_timer.as_ref().map_or(&[][..], GpuTimer::latest);
+            if let Err(e) = profile.write(&timing, gpu) {
+                eprintln!("Stopped profiling: {}", e);
+                self.profile = None;
+            }
+        }
+
+        self.record_frame();
+        Ok(())
+    }
+
+    fn submit(&mut self, encoder: wgpu::CommandEncoder) {
+        self.staging_belt.finish();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.staging_belt.recall();
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.after_submit();
+        }
+    }
+
+    // Hands a window event to the panel; true when the viewer shouldn't act on it too
+    pub fn overlay_event(&mut self, event: &WindowEvent) -> bool {
+        self.overlay.handle_event(event)
+    }
+
+    pub fn overlay_visible(&self) -> bool {
+        self.overlay.visible
+    }
+
+    pub fn model_path(&self) -> &str {
+        &self.model_path
+    }
+
+    pub fn has_geometry(&self) -> bool {
+        self.num_indices > 0 || self.points.point_count() > 0
+    }
+
+    pub fn set_auto_rotate(&mut self, auto_rotate: bool) {
+        self.auto_rotate = auto_rotate;
+    }
+
+    // Runs updates until the model, its analysis and any session are in place, for
+    // viewers without an event loop. `woken` receives a message whenever the Waker
+    // passed to new() is called.
+    pub fn wait_for_model(&mut self, woken: &Receiver<()>) {
+        loop {
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder")
+            });
+            self.update(&mut encoder);
+            self.submit(encoder);
+
+            let uploading = self.background_upload.is_some() || self.points.is_uploading();
+            if self.loader.is_none() && self.side_loader.is_none() && !uploading && self.pending_session.is_none() {
+                return;
+            }
+            // Uploads continue every pass; otherwise there's nothing to do until the loader sends more
+            if !uploading {
+                let _ = woken.recv_timeout(Duration::from_millis(100));
+            }
+        }
+    }
+
+    fn overlay_stats(&self) -> OverlayStats {
+        let file = Path::new(&self.model_path).file_name().map_or_else(|| self.model_path.clone(), |name| name.to_string_lossy().into_owned());
+        let loading = self.parsing_since.map(|since| {
+            let streamed = if self.streamed_vertices > 0 { format!(", {} vertices so far", self.streamed_vertices) } else { String::new() };
+            format!("Loading {} ({:.1} s{})", file, since.elapsed().as_secs_f32(), streamed)
+        });
+        OverlayStats {
+            file,
+            vertices: self.num_vertices,
+            indices: self.num_indices as usize,
+            loading,
+            culled_parts: (self.draw_batch.part_count() > 1).then_some(self.culled_parts),
+            grid: (self.grid != (1, 1)).then(|| {
+                let copies = self.grid.0 * self.grid.1;
+                format!("{} x {} ({} triangles)", self.grid.0, self.grid.1, copies as usize * self.num_indices as usize / 3)
+            }),
+            lod: (!self.lods.is_empty()).then(|| match self.active_lod() {
+                Some((level, lod)) => format!("{} of {} ({} triangles)", level, self.lods.len(), lod.num_indices / 3),
+                None => format!("Full detail, {} levels", self.lods.len()),
+            }),
+            frame_time: self.frame_history.average_total(),
+            gpu_time: self.gpu_timer.as_ref().map(|timer| timer.timings().map(|(_, ms)| ms).sum()),
+            camera_distance: self.camera_distance,
+            rotation: self.rotation,
+            pitch: self.pitch,
+            orbit_center: self.model_center.into(),
+            auto_rotate: self.auto_rotate,
+            recent: self.recent.names(),
+            animation: self.rig.as_ref().filter(|_| self.skinned).and_then(|rig| rig.clips.first()).map(|clip| AnimationStats {
+                name: clip.name.clone(),
+                time: self.animation_time,
+                duration: clip.duration(),
+                playing: self.animation_playing,
+            }),
+            background: self.background,
+            clip_plane: self.clip_plane,
+            normal_display: self.normal_display,
+            debug_view: self.debug_view,
+            explode: (self.draw_batch.part_count() > 1).then_some(self.explode),
+            point_size: (self.points.point_count() > 0).then_some(self.point_size),
+            parts: self.draw_batch.legend().into_iter().enumerate().map(|(part, (name, _))| (name, !self.hidden_parts.contains(&part))).collect(),
+            edges: self.topology.as_ref().map(|topology| topology.edges),
+            topology: self.topology.as_ref().map(TopologyReport::summary),
+            bounds: self.model_bounds.filter(|_| self.show_bounds).map(|(min, max)| {
+                let size = max - min;
+                self.unit.size([size.x, size.y, size.z].map(f64::from))
+            }),
+            distance: self.measurements.last_distance().map(|distance| self.unit.length(distance as f64)),
+            selection: self.selection.as_ref().zip(self.scene.as_ref()).map(|(hit, scene)| SelectionStats {
+                triangle: hit.triangle,
+                part: self.draw_batch.legend().get(hit.node).filter(|_| self.draw_batch.part_count() > 1).map(|(name, _)| name.clone()),
+                vertex: scene.nearest_vertex(hit),
+                barycentric: hit.barycentric,
+                point: format!("({:.4}, {:.4}, {:.4}){}", hit.point.x, hit.point.y, hit.point.z, self.unit.suffix()),
+            }),
+            // The columns are where the model's axes point on screen
+            axes: {
+                let axes = Self::camera_rotation(self.view_yaw, self.view_elevation) * self.orientation();
+                [axes.x.into(), axes.y.into(), axes.z.into()]
+            },
+        }
+    }
+
+    // Short status line with the latest measurements, shown in the window title
+    pub fn stats_text(&self) -> String {
+        let latest = self.frame_history.latest();
+        let cpu = format!(
+            "CPU {:.2} ms avg (events {:.2}, update {:.2}, encode {:.2}, present {:.2})",
+            self.frame_history.average_total(), latest.events, latest.update, latest.encode, latest.present,
+        );
+
+        let gpu = match &self.gpu_timer {
+            Some(timer) => timer
+                .timings()
+                .map(|(pass, ms)| format!("{} {:.2} ms GPU", pass, ms))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "GPU timings unavailable".to_string(),
+        };
+
+        let mut text = format!("{} | {}", cpu, gpu);
+        if !self.budget_violations.is_empty() {
+            text = format!("OVER BUDGET: {} (B to fit) | {}", self.budget_violations.join(", "), text);
+        }
+        if let Some(stats) = &self.geometry_stats {
+            text += &format!(" | {} | {}", stats.dimensions.summary(self.show_oriented_box, self.unit), stats.summary(self.unit));
+        }
+        if let Some(validation) = &self.validation {
+            text += &format!(" | Validation: {}", validation.summary());
+        }
+        if let Some(manifold) = self.manifold.as_ref().filter(|_| self.show_manifold_edges) {
+            text += &format!(" | {}", manifold.summary(self.unit));
+        }
+        if let Some(components) = self.components.as_ref().filter(|_| self.show_components) {
+            text += &format!(" | {}", components.summary());
+        }
+        if self.show_parts {
+            text += &format!(" | {} parts", self.draw_batch.part_count());
+        }
+        if let Some(deviation) = &self.deviation {
+            text += &format!(" | {}", deviation.summary(self.unit));
+        }
+        if let Some(section) = &self.section {
+            text += &format!(" | {}", section.summary(self.unit));
+        }
+        if let Some(layout) = self.uv_layout.as_ref().filter(|_| self.show_uv_layout) {
+            text += &format!(" | UV {}", layout.summary());
+        }
+        text
+    }
+}
+
+// Seconds since the epoch, to name output files
+fn timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn copy_to_clipboard(what: &str, text: String) {
+    let result = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+    match result {
+        Ok(()) => println!("Copied {} to the clipboard", what),
+        Err(e) => eprintln!("Failed to copy {}: {}", what, e),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VertexColors {
+    Curvature,
+    Deviation,
+    Components,
+    File,
+}
@@ -0,0 +1,138 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+
+use cgmath::{Vector3, VectorSpace};
+use winit::keyboard::KeyCode;
+
+// View presets //
+// Canonical views on the numpad, Blender style: 1 front, 3 right, 7 top, with Ctrl for
+// the opposite side, and 5 back to the default three-quarter view. The camera orbits the
+// fitted model to get there while the model turns back to its file orientation, so the
+// views line up with the model's own axes.
+
+// How long the camera takes to move to a preset, in seconds
+const TRANSITION_TIME: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewPreset {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Isometric,
+}
+
+impl ViewPreset {
+    pub fn for_key(key: KeyCode, control: bool) -> Option<Self> {
+        match (key, control) {
+            (KeyCode::Numpad1, false) => Some(ViewPreset::Front),
+            (KeyCode::Numpad1, true) => Some(ViewPreset::Back),
+            (KeyCode::Numpad3, false) => Some(ViewPreset::Right),
+            (KeyCode::Numpad3, true) => Some(ViewPreset::Left),
+            (KeyCode::Numpad7, false) => Some(ViewPreset::Top),
+            (KeyCode::Numpad7, true) => Some(ViewPreset::Bottom),
+            (KeyCode::Numpad5, _) => Some(ViewPreset::Isometric),
+            _ => None,
+        }
+    }
+
+    // For --view on the command line
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "front" => Some(ViewPreset::Front),
+            "back" => Some(ViewPreset::Back),
+            "left" => Some(ViewPreset::Left),
+            "right" => Some(ViewPreset::Right),
+            "top" => Some(ViewPreset::Top),
+            "bottom" => Some(ViewPreset::Bottom),
+            "iso" | "isometric" => Some(ViewPreset::Isometric),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewPreset::Front => "Front",
+            ViewPreset::Back => "Back",
+            ViewPreset::Left => "Left",
+            ViewPreset::Right => "Right",
+            ViewPreset::Top => "Top",
+            ViewPreset::Bottom => "Bottom",
+            ViewPreset::Isometric => "Isometric",
+        }
+    }
+
+    // Camera yaw (from +Z toward +X) and elevation, in radians
+    pub fn angles(self) -> (f32, f32) {
+        match self {
+            ViewPreset::Front => (0.0, 0.0),
+            ViewPreset::Back => (PI, 0.0),
+            ViewPreset::Left => (-FRAC_PI_2, 0.0),
+            ViewPreset::Right => (FRAC_PI_2, 0.0),
+            ViewPreset::Top => (0.0, FRAC_PI_2),
+            ViewPreset::Bottom => (0.0, -FRAC_PI_2),
+            ViewPreset::Isometric => DEFAULT_VIEW,
+        }
+    }
+}
+
+// Looking down from (1, 0.5, 1) toward the origin: 45 degrees around, asin(1/3) up
+pub const DEFAULT_VIEW: (f32, f32) = (FRAC_PI_4, 0.339_836_9);
+
+// Where the camera is and how the model is turned and centered in front of it
+#[derive(Clone, Copy, Debug)]
+pub struct CameraPose {
+    pub yaw: f32,
+    pub elevation: f32,
+    pub rotation: f32,
+    pub pitch: f32,
+    pub center: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl CameraPose {
+    // The same pose with the yaw and rotation moved by whole turns to be closest to `other`,
+    // so interpolating between them never goes the long way around
+    fn unwrapped_near(mut self, other: &CameraPose) -> Self {
+        self.yaw = other.yaw + wrap_angle(self.yaw - other.yaw);
+        self.rotation = other.rotation + wrap_angle(self.rotation - other.rotation);
+        self
+    }
+}
+
+// An eased move from one pose to another
+pub struct CameraTransition {
+    from: CameraPose,
+    to: CameraPose,
+    elapsed: f32,
+}
+
+impl CameraTransition {
+    pub fn new(from: CameraPose, to: CameraPose) -> Self {
+        Self { to: to.unwrapped_near(&from), from, elapsed: 0.0 }
+    }
+
+    // Advances by `delta` seconds and returns the pose to show, with whether the move is done
+    pub fn step(&mut self, delta: f32) -> (CameraPose, bool) {
+        self.elapsed += delta;
+        let t = (self.elapsed / TRANSITION_TIME).min(1.0);
+        // Smoothstep, so the camera eases in and out
+        let amount = t * t * (3.0 - 2.0 * t);
+        let lerp = |from: f32, to: f32| from + (to - from) * amount;
+        let pose = CameraPose {
+            yaw: lerp(self.from.yaw, self.to.yaw),
+            elevation: lerp(self.from.elevation, self.to.elevation),
+            rotation: lerp(self.from.rotation, self.to.rotation),
+            pitch: lerp(self.from.pitch, self.to.pitch),
+            center: self.from.center.lerp(self.to.center, amount),
+            distance: lerp(self.from.distance, self.to.distance),
+        };
+        (pose, t >= 1.0)
+    }
+}
+
+// Into -PI..PI
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
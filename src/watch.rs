@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::loader::Waker;
+
+// Watching the model for changes //
+// The model file and everything it refers to are watched through their folders, since
+// exporters often replace a file instead of writing to it, and a file that's missing
+// now may be written later. Exporters also write in several steps (a .gltf, then its
+// .bin), so a change is only reported once the folder has been quiet for a moment.
+
+// How long writes must stop before the model is reloaded
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+pub struct ModelWatcher {
+    // Stops watching when dropped, which also ends the settling thread
+    _watcher: RecommendedWatcher,
+    changed: Receiver<()>,
+}
+
+impl ModelWatcher {
+    pub fn new(files: impl IntoIterator<Item = PathBuf>, waker: Waker) -> Result<Self, String> {
+        let files: HashSet<PathBuf> = files.into_iter().filter_map(|file| std::path::absolute(file).ok()).collect();
+        let folders: HashSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+
+        let (event_sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher({
+            let files = files.clone();
+            move |result: notify::Result<Event>| {
+                let Ok(event) = result else {
+                    return;
+                };
+                if !matches!(event.kind, EventKind::Access(_)) && event.paths.iter().any(|path| files.contains(path)) {
+                    let _ = event_sender.send(());
+                }
+            }
+        })
+        .map_err(|e| e.to_string())?;
+
+        for folder in folders {
+            watcher.watch(folder, RecursiveMode::NonRecursive).map_err(|e| format!("{}: {}", folder.display(), e))?;
+        }
+
+        let (changed_sender, changed) = mpsc::channel();
+        thread::spawn(move || {
+            while events.recv().is_ok() {
+                loop {
+                    match events.recv_timeout(SETTLE_TIME) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                if changed_sender.send(()).is_err() {
+                    return;
+                }
+                waker();
+            }
+        });
+
+        Ok(Self { _watcher: watcher, changed })
+    }
+
+    // Whether any of the files changed since the last call
+    pub fn changed(&self) -> bool {
+        self.changed.try_iter().count() > 0
+    }
+}
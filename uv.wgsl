@@ -0,0 +1,34 @@
+struct Uniforms {
+    mvp: mat4x4<f32>,
+    position_scale: vec4<f32>,
+    position_offset: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// Squares per side of the 0-1 range
+const CHECKER_SIZE: f32 = 8.0;
+
+// Triangle strip over the 0-1 square, no vertex buffer needed
+@vertex
+fn vs_checker(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    out.clip_position = uniforms.mvp * vec4(uv, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_checker(in: VertexOutput) -> @location(0) vec4<f32> {
+    let cell = floor(in.uv * CHECKER_SIZE);
+    let dark = (i32(cell.x) + i32(cell.y)) % 2 == 0;
+    let shade = select(0.45, 0.3, dark);
+    return vec4(shade, shade, shade, 1.0);
+}